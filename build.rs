@@ -1,28 +1,191 @@
-use shaderc::{Compiler, ShaderKind};
+use shaderc::{CompileOptions, Compiler, IncludeType, ResolvedInclude, ShaderKind};
 use std::{
-	fs::{create_dir, File},
+	collections::BTreeMap,
+	env,
+	fmt::Write as _,
+	fs::{self, create_dir_all, File},
 	io::prelude::*,
-	path::Path,
+	path::{Path, PathBuf},
 };
 
-fn main() {
-	create_dir("build").ok();
-	build_shader("src/gfx/shaders/shader.vert", "build/shader.vert.spv", ShaderKind::Vertex);
-	build_shader("src/gfx/shaders/shader.frag", "build/shader.frag.spv", ShaderKind::Fragment);
+const SHADER_DIR: &str = "src/gfx/shaders";
+
+// a program is every stage sharing a file stem, e.g. `sprite.vert` and `sprite.frag`
+#[derive(Default)]
+struct Program {
+	stages: Vec<Stage>,
+}
+impl Program {
+	// every stage's permutations, without repeats
+	fn permutations(&self) -> Vec<&str> {
+		let mut permutations = vec![];
+		for define in self.stages.iter().flat_map(|stage| &stage.permutations) {
+			if !permutations.contains(&define.as_str()) {
+				permutations.push(define.as_str());
+			}
+		}
+		permutations
+	}
 }
 
-fn build_shader(input: &str, output: &str, kind: ShaderKind) {
-	let input = Path::new(input);
-	let output = Path::new(output);
+struct Stage {
+	path: PathBuf,
+	ext: &'static str,
+	kind: ShaderKind,
+	// only these are defined when compiling the stage, so a stage is compiled once for variants that differ only in
+	// the other stages' defines
+	permutations: Vec<String>,
+}
 
-	let mut file = File::open(input).unwrap();
-	let mut source = String::new();
-	file.read_to_string(&mut source).unwrap();
+fn main() {
+	println!("cargo:rerun-if-changed={}", SHADER_DIR);
+	let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+	create_dir_all(out_dir.join("shaders")).unwrap();
+
+	let mut programs: BTreeMap<String, Program> = BTreeMap::new();
+	let mut entries: Vec<_> = fs::read_dir(SHADER_DIR).unwrap().map(|entry| entry.unwrap().path()).collect();
+	entries.sort();
+	for path in entries {
+		println!("cargo:rerun-if-changed={}", path.display());
+
+		let (ext, kind) = match path.extension().and_then(|ext| ext.to_str()) {
+			Some("vert") => ("vert", ShaderKind::Vertex),
+			Some("frag") => ("frag", ShaderKind::Fragment),
+			Some("comp") => ("comp", ShaderKind::Compute),
+			// anything else (e.g. `.glsl` headers) is only reachable through `#include`
+			_ => continue,
+		};
+		let name = path.file_stem().unwrap().to_str().unwrap().to_owned();
+		let permutations = permutations(&read_source(&path));
+		programs.entry(name).or_default().stages.push(Stage { path, ext, kind, permutations });
+	}
 
 	let mut compiler = Compiler::new().unwrap();
-	let binary_result =
-		compiler.compile_into_spirv(&source, kind, input.file_name().unwrap().to_str().unwrap(), "main", None).unwrap();
+	// SPIR-V constants, one per compiled file, shared by the variants using it
+	let mut spirv = String::new();
+	let mut files: BTreeMap<String, usize> = BTreeMap::new();
+	let mut module = String::new();
+	writeln!(module, "pub const SHADERS: &[ShaderInfo] = &[").unwrap();
+	for (name, program) in &programs {
+		let stages: Vec<_> = program.stages.iter().map(|stage| format!("{:?}", stage.ext)).collect();
+		writeln!(module, "\tShaderInfo {{ name: {:?}, stages: &[{}], variants: &[", name, stages.join(", ")).unwrap();
+
+		let permutations = program.permutations();
+		for mask in 0..1usize << permutations.len() {
+			let defines: Vec<_> = permutations
+				.iter()
+				.enumerate()
+				.filter(|&(i, _)| mask & 1 << i != 0)
+				.map(|(_, &define)| define)
+				.collect();
+			let variant = variant_name(&defines);
+
+			let mut stages = vec![];
+			for stage in &program.stages {
+				let stage_defines: Vec<_> =
+					defines.iter().copied().filter(|define| stage.permutations.iter().any(|p| p == define)).collect();
+				let file = match variant_name(&stage_defines).as_str() {
+					"" => format!("{}.{}.spv", name, stage.ext),
+					variant => format!("{}.{}.{}.spv", name, variant, stage.ext),
+				};
+				let idx = match files.get(&file) {
+					Some(&idx) => idx,
+					None => {
+						let idx = files.len();
+						let output = out_dir.join("shaders").join(&file);
+						build_shader(&mut compiler, &stage.path, &output, stage.kind, &stage_defines);
+						writeln!(
+							spirv,
+							"const SPIRV_{}: &Spirv<[u8]> = &Spirv {{ _align: [], bytes: \
+							 *include_bytes!(concat!(env!(\"OUT_DIR\"), \"/shaders/{}\")) }};",
+							idx,
+							file,
+						)
+						.unwrap();
+						files.insert(file.clone(), idx);
+						idx
+					},
+				};
+				stages.push(format!("StageInfo {{ stage: {:?}, file: {:?}, spirv: SPIRV_{} }}", stage.ext, file, idx));
+			}
+
+			let defines: Vec<_> = defines.iter().map(|define| format!("{:?}", define)).collect();
+			writeln!(
+				module,
+				"\t\tVariantInfo {{ name: {:?}, defines: &[{}], stages: &[{}] }},",
+				variant,
+				defines.join(", "),
+				stages.join(", "),
+			)
+			.unwrap();
+		}
+
+		writeln!(module, "\t] }},").unwrap();
+	}
+	writeln!(module, "];").unwrap();
+
+	fs::write(out_dir.join("shaders.rs"), spirv + &module).unwrap();
+}
+
+fn build_shader(compiler: &mut Compiler, input: &Path, output: &Path, kind: ShaderKind, defines: &[&str]) {
+	let source = strip_pragmas(&read_source(input));
+
+	let mut options = CompileOptions::new().unwrap();
+	options.set_include_callback(|name, ty, includer, _depth| {
+		let base = match ty {
+			IncludeType::Relative => Path::new(includer).parent().unwrap().to_owned(),
+			IncludeType::Standard => PathBuf::from(SHADER_DIR),
+		};
+		let path = base.join(name);
+		let content = fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+		println!("cargo:rerun-if-changed={}", path.display());
+		Ok(ResolvedInclude { resolved_name: path.to_str().unwrap().to_owned(), content })
+	});
+	if env::var("PROFILE").map(|profile| profile == "debug").unwrap_or(false) {
+		options.add_macro_definition("DEBUG", None);
+	}
+	for define in defines {
+		options.add_macro_definition(define, None);
+	}
+
+	let input_name = input.to_str().unwrap();
+	let binary_result = match compiler.compile_into_spirv(&source, kind, input_name, "main", Some(&options)) {
+		Ok(result) => result,
+		Err(err) => panic!("failed to compile {} with defines {:?}\n{}", input_name, defines, err),
+	};
+	for warning in binary_result.get_warning_messages().lines() {
+		println!("cargo:warning={}", warning);
+	}
 
 	let mut file = File::create(output).unwrap();
 	file.write_all(binary_result.as_binary_u8()).unwrap();
 }
+
+fn read_source(path: &Path) -> String {
+	let mut file = File::open(path).unwrap();
+	let mut source = String::new();
+	file.read_to_string(&mut source).unwrap();
+	source
+}
+
+// `#pragma permutation A B` compiles every combination of `A` and `B` being defined
+fn permutations(source: &str) -> Vec<String> {
+	source
+		.lines()
+		.filter_map(|line| line.trim().strip_prefix("#pragma permutation"))
+		.flat_map(|defines| defines.split_whitespace().map(str::to_owned).collect::<Vec<_>>())
+		.collect()
+}
+
+// blanks out our own pragmas so line numbers in errors still match the source
+fn strip_pragmas(source: &str) -> String {
+	source
+		.lines()
+		.map(|line| if line.trim().starts_with("#pragma permutation") { "" } else { line })
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+fn variant_name(defines: &[&str]) -> String {
+	defines.iter().map(|define| define.to_lowercase()).collect::<Vec<_>>().join("+")
+}
@@ -0,0 +1,262 @@
+pub mod clip;
+pub mod sheet;
+
+use crate::gfx::texture::Subtex;
+use clip::{Clip, PlayMode};
+use sheet::SpriteSheet;
+use std::{sync::Arc, time::Duration};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AnimationEvent {
+	/// An event attached to the frame just shown.
+	Frame(String),
+	/// A `PlayMode::Once` clip reached its end.
+	Finished,
+}
+
+/// Plays clips from a sprite sheet, one at a time.
+pub struct Animator {
+	/// Multiplies the time passed to `update`. Must not be negative.
+	pub speed: f32,
+	pub paused: bool,
+	clip: Arc<Clip>,
+	// position in `clip.frames`
+	pos: usize,
+	// seconds spent on the current frame
+	elapsed: f32,
+	// going backward through a ping-pong clip
+	reverse: bool,
+	finished: bool,
+	events: Vec<AnimationEvent>,
+}
+impl Animator {
+	pub fn new(clip: Arc<Clip>) -> Self {
+		assert!(!clip.frames.is_empty(), "clip has no frames");
+		let mut ret = Self {
+			speed: 1.0,
+			paused: false,
+			clip,
+			pos: 0,
+			elapsed: 0.0,
+			reverse: false,
+			finished: false,
+			events: vec![],
+		};
+		ret.enter(0);
+		ret
+	}
+
+	pub fn clip(&self) -> &Arc<Clip> {
+		&self.clip
+	}
+
+	/// Switches to `clip` from its start, unless it's already playing, so this can be called every frame with whatever
+	/// the character is doing.
+	pub fn play(&mut self, clip: &Arc<Clip>) {
+		if !Arc::ptr_eq(&self.clip, clip) {
+			assert!(!clip.frames.is_empty(), "clip has no frames");
+			self.clip = clip.clone();
+			self.restart();
+		}
+	}
+
+	pub fn restart(&mut self) {
+		self.elapsed = 0.0;
+		self.reverse = false;
+		self.finished = false;
+		self.enter(0);
+	}
+
+	/// Whether a `PlayMode::Once` clip has reached its end.
+	pub fn finished(&self) -> bool {
+		self.finished
+	}
+
+	/// Index of the current frame in the sprite sheet.
+	pub fn frame(&self) -> u32 {
+		self.clip.frames[self.pos].index
+	}
+
+	pub fn subtex<'a>(&self, sheet: &'a SpriteSheet) -> &'a Subtex {
+		sheet.frame(self.frame())
+	}
+
+	/// Advances by `dt` of game time, possibly past several frames. Events from whole cycles of a repeating clip that
+	/// fit in `dt` are skipped.
+	pub fn update(&mut self, dt: Duration) {
+		// a clip without duration would never get past its frames
+		if self.paused || self.finished || self.clip.duration() <= 0.0 {
+			return;
+		}
+		self.elapsed += dt.as_secs_f32() * self.speed;
+		// a whole cycle comes back to the same frame, so a huge `dt` doesn't need stepping through frame by frame
+		let cycle = cycle_duration(&self.clip);
+		if self.clip.mode != PlayMode::Once && self.elapsed >= cycle {
+			self.elapsed %= cycle;
+		}
+		while self.elapsed >= self.clip.frames[self.pos].duration {
+			self.elapsed -= self.clip.frames[self.pos].duration;
+			if !self.advance() {
+				self.elapsed = 0.0;
+				break;
+			}
+		}
+	}
+
+	/// Events from frames shown since the last call, in order.
+	pub fn drain_events(&mut self) -> impl Iterator<Item = AnimationEvent> + '_ {
+		self.events.drain(..)
+	}
+
+	// returns false once a clip that doesn't repeat is over
+	fn advance(&mut self) -> bool {
+		let len = self.clip.frames.len();
+		let next = match self.clip.mode {
+			PlayMode::Once if self.pos + 1 < len => self.pos + 1,
+			PlayMode::Once => {
+				self.finished = true;
+				self.events.push(AnimationEvent::Finished);
+				return false;
+			},
+			PlayMode::Loop => (self.pos + 1) % len,
+			PlayMode::PingPong if len == 1 => 0,
+			PlayMode::PingPong => {
+				if (self.reverse && self.pos == 0) || (!self.reverse && self.pos + 1 == len) {
+					self.reverse = !self.reverse;
+				}
+				if self.reverse {
+					self.pos - 1
+				} else {
+					self.pos + 1
+				}
+			},
+		};
+		self.enter(next);
+		true
+	}
+
+	fn enter(&mut self, pos: usize) {
+		self.pos = pos;
+		let events = self.clip.events.iter().filter(|&&(event_pos, _)| event_pos == pos);
+		self.events.extend(events.map(|(_, name)| AnimationEvent::Frame(name.clone())));
+	}
+}
+
+// seconds for a repeating clip to come back to the same frame, going the same way
+fn cycle_duration(clip: &Clip) -> f32 {
+	match (clip.mode, &clip.frames[..]) {
+		// the end frames are only shown once per cycle
+		(PlayMode::PingPong, [first, .., last]) => 2.0 * clip.duration() - first.duration - last.duration,
+		_ => clip.duration(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn start(clip: Clip) -> Animator {
+		let mut animator = Animator::new(Arc::new(clip));
+		animator.drain_events().for_each(drop);
+		animator
+	}
+
+	// the frame after each step of `dt` seconds
+	fn frames(animator: &mut Animator, dt: f32, steps: usize) -> Vec<u32> {
+		(0..steps)
+			.map(|_| {
+				animator.update(Duration::from_secs_f32(dt));
+				animator.frame()
+			})
+			.collect()
+	}
+
+	#[test]
+	fn once() {
+		let clip = Clip::new("hit", PlayMode::Once).frame(4, 0.25).frame(5, 0.5).event(1, "swing");
+		let mut animator = start(clip);
+		assert_eq!(animator.frame(), 4);
+		assert_eq!(frames(&mut animator, 0.25, 3), [5, 5, 5]);
+		assert!(animator.finished());
+		assert_eq!(
+			animator.drain_events().collect::<Vec<_>>(),
+			[AnimationEvent::Frame("swing".into()), AnimationEvent::Finished]
+		);
+
+		// stays on the last frame without firing anything else
+		animator.update(Duration::from_secs(10));
+		assert_eq!(animator.frame(), 5);
+		assert_eq!(animator.drain_events().count(), 0);
+
+		animator.restart();
+		assert!(!animator.finished());
+		assert_eq!(animator.frame(), 4);
+	}
+
+	#[test]
+	fn looping() {
+		let clip = Clip::uniform("walk", 0..3, 0.25, PlayMode::Loop).event(0, "step");
+		let mut animator = start(clip);
+		assert_eq!(frames(&mut animator, 0.25, 4), [1, 2, 0, 1]);
+		assert_eq!(animator.drain_events().collect::<Vec<_>>(), [AnimationEvent::Frame("step".into())]);
+
+		// several frames in one update
+		animator.update(Duration::from_secs_f32(0.5));
+		assert_eq!(animator.frame(), 0);
+		assert!(!animator.finished());
+	}
+
+	#[test]
+	fn ping_pong() {
+		let clip = Clip::uniform("bob", 0..3, 0.25, PlayMode::PingPong);
+		let mut animator = start(clip);
+		// turns around on the end frames without showing them twice
+		assert_eq!(frames(&mut animator, 0.25, 6), [1, 2, 1, 0, 1, 2]);
+
+		let single = Clip::uniform("still", 7..8, 0.25, PlayMode::PingPong);
+		let mut animator = start(single);
+		assert_eq!(frames(&mut animator, 0.25, 2), [7, 7]);
+	}
+
+	#[test]
+	fn huge_dt() {
+		let mut animator = start(Clip::uniform("walk", 0..3, 0.25, PlayMode::Loop));
+		// 4000 whole cycles and one frame
+		animator.update(Duration::from_secs_f32(3000.25));
+		assert_eq!(animator.frame(), 1);
+
+		let mut animator = start(Clip::uniform("bob", 0..3, 0.25, PlayMode::PingPong));
+		// a cycle is 0, 1, 2, 1
+		animator.update(Duration::from_secs_f32(3000.5));
+		assert_eq!(animator.frame(), 2);
+		assert_eq!(frames(&mut animator, 0.25, 2), [1, 0]);
+	}
+
+	#[test]
+	fn speed_and_pause() {
+		let mut animator = start(Clip::uniform("walk", 0..4, 0.25, PlayMode::Loop));
+		animator.speed = 2.0;
+		assert_eq!(frames(&mut animator, 0.25, 2), [2, 0]);
+		animator.paused = true;
+		assert_eq!(frames(&mut animator, 0.25, 2), [0, 0]);
+	}
+
+	#[test]
+	fn play_keeps_current_clip() {
+		let walk = Arc::new(Clip::uniform("walk", 0..3, 0.25, PlayMode::Loop));
+		let mut animator = Animator::new(walk.clone());
+		animator.update(Duration::from_secs_f32(0.25));
+		animator.play(&walk);
+		assert_eq!(animator.frame(), 1);
+
+		let other = Arc::new(Clip::uniform("walk", 0..3, 0.25, PlayMode::Loop));
+		animator.play(&other);
+		assert_eq!(animator.frame(), 0);
+	}
+
+	#[test]
+	#[should_panic(expected = "frame duration must be positive")]
+	fn zero_duration() {
+		Clip::new("idle", PlayMode::Loop).frame(0, 0.0);
+	}
+}
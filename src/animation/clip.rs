@@ -0,0 +1,62 @@
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayMode {
+	/// Stops on the last frame.
+	Once,
+	Loop,
+	/// Plays forward then backward, without repeating the end frames.
+	PingPong,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+	/// Index into the sprite sheet's frames.
+	pub index: u32,
+	/// Seconds. Must be positive.
+	pub duration: f32,
+}
+
+/// A named sequence of sprite sheet frames.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clip {
+	pub name: String,
+	pub frames: Vec<Frame>,
+	pub mode: PlayMode,
+	/// Events fired when the frame at a position in `frames` is shown.
+	pub events: Vec<(usize, String)>,
+}
+impl Clip {
+	pub fn new(name: impl Into<String>, mode: PlayMode) -> Self {
+		Self { name: name.into(), frames: vec![], mode, events: vec![] }
+	}
+
+	/// A clip showing a range of sheet frames for the same duration each.
+	pub fn uniform(name: impl Into<String>, frames: Range<u32>, duration: f32, mode: PlayMode) -> Self {
+		check_duration(duration);
+		let mut clip = Self::new(name, mode);
+		clip.frames.extend(frames.map(|index| Frame { index, duration }));
+		clip
+	}
+
+	pub fn frame(mut self, index: u32, duration: f32) -> Self {
+		check_duration(duration);
+		self.frames.push(Frame { index, duration });
+		self
+	}
+
+	/// Fires `name` whenever the frame at `pos` in this clip is shown.
+	pub fn event(mut self, pos: usize, name: impl Into<String>) -> Self {
+		self.events.push((pos, name.into()));
+		self
+	}
+
+	/// Seconds to play through once. Ping-pong clips take about twice this to return to the start.
+	pub fn duration(&self) -> f32 {
+		self.frames.iter().map(|frame| frame.duration).sum()
+	}
+}
+
+fn check_duration(duration: f32) {
+	assert!(duration.is_finite() && duration > 0.0, "frame duration must be positive");
+}
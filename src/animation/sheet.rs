@@ -0,0 +1,259 @@
+use crate::{
+	animation::clip::{Clip, Frame, PlayMode},
+	fs::read_all_u8,
+	gfx::{texture::Subtex, Gfx},
+	json::Json,
+};
+use log::info;
+use std::{collections::HashMap, io, path::Path, sync::Arc};
+
+// what Aseprite uses when a frame doesn't say
+const DEFAULT_DURATION: f32 = 0.1;
+
+// x, y, width and height in texels
+type Rect = (u32, u32, u32, u32);
+
+/// Frames cut from a texture, along with the clips that play them.
+pub struct SpriteSheet {
+	frames: Vec<Subtex>,
+	clips: HashMap<String, Arc<Clip>>,
+}
+impl SpriteSheet {
+	/// Cuts `texture` into `frame_w` by `frame_h` cells, numbered row by row. Partial cells at the edges are skipped.
+	/// Fails if either size is 0.
+	pub fn grid(texture: &Subtex, frame_w: u32, frame_h: u32) -> io::Result<Self> {
+		let rects = grid_rects(texture.size(), frame_w, frame_h)?;
+		let frames = rects.into_iter().map(|(x, y, w, h)| texture.sub(x, y, w, h)).collect();
+		Ok(Self { frames, clips: HashMap::new() })
+	}
+
+	/// Reads frames from JSON in the format exported by Aseprite and TexturePacker, with `frames` either an array or
+	/// an object. Aseprite frame tags become clips. Trimming is ignored, so export untrimmed sheets.
+	pub fn from_json(texture: &Subtex, json: &str) -> io::Result<Self> {
+		let (rects, clips) = parse_json(json, texture.size())?;
+		let frames = rects.into_iter().map(|(x, y, w, h)| texture.sub(x, y, w, h)).collect();
+		let mut sheet = Self { frames, clips: HashMap::new() };
+		for clip in clips {
+			sheet.add_clip(clip);
+		}
+		Ok(sheet)
+	}
+
+	/// Loads JSON frame metadata from `path`, along with the image named by its `meta.image`, relative to `path`.
+	pub async fn load<P: AsRef<Path>>(gfx: &Arc<Gfx>, path: P) -> io::Result<Self> {
+		let path = path.as_ref();
+		let json = read_all_u8(path.to_owned()).await?;
+		let json = String::from_utf8(json).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+		let image_path = Json::parse(&json)?
+			.get("meta")
+			.and_then(|meta| meta.get("image"))
+			.and_then(Json::as_str)
+			.map(|image| path.with_file_name(image))
+			.ok_or_else(|| invalid_data("missing `meta.image`"))?;
+
+		let texture = Subtex::load(gfx, image_path).await?;
+
+		info!(target: "assets", "loaded sprite sheet {}", path.display());
+		Self::from_json(&texture, &json)
+	}
+
+	pub fn frames(&self) -> &[Subtex] {
+		&self.frames
+	}
+
+	pub fn frame(&self, index: u32) -> &Subtex {
+		&self.frames[index as usize]
+	}
+
+	pub fn clip(&self, name: &str) -> Option<&Arc<Clip>> {
+		self.clips.get(name)
+	}
+
+	pub fn clips(&self) -> impl Iterator<Item = &Arc<Clip>> {
+		self.clips.values()
+	}
+
+	/// Adds or replaces the clip with the same name.
+	pub fn add_clip(&mut self, clip: Clip) -> Arc<Clip> {
+		assert!(clip.frames.iter().all(|frame| (frame.index as usize) < self.frames.len()), "clip frame out of range");
+		let clip = Arc::new(clip);
+		self.clips.insert(clip.name.clone(), clip.clone());
+		clip
+	}
+}
+
+// cells of a grid over a texture of size `(w, h)`
+fn grid_rects((w, h): (u32, u32), frame_w: u32, frame_h: u32) -> io::Result<Vec<Rect>> {
+	if frame_w == 0 || frame_h == 0 {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty sprite sheet frames"));
+	}
+	let cells = (0..h / frame_h).flat_map(|y| (0..w / frame_w).map(move |x| (x, y)));
+	Ok(cells.map(|(x, y)| (x * frame_w, y * frame_h, frame_w, frame_h)).collect())
+}
+
+// frame rectangles and clips, checked against a texture of size `(w, h)`
+fn parse_json(json: &str, (w, h): (u32, u32)) -> io::Result<(Vec<Rect>, Vec<Clip>)> {
+	let json = Json::parse(json)?;
+	let frames = json.get("frames").ok_or_else(|| invalid_data("missing `frames`"))?;
+	let frames: Vec<_> = match frames {
+		Json::Array(frames) => frames.iter().collect(),
+		Json::Object(frames) => frames.iter().map(|(_, frame)| frame).collect(),
+		_ => return Err(invalid_data("`frames` must be an array or object")),
+	};
+
+	let mut rects = Vec::with_capacity(frames.len());
+	let mut durations = Vec::with_capacity(frames.len());
+	for frame in frames {
+		if frame.get("rotated").and_then(Json::as_bool) == Some(true) {
+			return Err(invalid_data("rotated frames aren't supported"));
+		}
+		let rect = frame.get("frame").ok_or_else(|| invalid_data("frame without `frame` rectangle"))?;
+		let field = |name| rect.get(name).and_then(Json::as_u32).ok_or_else(|| invalid_data("invalid frame rectangle"));
+		let (x, y, fw, fh) = (field("x")?, field("y")?, field("w")?, field("h")?);
+		if fw == 0 || fh == 0 {
+			return Err(invalid_data("empty frame rectangle"));
+		}
+		// rectangles near u32::MAX mustn't wrap around into the texture
+		let inside = |start: u32, len: u32, size| start.checked_add(len).is_some_and(|end| end <= size);
+		if !inside(x, fw, w) || !inside(y, fh, h) {
+			return Err(invalid_data("frame outside of the texture"));
+		}
+		rects.push((x, y, fw, fh));
+		let duration = frame.get("duration").and_then(Json::as_f64).map_or(DEFAULT_DURATION, |ms| ms as f32 / 1000.0);
+		// checked after converting, since a tiny duration rounds to 0 and a huge one to infinity
+		if !(duration.is_finite() && duration > 0.0) {
+			return Err(invalid_data("frame duration must be positive"));
+		}
+		durations.push(duration);
+	}
+
+	let mut clips = vec![];
+	let tags = json.get("meta").and_then(|meta| meta.get("frameTags")).and_then(Json::as_array).unwrap_or_default();
+	for tag in tags {
+		let name = tag.get("name").and_then(Json::as_str).ok_or_else(|| invalid_data("frame tag without a name"))?;
+		let from = tag.get("from").and_then(Json::as_u32).ok_or_else(|| invalid_data("invalid frame tag range"))?;
+		let to = tag.get("to").and_then(Json::as_u32).ok_or_else(|| invalid_data("invalid frame tag range"))?;
+		if from > to || to as usize >= durations.len() {
+			return Err(invalid_data("invalid frame tag range"));
+		}
+
+		// newer versions of Aseprite write the repeat count as a string
+		let repeat = tag.get("repeat").and_then(|repeat| match repeat {
+			Json::String(repeat) => repeat.parse().ok(),
+			repeat => repeat.as_u32(),
+		});
+		let direction = tag.get("direction").and_then(Json::as_str).unwrap_or("forward");
+		let (mode, reverse) = match direction {
+			"forward" => (PlayMode::Loop, false),
+			"reverse" => (PlayMode::Loop, true),
+			"pingpong" => (PlayMode::PingPong, false),
+			"pingpong_reverse" => (PlayMode::PingPong, true),
+			_ => return Err(invalid_data("unknown frame tag direction")),
+		};
+		let mode = if repeat == Some(1) { PlayMode::Once } else { mode };
+
+		let mut clip = Clip::new(name, mode);
+		clip.frames.extend((from..=to).map(|index| Frame { index, duration: durations[index as usize] }));
+		if reverse {
+			clip.frames.reverse();
+		}
+		clips.push(clip);
+	}
+	Ok((rects, clips))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const TAGS: &str = r#""meta": { "frameTags": [
+		{ "name": "walk", "from": 0, "to": 1, "direction": "pingpong" },
+		{ "name": "hit", "from": 1, "to": 2, "direction": "reverse", "repeat": "1" }
+	] }"#;
+
+	#[test]
+	fn frames_array() {
+		let json = format!(
+			r#"{{ "frames": [
+				{{ "filename": "a", "frame": {{ "x": 0, "y": 0, "w": 16, "h": 16 }}, "duration": 50 }},
+				{{ "filename": "b", "frame": {{ "x": 16, "y": 0, "w": 16, "h": 16 }}, "duration": 150 }},
+				{{ "filename": "c", "frame": {{ "x": 0, "y": 16, "w": 32, "h": 16 }} }}
+			], {} }}"#,
+			TAGS
+		);
+		let (rects, clips) = parse_json(&json, (32, 32)).unwrap();
+		assert_eq!(rects, [(0, 0, 16, 16), (16, 0, 16, 16), (0, 16, 32, 16)]);
+
+		let walk = Clip::new("walk", PlayMode::PingPong).frame(0, 0.05).frame(1, 0.15);
+		// a repeat of one plays once, whatever the direction
+		let hit = Clip::new("hit", PlayMode::Once).frame(2, DEFAULT_DURATION).frame(1, 0.15);
+		assert_eq!(clips, [walk, hit]);
+	}
+
+	#[test]
+	fn frames_object() {
+		// keyed by file name, in file order rather than sorted
+		let json = format!(
+			r#"{{ "frames": {{
+				"b.png": {{ "frame": {{ "x": 16, "y": 0, "w": 16, "h": 16 }}, "duration": 50 }},
+				"a.png": {{ "frame": {{ "x": 0, "y": 0, "w": 16, "h": 16 }}, "duration": 150 }},
+				"c.png": {{ "frame": {{ "x": 0, "y": 16, "w": 32, "h": 16 }} }}
+			}}, {} }}"#,
+			TAGS
+		);
+		let (rects, clips) = parse_json(&json, (32, 32)).unwrap();
+		assert_eq!(rects, [(16, 0, 16, 16), (0, 0, 16, 16), (0, 16, 32, 16)]);
+		assert_eq!(clips.len(), 2);
+		assert_eq!(clips[0].frames[1], Frame { index: 1, duration: 0.15 });
+	}
+
+	#[test]
+	fn frame_outside_texture() {
+		let frame = |x: u32, w: u32| {
+			format!(r#"{{ "frames": [{{ "frame": {{ "x": {}, "y": 0, "w": {}, "h": 8 }} }}] }}"#, x, w)
+		};
+		assert!(parse_json(&frame(24, 8), (32, 32)).is_ok());
+		assert!(parse_json(&frame(25, 8), (32, 32)).is_err());
+		assert!(parse_json(&frame(u32::MAX, 2), (32, 32)).is_err());
+	}
+
+	#[test]
+	fn grid_cells() {
+		// partial cells along the right and bottom edges are skipped
+		let rects = grid_rects((40, 20), 16, 8).unwrap();
+		assert_eq!(rects, [(0, 0, 16, 8), (16, 0, 16, 8), (0, 8, 16, 8), (16, 8, 16, 8)]);
+		assert!(grid_rects((8, 8), 16, 16).unwrap().is_empty());
+	}
+
+	#[test]
+	fn zero_size_frames() {
+		assert!(grid_rects((32, 32), 0, 8).is_err());
+		assert!(grid_rects((32, 32), 8, 0).is_err());
+		let json = r#"{ "frames": [{ "frame": { "x": 0, "y": 0, "w": 0, "h": 8 } }] }"#;
+		assert!(parse_json(json, (32, 32)).is_err());
+	}
+
+	#[test]
+	fn non_positive_durations() {
+		let frame = |duration: &str| {
+			let frame = r#"{ "x": 0, "y": 0, "w": 8, "h": 8 }"#;
+			format!(r#"{{ "frames": [{{ "frame": {}, "duration": {} }}] }}"#, frame, duration)
+		};
+		assert!(parse_json(&frame("1"), (8, 8)).is_ok());
+		assert!(parse_json(&frame("0"), (8, 8)).is_err());
+		assert!(parse_json(&frame("-100"), (8, 8)).is_err());
+		assert!(parse_json(&frame("1e-50"), (8, 8)).is_err());
+		assert!(parse_json(&frame("1e50"), (8, 8)).is_err());
+	}
+
+	#[test]
+	fn tag_out_of_range() {
+		let json = r#"{ "frames": [{ "frame": { "x": 0, "y": 0, "w": 8, "h": 8 } }],
+			"meta": { "frameTags": [{ "name": "idle", "from": 0, "to": 1 }] } }"#;
+		assert!(parse_json(json, (8, 8)).is_err());
+	}
+}
@@ -0,0 +1,73 @@
+pub mod backend;
+pub mod decode;
+pub mod mixer;
+pub mod sound;
+
+use backend::Backend;
+use mixer::Mixer;
+use std::{io, time::Duration};
+
+pub struct Audio<B: Backend> {
+	mixer: Mixer,
+	backend: B,
+	// fractional frames carried over between updates so no time is lost to rounding
+	pending: f64,
+	buf: Vec<f32>,
+}
+impl<B: Backend> Audio<B> {
+	pub fn new(backend: B) -> Self {
+		Self { mixer: Mixer::new(backend.sample_rate()), backend, pending: 0.0, buf: vec![] }
+	}
+
+	pub fn mixer(&mut self) -> &mut Mixer {
+		&mut self.mixer
+	}
+
+	pub fn backend(&mut self) -> &mut B {
+		&mut self.backend
+	}
+
+	pub fn into_backend(self) -> B {
+		self.backend
+	}
+
+	/// Mixes `dt` worth of audio and hands it to the backend. Fails if the backend couldn't take it, like a WAV file
+	/// on a full disk.
+	pub fn update(&mut self, dt: Duration) -> io::Result<()> {
+		self.pending += dt.as_secs_f64() * self.mixer.sample_rate() as f64;
+		let frames = self.pending as usize;
+		self.pending -= frames as f64;
+
+		self.buf.resize(frames * 2, 0.0);
+		self.mixer.mix(&mut self.buf);
+		self.backend.submit(&self.buf)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::audio::{
+		backend::WavBackend,
+		mixer::PlayParams,
+		sound::{tests::wav, Sound},
+	};
+	use hound::WavReader;
+	use std::{env, f32::consts::FRAC_1_SQRT_2, fs, process};
+
+	#[test]
+	fn writes_wav() {
+		let path = env::temp_dir().join(format!("game-engine-audio-{}.wav", process::id()));
+		let mut audio = Audio::new(WavBackend::create(&path, 1000).unwrap());
+		let sound = Sound::decode(wav(1, 1000, &[1.0; 2])).unwrap();
+		audio.mixer().play(&sound, PlayParams::default());
+		// a frame and a half each, with the leftover half carried into the next update
+		audio.update(Duration::from_micros(1500)).unwrap();
+		audio.update(Duration::from_micros(1500)).unwrap();
+		audio.into_backend().finish().unwrap();
+
+		let samples: Vec<f32> = WavReader::open(&path).unwrap().samples().map(Result::unwrap).collect();
+		fs::remove_file(&path).unwrap();
+		assert_eq!(samples, [FRAC_1_SQRT_2, FRAC_1_SQRT_2, FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0, 0.0]);
+	}
+}
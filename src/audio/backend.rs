@@ -0,0 +1,59 @@
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::{
+	fs::File,
+	io::{self, BufWriter},
+	path::Path,
+};
+
+/// Receives mixed audio as interleaved stereo f32 samples.
+pub trait Backend {
+	fn sample_rate(&self) -> u32;
+	fn submit(&mut self, samples: &[f32]) -> io::Result<()>;
+}
+
+/// Discards all audio. Used when there is no sound card, and in tests.
+pub struct NullBackend {
+	sample_rate: u32,
+}
+impl NullBackend {
+	pub fn new(sample_rate: u32) -> Self {
+		Self { sample_rate }
+	}
+}
+impl Backend for NullBackend {
+	fn sample_rate(&self) -> u32 {
+		self.sample_rate
+	}
+
+	fn submit(&mut self, _samples: &[f32]) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Writes all audio to a WAV file.
+pub struct WavBackend {
+	writer: WavWriter<BufWriter<File>>,
+}
+impl WavBackend {
+	pub fn create<P: AsRef<Path>>(path: P, sample_rate: u32) -> io::Result<Self> {
+		let spec = WavSpec { channels: 2, sample_rate, bits_per_sample: 32, sample_format: SampleFormat::Float };
+		let writer = WavWriter::create(path, spec).map_err(|err| io::Error::other(err.to_string()))?;
+		Ok(Self { writer })
+	}
+
+	pub fn finish(self) -> io::Result<()> {
+		self.writer.finalize().map_err(|err| io::Error::other(err.to_string()))
+	}
+}
+impl Backend for WavBackend {
+	fn sample_rate(&self) -> u32 {
+		self.writer.spec().sample_rate
+	}
+
+	fn submit(&mut self, samples: &[f32]) -> io::Result<()> {
+		for &sample in samples {
+			self.writer.write_sample(sample).map_err(|err| io::Error::other(err.to_string()))?;
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,86 @@
+use hound::{SampleFormat, WavReader};
+use lewton::inside_ogg::OggStreamReader;
+use std::io::{self, Read, Seek, SeekFrom};
+
+pub trait ReadSeek: Read + Seek + Send {}
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+const CHUNK_SAMPLES: usize = 4096;
+
+pub enum Decoder {
+	Wav(WavReader<Box<dyn ReadSeek>>),
+	Ogg(Box<OggStreamReader<Box<dyn ReadSeek>>>),
+}
+impl Decoder {
+	pub fn new(mut reader: Box<dyn ReadSeek>) -> io::Result<Self> {
+		let mut magic = [0; 4];
+		reader.read_exact(&mut magic)?;
+		reader.seek(SeekFrom::Start(0))?;
+
+		match &magic {
+			b"RIFF" => Ok(Self::Wav(WavReader::new(reader).map_err(invalid_data)?)),
+			b"OggS" => Ok(Self::Ogg(Box::new(OggStreamReader::new(reader).map_err(invalid_data)?))),
+			_ => Err(io::Error::new(io::ErrorKind::InvalidData, "unrecognized audio format")),
+		}
+	}
+
+	pub fn channels(&self) -> u16 {
+		match self {
+			Self::Wav(reader) => reader.spec().channels,
+			Self::Ogg(reader) => reader.ident_hdr.audio_channels as _,
+		}
+	}
+
+	pub fn sample_rate(&self) -> u32 {
+		match self {
+			Self::Wav(reader) => reader.spec().sample_rate,
+			Self::Ogg(reader) => reader.ident_hdr.audio_sample_rate,
+		}
+	}
+
+	/// Appends the next chunk of interleaved samples to `out`. Returns false once the end of the stream is reached.
+	pub fn read_chunk(&mut self, out: &mut Vec<f32>) -> io::Result<bool> {
+		match self {
+			Self::Wav(reader) => {
+				let spec = reader.spec();
+				let len = out.len();
+				match spec.sample_format {
+					SampleFormat::Float => {
+						for sample in reader.samples::<f32>().take(CHUNK_SAMPLES) {
+							out.push(sample.map_err(invalid_data)?);
+						}
+					},
+					SampleFormat::Int => {
+						let scale = 1.0 / (1u32 << (spec.bits_per_sample - 1)) as f32;
+						for sample in reader.samples::<i32>().take(CHUNK_SAMPLES) {
+							out.push(sample.map_err(invalid_data)? as f32 * scale);
+						}
+					},
+				}
+				Ok(out.len() > len)
+			},
+			Self::Ogg(reader) => loop {
+				match reader.read_dec_packet_itl().map_err(invalid_data)? {
+					// packets may legitimately decode to nothing
+					Some(packet) if packet.is_empty() => continue,
+					Some(packet) => {
+						out.extend(packet.into_iter().map(|sample| sample as f32 / 32768.0));
+						break Ok(true);
+					},
+					None => break Ok(false),
+				}
+			},
+		}
+	}
+
+	pub fn rewind(&mut self) -> io::Result<()> {
+		match self {
+			Self::Wav(reader) => reader.seek(0),
+			Self::Ogg(reader) => reader.seek_absgp_pg(0).map_err(invalid_data),
+		}
+	}
+}
+
+fn invalid_data<E: ToString>(err: E) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
@@ -0,0 +1,465 @@
+use crate::audio::sound::{Sound, Stream};
+use nalgebra::Vector2;
+use std::f32::consts::FRAC_PI_4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VoiceId(u64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BusId(usize);
+
+#[derive(Clone, Copy, Debug)]
+pub struct PlayParams {
+	pub bus: BusId,
+	pub volume: f32,
+	/// -1 is fully left, 1 is fully right.
+	pub pan: f32,
+	/// World position for attenuation and panning relative to the listener. `None` plays the sound as-is.
+	pub position: Option<Vector2<f32>>,
+	pub looping: bool,
+	/// Seconds to fade in from silence.
+	pub fade_in: f32,
+}
+impl Default for PlayParams {
+	fn default() -> Self {
+		Self { bus: Mixer::MASTER, volume: 1.0, pan: 0.0, position: None, looping: false, fade_in: 0.0 }
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Listener {
+	pub pos: Vector2<f32>,
+	/// Distance within which sounds play at full volume.
+	pub ref_distance: f32,
+	/// Distance beyond which sounds are silent.
+	pub max_distance: f32,
+	pub rolloff: f32,
+}
+impl Default for Listener {
+	fn default() -> Self {
+		Self { pos: Vector2::zeros(), ref_distance: 64.0, max_distance: 2048.0, rolloff: 1.0 }
+	}
+}
+impl Listener {
+	fn spatialize(&self, pos: Vector2<f32>) -> (f32, f32) {
+		let offset = pos - self.pos;
+		let distance = offset.norm();
+		if distance >= self.max_distance {
+			return (0.0, 0.0);
+		}
+
+		let gain = if distance <= self.ref_distance {
+			1.0
+		} else {
+			self.ref_distance / (self.ref_distance + self.rolloff * (distance - self.ref_distance))
+		};
+		let pan = offset.x / (distance + self.ref_distance);
+		(gain, pan)
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Fade {
+	from: f32,
+	to: f32,
+	frames: u64,
+	elapsed: u64,
+	stop: bool,
+}
+impl Fade {
+	fn new(from: f32, to: f32, secs: f32, sample_rate: u32, stop: bool) -> Self {
+		Self { from, to, frames: (secs.max(0.0) * sample_rate as f32) as u64, elapsed: 0, stop }
+	}
+
+	fn value(&self) -> f32 {
+		if self.elapsed >= self.frames {
+			self.to
+		} else {
+			self.from + (self.to - self.from) * (self.elapsed as f32 / self.frames as f32)
+		}
+	}
+
+	fn done(&self) -> bool {
+		self.elapsed >= self.frames
+	}
+}
+
+enum Source {
+	Sound(Sound),
+	Stream(Box<Stream>),
+}
+impl Source {
+	fn sample_rate(&self) -> u32 {
+		match self {
+			Self::Sound(sound) => sound.sample_rate(),
+			Self::Stream(stream) => stream.sample_rate(),
+		}
+	}
+
+	fn frame(&mut self, idx: usize, looping: bool) -> Option<[f32; 2]> {
+		match self {
+			Self::Sound(sound) if looping && sound.frames() > 0 => Some(sound.frame(idx % sound.frames())),
+			Self::Sound(sound) if idx < sound.frames() => Some(sound.frame(idx)),
+			Self::Sound(_) => None,
+			Self::Stream(stream) => stream.frame(idx, looping),
+		}
+	}
+}
+
+struct Voice {
+	id: VoiceId,
+	source: Source,
+	params: PlayParams,
+	// position in source frames
+	cursor: f64,
+	fade: Option<Fade>,
+	paused: bool,
+	finished: bool,
+}
+
+struct Bus {
+	parent: Option<BusId>,
+	volume: f32,
+	fade: Option<Fade>,
+}
+
+pub struct Mixer {
+	sample_rate: u32,
+	voices: Vec<Voice>,
+	buses: Vec<Bus>,
+	listener: Listener,
+	next_id: u64,
+}
+impl Mixer {
+	pub const MASTER: BusId = BusId(0);
+
+	pub fn new(sample_rate: u32) -> Self {
+		let master = Bus { parent: None, volume: 1.0, fade: None };
+		Self { sample_rate, voices: vec![], buses: vec![master], listener: Listener::default(), next_id: 0 }
+	}
+
+	pub fn sample_rate(&self) -> u32 {
+		self.sample_rate
+	}
+
+	pub fn add_bus(&mut self, parent: BusId) -> BusId {
+		self.buses.push(Bus { parent: Some(parent), volume: 1.0, fade: None });
+		BusId(self.buses.len() - 1)
+	}
+
+	pub fn set_bus_volume(&mut self, bus: BusId, volume: f32) {
+		let bus = &mut self.buses[bus.0];
+		bus.volume = volume;
+		bus.fade = None;
+	}
+
+	pub fn fade_bus(&mut self, bus: BusId, volume: f32, secs: f32) {
+		let bus = &mut self.buses[bus.0];
+		bus.fade = Some(Fade::new(bus.volume, volume, secs, self.sample_rate, false));
+	}
+
+	pub fn listener(&self) -> &Listener {
+		&self.listener
+	}
+
+	pub fn set_listener(&mut self, listener: Listener) {
+		self.listener = listener;
+	}
+
+	pub fn play(&mut self, sound: &Sound, params: PlayParams) -> VoiceId {
+		self.add_voice(Source::Sound(sound.clone()), params)
+	}
+
+	pub fn play_stream(&mut self, stream: Stream, params: PlayParams) -> VoiceId {
+		self.add_voice(Source::Stream(Box::new(stream)), params)
+	}
+
+	fn add_voice(&mut self, source: Source, params: PlayParams) -> VoiceId {
+		let id = VoiceId(self.next_id);
+		self.next_id += 1;
+		let fade = if params.fade_in > 0.0 {
+			Some(Fade::new(0.0, 1.0, params.fade_in, self.sample_rate, false))
+		} else {
+			None
+		};
+		self.voices.push(Voice { id, source, params, cursor: 0.0, fade, paused: false, finished: false });
+		id
+	}
+
+	pub fn is_playing(&self, id: VoiceId) -> bool {
+		self.voices.iter().any(|voice| voice.id == id)
+	}
+
+	pub fn stop(&mut self, id: VoiceId) {
+		self.voices.retain(|voice| voice.id != id);
+	}
+
+	pub fn stop_all(&mut self) {
+		self.voices.clear();
+	}
+
+	pub fn set_paused(&mut self, id: VoiceId, paused: bool) {
+		self.with_voice(id, |voice| voice.paused = paused);
+	}
+
+	pub fn set_volume(&mut self, id: VoiceId, volume: f32) {
+		self.with_voice(id, |voice| voice.params.volume = volume);
+	}
+
+	pub fn set_pan(&mut self, id: VoiceId, pan: f32) {
+		self.with_voice(id, |voice| voice.params.pan = pan);
+	}
+
+	pub fn set_position(&mut self, id: VoiceId, position: Option<Vector2<f32>>) {
+		self.with_voice(id, |voice| voice.params.position = position);
+	}
+
+	/// Fades the voice's volume to `volume` times its base volume over `secs` seconds.
+	pub fn fade(&mut self, id: VoiceId, volume: f32, secs: f32) {
+		let sample_rate = self.sample_rate;
+		self.with_voice(id, |voice| {
+			let from = voice.fade.map(|fade| fade.value()).unwrap_or(1.0);
+			voice.fade = Some(Fade::new(from, volume, secs, sample_rate, false));
+		});
+	}
+
+	/// Fades the voice to silence over `secs` seconds, then stops it.
+	pub fn fade_out(&mut self, id: VoiceId, secs: f32) {
+		let sample_rate = self.sample_rate;
+		self.with_voice(id, |voice| {
+			let from = voice.fade.map(|fade| fade.value()).unwrap_or(1.0);
+			voice.fade = Some(Fade::new(from, 0.0, secs, sample_rate, true));
+		});
+	}
+
+	fn with_voice(&mut self, id: VoiceId, f: impl FnOnce(&mut Voice)) {
+		if let Some(voice) = self.voices.iter_mut().find(|voice| voice.id == id) {
+			f(voice);
+		}
+	}
+
+	/// Mixes every playing voice into `out`, which holds interleaved stereo samples.
+	pub fn mix(&mut self, out: &mut [f32]) {
+		for sample in out.iter_mut() {
+			*sample = 0.0;
+		}
+		let frames = out.len() / 2;
+
+		let bus_gains = self.bus_gains(frames as u64);
+		let sample_rate = self.sample_rate;
+		let listener = self.listener;
+
+		for voice in &mut self.voices {
+			if voice.paused {
+				continue;
+			}
+
+			let (spatial_gain, spatial_pan) = match voice.params.position {
+				Some(pos) => listener.spatialize(pos),
+				None => (1.0, 0.0),
+			};
+			let gain = voice.params.volume * bus_gains[voice.params.bus.0] * spatial_gain;
+			let pan = (voice.params.pan + spatial_pan).clamp(-1.0, 1.0);
+			// constant power panning
+			let angle = (pan + 1.0) * FRAC_PI_4;
+			let (left, right) = (angle.cos(), angle.sin());
+
+			let step = voice.source.sample_rate() as f64 / sample_rate as f64;
+			for frame in out.chunks_exact_mut(2) {
+				let idx = voice.cursor as usize;
+				let t = (voice.cursor - idx as f64) as f32;
+				let a = match voice.source.frame(idx, voice.params.looping) {
+					Some(a) => a,
+					None => {
+						voice.finished = true;
+						break;
+					},
+				};
+				let b = voice.source.frame(idx + 1, voice.params.looping).unwrap_or(a);
+
+				let fade = match &mut voice.fade {
+					Some(fade) => {
+						fade.elapsed += 1;
+						fade.value()
+					},
+					None => 1.0,
+				};
+				let gain = gain * fade;
+				frame[0] += (a[0] + (b[0] - a[0]) * t) * gain * left;
+				frame[1] += (a[1] + (b[1] - a[1]) * t) * gain * right;
+
+				voice.cursor += step;
+			}
+
+			if let Some(fade) = voice.fade {
+				if fade.done() {
+					if fade.stop {
+						voice.finished = true;
+					} else if fade.to == 1.0 {
+						voice.fade = None;
+					}
+				}
+			}
+			if let Source::Stream(stream) = &mut voice.source {
+				stream.release(voice.cursor as usize);
+			}
+		}
+
+		self.voices.retain(|voice| !voice.finished);
+	}
+
+	// effective gain of every bus for this block, advancing bus fades by `frames`
+	fn bus_gains(&mut self, frames: u64) -> Vec<f32> {
+		for bus in &mut self.buses {
+			if let Some(fade) = &mut bus.fade {
+				fade.elapsed += frames;
+				bus.volume = fade.value();
+				if fade.done() {
+					bus.fade = None;
+				}
+			}
+		}
+
+		// parents are always created before their children
+		let mut gains: Vec<f32> = Vec::with_capacity(self.buses.len());
+		for bus in &self.buses {
+			let parent = bus.parent.map(|parent| gains[parent.0]).unwrap_or(1.0);
+			gains.push(parent * bus.volume);
+		}
+		gains
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::audio::sound::tests::wav;
+	use std::f32::consts::FRAC_1_SQRT_2;
+
+	const RATE: u32 = 1000;
+
+	fn sound(samples: &[f32]) -> Sound {
+		Sound::decode(wav(1, RATE, samples)).unwrap()
+	}
+
+	fn mix(mixer: &mut Mixer, frames: usize) -> Vec<[f32; 2]> {
+		let mut out = vec![0.0; frames * 2];
+		mixer.mix(&mut out);
+		out.chunks(2).map(|frame| [frame[0], frame[1]]).collect()
+	}
+
+	fn assert_close(a: f32, b: f32) {
+		assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+	}
+
+	#[test]
+	fn fade_in() {
+		let mut mixer = Mixer::new(RATE);
+		// 10 frames
+		mixer.play(&sound(&[1.0; 20]), PlayParams { fade_in: 0.01, ..PlayParams::default() });
+		let out = mix(&mut mixer, 20);
+		for (i, frame) in out.iter().enumerate() {
+			let gain = ((i + 1) as f32 / 10.0).min(1.0);
+			assert_close(frame[0], gain * FRAC_1_SQRT_2);
+		}
+	}
+
+	#[test]
+	fn fade_out_stops() {
+		let mut mixer = Mixer::new(RATE);
+		let id = mixer.play(&sound(&[1.0; 4]), PlayParams { looping: true, ..PlayParams::default() });
+		mix(&mut mixer, 4);
+		mixer.fade_out(id, 0.01);
+		let out = mix(&mut mixer, 20);
+		for (i, frame) in out.iter().enumerate() {
+			let gain = (1.0 - (i + 1) as f32 / 10.0).max(0.0);
+			assert_close(frame[0], gain * FRAC_1_SQRT_2);
+		}
+		assert!(!mixer.is_playing(id));
+	}
+
+	#[test]
+	fn one_shot_ends() {
+		let mut mixer = Mixer::new(RATE);
+		let id = mixer.play(&sound(&[0.1, 0.2, 0.3, 0.4]), PlayParams::default());
+		let out = mix(&mut mixer, 8);
+		let left: Vec<_> = out.iter().map(|frame| frame[0] / FRAC_1_SQRT_2).collect();
+		for (&a, &b) in left.iter().zip(&[0.1, 0.2, 0.3, 0.4, 0.0, 0.0, 0.0, 0.0]) {
+			assert_close(a, b);
+		}
+		assert!(!mixer.is_playing(id));
+	}
+
+	#[test]
+	fn looping_repeats() {
+		let mut mixer = Mixer::new(RATE);
+		let id = mixer.play(&sound(&[0.1, 0.2, 0.3, 0.4]), PlayParams { looping: true, ..PlayParams::default() });
+		let out = mix(&mut mixer, 10);
+		let left: Vec<_> = out.iter().map(|frame| frame[0] / FRAC_1_SQRT_2).collect();
+		for (&a, &b) in left.iter().zip(&[0.1, 0.2, 0.3, 0.4, 0.1, 0.2, 0.3, 0.4, 0.1, 0.2]) {
+			assert_close(a, b);
+		}
+		assert!(mixer.is_playing(id));
+	}
+
+	#[test]
+	fn bus_gain_and_mute() {
+		let mut mixer = Mixer::new(RATE);
+		let music = mixer.add_bus(Mixer::MASTER);
+		let quiet = mixer.add_bus(music);
+		mixer.set_bus_volume(Mixer::MASTER, 0.5);
+		mixer.set_bus_volume(music, 0.5);
+		let id = mixer.play(&sound(&[1.0; 8]), PlayParams { bus: quiet, ..PlayParams::default() });
+		// bus volumes multiply down the tree
+		assert_close(mix(&mut mixer, 1)[0][0], 0.25 * FRAC_1_SQRT_2);
+
+		// muting a parent silences its children without stopping them
+		mixer.set_bus_volume(music, 0.0);
+		assert_eq!(mix(&mut mixer, 1)[0], [0.0, 0.0]);
+		assert!(mixer.is_playing(id));
+	}
+
+	#[test]
+	fn constant_power_pan() {
+		let mut mixer = Mixer::new(RATE);
+		let id = mixer.play(&sound(&[1.0; 8]), PlayParams { pan: -1.0, ..PlayParams::default() });
+		let [left, right] = mix(&mut mixer, 1)[0];
+		assert_close(left, 1.0);
+		assert_close(right, 0.0);
+
+		mixer.set_pan(id, 1.0);
+		let [left, right] = mix(&mut mixer, 1)[0];
+		assert_close(left, 0.0);
+		assert_close(right, 1.0);
+
+		// the total power stays the same anywhere in between
+		mixer.set_pan(id, 0.3);
+		let [left, right] = mix(&mut mixer, 1)[0];
+		assert!(right > left);
+		assert_close(left * left + right * right, 1.0);
+	}
+
+	#[test]
+	fn listener_distance() {
+		let mut mixer = Mixer::new(RATE);
+		let listener = Listener { pos: Vector2::new(10.0, 10.0), ..Listener::default() };
+		mixer.set_listener(listener);
+		let at = |y: f32| PlayParams { position: Some(Vector2::new(10.0, 10.0 + y)), ..PlayParams::default() };
+		let sound = sound(&[1.0; 8]);
+
+		// full volume within the reference distance, then rolling off, then silent past the max distance
+		for &(y, gain) in &[(32.0, 1.0), (64.0, 1.0), (128.0, 0.5), (256.0, 0.25), (3000.0, 0.0)] {
+			let id = mixer.play(&sound, at(y));
+			let [left, right] = mix(&mut mixer, 1)[0];
+			assert_close(left, gain * FRAC_1_SQRT_2);
+			assert_close(right, gain * FRAC_1_SQRT_2);
+			mixer.stop(id);
+		}
+
+		// sounds to the right lean right
+		let id = mixer.play(&sound, PlayParams { position: Some(Vector2::new(100.0, 10.0)), ..PlayParams::default() });
+		let [left, right] = mix(&mut mixer, 1)[0];
+		assert!(right > left);
+		mixer.stop(id);
+	}
+}
@@ -0,0 +1,268 @@
+use crate::{audio::decode::Decoder, fs::read_all_u8, threads::FILE_THREAD};
+use futures::task::SpawnExt;
+use log::{info, warn};
+use std::{
+	collections::VecDeque,
+	fs::File,
+	io::{self, BufReader, Cursor},
+	path::Path,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc, Mutex,
+	},
+};
+
+/// A fully decoded sound, cheap to clone and play many times at once. Use for short effects.
+#[derive(Clone)]
+pub struct Sound {
+	samples: Arc<[f32]>,
+	channels: u16,
+	sample_rate: u32,
+}
+impl Sound {
+	pub async fn load<P: AsRef<Path> + Send + 'static>(path: P) -> io::Result<Self> {
+		let name = path.as_ref().display().to_string();
+		let sound = Self::decode(read_all_u8(path).await?)?;
+		info!(target: "assets", "loaded sound {}", name);
+		Ok(sound)
+	}
+
+	pub fn decode(bytes: Vec<u8>) -> io::Result<Self> {
+		let mut decoder = Decoder::new(Box::new(Cursor::new(bytes)))?;
+		let mut samples = vec![];
+		while decoder.read_chunk(&mut samples)? {}
+		Ok(Self { samples: samples.into(), channels: decoder.channels(), sample_rate: decoder.sample_rate() })
+	}
+
+	pub fn channels(&self) -> u16 {
+		self.channels
+	}
+
+	pub fn sample_rate(&self) -> u32 {
+		self.sample_rate
+	}
+
+	pub fn frames(&self) -> usize {
+		self.samples.len() / self.channels as usize
+	}
+
+	pub(super) fn frame(&self, idx: usize) -> [f32; 2] {
+		stereo(&self.samples[idx * self.channels as usize..], self.channels)
+	}
+}
+
+/// A sound decoded incrementally on the file thread while it plays. Use for music and other long sounds.
+pub struct Stream {
+	shared: Arc<Shared>,
+	channels: u16,
+	sample_rate: u32,
+}
+struct Shared {
+	// only locked on the file thread, so the mixer never waits on reading or decoding
+	decoder: Mutex<Decoder>,
+	ring: Mutex<Ring>,
+	// set while a refill is queued or running
+	filling: AtomicBool,
+}
+struct Ring {
+	samples: VecDeque<f32>,
+	// frame index of samples[0]
+	start: usize,
+	looping: bool,
+	ended: bool,
+}
+impl Stream {
+	pub async fn open<P: AsRef<Path> + Send + 'static>(path: P) -> io::Result<Self> {
+		let decoder = FILE_THREAD
+			.lock()
+			.unwrap()
+			.spawn_with_handle(async move { Decoder::new(Box::new(BufReader::new(File::open(path)?))) })
+			.unwrap();
+		Ok(Self::new(decoder.await?))
+	}
+
+	fn new(decoder: Decoder) -> Self {
+		let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+		let ring = Ring { samples: VecDeque::new(), start: 0, looping: false, ended: false };
+		let shared = Shared { decoder: Mutex::new(decoder), ring: Mutex::new(ring), filling: AtomicBool::new(false) };
+		let stream = Self { shared: Arc::new(shared), channels, sample_rate };
+		stream.fill();
+		stream
+	}
+
+	pub fn channels(&self) -> u16 {
+		self.channels
+	}
+
+	pub fn sample_rate(&self) -> u32 {
+		self.sample_rate
+	}
+
+	/// Frame indices keep increasing across loops, so a looping stream never has to seek backwards in its buffer.
+	/// Frames the file thread hasn't decoded yet are silent, rather than stalling the mixer.
+	pub(super) fn frame(&mut self, idx: usize, looping: bool) -> Option<[f32; 2]> {
+		let channels = self.channels as usize;
+		let mut ring = self.shared.ring.lock().unwrap();
+		// the first fill runs before the voice says whether it loops, and may already have reached the end
+		let resume = looping && !ring.looping && ring.ended;
+		ring.looping = looping;
+		if resume {
+			ring.ended = false;
+			drop(ring);
+			self.fill();
+			ring = self.shared.ring.lock().unwrap();
+		}
+		if idx >= ring.start + ring.samples.len() / channels {
+			return if ring.ended { None } else { Some([0.0; 2]) };
+		}
+
+		let start = (idx - ring.start) * channels;
+		let right = if channels == 1 { start } else { start + 1 };
+		Some([ring.samples[start], ring.samples[right]])
+	}
+
+	/// Drops buffered frames before `idx`, which the voice will never read again, and decodes more once the buffer runs
+	/// low.
+	pub(super) fn release(&mut self, idx: usize) {
+		let channels = self.channels as usize;
+		let mut ring = self.shared.ring.lock().unwrap();
+		let frames = idx.saturating_sub(ring.start).min(ring.samples.len() / channels);
+		ring.samples.drain(..frames * channels);
+		ring.start += frames;
+		let low = !ring.ended && ring.samples.len() / channels < BUFFER_FRAMES / 2;
+		drop(ring);
+		if low {
+			self.fill();
+		}
+	}
+
+	// queues decoding up to a full buffer on the file thread, unless it's already queued
+	fn fill(&self) {
+		if self.shared.filling.swap(true, Ordering::AcqRel) {
+			return;
+		}
+		let (shared, channels) = (self.shared.clone(), self.channels as usize);
+		FILE_THREAD
+			.lock()
+			.unwrap()
+			.spawn(async move {
+				shared.decode_ahead(channels);
+				shared.filling.store(false, Ordering::Release);
+			})
+			.unwrap();
+	}
+}
+impl Shared {
+	fn decode_ahead(&self, channels: usize) {
+		let mut decoder = self.decoder.lock().unwrap();
+		let mut chunk = vec![];
+		// a loop whose rewind decodes nothing would otherwise rewind forever
+		let mut rewound = false;
+		loop {
+			let looping = {
+				let ring = self.ring.lock().unwrap();
+				if ring.ended || ring.samples.len() / channels >= BUFFER_FRAMES {
+					return;
+				}
+				ring.looping
+			};
+
+			chunk.clear();
+			let ended = match decoder.read_chunk(&mut chunk) {
+				Ok(true) => {
+					rewound = false;
+					false
+				},
+				Ok(false) if looping && !rewound => {
+					rewound = true;
+					decoder.rewind().is_err()
+				},
+				Ok(false) => true,
+				Err(err) => {
+					warn!(target: "assets", "stopped streaming: {}", err);
+					true
+				},
+			};
+			let mut ring = self.ring.lock().unwrap();
+			ring.samples.extend(&chunk);
+			ring.ended = ended;
+		}
+	}
+}
+
+// frames decoded ahead of the mixer, about 3 seconds at 44.1 kHz
+const BUFFER_FRAMES: usize = 1 << 17;
+
+fn stereo(samples: &[f32], channels: u16) -> [f32; 2] {
+	match channels {
+		1 => [samples[0], samples[0]],
+		_ => [samples[0], samples[1]],
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use crate::audio::{backend::NullBackend, mixer::PlayParams, Audio};
+	use hound::{SampleFormat, WavSpec, WavWriter};
+	use std::time::Duration;
+
+	/// A float WAV file holding `samples`, interleaved over `channels`.
+	pub(crate) fn wav(channels: u16, sample_rate: u32, samples: &[f32]) -> Vec<u8> {
+		let spec = WavSpec { channels, sample_rate, bits_per_sample: 32, sample_format: SampleFormat::Float };
+		let mut bytes = Cursor::new(vec![]);
+		let mut writer = WavWriter::new(&mut bytes, spec).unwrap();
+		for &sample in samples {
+			writer.write_sample(sample).unwrap();
+		}
+		writer.finalize().unwrap();
+		bytes.into_inner()
+	}
+
+	#[test]
+	fn decodes_frames() {
+		let sound = Sound::decode(wav(2, 1000, &[0.1, 0.2, 0.3, 0.4])).unwrap();
+		assert_eq!((sound.channels(), sound.sample_rate(), sound.frames()), (2, 1000, 2));
+		assert_eq!(sound.frame(1), [0.3, 0.4]);
+		// mono plays on both sides
+		let sound = Sound::decode(wav(1, 1000, &[0.5])).unwrap();
+		assert_eq!(sound.frame(0), [0.5, 0.5]);
+	}
+
+	#[test]
+	fn stream_ends() {
+		let decoder = Decoder::new(Box::new(Cursor::new(wav(1, 1000, &[0.5; 30])))).unwrap();
+		let stream = Stream::new(decoder);
+		// decode the whole file now rather than racing the file thread
+		stream.shared.decode_ahead(1);
+
+		let mut audio = Audio::new(NullBackend::new(1000));
+		let id = audio.mixer().play_stream(stream, PlayParams::default());
+		audio.update(Duration::from_millis(20)).unwrap();
+		assert!(audio.mixer().is_playing(id));
+		audio.update(Duration::from_millis(20)).unwrap();
+		assert!(!audio.mixer().is_playing(id));
+	}
+
+	#[test]
+	fn short_stream_loops() {
+		let decoder = Decoder::new(Box::new(Cursor::new(wav(1, 1000, &[0.5; 30])))).unwrap();
+		let stream = Stream::new(decoder);
+		let shared = stream.shared.clone();
+		// the whole file fits in the first fill, which doesn't know the voice loops yet
+		shared.decode_ahead(1);
+		assert!(shared.ring.lock().unwrap().ended);
+
+		let mut audio = Audio::new(NullBackend::new(1000));
+		let id = audio.mixer().play_stream(stream, PlayParams { looping: true, ..PlayParams::default() });
+		audio.update(Duration::from_millis(20)).unwrap();
+		shared.decode_ahead(1);
+		{
+			let ring = shared.ring.lock().unwrap();
+			assert!(!ring.ended);
+			assert!(ring.start + ring.samples.len() > 60);
+		}
+		audio.update(Duration::from_millis(100)).unwrap();
+		assert!(audio.mixer().is_playing(id));
+	}
+}
@@ -1,16 +1,28 @@
 // TODO: replace this file with tokio
 
-use crate::threads::FILE_THREAD;
-use byteorder::{NativeEndian, ReadBytesExt};
+use crate::{profile, threads::FILE_THREAD};
+#[cfg(feature = "hot-reload")]
+use byteorder::NativeEndian;
+use byteorder::ReadBytesExt;
 use futures::{future::RemoteHandle, task::SpawnExt};
-use std::{fs::File, io, mem::size_of, path::Path};
+use log::debug;
+#[cfg(feature = "hot-reload")]
+use std::mem::size_of;
+use std::{
+	fs::File,
+	io::{self, Write},
+	path::Path,
+};
 
+// only shaders rebuilt while the game is running are read as words
+#[cfg(feature = "hot-reload")]
 pub fn read_all_u32<P: AsRef<Path> + Send + 'static>(path: P) -> RemoteHandle<io::Result<Vec<u32>>> {
 	FILE_THREAD
 		.lock()
 		.unwrap()
 		.spawn_with_handle(async move {
-			let mut file = File::open(path)?;
+			let _scope = profile::scope("read_all_u32");
+			let mut file = File::open(&path)?;
 			let len = file.metadata()?.len() as usize;
 			assert!(len % 4 == 0);
 			let mut source = Vec::with_capacity(len / size_of::<u32>());
@@ -21,6 +33,7 @@ pub fn read_all_u32<P: AsRef<Path> + Send + 'static>(path: P) -> RemoteHandle<io
 					Err(err) => return Err(err),
 				}
 			}
+			debug!("read {} words from {}", source.len(), path.as_ref().display());
 			Ok::<_, io::Error>(source)
 		})
 		.unwrap()
@@ -31,7 +44,8 @@ pub fn read_all_u8<P: AsRef<Path> + Send + 'static>(path: P) -> RemoteHandle<io:
 		.lock()
 		.unwrap()
 		.spawn_with_handle(async move {
-			let mut file = File::open(path)?;
+			let _scope = profile::scope("read_all_u8");
+			let mut file = File::open(&path)?;
 			let mut source = Vec::with_capacity(file.metadata()?.len() as usize);
 			loop {
 				match file.read_u8() {
@@ -40,7 +54,21 @@ pub fn read_all_u8<P: AsRef<Path> + Send + 'static>(path: P) -> RemoteHandle<io:
 					Err(err) => return Err(err),
 				}
 			}
+			debug!("read {} bytes from {}", source.len(), path.as_ref().display());
 			Ok::<_, io::Error>(source)
 		})
 		.unwrap()
 }
+
+pub fn write_all<P: AsRef<Path> + Send + 'static>(path: P, data: Vec<u8>) -> RemoteHandle<io::Result<()>> {
+	FILE_THREAD
+		.lock()
+		.unwrap()
+		.spawn_with_handle(async move {
+			let _scope = profile::scope("write_all");
+			File::create(&path)?.write_all(&data)?;
+			debug!("wrote {} bytes to {}", data.len(), path.as_ref().display());
+			Ok::<_, io::Error>(())
+		})
+		.unwrap()
+}
@@ -1,26 +1,54 @@
+pub mod bindless;
+pub mod camera;
+pub mod debug;
+pub mod graph;
 pub mod gui;
+pub mod light;
+pub mod material;
+pub mod particles;
+pub mod pipeline;
+pub mod post;
+pub mod profile;
+pub mod render_texture;
+pub mod sampler;
+pub mod shader;
+pub mod shape;
+pub mod sprite;
 pub mod texture;
+pub mod tilemap;
+pub mod upscale;
+pub mod validation;
+pub mod vertex;
 pub mod window;
 
-use crate::fs::{read_all_u32, read_all_u8};
-use gui::font::Font;
-use memoffset::offset_of;
+use crate::fs::{read_all_u8, write_all};
+use bindless::{TextureArray, MAX_TEXTURES};
+use futures::executor::block_on;
+use log::{debug, info, warn};
 use nalgebra::Vector2;
-use std::{iter::once, mem::size_of, sync::Arc};
+use pipeline::PipelineCache;
+use sampler::{SamplerCache, SamplerDesc};
+use shader::ShaderProgram;
+use std::{
+	collections::HashMap,
+	env,
+	io,
+	path::PathBuf,
+	sync::{Arc, Mutex},
+};
+use texture::{upload_image, Subtex};
 use typenum::{B0, B1};
+use vertex::vertex_input;
 use vulkan::{
 	buffer::Buffer,
 	command::CommandPool,
-	descriptor::{DescriptorPool, DescriptorSet, DescriptorSetLayout, DescriptorType},
+	debug::DebugUtilsMessenger,
 	device::{BufferUsageFlags, Device, Queue},
-	image::{
-		Format, Image, ImageAspectFlags, ImageLayout, ImageSubresourceRange, ImageType, ImageUsageFlags, ImageView,
-		Sampler,
-	},
+	image::{Format, Sampler},
 	instance::{Instance, Version},
-	physical_device::PhysicalDevice,
-	pipeline::{PipelineLayout, VertexDesc, VertexInputAttributeDescription},
-	shader::{ShaderModule, ShaderStageFlags},
+	physical_device::{
+		MemoryHeapFlags, PhysicalDevice, PhysicalDeviceDescriptorIndexingFeatures, PhysicalDeviceFeatures,
+	},
 	sync::GpuFuture,
 	Vulkan,
 };
@@ -29,18 +57,30 @@ pub struct Gfx {
 	instance: Arc<Instance>,
 	device: Arc<Device>,
 	queue: Arc<Queue>,
-	layout: Arc<PipelineLayout>,
+	cmdpool: Arc<CommandPool>,
 	verts: Arc<Buffer<[TriangleVertex]>>,
-	desc_set: Arc<DescriptorSet>,
-	vshader: Arc<ShaderModule>,
-	fshader: Arc<ShaderModule>,
+	// drawn by sprites without a texture of their own
+	default_texture: Subtex,
+	programs: Mutex<HashMap<(String, String), Arc<ShaderProgram>>>,
+	pipelines: PipelineCache,
+	samplers: SamplerCache,
+	texture_array: Option<Arc<TextureArray>>,
+	// nanoseconds per timestamp tick
+	timestamp_period: Option<f32>,
+	_messenger: Option<DebugUtilsMessenger>,
 }
 impl Gfx {
+	/// Validates if `validation::default_enabled`.
 	pub async fn new() -> Arc<Self> {
+		Self::with_validation(validation::default_enabled()).await
+	}
+
+	/// With `validation`, enables the validation layers if they're installed, logs their messages and names objects
+	/// for debuggers.
+	pub async fn with_validation(validation: bool) -> Arc<Self> {
 		// start reading files now to use later
 		let img = read_all_u8("assets/colors.png");
-		let vert_spv = read_all_u32("build/shader.vert.spv");
-		let frag_spv = read_all_u32("build/shader.frag.spv");
+		let pipeline_cache = pipeline_cache_path().map(read_all_u8);
 
 		let vulkan = Vulkan::new().unwrap();
 
@@ -50,36 +90,63 @@ impl Gfx {
 			env!("CARGO_PKG_VERSION_MINOR").parse().unwrap(),
 			env!("CARGO_PKG_VERSION_PATCH").parse().unwrap(),
 		);
-		let instance = Instance::new(vulkan, name, version);
-
-		let (device, mut queue) = {
-			let physical_device = PhysicalDevice::enumerate(&instance).next().unwrap();
-
-			let queue_family = physical_device
+		let (layers, extensions) =
+			if validation { validation::instance_requirements(&vulkan) } else { (vec![], vec![]) };
+		let debug_utils = extensions.contains(&validation::DEBUG_UTILS_EXTENSION);
+		let instance = Instance::with_layers(vulkan, name, version, &layers, &extensions);
+		let messenger = if debug_utils { Some(validation::messenger(&instance)) } else { None };
+		info!("validation {}", if layers.is_empty() { "off" } else { "on" });
+
+		let (device, mut queue, max_anisotropy, bindless, timestamp_period) = {
+			let physical_device = PhysicalDevice::enumerate(&instance).next().expect("no Vulkan device");
+			let props = physical_device.get_properties();
+			info!("using {} ({:?}), Vulkan {}", props.device_name(), props.device_type, props.api_version());
+			let anisotropy = physical_device.get_features().sampler_anisotropy != 0;
+			let max_anisotropy = if anisotropy { props.limits.max_sampler_anisotropy } else { 1.0 };
+			let indexing = physical_device.get_descriptor_indexing_features();
+			let indexing_limits = physical_device.get_descriptor_indexing_properties();
+			let bindless = indexing.runtime_descriptor_array != 0
+				&& indexing.descriptor_binding_partially_bound != 0
+				&& indexing.descriptor_binding_sampled_image_update_after_bind != 0
+				&& indexing.shader_sampled_image_array_non_uniform_indexing != 0
+				&& indexing_limits.max_descriptor_set_update_after_bind_sampled_images >= MAX_TEXTURES;
+
+			let queue_props = physical_device
 				.get_queue_family_properties()
 				.filter(|props| props.queue_flags().graphics())
 				.next()
-				.unwrap()
-				.family();
-
-			let (device, mut queues) = Device::new(physical_device, vec![(queue_family, &[1.0][..])]);
-			(device, queues.next().unwrap())
+				.unwrap();
+			// queues without valid bits can't write timestamps
+			let timestamp_period =
+				if queue_props.timestamp_valid_bits() > 0 { Some(props.limits.timestamp_period) } else { None };
+			let queue_family = queue_props.family();
+
+			// only what's used, since some features cost performance even when unused
+			let features = PhysicalDeviceFeatures::builder().sampler_anisotropy(anisotropy).build();
+			let indexing = PhysicalDeviceDescriptorIndexingFeatures::builder()
+				.runtime_descriptor_array(bindless)
+				.descriptor_binding_partially_bound(bindless)
+				.descriptor_binding_sampled_image_update_after_bind(bindless)
+				.shader_sampled_image_array_non_uniform_indexing(bindless)
+				.build();
+			let queues = vec![(queue_family, &[1.0][..])];
+			let (device, mut queues) = Device::with_features(physical_device, queues, features, indexing);
+			debug!(
+				"anisotropy {}, texture array {}, timestamps {}",
+				max_anisotropy,
+				bindless,
+				timestamp_period.is_some()
+			);
+			(device, queues.next().unwrap(), max_anisotropy, bindless, timestamp_period)
 		};
 
 		let cmdpool = CommandPool::new(device.clone(), queue.family().clone(), true);
 
 		let img = image::load_from_memory(&img.await.unwrap()).unwrap().into_rgba();
-		let (width, height) = (img.width(), img.height());
-		let data: Vec<_> = img.pixels().cloned().collect();
-		let pixels = Buffer::init_slice(device.clone(), data.len() as _, B1, BufferUsageFlags::TRANSFER_SRC)
-			.copy_from_slice(&data);
-		let format = Format::R8G8B8A8_UNORM;
-		let usage = ImageUsageFlags::TRANSFER_DST | ImageUsageFlags::SAMPLED;
-		let (image, image_future) = Image::init(device.clone(), ImageType::TYPE_2D, width, height, 1, format, usage)
-			.copy_from_buffer(&queue, &cmdpool, pixels);
-		let subresource =
-			ImageSubresourceRange::builder().aspect_mask(ImageAspectFlags::COLOR).level_count(1).layer_count(1).build();
-		let image_view = ImageView::new(image, format, subresource);
+		let (width, height) = img.dimensions();
+		let (image_view, image_future) = upload_image(&queue, &cmdpool, img);
+		validation::set_name(&device, &*image_view, "assets/colors.png");
+		let default_texture = Subtex::new(image_view, width, height);
 
 		let data = [
 			TriangleVertex { pos: [0.0, 0.0].into() },
@@ -98,64 +165,89 @@ impl Gfx {
 			BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::VERTEX_BUFFER,
 		)
 		.copy_from_buffer(&mut queue, &cmdpool, verts);
+		validation::set_name(&device, &*verts, "quad verts");
 
 		let future = image_future.join(verts_future).then_signal_fence();
 
-		let sampler = Sampler::new(device.clone());
+		let samplers = SamplerCache::new(device.clone(), max_anisotropy);
+		let texture_array = if bindless { Some(TextureArray::new(device.clone())) } else { None };
+		future.wait();
 
-		let desc_layout = DescriptorSetLayout::builder(device.clone())
-			.desc(
-				DescriptorType::COMBINED_IMAGE_SAMPLER,
-				1,
-				ShaderStageFlags::FRAGMENT | ShaderStageFlags::VERTEX,
-				once(sampler),
-			)
-			.build();
+		let pipeline_cache = match pipeline_cache {
+			Ok(data) => data.await.unwrap_or_default(),
+			Err(_) => vec![],
+		};
+		let pipelines = PipelineCache::new(device.clone(), &pipeline_cache);
+
+		Arc::new(Self {
+			instance,
+			device,
+			queue,
+			cmdpool,
+			verts,
+			default_texture,
+			programs: Mutex::default(),
+			pipelines,
+			samplers,
+			texture_array,
+			timestamp_period,
+			_messenger: messenger,
+		})
+	}
 
-		let layout = PipelineLayout::new(
-			device.clone(),
-			vec![desc_layout.clone()],
-			once((ShaderStageFlags::VERTEX, 0, size_of::<[Vector2<f32>; 2]>() as _)),
-		);
+	/// A program compiled by the build script, loaded once and shared. Fails if there's no such shader or variant.
+	pub async fn program(&self, name: &str, variant: &str) -> io::Result<Arc<ShaderProgram>> {
+		let key = (name.to_owned(), variant.to_owned());
+		if let Some(program) = self.programs.lock().unwrap().get(&key) {
+			return Ok(program.clone());
+		}
 
-		let desc_pool =
-			DescriptorPool::new(device.clone(), 1, vec![(DescriptorType::COMBINED_IMAGE_SAMPLER, 1).into()]);
+		let program = ShaderProgram::load(self.device.clone(), name, variant).await?;
+		Ok(self.programs.lock().unwrap().entry(key).or_insert(program).clone())
+	}
 
-		let desc_set = DescriptorSet::alloc(desc_pool, vec![desc_layout]).next().unwrap();
-		DescriptorSet::update_builder(&device)
-			.write(
-				&desc_set,
-				0,
-				DescriptorType::COMBINED_IMAGE_SAMPLER,
-				once((None, image_view, ImageLayout::SHADER_READ_ONLY_OPTIMAL)),
-			)
-			.submit();
+	/// What sprites without a texture of their own draw, `assets/colors.png`.
+	pub fn default_texture(&self) -> &Subtex {
+		&self.default_texture
+	}
 
-		let _font = Font::new(queue.clone(), cmdpool.clone());
+	/// A sampler shared with every other texture read the same way.
+	pub fn sampler(&self, desc: SamplerDesc) -> Arc<Sampler> {
+		self.samplers.get(desc)
+	}
 
-		future.wait();
+	/// The texture array renderers batch draws with, if the device supports descriptor indexing.
+	pub fn texture_array(&self) -> Option<&Arc<TextureArray>> {
+		self.texture_array.as_ref()
+	}
 
-		let vshader = unsafe { ShaderModule::new(device.clone(), &vert_spv.await.unwrap()) };
-		let fshader = unsafe { ShaderModule::new(device.clone(), &frag_spv.await.unwrap()) };
+	/// Bytes used and the budget of each device-local memory heap, if the driver reports them.
+	pub fn memory_usage(&self) -> Option<Vec<(u64, u64)>> {
+		let physical_device = self.device.physical_device();
+		let budget = physical_device.get_memory_budget()?;
+		let props = physical_device.get_memory_properties();
+		let heaps = props.memory_heaps[..props.memory_heap_count as usize].iter().enumerate();
+		let local = heaps.filter(|(_, heap)| heap.flags.contains(MemoryHeapFlags::DEVICE_LOCAL));
+		Some(local.map(|(i, _)| (budget.heap_usage[i], budget.heap_budget[i])).collect())
+	}
 
-		Arc::new(Self { instance, device, queue, layout, verts, desc_set, vshader, fshader })
+	/// Writes the pipeline cache beside the executable. The cache only speeds up the next start, so failing to write it
+	/// is logged rather than returned.
+	pub fn save_pipeline_cache(&self) {
+		let res = pipeline_cache_path().and_then(|path| block_on(write_all(path, self.pipelines.data())));
+		if let Err(err) = res {
+			warn!(target: "fs", "couldn't save the pipeline cache: {}", err);
+		}
 	}
 }
 
-#[derive(Clone, Copy, Debug)]
-#[repr(C)]
-pub struct TriangleVertex {
-	pub pos: Vector2<f32>,
+fn pipeline_cache_path() -> io::Result<PathBuf> {
+	Ok(env::current_exe()?.with_file_name("pipeline_cache.bin"))
 }
-impl VertexDesc for TriangleVertex {
-	fn attribute_descs() -> Vec<VertexInputAttributeDescription> {
-		vec![
-			VertexInputAttributeDescription::builder()
-				.binding(0)
-				.location(0)
-				.format(Format::R32G32_SFLOAT)
-				.offset(offset_of!(Self, pos) as _)
-				.build(),
-		]
+
+vertex_input! {
+	#[derive(Clone, Copy, Debug)]
+	pub struct TriangleVertex {
+		pub pos: Vector2<f32>,
 	}
 }
@@ -0,0 +1,277 @@
+use crate::gfx::{
+	material::{Material, MaterialParam},
+	sampler::SamplerDesc,
+	shader::ShaderProgram,
+	Gfx,
+};
+use std::{
+	collections::{hash_map::Entry, HashMap, HashSet},
+	io,
+	iter::once,
+	mem, slice,
+	sync::{Arc, Mutex},
+};
+use vulkan::{
+	descriptor::{
+		DescriptorBindingFlags, DescriptorPool, DescriptorPoolCreateFlags, DescriptorSet, DescriptorSetLayout,
+		DescriptorSetLayoutCreateFlags, DescriptorType,
+	},
+	device::Device,
+	image::{ImageLayout, ImageView, Sampler},
+	shader::ShaderStageFlags,
+};
+
+/// Slots in the texture array. Shaders declare it unsized, as `sampler2D textures[]`.
+pub const MAX_TEXTURES: u32 = 4096;
+
+/// The layout of a set holding only the texture array. Programs get it for any set with an unsized array, so their
+/// pipelines can bind `TextureArray::desc_set`.
+pub(super) fn array_layout(device: Arc<Device>) -> Arc<DescriptorSetLayout> {
+	// slots are written while frames reading other slots are in flight, and most are never written at all
+	let flags = DescriptorBindingFlags::PARTIALLY_BOUND | DescriptorBindingFlags::UPDATE_AFTER_BIND;
+	DescriptorSetLayout::builder(device)
+		.flags(DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+		.desc_with_flags(
+			DescriptorType::COMBINED_IMAGE_SAMPLER,
+			MAX_TEXTURES,
+			ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
+			vec![],
+			flags,
+		)
+		.build()
+}
+
+/// Every texture drawn through it in one descriptor set, so draws don't break when the texture changes. Only
+/// available on devices supporting descriptor indexing, see `Gfx::texture_array`.
+pub struct TextureArray {
+	device: Arc<Device>,
+	desc_set: Arc<DescriptorSet>,
+	slots: Mutex<Slots>,
+}
+impl TextureArray {
+	pub(super) fn new(device: Arc<Device>) -> Arc<Self> {
+		let layout = array_layout(device.clone());
+		let sizes = vec![(DescriptorType::COMBINED_IMAGE_SAMPLER, MAX_TEXTURES).into()];
+		let pool = DescriptorPool::with_flags(device.clone(), 1, sizes, DescriptorPoolCreateFlags::UPDATE_AFTER_BIND);
+		let desc_set = DescriptorSet::alloc(pool, vec![layout]).next().unwrap();
+		Arc::new(Self { device, desc_set, slots: Mutex::default() })
+	}
+
+	pub fn desc_set(&self) -> &Arc<DescriptorSet> {
+		&self.desc_set
+	}
+
+	/// Puts a texture in a free slot until the returned handle is dropped. Fails if every slot is taken, counting
+	/// released slots frames in flight may still read.
+	pub fn register(self: &Arc<Self>, image_view: Arc<ImageView>, sampler: Arc<Sampler>) -> io::Result<TextureSlot> {
+		let mut slots = self.slots.lock().unwrap();
+		let slot = slots
+			.alloc()
+			.ok_or_else(|| io::Error::other(format!("more than {} textures registered", MAX_TEXTURES)))?;
+		DescriptorSet::update_builder(&self.device)
+			.write_at(
+				&self.desc_set,
+				0,
+				slot,
+				DescriptorType::COMBINED_IMAGE_SAMPLER,
+				once((Some(sampler), image_view.clone(), ImageLayout::SHADER_READ_ONLY_OPTIMAL)),
+			)
+			.submit();
+		slots.textures.insert(slot, image_view);
+		Ok(TextureSlot { array: self.clone(), slot })
+	}
+
+	/// Slots holding a texture, including released ones frames in flight may still read.
+	pub fn used(&self) -> u32 {
+		self.slots.lock().unwrap().used()
+	}
+
+	/// Called once per frame. Slots released more than `frames_in_flight` frames ago can be reused, since no frame
+	/// still reads them.
+	pub fn next_frame(&self, frames_in_flight: usize) {
+		self.slots.lock().unwrap().next_frame(frames_in_flight);
+	}
+
+	fn release(&self, slot: u32) {
+		// the image stays alive until the slot is rewritten, in case a frame in flight still samples it
+		self.slots.lock().unwrap().release(slot);
+	}
+}
+
+#[derive(Default)]
+struct Slots {
+	next: u32,
+	free: Vec<u32>,
+	// released slots and the frame they were released in
+	retired: Vec<(u32, u64)>,
+	textures: HashMap<u32, Arc<ImageView>>,
+	frame: u64,
+}
+impl Slots {
+	// a free slot, or `None` once every slot is taken
+	fn alloc(&mut self) -> Option<u32> {
+		if let Some(slot) = self.free.pop() {
+			return Some(slot);
+		}
+		if self.next == MAX_TEXTURES {
+			return None;
+		}
+		self.next += 1;
+		Some(self.next - 1)
+	}
+
+	fn release(&mut self, slot: u32) {
+		let frame = self.frame;
+		self.retired.push((slot, frame));
+	}
+
+	fn used(&self) -> u32 {
+		self.next - self.free.len() as u32
+	}
+
+	fn next_frame(&mut self, frames_in_flight: usize) {
+		self.frame += 1;
+		let frame = self.frame;
+		let (ready, retired): (Vec<_>, _) = mem::take(&mut self.retired)
+			.into_iter()
+			.partition(|&(_, released)| frame - released > frames_in_flight as u64);
+		self.retired = retired;
+		self.free.extend(ready.into_iter().map(|(slot, _)| slot));
+	}
+}
+
+/// A texture's place in a `TextureArray`, given to shaders as a vertex attribute.
+pub struct TextureSlot {
+	array: Arc<TextureArray>,
+	slot: u32,
+}
+impl TextureSlot {
+	pub fn index(&self) -> u32 {
+		self.slot
+	}
+}
+impl Drop for TextureSlot {
+	fn drop(&mut self) {
+		self.array.release(self.slot);
+	}
+}
+
+/// Identifies a texture as read through a sampler.
+pub(super) type TextureKey = (usize, SamplerDesc);
+
+/// The textures of a renderer drawing from many. With a texture array each gets a slot and every batch binds the same
+/// set; without, each gets a material and batches break wherever the texture changes.
+pub(super) struct Textures {
+	array: Option<Arc<TextureArray>>,
+	// only used without an array
+	program: Arc<ShaderProgram>,
+	textures: HashMap<TextureKey, Binding>,
+}
+enum Binding {
+	Material(Arc<ImageView>, Material),
+	Slot(TextureSlot),
+}
+impl Textures {
+	/// `program` reads the texture as `tex` when there's no texture array.
+	pub fn new(gfx: &Gfx, program: Arc<ShaderProgram>) -> Self {
+		Self { array: gfx.texture_array().cloned(), program, textures: HashMap::new() }
+	}
+
+	/// What batches drawing the texture are keyed by, `None` if every texture shares the array.
+	pub fn batch_key(&self, key: TextureKey) -> Option<TextureKey> {
+		Some(key).filter(|_| self.array.is_none())
+	}
+
+	/// Returns the texture's key and the slot vertices should index, 0 without an array. Fails if the texture array is
+	/// full, or the program can't read the texture without one.
+	pub fn get(
+		&mut self,
+		gfx: &Gfx,
+		image_view: &Arc<ImageView>,
+		sampler: SamplerDesc,
+	) -> io::Result<(TextureKey, u32)> {
+		let key = (Arc::as_ptr(image_view) as usize, sampler);
+		let binding = match self.textures.entry(key) {
+			Entry::Occupied(entry) => entry.into_mut(),
+			Entry::Vacant(entry) => {
+				let binding = match &self.array {
+					Some(array) => Binding::Slot(array.register(image_view.clone(), gfx.sampler(sampler))?),
+					None => {
+						let mut params = HashMap::new();
+						let param = MaterialParam::Texture(image_view.clone(), gfx.sampler(sampler));
+						params.insert("tex".to_owned(), param);
+						Binding::Material(image_view.clone(), Material::new(self.program.clone(), params)?)
+					},
+				};
+				entry.insert(binding)
+			},
+		};
+		match binding {
+			Binding::Material(..) => Ok((key, 0)),
+			Binding::Slot(slot) => Ok((key, slot.index())),
+		}
+	}
+
+	/// Only valid for batch keys of textures returned by `get` since the last `retain`.
+	pub fn desc_sets(&self, key: Option<TextureKey>) -> &[Arc<DescriptorSet>] {
+		match key.map(|key| &self.textures[&key]) {
+			Some(Binding::Material(_, material)) => material.desc_sets(),
+			_ => slice::from_ref(self.array.as_ref().unwrap().desc_set()),
+		}
+	}
+
+	/// Drops every texture not in `used`.
+	pub fn retain(&mut self, used: &HashSet<TextureKey>) {
+		self.textures.retain(|key, _| used.contains(key));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn released_slots_wait_for_frames_in_flight() {
+		let mut slots = Slots::default();
+		let (a, b) = (slots.alloc().unwrap(), slots.alloc().unwrap());
+		assert_eq!((a, b), (0, 1));
+		slots.release(a);
+		// still counted, since a frame in flight may read it
+		assert_eq!(slots.used(), 2);
+		slots.next_frame(2);
+		slots.next_frame(2);
+		assert_eq!(slots.alloc(), Some(2));
+		slots.next_frame(2);
+		assert_eq!(slots.used(), 2);
+		assert_eq!(slots.alloc(), Some(a));
+	}
+
+	#[test]
+	fn free_slots_reused_before_new_ones() {
+		let mut slots = Slots::default();
+		for _ in 0..4 {
+			slots.alloc();
+		}
+		slots.release(1);
+		slots.release(3);
+		slots.next_frame(0);
+		assert_eq!(slots.used(), 2);
+		let mut reused = vec![slots.alloc().unwrap(), slots.alloc().unwrap()];
+		reused.sort();
+		assert_eq!(reused, [1, 3]);
+		assert_eq!(slots.alloc(), Some(4));
+	}
+
+	#[test]
+	fn full() {
+		let mut slots = Slots::default();
+		for slot in 0..MAX_TEXTURES {
+			assert_eq!(slots.alloc(), Some(slot));
+		}
+		assert_eq!(slots.alloc(), None);
+		slots.release(7);
+		assert_eq!(slots.alloc(), None);
+		slots.next_frame(0);
+		assert_eq!(slots.alloc(), Some(7));
+	}
+}
@@ -0,0 +1,29 @@
+use nalgebra::Vector2;
+
+/// Which part of the world the window shows. Sprites and tilemaps are in world pixels; the GUI ignores the camera.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+	/// World position shown at the top-left corner of the window.
+	pub pos: Vector2<f32>,
+	/// Screen pixels per world pixel.
+	pub zoom: f32,
+}
+impl Camera {
+	/// World-space rectangle visible in a window of `win_size`, as its min and max corners.
+	pub fn view(&self, win_size: Vector2<f32>) -> (Vector2<f32>, Vector2<f32>) {
+		(self.pos, self.pos + win_size / self.zoom)
+	}
+
+	pub fn world_to_screen(&self, pos: Vector2<f32>) -> Vector2<f32> {
+		(pos - self.pos) * self.zoom
+	}
+
+	pub fn screen_to_world(&self, pos: Vector2<f32>) -> Vector2<f32> {
+		pos / self.zoom + self.pos
+	}
+}
+impl Default for Camera {
+	fn default() -> Self {
+		Self { pos: Vector2::zeros(), zoom: 1.0 }
+	}
+}
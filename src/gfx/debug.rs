@@ -0,0 +1,266 @@
+use crate::{
+	gfx::{
+		gui::{layout::Rect, theme::Color, Gui},
+		shape::{Path, Stroke},
+	},
+	profile,
+};
+use lazy_static::lazy_static;
+use log::debug;
+use nalgebra::Vector2;
+use std::{
+	collections::VecDeque,
+	mem,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Mutex,
+	},
+	time::{Duration, Instant},
+};
+use vulkan::{image::Format, surface::PresentMode, Extent2D};
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+// frames shown in the frame time graph
+const HISTORY: usize = 120;
+// logical pixels
+const PANEL_WIDTH: f32 = 260.0;
+const GRAPH_HEIGHT: f32 = 40.0;
+const MARGIN: f32 = 8.0;
+// the graph's full height, in milliseconds
+const GRAPH_MS: f32 = 33.3;
+
+lazy_static! {
+	static ref QUEUE: Mutex<Vec<DebugShape>> = Mutex::default();
+}
+// mirrors `DebugOverlay::shown`, so drawing from anywhere is free while hidden
+static SHOWN: AtomicBool = AtomicBool::new(false);
+
+enum DebugShape {
+	Line(Vector2<f32>, Vector2<f32>, Color),
+	Rect(Vector2<f32>, Vector2<f32>, Color),
+	Circle(Vector2<f32>, f32, Color),
+	Text(Vector2<f32>, String, Color),
+}
+
+/// Draws a line between two world positions over the next frame, if the debug overlay is shown.
+pub fn line(from: Vector2<f32>, to: Vector2<f32>, color: Color) {
+	push(|| DebugShape::Line(from, to, color));
+}
+
+/// Outlines a world-space box over the next frame, if the debug overlay is shown. `pos` is the top-left corner.
+pub fn rect(pos: Vector2<f32>, size: Vector2<f32>, color: Color) {
+	push(|| DebugShape::Rect(pos, size, color));
+}
+
+/// Outlines a world-space circle over the next frame, if the debug overlay is shown.
+pub fn circle(center: Vector2<f32>, radius: f32, color: Color) {
+	push(|| DebugShape::Circle(center, radius, color));
+}
+
+/// Writes text with its top-left corner at a world position over the next frame, if the debug overlay is shown.
+pub fn text(pos: Vector2<f32>, text: impl Into<String>, color: Color) {
+	push(|| DebugShape::Text(pos, text.into(), color));
+}
+
+fn push(shape: impl FnOnce() -> DebugShape) {
+	if SHOWN.load(Ordering::Relaxed) {
+		QUEUE.lock().unwrap().push(shape());
+	}
+}
+
+/// What a frame drew, shown in the stats panel.
+#[derive(Clone, Debug)]
+pub struct FrameStats {
+	pub draw_calls: u32,
+	pub sprites: u32,
+	pub shape_batches: u32,
+	pub tile_chunks: u32,
+	pub particles: u32,
+	pub lights: u32,
+	/// Fraction of the GUI's glyph atlas in use.
+	pub glyph_atlas: f32,
+	/// Slots in use in the texture array, if there is one.
+	pub texture_slots: Option<u32>,
+	/// Bytes used and budgeted per device-local heap, if the driver reports them.
+	pub memory: Option<Vec<(u64, u64)>>,
+	pub swapchain_extent: Extent2D,
+	pub swapchain_images: u32,
+	pub swapchain_format: Format,
+	pub present_mode: PresentMode,
+}
+
+/// Shapes and text drawn from anywhere with this module's functions, plus a stats panel, over everything else. Shown
+/// and hidden with `toggle_key`, and `profile_key` turns profiling on and off, adding its timings to the panel.
+pub struct DebugOverlay {
+	pub toggle_key: VirtualKeyCode,
+	pub profile_key: VirtualKeyCode,
+	// seconds, oldest first
+	frame_times: VecDeque<f32>,
+	last_frame: Option<Instant>,
+	stats: Option<FrameStats>,
+}
+impl DebugOverlay {
+	pub fn new() -> Self {
+		Self {
+			toggle_key: VirtualKeyCode::F3,
+			profile_key: VirtualKeyCode::F4,
+			frame_times: VecDeque::new(),
+			last_frame: None,
+			stats: None,
+		}
+	}
+
+	pub fn shown(&self) -> bool {
+		SHOWN.load(Ordering::Relaxed)
+	}
+
+	pub fn set_shown(&mut self, shown: bool) {
+		SHOWN.store(shown, Ordering::Relaxed);
+		if !shown {
+			QUEUE.lock().unwrap().clear();
+		}
+	}
+
+	/// Handles `toggle_key` and `profile_key`. Returns true if it consumed the event.
+	pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+		match event {
+			WindowEvent::KeyboardInput {
+				input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(key), .. },
+				..
+			} if *key == self.toggle_key => {
+				let shown = !self.shown();
+				self.set_shown(shown);
+				debug!(target: "input", "debug overlay {}", if shown { "shown" } else { "hidden" });
+				true
+			},
+			WindowEvent::KeyboardInput {
+				input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(key), .. },
+				..
+			} if *key == self.profile_key => {
+				let enabled = !profile::enabled();
+				profile::set_enabled(enabled);
+				debug!(target: "input", "profiling {}", if enabled { "on" } else { "off" });
+				true
+			},
+			_ => false,
+		}
+	}
+
+	/// The last frame's stats, once a frame has been drawn.
+	pub fn stats(&self) -> Option<&FrameStats> {
+		self.stats.as_ref()
+	}
+
+	/// Seconds between recent frames, oldest first.
+	pub fn frame_times(&self) -> impl Iterator<Item = f32> + '_ {
+		self.frame_times.iter().copied()
+	}
+
+	pub(super) fn end_frame(&mut self, stats: FrameStats) {
+		let now = Instant::now();
+		if let Some(last) = self.last_frame.replace(now) {
+			if self.frame_times.len() == HISTORY {
+				self.frame_times.pop_front();
+			}
+			self.frame_times.push_back((now - last).as_secs_f32());
+		}
+		self.stats = Some(stats);
+	}
+
+	/// Draws queued shapes through `to_window`, which maps world positions to physical window pixels, then the stats
+	/// panel.
+	pub(super) fn draw(&mut self, gui: &mut Gui, to_window: impl Fn(Vector2<f32>) -> Vector2<f32>) {
+		let shapes = mem::take(&mut *QUEUE.lock().unwrap());
+		if !self.shown() {
+			return;
+		}
+
+		let mut painter = gui.painter();
+		let scale = painter.scale();
+		let to_gui = |pos| to_window(pos) / scale;
+		let stroke = Stroke::new(1.0);
+		for shape in shapes {
+			match shape {
+				DebugShape::Line(from, to, color) => {
+					painter.stroke(&Path::line(to_gui(from), to_gui(to)), &stroke, color);
+				},
+				DebugShape::Rect(pos, size, color) => {
+					let (min, max) = (to_gui(pos), to_gui(pos + size));
+					painter.stroke(&Path::rect(min, max - min), &stroke, color);
+				},
+				DebugShape::Circle(center, radius, color) => {
+					let (center, edge) = (to_gui(center), to_gui(center + Vector2::new(radius, 0.0)));
+					painter.stroke(&Path::circle(center, (edge - center).norm()), &stroke, color);
+				},
+				DebugShape::Text(pos, text, color) => {
+					painter.text(to_gui(pos), &text, color);
+				},
+			}
+		}
+
+		let mut lines = vec![];
+		let frame_time = self.frame_times.back().copied().unwrap_or(0.0);
+		let average = self.frame_times.iter().sum::<f32>() / self.frame_times.len().max(1) as f32;
+		lines.push(format!("{:.0} fps, {:.2} ms", 1.0 / average.max(1e-6), frame_time * 1000.0));
+		if let Some(stats) = &self.stats {
+			lines.push(format!("draw calls: {}", stats.draw_calls));
+			lines.push(format!("sprites: {}, shape batches: {}", stats.sprites, stats.shape_batches));
+			lines.push(format!("tile chunks: {}, particles: {}", stats.tile_chunks, stats.particles));
+			lines.push(format!("lights: {}", stats.lights));
+			lines.push(format!("glyph atlas: {:.0}%", stats.glyph_atlas * 100.0));
+			if let Some(slots) = stats.texture_slots {
+				lines.push(format!("texture slots: {}", slots));
+			}
+			for (i, &(used, budget)) in stats.memory.iter().flatten().enumerate() {
+				lines.push(format!("heap {}: {} / {} MiB", i, used >> 20, budget >> 20));
+			}
+			let extent = stats.swapchain_extent;
+			lines.push(format!("swapchain: {}x{}, {} images", extent.width, extent.height, stats.swapchain_images));
+			lines.push(format!("{:?}, {:?}", stats.swapchain_format, stats.present_mode));
+		}
+		if let Some(frame) = profile::last_frame() {
+			let ms = |time: Duration| time.as_secs_f32() * 1000.0;
+			// blocking on the previous frame's fence means the GPU is behind
+			let wait = frame.cpu_time("wait for gpu");
+			let bound = if wait > (frame.end - frame.start) / 10 { "gpu bound" } else { "cpu bound" };
+			lines.push(format!("frame {}: {}", frame.number, bound));
+			let draw = frame.cpu_time("draw").saturating_sub(wait);
+			lines.push(format!("cpu: {:.2} ms, waiting on gpu: {:.2} ms", ms(draw), ms(wait)));
+			if let Some(gpu_time) = frame.gpu_time() {
+				lines.push(format!("gpu: {:.2} ms", ms(gpu_time)));
+			}
+			for span in &frame.gpu {
+				lines.push(format!("  {}: {:.2} ms", span.name, ms(span.duration())));
+			}
+		}
+
+		let line_height = painter.line_height();
+		let height = MARGIN * 3.0 + line_height * lines.len() as f32 + GRAPH_HEIGHT;
+		let panel = Rect::new(MARGIN, MARGIN, PANEL_WIDTH, height);
+		painter.rect(panel, [0.0, 0.0, 0.0, 0.7]);
+		let mut pos = panel.pos + Vector2::new(MARGIN, MARGIN);
+		for line in &lines {
+			painter.text(pos, line, [1.0; 4]);
+			pos.y += line_height;
+		}
+
+		// one bar per frame, right-aligned, going red past 60 fps
+		let graph = Rect::new(pos.x, pos.y + MARGIN, PANEL_WIDTH - MARGIN * 2.0, GRAPH_HEIGHT);
+		let bar_width = graph.size.x / HISTORY as f32;
+		let start = graph.pos.x + graph.size.x - bar_width * self.frame_times.len() as f32;
+		for (i, &time) in self.frame_times.iter().enumerate() {
+			let ms = time * 1000.0;
+			let height = (ms / GRAPH_MS).min(1.0) * graph.size.y;
+			let color = if ms > 1000.0 / 60.0 { [1.0, 0.3, 0.3, 1.0] } else { [0.3, 1.0, 0.3, 1.0] };
+			let bar = Rect::new(start + bar_width * i as f32, graph.pos.y + graph.size.y - height, bar_width, height);
+			painter.rect(bar, color);
+		}
+		let target = graph.pos.y + graph.size.y * (1.0 - 1000.0 / 60.0 / GRAPH_MS);
+		painter.rect(Rect::new(graph.pos.x, target, graph.size.x, 1.0), [1.0, 1.0, 1.0, 0.5]);
+	}
+}
+impl Default for DebugOverlay {
+	fn default() -> Self {
+		Self::new()
+	}
+}
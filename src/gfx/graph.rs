@@ -0,0 +1,525 @@
+use crate::gfx::{
+	material::{Material, MaterialParam},
+	pipeline::{BlendMode, PipelineDesc},
+	sampler::SamplerDesc,
+	shader::ShaderProgram,
+	window::DEPTH_FORMAT,
+	Gfx, TriangleVertex,
+};
+use std::{collections::HashMap, io, iter::once, sync::Arc};
+use vulkan::{
+	command::CommandBufferBuilder,
+	image::{
+		Format, Framebuffer, Image, ImageAspectFlags, ImageLayout, ImageSubresourceRange, ImageType, ImageUsageFlags,
+		ImageView,
+	},
+	ordered_passes_renderpass,
+	pipeline::GraphicsPipeline,
+	render_pass::RenderPass,
+	Extent2D,
+};
+
+/// An image written by one pass of a `RenderGraph` and read by later ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ResourceId(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TargetDesc {
+	pub format: Format,
+	/// Fraction of the window size, e.g. 0.5 for half resolution.
+	pub scale: f32,
+}
+
+/// Draws a fullscreen quad into `output` with a program reading earlier targets.
+pub struct FullscreenPass {
+	pub name: String,
+	/// Its vertex stage must be `fullscreen.glsl`.
+	pub program: Arc<ShaderProgram>,
+	/// Targets sampled by the shader, by binding name.
+	pub inputs: Vec<(String, ResourceId)>,
+	/// Every other material parameter of the program.
+	pub params: HashMap<String, MaterialParam>,
+	pub output: ResourceId,
+}
+impl FullscreenPass {
+	pub fn new(name: impl Into<String>, program: Arc<ShaderProgram>, output: ResourceId) -> Self {
+		Self { name: name.into(), program, inputs: vec![], params: HashMap::new(), output }
+	}
+
+	pub fn input(mut self, binding: impl Into<String>, target: ResourceId) -> Self {
+		self.inputs.push((binding.into(), target));
+		self
+	}
+
+	pub fn param(mut self, name: impl Into<String>, param: MaterialParam) -> Self {
+		self.params.insert(name.into(), param);
+		self
+	}
+}
+
+/// Describes the passes that turn the drawn scene into what the window shows. Passes run in the order they're added
+/// and only read targets written before them. Passes that don't contribute to the presented target are skipped, and
+/// targets whose lifetimes don't overlap share an image.
+pub struct RenderGraph {
+	targets: Vec<TargetDesc>,
+	passes: Vec<FullscreenPass>,
+	output: ResourceId,
+	// normals and light buffer
+	lighting: Option<(ResourceId, ResourceId)>,
+}
+impl RenderGraph {
+	/// Where sprites, tilemaps and particles are drawn, at full size.
+	pub const SCENE: ResourceId = ResourceId(0);
+
+	/// `format` is the format of the scene target, usually `Window::color_format`.
+	pub fn new(format: Format) -> Self {
+		Self { targets: vec![TargetDesc { format, scale: 1.0 }], passes: vec![], output: Self::SCENE, lighting: None }
+	}
+
+	pub fn target(&mut self, format: Format, scale: f32) -> ResourceId {
+		assert!(scale > 0.0, "target scale must be positive");
+		self.targets.push(TargetDesc { format, scale });
+		ResourceId(self.targets.len() - 1)
+	}
+
+	/// Adds a target the window fills with the light of `Window::lighting` reaching each pixel, along with the
+	/// normals of the scene it lights. Like the scene, it's drawn before any pass runs.
+	pub fn lights(&mut self, format: Format, scale: f32) -> ResourceId {
+		assert!(self.lighting.is_none(), "a render graph has a single light buffer");
+		let normals = self.target(NORMALS_FORMAT, 1.0);
+		let lights = self.target(format, scale);
+		self.lighting = Some((normals, lights));
+		lights
+	}
+
+	pub fn desc(&self, target: ResourceId) -> TargetDesc {
+		self.targets[target.0]
+	}
+
+	/// Fails without adding the pass if it reads a target no earlier pass writes or its own output, or writes the
+	/// scene target.
+	pub fn add_pass(&mut self, pass: FullscreenPass) -> io::Result<()> {
+		let written = |target: ResourceId| {
+			target == Self::SCENE
+				|| self.lighting.is_some_and(|(_, lights)| lights == target)
+				|| self.passes.iter().any(|pass| pass.output == target)
+		};
+		let invalid = |msg: String| Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+		for (binding, input) in &pass.inputs {
+			if !written(*input) {
+				return invalid(format!("{}: `{}` reads a target no earlier pass writes", pass.name, binding));
+			}
+			if *input == pass.output {
+				return invalid(format!("{}: `{}` reads the pass's own output", pass.name, binding));
+			}
+		}
+		if pass.output == Self::SCENE {
+			return invalid(format!("{}: only the scene draws into the scene target", pass.name));
+		}
+		self.passes.push(pass);
+		Ok(())
+	}
+
+	/// Picks the target shown in the window. It starts as the scene.
+	pub fn present(&mut self, target: ResourceId) {
+		self.output = target;
+	}
+
+	pub fn output(&self) -> ResourceId {
+		self.output
+	}
+}
+
+// encoded like a normal map, flat where nothing with a normal map was drawn
+pub(super) const NORMALS_FORMAT: Format = Format::R8G8B8A8_UNORM;
+
+pub(super) struct CompiledPass {
+	pub program: Arc<ShaderProgram>,
+	pub pipeline: Arc<GraphicsPipeline>,
+	pub render_pass: Arc<RenderPass>,
+	pub extent: Extent2D,
+	/// One per frame in flight.
+	pub framebuffers: Vec<Arc<Framebuffer>>,
+	/// One per frame in flight.
+	pub materials: Vec<Material>,
+}
+impl CompiledPass {
+	/// Records the pass's full-screen quad for frame in flight `frame`. Expects the viewport and scissor to be set.
+	pub fn record(&self, gfx: &Gfx, cmd: CommandBufferBuilder, frame: usize) -> CommandBufferBuilder {
+		let desc_sets = self.materials[frame].desc_sets().iter().cloned();
+		cmd.bind_pipeline(self.pipeline.clone())
+			.bind_vertex_buffers(0, once(gfx.verts.clone() as _), &[0])
+			.bind_descriptor_sets(self.program.layout.clone(), 0, desc_sets, &[])
+			.draw(6, 1, 0, 0)
+	}
+}
+
+/// Targets drawn by the window for `RenderGraph::lights`, cleared when their pass begins.
+pub(super) struct CompiledLighting {
+	pub normals_pass: Arc<RenderPass>,
+	pub lights_pass: Arc<RenderPass>,
+	pub lights_extent: Extent2D,
+	/// One per frame in flight.
+	pub normals_framebuffers: Vec<Arc<Framebuffer>>,
+	/// One per frame in flight.
+	pub lights_framebuffers: Vec<Arc<Framebuffer>>,
+	/// One per frame in flight, read while drawing lights.
+	pub normals: Vec<Arc<ImageView>>,
+}
+
+/// A `RenderGraph` with images, render passes and pipelines created for a window size.
+pub(super) struct CompiledGraph {
+	pub scene_pass: Arc<RenderPass>,
+	/// One per frame in flight.
+	pub scene_framebuffers: Vec<Arc<Framebuffer>>,
+	/// `None` if the graph has no light buffer or nothing reads it.
+	pub lighting: Option<CompiledLighting>,
+	pub passes: Vec<CompiledPass>,
+	/// Reads the output into the window, one material per frame in flight.
+	pub present_materials: Vec<Material>,
+}
+impl CompiledGraph {
+	/// Every frame in flight gets its own images, so a frame never overwrites what the previous one still reads. Fails
+	/// if a pass's material can't be built from its params and inputs.
+	pub fn new(
+		gfx: &Gfx,
+		graph: &RenderGraph,
+		depth_view: &Arc<ImageView>,
+		extent: Extent2D,
+		frames: usize,
+		present_program: &Arc<ShaderProgram>,
+	) -> io::Result<Self> {
+		let wiring: Vec<_> = graph.passes.iter().map(Wiring::new).collect();
+		let (live, lit) = live_passes(graph.targets.len(), &wiring, graph.output, graph.lighting);
+		let lighting = graph.lighting.filter(|_| lit);
+		let live_wiring: Vec<_> = live.iter().map(|&pass| wiring[pass].clone()).collect();
+		let physical = alias_targets(&graph.targets, &live_wiring, graph.output, lighting);
+		let passes = live.into_iter().map(|pass| &graph.passes[pass]);
+
+		let scene_format = graph.targets[0].format;
+		let scene_pass = ordered_passes_renderpass!(&gfx.device,
+			attachments: {
+				color: {
+					load: Clear,
+					store: Store,
+					format: scene_format,
+					samples: 1,
+					initial_layout: ImageLayout::UNDEFINED,
+					final_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+				},
+				depth: { load: Clear, store: DontCare, format: DEPTH_FORMAT, samples: 1, }
+			},
+			passes: [{ color: [color], depth_stencil: {depth}, input: [] }]
+		);
+
+		// fullscreen passes overwrite every pixel, window-drawn targets start cleared
+		let mut color_passes: HashMap<Format, Arc<RenderPass>> = HashMap::new();
+		let mut color_pass = |format: Format| {
+			color_passes
+				.entry(format)
+				.or_insert_with(|| {
+					ordered_passes_renderpass!(&gfx.device,
+						attachments: {
+							color: {
+								load: DontCare,
+								store: Store,
+								format: format,
+								samples: 1,
+								initial_layout: ImageLayout::UNDEFINED,
+								final_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+							}
+						},
+						passes: [{ color: [color], depth_stencil: {}, input: [] }]
+					)
+				})
+				.clone()
+		};
+		let clear_pass = |format: Format| {
+			ordered_passes_renderpass!(&gfx.device,
+				attachments: {
+					color: {
+						load: Clear,
+						store: Store,
+						format: format,
+						samples: 1,
+						initial_layout: ImageLayout::UNDEFINED,
+						final_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+					}
+				},
+				passes: [{ color: [color], depth_stencil: {}, input: [] }]
+			)
+		};
+
+		// images for each frame in flight, by target, only for targets something uses
+		let images: Vec<HashMap<usize, Arc<ImageView>>> = (0..frames)
+			.map(|_| {
+				let mut created: HashMap<usize, Arc<ImageView>> = HashMap::new();
+				physical
+					.iter()
+					.enumerate()
+					.filter_map(|(target, image)| Some((target, (*image)?)))
+					.map(|(target, image)| {
+						let desc = graph.targets[image];
+						(target, created.entry(image).or_insert_with(|| create_target(gfx, desc, extent)).clone())
+					})
+					.collect()
+			})
+			.collect();
+
+		let scene_framebuffers = images
+			.iter()
+			.map(|images| {
+				let attachments = vec![images[&RenderGraph::SCENE.0].clone(), depth_view.clone()];
+				Framebuffer::new(gfx.device.clone(), scene_pass.clone(), attachments, extent.width, extent.height)
+			})
+			.collect();
+
+		let lighting = lighting.map(|(normals, lights)| {
+			let normals_pass = clear_pass(NORMALS_FORMAT);
+			let lights_desc = graph.targets[lights.0];
+			let lights_pass = clear_pass(lights_desc.format);
+			let lights_extent = scaled(extent, lights_desc.scale);
+			let framebuffers = |render_pass: &Arc<RenderPass>, target: ResourceId, extent: Extent2D| {
+				images
+					.iter()
+					.map(|images| {
+						let attachments = vec![images[&target.0].clone()];
+						Framebuffer::new(
+							gfx.device.clone(),
+							render_pass.clone(),
+							attachments,
+							extent.width,
+							extent.height,
+						)
+					})
+					.collect()
+			};
+			CompiledLighting {
+				normals_framebuffers: framebuffers(&normals_pass, normals, extent),
+				lights_framebuffers: framebuffers(&lights_pass, lights, lights_extent),
+				normals: images.iter().map(|images| images[&normals.0].clone()).collect(),
+				normals_pass,
+				lights_pass,
+				lights_extent,
+			}
+		});
+
+		// targets scaled to other sizes are filtered when read
+		let sampler = gfx.sampler(SamplerDesc::default());
+		let passes = passes
+			.map(|pass| {
+				let desc = graph.targets[pass.output.0];
+				let pass_extent = scaled(extent, desc.scale);
+				let render_pass = color_pass(desc.format);
+				let pipeline = gfx.pipelines.get::<TriangleVertex>(&gfx.device, PipelineDesc {
+					layout: pass.program.layout.clone(),
+					render_pass: render_pass.clone(),
+					subpass: 0,
+					vshader: pass.program.vshader.clone(),
+					fshader: pass.program.fshader.clone(),
+					blend: BlendMode::Opaque,
+				});
+				let framebuffers = images
+					.iter()
+					.map(|images| {
+						let attachments = vec![images[&pass.output.0].clone()];
+						let (width, height) = (pass_extent.width, pass_extent.height);
+						Framebuffer::new(gfx.device.clone(), render_pass.clone(), attachments, width, height)
+					})
+					.collect();
+				let materials = images
+					.iter()
+					.map(|images| {
+						let mut params = pass.params.clone();
+						for (binding, input) in &pass.inputs {
+							let image_view = images[&input.0].clone();
+							params.insert(binding.clone(), MaterialParam::Texture(image_view, sampler.clone()));
+						}
+						Material::new(pass.program.clone(), params)
+					})
+					.collect::<io::Result<_>>()?;
+				Ok(CompiledPass {
+					program: pass.program.clone(),
+					pipeline,
+					render_pass,
+					extent: pass_extent,
+					framebuffers,
+					materials,
+				})
+			})
+			.collect::<io::Result<_>>()?;
+
+		let present_materials = images
+			.iter()
+			.map(|images| {
+				let mut params = HashMap::new();
+				let image_view = images[&graph.output.0].clone();
+				params.insert("tex".to_owned(), MaterialParam::Texture(image_view, sampler.clone()));
+				Material::new(present_program.clone(), params)
+			})
+			.collect::<io::Result<_>>()?;
+
+		Ok(Self { scene_pass, scene_framebuffers, lighting, passes, present_materials })
+	}
+}
+
+// the targets a pass reads and writes, which is all that deciding what runs and which images are shared looks at
+#[derive(Clone, Debug)]
+struct Wiring {
+	inputs: Vec<ResourceId>,
+	output: ResourceId,
+}
+impl Wiring {
+	fn new(pass: &FullscreenPass) -> Self {
+		Self { inputs: pass.inputs.iter().map(|&(_, input)| input).collect(), output: pass.output }
+	}
+}
+
+// indices of the passes contributing to `output`, in order, and whether the light buffer does
+fn live_passes(
+	targets: usize,
+	passes: &[Wiring],
+	output: ResourceId,
+	lighting: Option<(ResourceId, ResourceId)>,
+) -> (Vec<usize>, bool) {
+	let mut needed = vec![false; targets];
+	needed[output.0] = true;
+	let mut live = vec![];
+	for (idx, pass) in passes.iter().enumerate().rev() {
+		if !needed[pass.output.0] {
+			continue;
+		}
+		// a target written twice only needs its last writer from here on
+		needed[pass.output.0] = false;
+		for input in &pass.inputs {
+			needed[input.0] = true;
+		}
+		live.push(idx);
+	}
+	live.reverse();
+	let lit = lighting.is_some_and(|(_, lights)| needed[lights.0]);
+	(live, lit)
+}
+
+// maps each target to the target whose image it uses, reusing images of targets nothing reads anymore, or to `None`
+// for unused targets
+fn alias_targets(
+	targets: &[TargetDesc],
+	passes: &[Wiring],
+	output: ResourceId,
+	lighting: Option<(ResourceId, ResourceId)>,
+) -> Vec<Option<usize>> {
+	// first and last pass touching each target, with window-drawn targets written before every pass and the output read
+	// after
+	let mut spans: Vec<Option<(usize, usize)>> = vec![None; targets.len()];
+	let mut touch = |target: ResourceId, at: usize| {
+		let span = spans[target.0].get_or_insert((at, at));
+		span.0 = span.0.min(at);
+		span.1 = span.1.max(at);
+	};
+	touch(RenderGraph::SCENE, 0);
+	if let Some((normals, lights)) = lighting {
+		touch(normals, 0);
+		touch(lights, 0);
+	}
+	for (i, pass) in passes.iter().enumerate() {
+		for input in &pass.inputs {
+			touch(*input, i + 1);
+		}
+		touch(pass.output, i + 1);
+	}
+	touch(output, passes.len() + 1);
+
+	let mut order: Vec<_> = (0..targets.len()).filter(|&target| spans[target].is_some()).collect();
+	order.sort_by_key(|&target| spans[target].unwrap().0);
+
+	let mut physical = vec![None; targets.len()];
+	// images in use, with the last pass reading them
+	let mut images: Vec<(usize, usize)> = vec![];
+	for target in order {
+		let (first, last) = spans[target].unwrap();
+		let desc = targets[target];
+		let free = images.iter_mut().find(|(image, end)| *end < first && targets[*image] == desc);
+		match free {
+			Some((image, end)) => {
+				physical[target] = Some(*image);
+				*end = last;
+			},
+			None => {
+				physical[target] = Some(target);
+				images.push((target, last));
+			},
+		}
+	}
+	physical
+}
+
+fn create_target(gfx: &Gfx, desc: TargetDesc, extent: Extent2D) -> Arc<ImageView> {
+	let Extent2D { width, height } = scaled(extent, desc.scale);
+	let usage = ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::SAMPLED;
+	let image = Image::new(gfx.device.clone(), ImageType::TYPE_2D, width, height, 1, desc.format, usage);
+	let range =
+		ImageSubresourceRange::builder().aspect_mask(ImageAspectFlags::COLOR).level_count(1).layer_count(1).build();
+	ImageView::new(image, desc.format, range)
+}
+
+fn scaled(extent: Extent2D, scale: f32) -> Extent2D {
+	let scale = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+	Extent2D { width: scale(extent.width), height: scale(extent.height) }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const COLOR: TargetDesc = TargetDesc { format: Format::R8G8B8A8_UNORM, scale: 1.0 };
+	const HDR: TargetDesc = TargetDesc { format: Format::R16G16B16A16_SFLOAT, scale: 1.0 };
+
+	fn pass(inputs: &[usize], output: usize) -> Wiring {
+		Wiring { inputs: inputs.iter().map(|&input| ResourceId(input)).collect(), output: ResourceId(output) }
+	}
+
+	#[test]
+	fn dead_passes() {
+		// 2 is never read, and the first write of 1 is overwritten before anything reads it
+		let passes = [pass(&[0], 1), pass(&[0], 2), pass(&[0], 1), pass(&[1], 3)];
+		assert_eq!(live_passes(4, &passes, ResourceId(3), None), (vec![2, 3], false));
+		// presenting the scene needs no passes at all
+		assert_eq!(live_passes(4, &passes, ResourceId(0), None), (vec![], false));
+	}
+
+	#[test]
+	fn dead_lighting() {
+		// normals 1 and lights 2, composited into 3 but blurred into the unused 4
+		let lighting = Some((ResourceId(1), ResourceId(2)));
+		let passes = [pass(&[2], 4), pass(&[0, 2], 3)];
+		assert_eq!(live_passes(5, &passes, ResourceId(3), lighting), (vec![1], true));
+		assert_eq!(live_passes(5, &passes, ResourceId(4), lighting), (vec![0], true));
+		assert_eq!(live_passes(5, &passes, ResourceId(0), lighting), (vec![], false));
+	}
+
+	#[test]
+	fn chain_reuses_images() {
+		// each target is only read by the next pass, so two images take turns
+		let targets = [COLOR, COLOR, COLOR, COLOR];
+		let passes = [pass(&[0], 1), pass(&[1], 2), pass(&[2], 3)];
+		let physical = alias_targets(&targets, &passes, ResourceId(3), None);
+		assert_eq!(physical, [Some(0), Some(1), Some(0), Some(1)]);
+	}
+
+	#[test]
+	fn aliasing_respects_lifetimes_and_formats() {
+		// the scene is read again by the last pass, 2 has a different format and 4 is unused
+		let targets = [COLOR, COLOR, HDR, COLOR, COLOR, COLOR];
+		let passes = [pass(&[0], 1), pass(&[1], 2), pass(&[2], 3), pass(&[0, 3], 5)];
+		let physical = alias_targets(&targets, &passes, ResourceId(5), None);
+		assert_eq!(physical, [Some(0), Some(1), Some(2), Some(1), None, Some(5)]);
+
+		// window-drawn targets live from before the first pass, and the normals are done with once lights are drawn
+		let targets = [COLOR, COLOR, COLOR, COLOR];
+		let lighting = Some((ResourceId(1), ResourceId(2)));
+		let passes = [pass(&[0, 2], 3)];
+		let physical = alias_targets(&targets, &passes, ResourceId(3), lighting);
+		assert_eq!(physical, [Some(0), Some(1), Some(2), Some(1)]);
+	}
+}
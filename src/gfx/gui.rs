@@ -1 +1,319 @@
+pub mod draw;
 pub mod font;
+pub mod input;
+pub mod layout;
+pub mod painter;
+pub mod render;
+pub mod theme;
+mod widgets;
+
+use draw::DrawList;
+use font::Font;
+use input::Input;
+use layout::{Anchor, Direction, Layout, Rect};
+use nalgebra::Vector2;
+use painter::Painter;
+use std::{
+	collections::{hash_map::DefaultHasher, HashMap},
+	hash::{Hash, Hasher},
+};
+use theme::{Color, Theme};
+use winit::event::WindowEvent;
+
+/// Identifies a widget across frames. Derived from its label and the ids of the containers around it, so labels only
+/// need to be unique within a container; use `Ui::scope` to tell apart widgets that share a label.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Id(u64);
+impl Id {
+	const ROOT: Id = Id(0);
+
+	fn new(parent: Id, name: impl Hash) -> Self {
+		let mut hasher = DefaultHasher::new();
+		parent.0.hash(&mut hasher);
+		name.hash(&mut hasher);
+		Id(hasher.finish())
+	}
+}
+
+// everything drawn into one window (or the background), so windows can be reordered as a whole
+struct Layer {
+	id: Id,
+	draw: DrawList,
+	// areas that capture the mouse
+	rects: Vec<Rect>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct ScrollState {
+	offset: f32,
+	content_height: f32,
+}
+
+/// Immediate-mode GUI. Feed it window events, then describe the whole interface every frame inside `frame`.
+pub struct Gui {
+	pub theme: Theme,
+	font: Font,
+	input: Input,
+	win_size: Vector2<f32>,
+	layers: Vec<Layer>,
+	// windows back to front, which the background layer is always behind
+	order: Vec<Id>,
+	// last frame's mouse-capturing areas, front to back
+	prev_rects: Vec<(Id, Vec<Rect>)>,
+	hover_layer: Option<Id>,
+	active: Option<Id>,
+	focus: Option<Id>,
+	focus_taken: bool,
+	// byte offset into the focused text input
+	text_cursor: usize,
+	scroll: HashMap<Id, ScrollState>,
+	windows: HashMap<Id, Rect>,
+	draw: DrawList,
+}
+impl Gui {
+	pub fn new(scale: f32) -> Self {
+		let mut input = Input::new();
+		input.scale = scale;
+		Self {
+			theme: Theme::default(),
+			font: Font::new(),
+			input,
+			win_size: Vector2::zeros(),
+			layers: vec![],
+			order: vec![],
+			prev_rects: vec![],
+			hover_layer: None,
+			active: None,
+			focus: None,
+			focus_taken: false,
+			text_cursor: 0,
+			scroll: HashMap::new(),
+			windows: HashMap::new(),
+			draw: DrawList::default(),
+		}
+	}
+
+	/// Returns true if the GUI consumed the event, in which case gameplay should ignore it.
+	pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+		self.input.handle(event);
+		match event {
+			WindowEvent::MouseInput { .. } | WindowEvent::MouseWheel { .. } => self.wants_mouse(),
+			WindowEvent::KeyboardInput { .. } | WindowEvent::ReceivedCharacter(_) => self.wants_keyboard(),
+			_ => false,
+		}
+	}
+
+	/// Whether the mouse is over the GUI or dragging something in it.
+	pub fn wants_mouse(&self) -> bool {
+		self.active.is_some() || self.layer_at(self.input.mouse_pos).is_some()
+	}
+
+	/// Whether a text input has keyboard focus.
+	pub fn wants_keyboard(&self) -> bool {
+		self.focus.is_some()
+	}
+
+	pub fn scale(&self) -> f32 {
+		self.input.scale
+	}
+
+	/// Builds this frame's interface. `win_size` is in physical pixels.
+	pub fn frame(&mut self, win_size: Vector2<f32>, f: impl FnOnce(&mut Ui)) {
+		self.font.begin_frame();
+		self.win_size = win_size / self.input.scale;
+		self.hover_layer = self.layer_at(self.input.mouse_pos);
+		if self.input.mouse_pressed {
+			// clicking a window brings it to the front
+			if let Some(pos) = self.order.iter().position(|&id| Some(id) == self.hover_layer) {
+				let id = self.order.remove(pos);
+				self.order.push(id);
+			}
+		}
+		self.layers.clear();
+		self.focus_taken = false;
+
+		let bounds = Rect { pos: Vector2::zeros(), size: self.win_size };
+		let spacing = self.theme.spacing;
+		let layer = self.layer(Id::ROOT);
+		f(&mut Ui {
+			gui: self,
+			id: Id::ROOT,
+			layer,
+			layout: Layout::new(bounds, Direction::Column, spacing),
+			clip: bounds,
+		});
+
+		// forget windows that weren't shown this frame
+		let layers = &self.layers;
+		self.order.retain(|id| layers.iter().any(|layer| layer.id == *id));
+		let mut layers: Vec<_> = self.layers.drain(..).collect();
+		let order = &self.order;
+		layers.sort_by_key(|layer| order.iter().position(|&id| id == layer.id).map_or(0, |pos| pos + 1));
+
+		self.draw.clear();
+		for layer in &layers {
+			self.draw.append(&layer.draw);
+		}
+		self.prev_rects = layers.into_iter().rev().map(|layer| (layer.id, layer.rects)).collect();
+
+		if self.input.mouse_pressed && !self.focus_taken {
+			self.focus = None;
+		}
+		if !self.input.mouse_down {
+			self.active = None;
+		}
+		self.input.end_frame();
+	}
+
+	/// The interface built by the last call to `frame`.
+	pub fn draw_list(&self) -> &DrawList {
+		&self.draw
+	}
+
+	/// Draws over the interface built by the last call to `frame`, until the next one.
+	pub fn painter(&mut self) -> Painter {
+		Painter { gui: self }
+	}
+
+	/// Fraction of the glyph atlas in use, from 0 to 1.
+	pub fn atlas_usage(&self) -> f32 {
+		self.font.atlas_usage()
+	}
+
+	fn layer_at(&self, pos: Vector2<f32>) -> Option<Id> {
+		self.prev_rects.iter().find(|(_, rects)| rects.iter().any(|rect| rect.contains(pos))).map(|&(id, _)| id)
+	}
+
+	fn layer(&mut self, id: Id) -> usize {
+		if id != Id::ROOT && !self.order.contains(&id) {
+			self.order.push(id);
+		}
+		match self.layers.iter().position(|layer| layer.id == id) {
+			Some(idx) => idx,
+			None => {
+				self.layers.push(Layer { id, draw: DrawList::default(), rects: vec![] });
+				self.layers.len() - 1
+			},
+		}
+	}
+}
+
+#[derive(Clone, Copy, Default)]
+struct Response {
+	hovered: bool,
+	pressed: bool,
+	clicked: bool,
+	active: bool,
+}
+
+/// Places and draws widgets inside a container. Every widget is laid out along the container's row or column.
+pub struct Ui<'a> {
+	gui: &'a mut Gui,
+	id: Id,
+	layer: usize,
+	layout: Layout,
+	clip: Rect,
+}
+impl<'a> Ui<'a> {
+	pub fn theme(&self) -> &Theme {
+		&self.gui.theme
+	}
+
+	/// Space left in this container.
+	pub fn available(&self) -> Rect {
+		self.layout.remaining()
+	}
+
+	pub fn space(&mut self, amount: f32) {
+		self.layout.space(amount);
+	}
+
+	/// Lays out widgets left to right.
+	pub fn row<R>(&mut self, f: impl FnOnce(&mut Ui) -> R) -> R {
+		self.group(Direction::Row, f)
+	}
+
+	/// Lays out widgets top to bottom.
+	pub fn column<R>(&mut self, f: impl FnOnce(&mut Ui) -> R) -> R {
+		self.group(Direction::Column, f)
+	}
+
+	/// Lays out widgets in a column of `size` placed against part of this container, without taking space from it.
+	/// Useful for HUD elements pinned to screen corners.
+	pub fn anchored<R>(&mut self, anchor: Anchor, size: Vector2<f32>, f: impl FnOnce(&mut Ui) -> R) -> R {
+		let rect = anchor.place(self.layout.bounds, size);
+		let layout = Layout::new(rect, Direction::Column, self.gui.theme.spacing);
+		let clip = self.clip;
+		f(&mut self.child(self.id, self.layer, layout, clip))
+	}
+
+	/// Gives widgets inside `f` their own id namespace.
+	pub fn scope<R>(&mut self, name: impl Hash, f: impl FnOnce(&mut Ui) -> R) -> R {
+		let id = Id::new(self.id, name);
+		let (layer, layout, clip) = (self.layer, self.layout, self.clip);
+		let mut child = self.child(id, layer, layout, clip);
+		let ret = f(&mut child);
+		let layout = child.layout;
+		self.layout = layout;
+		ret
+	}
+
+	fn group<R>(&mut self, dir: Direction, f: impl FnOnce(&mut Ui) -> R) -> R {
+		let layout = Layout::new(self.layout.remaining(), dir, self.gui.theme.spacing);
+		let (id, layer, clip) = (self.id, self.layer, self.clip);
+		let mut child = self.child(id, layer, layout, clip);
+		let ret = f(&mut child);
+		let used = child.layout.used();
+		self.layout.alloc(used);
+		ret
+	}
+
+	fn child(&mut self, id: Id, layer: usize, layout: Layout, clip: Rect) -> Ui {
+		Ui { gui: &mut *self.gui, id, layer, layout, clip }
+	}
+
+	fn interact(&mut self, id: Id, rect: Rect) -> Response {
+		self.capture(rect);
+		let input = &self.gui.input;
+		let hovered = self.gui.hover_layer == Some(self.gui.layers[self.layer].id)
+			&& rect.contains(input.mouse_pos)
+			&& self.clip.contains(input.mouse_pos);
+		let pressed = hovered && input.mouse_pressed;
+		if pressed {
+			self.gui.active = Some(id);
+		}
+		let active = self.gui.active == Some(id);
+		let clicked = active && hovered && input.mouse_released;
+		Response { hovered, pressed, clicked, active }
+	}
+
+	// makes the mouse over `rect` belong to the GUI from next frame on
+	fn capture(&mut self, rect: Rect) {
+		let rect = rect.intersection(&self.clip);
+		self.gui.layers[self.layer].rects.push(rect);
+	}
+
+	fn line_height(&self) -> f32 {
+		let theme = &self.gui.theme;
+		let scale = self.gui.input.scale;
+		self.gui.font.line_height(theme.font_size * scale) / scale
+	}
+
+	fn text_width(&mut self, text: &str) -> f32 {
+		let scale = self.gui.input.scale;
+		let px = self.gui.theme.font_size * scale;
+		self.gui.font.measure(text, px) / scale
+	}
+
+	fn draw_rect(&mut self, rect: Rect, color: Color) {
+		let gui = &mut *self.gui;
+		gui.layers[self.layer].draw.rect(&gui.font, rect, color, self.clip, gui.input.scale);
+	}
+
+	fn draw_text(&mut self, pos: Vector2<f32>, text: &str, color: Color, clip: Rect) {
+		let gui = &mut *self.gui;
+		let clip = clip.intersection(&self.clip);
+		let px = gui.theme.font_size;
+		gui.layers[self.layer].draw.text(&mut gui.font, pos, text, px, color, clip, gui.input.scale);
+	}
+}
@@ -0,0 +1,142 @@
+use crate::gfx::{
+	gui::{font::Font, layout::Rect, theme::Color},
+	vertex::vertex_input,
+};
+use nalgebra::Vector2;
+
+vertex_input! {
+	#[derive(Clone, Copy, Debug)]
+	pub struct GuiVertex {
+		/// Physical pixels.
+		pub pos: Vector2<f32>,
+		pub uv: Vector2<f32>,
+		pub color: Color,
+	}
+}
+
+/// A run of vertices sharing a clip rectangle.
+#[derive(Clone, Copy, Debug)]
+pub struct DrawCmd {
+	/// Physical pixels.
+	pub clip: Rect,
+	pub first_vertex: u32,
+	pub vertex_count: u32,
+}
+
+/// Triangles for a whole frame of GUI, batched into as few draws as clipping allows.
+#[derive(Default)]
+pub struct DrawList {
+	pub(super) verts: Vec<GuiVertex>,
+	pub(super) cmds: Vec<DrawCmd>,
+}
+impl DrawList {
+	pub fn verts(&self) -> &[GuiVertex] {
+		&self.verts
+	}
+
+	pub fn cmds(&self) -> &[DrawCmd] {
+		&self.cmds
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.verts.is_empty()
+	}
+
+	pub(super) fn clear(&mut self) {
+		self.verts.clear();
+		self.cmds.clear();
+	}
+
+	pub(super) fn append(&mut self, other: &DrawList) {
+		let base = self.verts.len() as u32;
+		self.verts.extend_from_slice(&other.verts);
+		for cmd in &other.cmds {
+			self.push_cmd(cmd.clip, base + cmd.first_vertex, cmd.vertex_count);
+		}
+	}
+
+	/// Adds a quad with corners and clip rectangle in physical pixels.
+	pub(super) fn quad(
+		&mut self,
+		min: Vector2<f32>,
+		max: Vector2<f32>,
+		uv: [Vector2<f32>; 2],
+		color: Color,
+		clip: Rect,
+	) {
+		let first = self.verts.len() as u32;
+		let corners = [
+			(min, uv[0]),
+			(Vector2::new(max.x, min.y), Vector2::new(uv[1].x, uv[0].y)),
+			(max, uv[1]),
+			(max, uv[1]),
+			(Vector2::new(min.x, max.y), Vector2::new(uv[0].x, uv[1].y)),
+			(min, uv[0]),
+		];
+		self.verts.extend(corners.iter().map(|&(pos, uv)| GuiVertex { pos, uv, color }));
+		self.push_cmd(clip, first, 6);
+	}
+
+	/// Adds solid triangles, three corners each, in physical pixels.
+	pub(super) fn triangles(
+		&mut self,
+		font: &Font,
+		corners: impl Iterator<Item = Vector2<f32>>,
+		color: Color,
+		clip: Rect,
+	) {
+		let first = self.verts.len() as u32;
+		let uv = font.white_uv();
+		self.verts.extend(corners.map(|pos| GuiVertex { pos, uv, color }));
+		let count = self.verts.len() as u32 - first;
+		if count > 0 {
+			self.push_cmd(clip, first, count);
+		}
+	}
+
+	/// Adds a solid rectangle. `rect` and `clip` are in logical pixels.
+	pub(super) fn rect(&mut self, font: &Font, rect: Rect, color: Color, clip: Rect, scale: f32) {
+		let uv = font.white_uv();
+		self.quad(rect.min() * scale, rect.max() * scale, [uv, uv], color, physical(clip, scale));
+	}
+
+	/// Adds a line of text with its top-left corner at `pos`. `pos`, `px` and `clip` are in logical pixels.
+	pub(super) fn text(
+		&mut self,
+		font: &mut Font,
+		pos: Vector2<f32>,
+		text: &str,
+		px: f32,
+		color: Color,
+		clip: Rect,
+		scale: f32,
+	) {
+		let clip = physical(clip, scale);
+		let px = px * scale;
+		// snap the pen to whole pixels so glyphs aren't resampled
+		let mut pen = Vector2::new((pos.x * scale).round(), (pos.y * scale + font.ascent(px)).round());
+		for ch in text.chars() {
+			let glyph = font.glyph(ch, px);
+			if glyph.size.x > 0.0 {
+				let min = pen + glyph.offset;
+				self.quad(min, min + glyph.size, glyph.uv, color, clip);
+			}
+			pen.x += glyph.advance;
+		}
+	}
+
+	// extends the last command instead of starting a new one when the clip matches
+	fn push_cmd(&mut self, clip: Rect, first_vertex: u32, vertex_count: u32) {
+		if let Some(last) = self.cmds.last_mut() {
+			if last.clip == clip && last.first_vertex + last.vertex_count == first_vertex {
+				last.vertex_count += vertex_count;
+				return;
+			}
+		}
+		self.cmds.push(DrawCmd { clip, first_vertex, vertex_count });
+	}
+}
+
+fn physical(rect: Rect, scale: f32) -> Rect {
+	Rect { pos: rect.pos * scale, size: rect.size * scale }
+}
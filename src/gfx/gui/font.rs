@@ -1,36 +1,212 @@
-use crate::gfx::texture::TexAtlas;
 use font_kit::{
 	canvas::{Canvas, Format, RasterizationOptions},
 	hinting::HintingOptions,
 	loaders::default::Font as KitFont,
 	source::SystemSource,
 };
+use image::{Rgba, RgbaImage};
+use nalgebra::Vector2;
 use pathfinder_geometry::{
 	transform2d::Transform2F,
 	vector::{Vector2F, Vector2I},
 };
-use std::sync::Arc;
-use vulkan::{command::CommandPool, device::Queue};
+use std::collections::HashMap;
 
+const ATLAS_SIZE: u32 = 1024;
+// the top-left texels are always opaque white, so solid quads can share the glyph texture
+const WHITE_SIZE: u32 = 2;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Glyph {
+	/// Offset of the top-left corner from the pen position on the baseline, in pixels.
+	pub offset: Vector2<f32>,
+	pub size: Vector2<f32>,
+	pub advance: f32,
+	/// Top-left and bottom-right texture coordinates in the atlas.
+	pub uv: [Vector2<f32>; 2],
+}
+
+/// Rasterizes glyphs on demand into a single atlas texture. Each pixel size gets its own glyphs, so text stays crisp
+/// at any scale.
 pub struct Font {
 	font: KitFont,
-	atlas: TexAtlas,
+	units_per_em: f32,
+	glyphs: HashMap<(char, u32), Glyph>,
+	atlas: RgbaImage,
+	// shelf packing: glyphs fill rows left to right
+	shelf_pos: Vector2I,
+	shelf_height: i32,
+	dirty: bool,
+	// a glyph didn't fit, so the atlas is cleared before the next frame
+	full: bool,
 }
 impl Font {
-	pub fn new(queue: Arc<Queue>, pool: Arc<CommandPool>) -> Self {
+	pub fn new() -> Self {
 		let font = SystemSource::new().select_by_postscript_name("ArialMT").unwrap().load().unwrap();
-		Self { font, atlas: TexAtlas::new(queue, pool) }
+		let units_per_em = font.metrics().units_per_em as f32;
+		let mut ret = Self {
+			font,
+			units_per_em,
+			glyphs: HashMap::new(),
+			atlas: RgbaImage::new(ATLAS_SIZE, ATLAS_SIZE),
+			shelf_pos: Vector2I::zero(),
+			shelf_height: 0,
+			dirty: true,
+			full: false,
+		};
+		ret.clear_atlas();
+		ret
 	}
 
-	pub fn load_char(&mut self, ch: char) {
-		let mut canvas = Canvas::new(Vector2I::splat(32), Format::A8);
+	pub fn ascent(&self, px: f32) -> f32 {
+		self.font.metrics().ascent * px / self.units_per_em
+	}
 
-		let glyph = self.font.glyph_for_char(ch).unwrap();
-		let transform = Transform2F::from_translation(Vector2F::new(0.0, 32.0));
-		let rasterization = RasterizationOptions::GrayscaleAa;
+	pub fn line_height(&self, px: f32) -> f32 {
+		let metrics = self.font.metrics();
+		(metrics.ascent - metrics.descent + metrics.line_gap) * px / self.units_per_em
+	}
+
+	/// Width of `text` in pixels.
+	pub fn measure(&mut self, text: &str, px: f32) -> f32 {
+		text.chars().map(|ch| self.glyph(ch, px).advance).sum()
+	}
+
+	/// Rasterizes the glyph into the atlas if it isn't there yet. Glyphs that don't fit have no size, so only their
+	/// advance counts: ones larger than the atlas always, others until `begin_frame` makes room.
+	pub fn glyph(&mut self, ch: char, px: f32) -> Glyph {
+		let key = (ch, px.round() as u32);
+		if let Some(glyph) = self.glyphs.get(&key) {
+			return *glyph;
+		}
+
+		let glyph = match self.rasterize(ch, key.1 as f32) {
+			Some(glyph) => glyph,
+			None => {
+				// glyphs already drawn this frame point into the atlas, so it can't be cleared until the frame is over
+				self.full = true;
+				return self.blank(ch, key.1 as f32);
+			},
+		};
+		self.glyphs.insert(key, glyph);
+		glyph
+	}
+
+	/// Clears the atlas if a glyph didn't fit in it during the last frame. Call before drawing any text of a frame.
+	pub fn begin_frame(&mut self) {
+		if self.full {
+			self.full = false;
+			self.clear_atlas();
+		}
+	}
+
+	/// Texture coordinates of a fully opaque texel.
+	pub fn white_uv(&self) -> Vector2<f32> {
+		Vector2::new(WHITE_SIZE as f32 * 0.5, WHITE_SIZE as f32 * 0.5) / ATLAS_SIZE as f32
+	}
+
+	pub fn atlas(&self) -> &RgbaImage {
+		&self.atlas
+	}
+
+	/// Fraction of the atlas taken by glyph rows so far, from 0 to 1.
+	pub fn atlas_usage(&self) -> f32 {
+		(self.shelf_pos.y() + self.shelf_height) as f32 / ATLAS_SIZE as f32
+	}
+
+	/// Returns whether the atlas changed since the last call.
+	pub fn take_dirty(&mut self) -> bool {
+		let dirty = self.dirty;
+		self.dirty = false;
+		dirty
+	}
+
+	// `None` if there's no room left in the atlas
+	fn rasterize(&mut self, ch: char, px: f32) -> Option<Glyph> {
+		let id = self.glyph_id(ch);
 		let hinting = HintingOptions::None;
-		self.font.rasterize_glyph(&mut canvas, glyph, 32.0, transform, hinting, rasterization).unwrap();
+		let rasterization = RasterizationOptions::GrayscaleAa;
+		let bounds = self.font.raster_bounds(id, px, Transform2F::default(), hinting, rasterization).unwrap();
+		let size = bounds.size();
+		// whitespace, or too large to fit next to the white texels even in an empty atlas
+		let limit = (ATLAS_SIZE - WHITE_SIZE - 1) as i32;
+		if size.x() <= 0 || size.y() <= 0 || size.x() >= limit || size.y() >= limit {
+			return Some(self.blank(ch, px));
+		}
+
+		let pos = self.alloc(size)?;
+		let mut canvas = Canvas::new(size, Format::A8);
+		let transform =
+			Transform2F::from_translation(Vector2F::new(-bounds.origin_x() as f32, -bounds.origin_y() as f32));
+		self.font.rasterize_glyph(&mut canvas, id, px, transform, hinting, rasterization).unwrap();
+
+		for y in 0..size.y() {
+			for x in 0..size.x() {
+				let coverage = canvas.pixels[y as usize * canvas.stride + x as usize];
+				self.atlas.put_pixel((pos.x() + x) as u32, (pos.y() + y) as u32, Rgba([255, 255, 255, coverage]));
+			}
+		}
+		self.dirty = true;
+
+		let min = Vector2::new(pos.x() as f32, pos.y() as f32);
+		let size = Vector2::new(size.x() as f32, size.y() as f32);
+		Some(Glyph {
+			offset: Vector2::new(bounds.origin_x() as f32, bounds.origin_y() as f32),
+			size,
+			advance: self.advance(id, px),
+			uv: [min / ATLAS_SIZE as f32, (min + size) / ATLAS_SIZE as f32],
+		})
+	}
+
+	// a glyph with nothing to draw
+	fn blank(&self, ch: char, px: f32) -> Glyph {
+		let uv = [self.white_uv(), self.white_uv()];
+		Glyph { offset: Vector2::zeros(), size: Vector2::zeros(), advance: self.advance(self.glyph_id(ch), px), uv }
+	}
+
+	fn glyph_id(&self, ch: char) -> u32 {
+		self.font.glyph_for_char(ch).or_else(|| self.font.glyph_for_char('?')).unwrap_or(0)
+	}
+
+	fn advance(&self, id: u32, px: f32) -> f32 {
+		self.font.advance(id).unwrap().x() * px / self.units_per_em
+	}
+
+	fn alloc(&mut self, size: Vector2I) -> Option<Vector2I> {
+		// leave a pixel between glyphs so linear filtering doesn't bleed
+		let (w, h) = (size.x() + 1, size.y() + 1);
+		if self.shelf_pos.x() + w > ATLAS_SIZE as i32 {
+			self.shelf_pos = Vector2I::new(0, self.shelf_pos.y() + self.shelf_height);
+			self.shelf_height = 0;
+		}
+		if self.shelf_pos.y() + h > ATLAS_SIZE as i32 || w > ATLAS_SIZE as i32 {
+			return None;
+		}
+
+		let pos = self.shelf_pos;
+		self.shelf_pos = Vector2I::new(pos.x() + w, pos.y());
+		self.shelf_height = self.shelf_height.max(h);
+		Some(pos)
+	}
+
+	fn clear_atlas(&mut self) {
+		self.glyphs.clear();
+		for pixel in self.atlas.pixels_mut() {
+			*pixel = Rgba([0, 0, 0, 0]);
+		}
+		for y in 0..WHITE_SIZE {
+			for x in 0..WHITE_SIZE {
+				self.atlas.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+			}
+		}
+		self.shelf_pos = Vector2I::new(WHITE_SIZE as i32 + 1, 0);
+		self.shelf_height = WHITE_SIZE as i32 + 1;
+		self.dirty = true;
+	}
+}
 
-		let (tex, future) = self.atlas.alloc(32, 32);
+impl Default for Font {
+	fn default() -> Self {
+		Self::new()
 	}
 }
@@ -0,0 +1,108 @@
+use nalgebra::Vector2;
+use winit::event::{ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+
+// pixels scrolled per wheel notch
+const LINE_HEIGHT: f32 = 40.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+	Backspace,
+	Delete,
+	Left,
+	Right,
+	Home,
+	End,
+	Enter,
+	Escape,
+}
+
+/// Text typed into the focused text input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Typed {
+	Char(char),
+	Key(Key),
+}
+
+/// Input gathered since the last frame, in logical pixels.
+#[derive(Default)]
+pub(super) struct Input {
+	pub(super) scale: f32,
+	pub(super) mouse_pos: Vector2<f32>,
+	pub(super) mouse_delta: Vector2<f32>,
+	pub(super) mouse_down: bool,
+	pub(super) mouse_pressed: bool,
+	pub(super) mouse_released: bool,
+	pub(super) scroll: Vector2<f32>,
+	// in the order it arrived, so a character typed after pressing Home goes at the start
+	pub(super) typed: Vec<Typed>,
+}
+impl Input {
+	pub(super) fn new() -> Self {
+		Self { scale: 1.0, mouse_pos: Vector2::new(-1.0, -1.0), ..Self::default() }
+	}
+
+	pub(super) fn handle(&mut self, event: &WindowEvent) {
+		match event {
+			WindowEvent::CursorMoved { position, .. } => {
+				let pos = Vector2::new(position.x as f32, position.y as f32) / self.scale;
+				self.mouse_delta += pos - self.mouse_pos;
+				self.mouse_pos = pos;
+			},
+			// park the cursor somewhere no widget can be, so nothing stays hovered
+			WindowEvent::CursorLeft { .. } => self.mouse_pos = Vector2::new(-1.0, -1.0),
+			WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => match state {
+				ElementState::Pressed => {
+					self.mouse_down = true;
+					self.mouse_pressed = true;
+				},
+				ElementState::Released => {
+					self.mouse_down = false;
+					self.mouse_released = true;
+				},
+			},
+			WindowEvent::MouseWheel { delta, .. } => match delta {
+				MouseScrollDelta::LineDelta(x, y) => self.scroll += Vector2::new(*x, *y) * LINE_HEIGHT,
+				MouseScrollDelta::PixelDelta(pos) => {
+					self.scroll += Vector2::new(pos.x as f32, pos.y as f32) / self.scale
+				},
+			},
+			WindowEvent::ReceivedCharacter(ch) if !ch.is_control() => self.typed.push(Typed::Char(*ch)),
+			WindowEvent::KeyboardInput {
+				input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(key), .. },
+				..
+			} => {
+				let key = match key {
+					VirtualKeyCode::Back => Key::Backspace,
+					VirtualKeyCode::Delete => Key::Delete,
+					VirtualKeyCode::Left => Key::Left,
+					VirtualKeyCode::Right => Key::Right,
+					VirtualKeyCode::Home => Key::Home,
+					VirtualKeyCode::End => Key::End,
+					VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Key::Enter,
+					VirtualKeyCode::Escape => Key::Escape,
+					_ => return,
+				};
+				self.typed.push(Typed::Key(key));
+			},
+			WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+				let scale = *scale_factor as f32;
+				self.mouse_pos *= self.scale / scale;
+				self.scale = scale;
+			},
+			_ => (),
+		}
+	}
+
+	pub(super) fn key(&self, key: Key) -> bool {
+		self.typed.contains(&Typed::Key(key))
+	}
+
+	// forget everything that only lasts one frame
+	pub(super) fn end_frame(&mut self) {
+		self.mouse_delta = Vector2::zeros();
+		self.mouse_pressed = false;
+		self.mouse_released = false;
+		self.scroll = Vector2::zeros();
+		self.typed.clear();
+	}
+}
@@ -0,0 +1,180 @@
+use nalgebra::Vector2;
+
+/// A rectangle in logical pixels, with y pointing down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+	pub pos: Vector2<f32>,
+	pub size: Vector2<f32>,
+}
+impl Rect {
+	pub fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+		Self { pos: Vector2::new(x, y), size: Vector2::new(w, h) }
+	}
+
+	pub fn min(&self) -> Vector2<f32> {
+		self.pos
+	}
+
+	pub fn max(&self) -> Vector2<f32> {
+		self.pos + self.size
+	}
+
+	pub fn contains(&self, point: Vector2<f32>) -> bool {
+		let max = self.max();
+		self.pos.x <= point.x && point.x < max.x && self.pos.y <= point.y && point.y < max.y
+	}
+
+	pub fn intersection(&self, other: &Rect) -> Rect {
+		let (min, max) = (self.min(), self.max());
+		let (other_min, other_max) = (other.min(), other.max());
+		let pos = Vector2::new(min.x.max(other_min.x), min.y.max(other_min.y));
+		let end = Vector2::new(max.x.min(other_max.x), max.y.min(other_max.y));
+		Rect { pos, size: Vector2::new((end.x - pos.x).max(0.0), (end.y - pos.y).max(0.0)) }
+	}
+
+	/// Shrinks every side by `amount`.
+	pub fn shrink(&self, amount: f32) -> Rect {
+		let size = self.size - Vector2::new(amount, amount) * 2.0;
+		Rect { pos: self.pos + Vector2::new(amount, amount), size: Vector2::new(size.x.max(0.0), size.y.max(0.0)) }
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+	Row,
+	Column,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Anchor {
+	TopLeft,
+	Top,
+	TopRight,
+	Left,
+	Center,
+	Right,
+	BottomLeft,
+	Bottom,
+	BottomRight,
+}
+impl Anchor {
+	/// Places a rectangle of `size` inside `container`.
+	pub fn place(self, container: Rect, size: Vector2<f32>) -> Rect {
+		let space = container.size - size;
+		let (x, y) = match self {
+			Self::TopLeft => (0.0, 0.0),
+			Self::Top => (0.5, 0.0),
+			Self::TopRight => (1.0, 0.0),
+			Self::Left => (0.0, 0.5),
+			Self::Center => (0.5, 0.5),
+			Self::Right => (1.0, 0.5),
+			Self::BottomLeft => (0.0, 1.0),
+			Self::Bottom => (0.5, 1.0),
+			Self::BottomRight => (1.0, 1.0),
+		};
+		Rect { pos: container.pos + Vector2::new(space.x * x, space.y * y), size }
+	}
+}
+
+/// Hands out space for widgets one after another along a row or column.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Layout {
+	pub(super) bounds: Rect,
+	dir: Direction,
+	spacing: f32,
+	cursor: f32,
+	// largest extent across the main direction
+	cross: f32,
+}
+impl Layout {
+	pub(super) fn new(bounds: Rect, dir: Direction, spacing: f32) -> Self {
+		Self { bounds, dir, spacing, cursor: 0.0, cross: 0.0 }
+	}
+
+	/// Where the next widget goes, with all the space left after it.
+	pub(super) fn remaining(&self) -> Rect {
+		let offset = self.offset();
+		let size = self.bounds.size - offset;
+		Rect { pos: self.bounds.pos + offset, size: Vector2::new(size.x.max(0.0), size.y.max(0.0)) }
+	}
+
+	pub(super) fn alloc(&mut self, size: Vector2<f32>) -> Rect {
+		let rect = Rect { pos: self.bounds.pos + self.offset(), size };
+		let (main, cross) = match self.dir {
+			Direction::Row => (size.x, size.y),
+			Direction::Column => (size.y, size.x),
+		};
+		self.cursor += main + self.spacing;
+		self.cross = self.cross.max(cross);
+		rect
+	}
+
+	pub(super) fn space(&mut self, amount: f32) {
+		self.cursor += amount;
+	}
+
+	/// Size of everything allocated so far.
+	pub(super) fn used(&self) -> Vector2<f32> {
+		let main = (self.cursor - self.spacing).max(0.0);
+		match self.dir {
+			Direction::Row => Vector2::new(main, self.cross),
+			Direction::Column => Vector2::new(self.cross, main),
+		}
+	}
+
+	fn offset(&self) -> Vector2<f32> {
+		match self.dir {
+			Direction::Row => Vector2::new(self.cursor, 0.0),
+			Direction::Column => Vector2::new(0.0, self.cursor),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rect_intersection() {
+		let rect = Rect::new(0.0, 0.0, 10.0, 10.0);
+		assert_eq!(rect.intersection(&Rect::new(5.0, -5.0, 10.0, 10.0)), Rect::new(5.0, 0.0, 5.0, 5.0));
+		// disjoint rectangles meet in an empty one rather than a negative size
+		assert_eq!(rect.intersection(&Rect::new(20.0, 20.0, 5.0, 5.0)).size, Vector2::zeros());
+		// the far edges are outside
+		assert!(rect.contains(Vector2::new(0.0, 9.9)));
+		assert!(!rect.contains(Vector2::new(10.0, 5.0)));
+		assert_eq!(rect.shrink(2.0), Rect::new(2.0, 2.0, 6.0, 6.0));
+		assert_eq!(rect.shrink(8.0).size, Vector2::zeros());
+	}
+
+	#[test]
+	fn anchors() {
+		let container = Rect::new(10.0, 20.0, 100.0, 50.0);
+		let size = Vector2::new(20.0, 10.0);
+		assert_eq!(Anchor::TopLeft.place(container, size), Rect::new(10.0, 20.0, 20.0, 10.0));
+		assert_eq!(Anchor::Center.place(container, size), Rect::new(50.0, 40.0, 20.0, 10.0));
+		assert_eq!(Anchor::BottomRight.place(container, size), Rect::new(90.0, 60.0, 20.0, 10.0));
+	}
+
+	#[test]
+	fn column() {
+		let mut layout = Layout::new(Rect::new(10.0, 10.0, 100.0, 100.0), Direction::Column, 4.0);
+		assert_eq!(layout.used(), Vector2::zeros());
+		assert_eq!(layout.alloc(Vector2::new(30.0, 10.0)), Rect::new(10.0, 10.0, 30.0, 10.0));
+		layout.space(6.0);
+		assert_eq!(layout.alloc(Vector2::new(50.0, 20.0)), Rect::new(10.0, 30.0, 50.0, 20.0));
+		// no spacing after the last widget
+		assert_eq!(layout.used(), Vector2::new(50.0, 40.0));
+		assert_eq!(layout.remaining(), Rect::new(10.0, 54.0, 100.0, 56.0));
+	}
+
+	#[test]
+	fn row_overflow() {
+		let mut layout = Layout::new(Rect::new(0.0, 0.0, 50.0, 20.0), Direction::Row, 5.0);
+		layout.alloc(Vector2::new(30.0, 10.0));
+		assert_eq!(layout.alloc(Vector2::new(30.0, 15.0)), Rect::new(35.0, 0.0, 30.0, 15.0));
+		assert_eq!(layout.used(), Vector2::new(65.0, 15.0));
+		// what's left can't go negative once widgets run past the bounds
+		assert_eq!(layout.remaining(), Rect::new(70.0, 0.0, 0.0, 20.0));
+	}
+}
@@ -0,0 +1,62 @@
+use crate::gfx::{
+	gui::{layout::Rect, theme::Color, Gui},
+	shape::{Path, Stroke},
+};
+use nalgebra::Vector2;
+
+/// Draws shapes and text on top of a `Gui`, without layout or input. Coordinates are in logical pixels.
+pub struct Painter<'a> {
+	pub(super) gui: &'a mut Gui,
+}
+impl Painter<'_> {
+	/// Size of the window in logical pixels.
+	pub fn size(&self) -> Vector2<f32> {
+		self.gui.win_size
+	}
+
+	pub fn scale(&self) -> f32 {
+		self.gui.input.scale
+	}
+
+	pub fn rect(&mut self, rect: Rect, color: Color) {
+		let gui = &mut *self.gui;
+		gui.draw.rect(&gui.font, rect, color, self.bounds(), gui.input.scale);
+	}
+
+	pub fn fill(&mut self, path: &Path, color: Color) {
+		let mut verts = vec![];
+		path.fill(color, &mut verts);
+		self.triangles(verts.iter().map(|vert| vert.pos), color);
+	}
+
+	pub fn stroke(&mut self, path: &Path, stroke: &Stroke, color: Color) {
+		let mut verts = vec![];
+		path.stroke(stroke, color, &mut verts);
+		self.triangles(verts.iter().map(|vert| vert.pos), color);
+	}
+
+	/// Draws a line of text in the theme's font with its top-left corner at `pos`, returning its width.
+	pub fn text(&mut self, pos: Vector2<f32>, text: &str, color: Color) -> f32 {
+		let bounds = self.bounds();
+		let gui = &mut *self.gui;
+		let px = gui.theme.font_size;
+		gui.draw.text(&mut gui.font, pos, text, px, color, bounds, gui.input.scale);
+		gui.font.measure(text, px * gui.input.scale) / gui.input.scale
+	}
+
+	pub fn line_height(&self) -> f32 {
+		let scale = self.gui.input.scale;
+		self.gui.font.line_height(self.gui.theme.font_size * scale) / scale
+	}
+
+	fn triangles(&mut self, corners: impl Iterator<Item = Vector2<f32>>, color: Color) {
+		let gui = &mut *self.gui;
+		let scale = gui.input.scale;
+		let clip = Rect { pos: Vector2::zeros(), size: gui.win_size * scale };
+		gui.draw.triangles(&gui.font, corners.map(|pos| pos * scale), color, clip);
+	}
+
+	fn bounds(&self) -> Rect {
+		Rect { pos: Vector2::zeros(), size: self.gui.win_size }
+	}
+}
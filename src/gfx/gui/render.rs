@@ -0,0 +1,115 @@
+use crate::gfx::{
+	gui::{draw::GuiVertex, layout::Rect, Gui},
+	material::{Material, MaterialParam},
+	pipeline::{BlendMode, PipelineDesc},
+	sampler::SamplerDesc,
+	texture::upload_image,
+	validation, Gfx,
+};
+use futures::executor::block_on;
+use std::{collections::HashMap, iter::once, sync::Arc};
+use typenum::B1;
+use vulkan::{
+	buffer::Buffer, command::CommandBufferBuilder, device::BufferUsageFlags, pipeline::GraphicsPipeline,
+	render_pass::RenderPass, sync::GpuFuture, Extent2D, Offset2D, Rect2D,
+};
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct GuiPushConsts {
+	pub win_size: [f32; 2],
+}
+
+/// Turns a `Gui`'s draw list into GPU resources, re-uploading the glyph atlas whenever new glyphs are rasterized.
+pub struct GuiRenderer {
+	pipeline: Arc<GraphicsPipeline>,
+	material: Option<Material>,
+}
+impl GuiRenderer {
+	pub fn new(gfx: &Gfx, render_pass: Arc<RenderPass>) -> Self {
+		let program = block_on(gfx.program("gui", "")).unwrap();
+		let desc = PipelineDesc {
+			layout: program.layout.clone(),
+			render_pass,
+			subpass: 0,
+			vshader: program.vshader.clone(),
+			fshader: program.fshader.clone(),
+			blend: BlendMode::Alpha,
+		};
+		let pipeline = gfx.pipelines.get::<GuiVertex>(&gfx.device, desc);
+		Self { pipeline, material: None }
+	}
+
+	pub fn pipeline(&self) -> &Arc<GraphicsPipeline> {
+		&self.pipeline
+	}
+
+	/// Only valid after `prepare`.
+	pub fn material(&self) -> &Material {
+		self.material.as_ref().unwrap()
+	}
+
+	/// Uploads everything needed to draw the last frame of `gui`. Returns `None` if there's nothing to draw.
+	pub fn prepare(&mut self, gfx: &Gfx, gui: &mut Gui) -> Option<Arc<Buffer<[GuiVertex]>>> {
+		if gui.font.take_dirty() || self.material.is_none() {
+			let (atlas, future) = upload_image(&gfx.queue, &gfx.cmdpool, gui.font.atlas().clone());
+			validation::set_name(&gfx.device, &*atlas, "glyph atlas");
+			// the atlas only changes when new glyphs show up, so stalling here is rare
+			future.then_signal_fence().wait();
+
+			let program = block_on(gfx.program("gui", "")).unwrap();
+			let mut params = HashMap::new();
+			params.insert("tex".to_owned(), MaterialParam::Texture(atlas, gfx.sampler(SamplerDesc::default())));
+			self.material = Some(Material::new(program, params).unwrap());
+		}
+
+		let verts = gui.draw_list().verts();
+		if verts.is_empty() {
+			return None;
+		}
+		let buffer = Buffer::init_slice(gfx.device.clone(), verts.len() as _, B1, BufferUsageFlags::VERTEX_BUFFER)
+			.copy_from_slice(verts);
+		validation::set_name(&gfx.device, &*buffer, "gui verts");
+		Some(buffer)
+	}
+
+	/// Records `gui`'s draw list with `verts` from the last `prepare`, over all of `extent`. Expects the viewport to be
+	/// set, and sets the scissor to each draw's clip.
+	pub fn record(
+		&self,
+		cmd: CommandBufferBuilder,
+		verts: &Arc<Buffer<[GuiVertex]>>,
+		gui: &Gui,
+		extent: Extent2D,
+	) -> CommandBufferBuilder {
+		let material = self.material();
+		let layout = material.program().layout.clone();
+		let stages = material.program().push_constant_stages();
+		let pc = GuiPushConsts { win_size: [extent.width as _, extent.height as _] };
+		let mut cmd = cmd
+			.bind_pipeline(self.pipeline.clone())
+			.bind_vertex_buffers(0, once(verts.clone() as _), &[0])
+			.bind_descriptor_sets(layout.clone(), 0, material.desc_sets().iter().cloned(), &[])
+			.push_constants(layout, stages, 0, &pc);
+		for draw in gui.draw_list().cmds() {
+			cmd = cmd
+				.set_scissor(0, &[clip_to_scissor(&draw.clip, extent)])
+				.draw(draw.vertex_count, 1, draw.first_vertex, 0);
+		}
+		cmd
+	}
+}
+
+/// Converts a clip rectangle in physical pixels to a scissor, clamped to the framebuffer.
+pub fn clip_to_scissor(clip: &Rect, extent: Extent2D) -> Rect2D {
+	let min = clip.min();
+	let max = clip.max();
+	let x = (min.x.max(0.0) as u32).min(extent.width);
+	let y = (min.y.max(0.0) as u32).min(extent.height);
+	let right = (max.x.max(0.0).ceil() as u32).min(extent.width).max(x);
+	let bottom = (max.y.max(0.0).ceil() as u32).min(extent.height).max(y);
+	Rect2D::builder()
+		.offset(Offset2D { x: x as _, y: y as _ })
+		.extent(Extent2D { width: right - x, height: bottom - y })
+		.build()
+}
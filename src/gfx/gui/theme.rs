@@ -0,0 +1,44 @@
+pub type Color = [f32; 4];
+
+/// Colors and metrics for every widget, in logical pixels.
+#[derive(Clone, Debug)]
+pub struct Theme {
+	pub font_size: f32,
+	/// Space between a widget's border and its contents.
+	pub padding: f32,
+	/// Space between neighbouring widgets.
+	pub spacing: f32,
+	pub title_height: f32,
+	pub scrollbar_width: f32,
+	pub slider_width: f32,
+	pub text_input_width: f32,
+	pub text: Color,
+	pub window: Color,
+	pub title: Color,
+	pub panel: Color,
+	pub widget: Color,
+	pub hovered: Color,
+	pub active: Color,
+	pub accent: Color,
+}
+impl Default for Theme {
+	fn default() -> Self {
+		Self {
+			font_size: 16.0,
+			padding: 6.0,
+			spacing: 4.0,
+			title_height: 24.0,
+			scrollbar_width: 8.0,
+			slider_width: 160.0,
+			text_input_width: 200.0,
+			text: [0.92, 0.92, 0.92, 1.0],
+			window: [0.1, 0.1, 0.12, 0.94],
+			title: [0.18, 0.2, 0.26, 1.0],
+			panel: [0.06, 0.06, 0.08, 0.9],
+			widget: [0.24, 0.26, 0.32, 1.0],
+			hovered: [0.32, 0.35, 0.44, 1.0],
+			active: [0.4, 0.44, 0.56, 1.0],
+			accent: [0.36, 0.6, 0.96, 1.0],
+		}
+	}
+}
@@ -0,0 +1,317 @@
+use crate::gfx::gui::{
+	input::{Key, Typed},
+	layout::{Direction, Layout, Rect},
+	Id, Response, ScrollState, Ui,
+};
+use nalgebra::Vector2;
+use std::{f32::INFINITY, ops::RangeInclusive};
+
+impl<'a> Ui<'a> {
+	pub fn label(&mut self, text: &str) {
+		let size = Vector2::new(self.text_width(text), self.line_height());
+		let rect = self.layout.alloc(size);
+		let color = self.gui.theme.text;
+		self.draw_text(rect.pos, text, color, rect);
+	}
+
+	/// Returns true when clicked.
+	pub fn button(&mut self, label: &str) -> bool {
+		let padding = self.gui.theme.padding;
+		let size = Vector2::new(self.text_width(label), self.line_height()) + Vector2::new(padding, padding) * 2.0;
+		let rect = self.layout.alloc(size);
+		let response = self.interact(Id::new(self.id, label), rect);
+
+		let color = self.widget_color(response);
+		self.draw_rect(rect, color);
+		let text = self.gui.theme.text;
+		self.draw_text(rect.pos + Vector2::new(padding, padding), label, text, rect);
+		response.clicked
+	}
+
+	/// Returns true when toggled.
+	pub fn checkbox(&mut self, label: &str, value: &mut bool) -> bool {
+		let line_height = self.line_height();
+		let spacing = self.gui.theme.spacing;
+		let size = Vector2::new(line_height + spacing + self.text_width(label), line_height);
+		let rect = self.layout.alloc(size);
+		let response = self.interact(Id::new(self.id, label), rect);
+		if response.clicked {
+			*value = !*value;
+		}
+
+		let check = Rect { pos: rect.pos, size: Vector2::new(line_height, line_height) };
+		let color = self.widget_color(response);
+		self.draw_rect(check, color);
+		if *value {
+			let accent = self.gui.theme.accent;
+			self.draw_rect(check.shrink(line_height * 0.25), accent);
+		}
+		let text = self.gui.theme.text;
+		self.draw_text(rect.pos + Vector2::new(line_height + spacing, 0.0), label, text, rect);
+		response.clicked
+	}
+
+	/// Returns true when the value changed.
+	pub fn slider(&mut self, label: &str, value: &mut f32, range: RangeInclusive<f32>) -> bool {
+		let theme = &self.gui.theme;
+		let (padding, width) = (theme.padding, theme.slider_width);
+		let size = Vector2::new(width, self.line_height() + padding * 2.0);
+		let rect = self.layout.alloc(size);
+		let response = self.interact(Id::new(self.id, label), rect);
+
+		let (min, max) = (*range.start(), *range.end());
+		let old = *value;
+		if response.active {
+			let t = ((self.gui.input.mouse_pos.x - rect.pos.x) / rect.size.x).max(0.0).min(1.0);
+			*value = min + (max - min) * t;
+		}
+
+		let color = self.widget_color(response);
+		self.draw_rect(rect, color);
+		let t = if max > min { ((*value - min) / (max - min)).max(0.0).min(1.0) } else { 0.0 };
+		let fill = Rect { pos: rect.pos, size: Vector2::new(rect.size.x * t, rect.size.y) };
+		let accent = self.gui.theme.accent;
+		self.draw_rect(fill, accent);
+
+		let text = format!("{}: {:.2}", label, *value);
+		let text_pos = rect.pos + Vector2::new((rect.size.x - self.text_width(&text)) * 0.5, padding);
+		let color = self.gui.theme.text;
+		self.draw_text(text_pos, &text, color, rect);
+		*value != old
+	}
+
+	/// A single line of editable text. Returns true when the text changed.
+	pub fn text_input(&mut self, label: &str, text: &mut String) -> bool {
+		let theme = &self.gui.theme;
+		let (padding, spacing, width) = (theme.padding, theme.spacing, theme.text_input_width);
+		let line_height = self.line_height();
+		let size = Vector2::new(width + spacing + self.text_width(label), line_height + padding * 2.0);
+		let rect = self.layout.alloc(size);
+		let field = Rect { pos: rect.pos, size: Vector2::new(width, rect.size.y) };
+		let id = Id::new(self.id, label);
+		let response = self.interact(id, field);
+
+		if response.pressed {
+			self.gui.focus = Some(id);
+			self.gui.text_cursor = text.len();
+		}
+		if response.hovered && self.gui.input.mouse_pressed {
+			self.gui.focus_taken = true;
+		}
+
+		let focused = self.gui.focus == Some(id);
+		let old_len = text.len();
+		let mut changed = false;
+		if focused {
+			changed = self.edit_text(text);
+		}
+
+		let color = if focused { self.gui.theme.active } else { self.widget_color(response) };
+		self.draw_rect(field, color);
+		let text_color = self.gui.theme.text;
+		let text_pos = field.pos + Vector2::new(padding, padding);
+		self.draw_text(text_pos, text, text_color, field.shrink(padding * 0.5));
+		if focused {
+			let cursor = char_boundary(text, self.gui.text_cursor);
+			let caret_x = self.text_width(&text[..cursor]);
+			let caret = Rect { pos: text_pos + Vector2::new(caret_x, 0.0), size: Vector2::new(1.0, line_height) };
+			self.draw_rect(caret, text_color);
+		}
+		self.draw_text(rect.pos + Vector2::new(width + spacing, padding), label, text_color, rect);
+
+		changed || text.len() != old_len
+	}
+
+	/// A fixed-height region that scrolls its contents with the mouse wheel.
+	pub fn scroll_panel<R>(&mut self, name: &str, height: f32, f: impl FnOnce(&mut Ui) -> R) -> R {
+		let theme = &self.gui.theme;
+		let (padding, spacing, scrollbar_width, panel) =
+			(theme.padding, theme.spacing, theme.scrollbar_width, theme.panel);
+		let id = Id::new(self.id, name);
+		let rect = self.layout.alloc(Vector2::new(self.layout.remaining().size.x, height));
+		self.draw_rect(rect, panel);
+		self.capture(rect);
+
+		let mut state = self.gui.scroll.get(&id).copied().unwrap_or_default();
+		let input = &self.gui.input;
+		let hovered = self.gui.hover_layer == Some(self.gui.layers[self.layer].id)
+			&& rect.contains(input.mouse_pos)
+			&& self.clip.contains(input.mouse_pos);
+		if hovered {
+			state.offset -= input.scroll.y;
+		}
+		state.offset = state.offset.min(state.content_height - height).max(0.0);
+
+		let content = Rect {
+			pos: rect.pos + Vector2::new(padding, padding - state.offset),
+			size: Vector2::new(rect.size.x - padding * 2.0 - scrollbar_width, INFINITY),
+		};
+		let clip = rect.intersection(&self.clip);
+		let layer = self.layer;
+		let mut child = self.child(id, layer, Layout::new(content, Direction::Column, spacing), clip);
+		let ret = f(&mut child);
+		state.content_height = child.layout.used().y + padding * 2.0;
+
+		if state.content_height > height {
+			let thumb_height = height * height / state.content_height;
+			let thumb_y = state.offset / (state.content_height - height) * (height - thumb_height);
+			let thumb = Rect::new(rect.max().x - scrollbar_width, rect.pos.y + thumb_y, scrollbar_width, thumb_height);
+			let color = self.gui.theme.widget;
+			self.draw_rect(thumb, color);
+		}
+
+		self.gui.scroll.insert(id, state);
+		ret
+	}
+
+	/// A movable window on top of everything else. `initial` is its placement the first time it's shown.
+	pub fn window<R>(&mut self, title: &str, initial: Rect, f: impl FnOnce(&mut Ui) -> R) -> R {
+		let theme = &self.gui.theme;
+		let (padding, spacing, title_height) = (theme.padding, theme.spacing, theme.title_height);
+		let (window_color, title_color, text_color) = (theme.window, theme.title, theme.text);
+
+		// windows share one namespace, wherever they're declared
+		let id = Id::new(Id::ROOT, title);
+		let layer = self.gui.layer(id);
+		let screen = Rect { pos: Vector2::zeros(), size: self.gui.win_size };
+		let mut rect = *self.gui.windows.entry(id).or_insert(initial);
+
+		let mut window = self.child(id, layer, Layout::new(rect, Direction::Column, spacing), screen);
+		let title_bar = Rect { pos: rect.pos, size: Vector2::new(rect.size.x, title_height) };
+		let response = window.interact(Id::new(id, "title"), title_bar);
+		if response.active {
+			rect.pos += window.gui.input.mouse_delta;
+			// keep the title bar reachable
+			let max = screen.size - Vector2::new(title_height, title_height);
+			rect.pos = Vector2::new(rect.pos.x.max(0.0).min(max.x), rect.pos.y.max(0.0).min(max.y));
+		}
+		window.gui.windows.insert(id, rect);
+
+		let title_bar = Rect { pos: rect.pos, size: title_bar.size };
+		window.capture(rect);
+		window.draw_rect(rect, window_color);
+		window.draw_rect(title_bar, title_color);
+		window.draw_text(
+			title_bar.pos + Vector2::new(padding, (title_height - window.line_height()) * 0.5),
+			title,
+			text_color,
+			title_bar,
+		);
+
+		let body =
+			Rect { pos: rect.pos + Vector2::new(0.0, title_height), size: rect.size - Vector2::new(0.0, title_height) };
+		window.layout = Layout::new(body.shrink(padding), Direction::Column, spacing);
+		window.clip = body.intersection(&screen);
+		f(&mut window)
+	}
+
+	fn widget_color(&self, response: Response) -> [f32; 4] {
+		let theme = &self.gui.theme;
+		if response.active {
+			theme.active
+		} else if response.hovered {
+			theme.hovered
+		} else {
+			theme.widget
+		}
+	}
+
+	fn edit_text(&mut self, text: &mut String) -> bool {
+		// the text may have changed since the cursor was placed, even to put a multi-byte character under it
+		let mut cursor = char_boundary(text, self.gui.text_cursor);
+		let (changed, done) = type_into(text, &mut cursor, &self.gui.input.typed);
+		// typing was handled here, so nothing else acts on it
+		self.gui.input.typed.clear();
+		if done {
+			self.gui.focus = None;
+		}
+		self.gui.text_cursor = cursor;
+		changed
+	}
+}
+
+// the closest character boundary at or before byte offset `idx`
+fn char_boundary(text: &str, idx: usize) -> usize {
+	let mut idx = idx.min(text.len());
+	while !text.is_char_boundary(idx) {
+		idx -= 1;
+	}
+	idx
+}
+
+// edits `text` at byte offset `cursor`, which must be a character boundary, returning whether the text changed and
+// whether Enter or Escape was pressed
+fn type_into(text: &mut String, cursor: &mut usize, typed: &[Typed]) -> (bool, bool) {
+	let (mut changed, mut done) = (false, false);
+	for &typed in typed {
+		match typed {
+			Typed::Char(ch) => {
+				text.insert(*cursor, ch);
+				*cursor += ch.len_utf8();
+				changed = true;
+			},
+			Typed::Key(Key::Backspace) => {
+				if let Some(ch) = text[..*cursor].chars().next_back() {
+					*cursor -= ch.len_utf8();
+					text.remove(*cursor);
+					changed = true;
+				}
+			},
+			Typed::Key(Key::Delete) => {
+				if *cursor < text.len() {
+					text.remove(*cursor);
+					changed = true;
+				}
+			},
+			Typed::Key(Key::Left) => *cursor -= text[..*cursor].chars().next_back().map_or(0, char::len_utf8),
+			Typed::Key(Key::Right) => *cursor += text[*cursor..].chars().next().map_or(0, char::len_utf8),
+			Typed::Key(Key::Home) => *cursor = 0,
+			Typed::Key(Key::End) => *cursor = text.len(),
+			Typed::Key(Key::Enter | Key::Escape) => done = true,
+		}
+	}
+	(changed, done)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn typing(text: &str, cursor: usize, typed: &[Typed]) -> (String, usize, bool) {
+		let (mut text, mut cursor) = (text.to_owned(), cursor);
+		let (_, done) = type_into(&mut text, &mut cursor, typed);
+		(text, cursor, done)
+	}
+
+	#[test]
+	fn typing_in_order() {
+		use Typed::{Char, Key as K};
+
+		// characters typed after a key go where the key left the cursor
+		let typed = [Char('b'), K(Key::Home), Char('a'), K(Key::End), Char('c')];
+		assert_eq!(typing("", 0, &typed), ("abc".to_owned(), 3, false));
+		let typed = [Char('x'), K(Key::Backspace), K(Key::Left), K(Key::Delete), Char('y'), K(Key::Enter)];
+		assert_eq!(typing("ab", 2, &typed), ("ay".to_owned(), 2, true));
+		// nothing to delete at either end
+		let typed = [K(Key::Backspace), K(Key::Left), K(Key::End), K(Key::Delete), K(Key::Right)];
+		assert_eq!(typing("ab", 0, &typed), ("ab".to_owned(), 2, false));
+	}
+
+	#[test]
+	fn multi_byte_characters() {
+		use Typed::{Char, Key as K};
+
+		let typed = [K(Key::Left), K(Key::Backspace), Char('ü'), K(Key::Right)];
+		assert_eq!(typing("aé€", 6, &typed), ("aü€".to_owned(), 6, false));
+	}
+
+	#[test]
+	fn cursor_inside_character() {
+		// 'é' takes bytes 1 and 2, '€' bytes 3 to 5
+		assert_eq!(char_boundary("aé€", 2), 1);
+		assert_eq!(char_boundary("aé€", 5), 3);
+		assert_eq!(char_boundary("aé€", 6), 6);
+		assert_eq!(char_boundary("aé€", 100), 6);
+		assert_eq!(char_boundary("", 3), 0);
+	}
+}
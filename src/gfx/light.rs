@@ -0,0 +1,583 @@
+use crate::gfx::{
+	bindless::{TextureKey, Textures},
+	camera::Camera,
+	graph::CompiledLighting,
+	material::{Material, MaterialParam},
+	pipeline::{BlendMode, PipelineDesc},
+	sampler::SamplerDesc,
+	shader::ShaderProgram,
+	sprite::{SpriteDraw, SpriteList, SpritePushConsts},
+	texture::{upload_image, Subtex},
+	validation,
+	vertex::vertex_input,
+	Gfx, TriangleVertex,
+};
+use futures::executor::block_on;
+use image::{Rgba, RgbaImage};
+use log::warn;
+use nalgebra::Vector2;
+use std::{
+	collections::{HashMap, HashSet},
+	f32::consts::PI,
+	iter::once,
+	sync::Arc,
+};
+use typenum::B1;
+use vulkan::{
+	buffer::Buffer, command::CommandBufferBuilder, descriptor::DescriptorSet, device::BufferUsageFlags,
+	pipeline::GraphicsPipeline,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+	/// Shines in every direction.
+	Point,
+	/// Shines in a cone around `direction`, in radians, `angle` to each side. The edge fades over `softness` radians.
+	Spot { direction: f32, angle: f32, softness: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+	/// World pixels.
+	pub pos: Vector2<f32>,
+	pub kind: LightKind,
+	pub color: [f32; 3],
+	pub intensity: f32,
+	/// Distance at which the light fades out completely.
+	pub radius: f32,
+	/// Pixels above the scene. Lower lights graze normal-mapped surfaces at a flatter angle.
+	pub height: f32,
+	/// Whether occluders block the light.
+	pub shadows: bool,
+}
+impl Light {
+	pub fn point(pos: Vector2<f32>, radius: f32) -> Self {
+		Self { pos, kind: LightKind::Point, color: [1.0; 3], intensity: 1.0, radius, height: 64.0, shadows: true }
+	}
+
+	pub fn spot(pos: Vector2<f32>, radius: f32, direction: f32, angle: f32) -> Self {
+		let kind = LightKind::Spot { direction, angle, softness: angle * 0.25 };
+		Self { kind, ..Self::point(pos, radius) }
+	}
+
+	pub fn color(mut self, color: [f32; 3]) -> Self {
+		self.color = color;
+		self
+	}
+
+	pub fn intensity(mut self, intensity: f32) -> Self {
+		self.intensity = intensity;
+		self
+	}
+
+	pub fn height(mut self, height: f32) -> Self {
+		self.height = height;
+		self
+	}
+
+	pub fn shadows(mut self, shadows: bool) -> Self {
+		self.shadows = shadows;
+		self
+	}
+}
+
+/// A polygon that blocks light, like a wall or a pillar. The area it covers stays dark.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Occluder {
+	/// World pixels, in order around the outline. The last point connects back to the first.
+	pub points: Vec<Vector2<f32>>,
+}
+impl Occluder {
+	pub fn new(points: Vec<Vector2<f32>>) -> Self {
+		Self { points }
+	}
+
+	pub fn rect(pos: Vector2<f32>, size: Vector2<f32>) -> Self {
+		let (x, y) = (Vector2::new(size.x, 0.0), Vector2::new(0.0, size.y));
+		Self::new(vec![pos, pos + x, pos + size, pos + y])
+	}
+
+	fn edges(&self) -> impl Iterator<Item = (Vector2<f32>, Vector2<f32>)> + '_ {
+		let next = self.points.iter().cycle().skip(1);
+		self.points.iter().copied().zip(next.copied())
+	}
+}
+
+/// The lights and occluders of the world, drawn into the light buffer of the window's render graph, if it has one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LightScene {
+	/// Light reaching every pixel regardless of lights.
+	pub ambient: [f32; 3],
+	pub lights: Vec<Light>,
+	pub occluders: Vec<Occluder>,
+}
+impl Default for LightScene {
+	fn default() -> Self {
+		Self { ambient: [0.2; 3], lights: vec![], occluders: vec![] }
+	}
+}
+
+/// The area lit by a light at `center` out to `radius`, as a list of triangles. Soft edges come from blurring the light
+/// buffer afterwards.
+pub fn visibility(center: Vector2<f32>, radius: f32, occluders: &[Occluder]) -> Vec<Vector2<f32>> {
+	let (min, max) = (center.add_scalar(-radius), center.add_scalar(radius));
+	let corners = [min, Vector2::new(max.x, min.y), max, Vector2::new(min.x, max.y)];
+	let mut segments: Vec<_> = (0..4).map(|i| (corners[i], corners[(i + 1) % 4])).collect();
+	for occluder in occluders {
+		segments.extend(occluder.edges().filter(|(a, b)| {
+			a.x.max(b.x) >= min.x && a.x.min(b.x) <= max.x && a.y.max(b.y) >= min.y && a.y.min(b.y) <= max.y
+		}));
+	}
+
+	// the outline only changes direction at segment ends, so cast rays at them and just past either side
+	let mut angles: Vec<f32> = segments
+		.iter()
+		.flat_map(|&(a, b)| vec![a, b])
+		.flat_map(|point| {
+			let angle = (point.y - center.y).atan2(point.x - center.x);
+			vec![angle - 0.0001, angle, angle + 0.0001]
+		})
+		.collect();
+	angles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+	angles.dedup();
+
+	let outline: Vec<_> = angles
+		.into_iter()
+		.map(|angle| {
+			let dir = Vector2::new(angle.cos(), angle.sin());
+			// the bounding square surrounds the center, so every ray hits something
+			let dist = segments.iter().filter_map(|&(a, b)| ray_hit(center, dir, a, b)).fold(f32::INFINITY, f32::min);
+			center + dir * dist
+		})
+		.collect();
+
+	let mut triangles = Vec::with_capacity(outline.len() * 3);
+	for (i, &point) in outline.iter().enumerate() {
+		triangles.extend_from_slice(&[center, point, outline[(i + 1) % outline.len()]]);
+	}
+	triangles
+}
+
+// distance along `dir` from `origin` to the segment from `a` to `b`
+fn ray_hit(origin: Vector2<f32>, dir: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>) -> Option<f32> {
+	let cross = |u: Vector2<f32>, v: Vector2<f32>| u.x * v.y - u.y * v.x;
+	let edge = b - a;
+	let denom = cross(dir, edge);
+	if denom.abs() < 1e-9 {
+		return None;
+	}
+	let to_a = a - origin;
+	let t = cross(to_a, edge) / denom;
+	let s = cross(to_a, dir) / denom;
+	Some(t).filter(|&t| t >= 0.0 && (0.0..=1.0).contains(&s))
+}
+
+vertex_input! {
+	#[derive(Clone, Copy, Debug)]
+	pub struct LightVertex {
+		/// World pixels.
+		pub pos: Vector2<f32>,
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct LightPushConsts {
+	/// Color times intensity.
+	pub color: [f32; 4],
+	pub center: [f32; 2],
+	pub win_size: [f32; 2],
+	pub view_pos: [f32; 2],
+	pub direction: [f32; 2],
+	pub zoom: f32,
+	pub radius: f32,
+	pub height: f32,
+	pub cos_inner: f32,
+	/// -1 for point lights.
+	pub cos_outer: f32,
+}
+
+/// The lit area of one light.
+#[derive(Clone, Copy, Debug)]
+pub struct LightDraw {
+	pub first_vertex: u32,
+	pub vertex_count: u32,
+	pub light: Light,
+}
+impl LightDraw {
+	pub fn push_consts(&self, win_size: [f32; 2], view_pos: [f32; 2], zoom: f32) -> LightPushConsts {
+		let light = &self.light;
+		let [r, g, b] = light.color;
+		let (direction, cos_inner, cos_outer) = match light.kind {
+			LightKind::Point => ([1.0, 0.0], -1.0, -1.0),
+			LightKind::Spot { direction, angle, softness } => {
+				let outer = angle.min(PI);
+				let inner = (angle - softness).max(0.0).min(outer);
+				([direction.cos(), direction.sin()], inner.cos(), outer.cos())
+			},
+		};
+		LightPushConsts {
+			color: [r * light.intensity, g * light.intensity, b * light.intensity, 1.0],
+			center: light.pos.into(),
+			win_size,
+			view_pos,
+			direction,
+			zoom,
+			radius: light.radius,
+			height: light.height,
+			cos_inner,
+			cos_outer,
+		}
+	}
+}
+
+vertex_input! {
+	/// One sprite's normals, drawn as an instance of the unit quad.
+	#[derive(Clone, Copy, Debug)]
+	pub struct NormalInstance {
+		/// World position of the top-left corner.
+		pub pos: Vector2<f32>,
+		/// World pixels, negative to mirror.
+		pub size: Vector2<f32>,
+		/// Region of the sprite's texture, in texels. Normals are only drawn where it's opaque.
+		pub uv_rect: [f32; 4],
+		/// Region of the normal map, in texels.
+		pub normal_rect: [f32; 4],
+		/// Slots in the texture array, if there is one.
+		pub texture: u32,
+		pub normal_map: u32,
+		/// Radians about `pos`, which turns the normals too.
+		pub rotation: f32,
+	}
+}
+
+/// Sprite normals next to each other in draw order that share their bindings, drawn as one instanced draw. With a
+/// texture array that's every sprite.
+#[derive(Clone, Copy, Debug)]
+pub struct NormalBatch {
+	// key into the renderer's normal materials
+	textures: Option<(TextureKey, TextureKey)>,
+	pub first_instance: u32,
+	pub instance_count: u32,
+}
+
+/// Draws `LightScene`s into the light buffer, and sprite normals into the normals target it's lit with.
+pub struct LightRenderer {
+	program: Arc<ShaderProgram>,
+	normal_program: Arc<ShaderProgram>,
+	// created for the render passes of the compiled graph
+	light_pipeline: Option<Arc<GraphicsPipeline>>,
+	normal_pipeline: Option<Arc<GraphicsPipeline>>,
+	// one per frame in flight, reading that frame's normals
+	light_materials: Vec<Material>,
+	// stands in for the normal map of sprites without one
+	flat: Subtex,
+	// with a texture array, sprite textures and normal maps take slots in it; without, each pair gets a material
+	textures: Option<Textures>,
+	normal_materials: HashMap<(TextureKey, TextureKey), Material>,
+}
+impl LightRenderer {
+	pub fn new(gfx: &Gfx) -> Self {
+		let program = block_on(gfx.program("light", "")).unwrap();
+		let variant = if gfx.texture_array().is_some() { "bindless" } else { "" };
+		let normal_program = block_on(gfx.program("sprite_normal", variant)).unwrap();
+		let (flat, future) =
+			upload_image(&gfx.queue, &gfx.cmdpool, RgbaImage::from_pixel(1, 1, Rgba([128, 128, 255, 255])));
+		future.then_signal_fence().wait();
+		let textures = gfx.texture_array().map(|_| Textures::new(gfx, normal_program.clone()));
+		Self {
+			program,
+			normal_program,
+			light_pipeline: None,
+			normal_pipeline: None,
+			light_materials: vec![],
+			flat: Subtex::new(flat, 1, 1),
+			textures,
+			normal_materials: HashMap::new(),
+		}
+	}
+
+	pub fn program(&self) -> &Arc<ShaderProgram> {
+		&self.program
+	}
+
+	pub fn normal_program(&self) -> &Arc<ShaderProgram> {
+		&self.normal_program
+	}
+
+	/// Only `Some` while the compiled graph has a light buffer.
+	pub fn light_pipeline(&self) -> Option<&Arc<GraphicsPipeline>> {
+		self.light_pipeline.as_ref()
+	}
+
+	pub fn normal_pipeline(&self) -> Option<&Arc<GraphicsPipeline>> {
+		self.normal_pipeline.as_ref()
+	}
+
+	pub fn light_material(&self, frame: usize) -> &Material {
+		&self.light_materials[frame]
+	}
+
+	/// Only valid for batches returned by the last `prepare_normals`.
+	pub fn normal_desc_sets(&self, batch: &NormalBatch) -> &[Arc<DescriptorSet>] {
+		match &batch.textures {
+			Some(pair) => self.normal_materials[pair].desc_sets(),
+			None => self.textures.as_ref().unwrap().desc_sets(None),
+		}
+	}
+
+	/// Called whenever the window recompiles its render graph.
+	pub(super) fn bind(&mut self, gfx: &Gfx, lighting: Option<&CompiledLighting>) {
+		let lighting = match lighting {
+			Some(lighting) => lighting,
+			None => {
+				self.light_pipeline = None;
+				self.normal_pipeline = None;
+				self.light_materials.clear();
+				return;
+			},
+		};
+		let light_desc = PipelineDesc {
+			layout: self.program.layout.clone(),
+			render_pass: lighting.lights_pass.clone(),
+			subpass: 0,
+			vshader: self.program.vshader.clone(),
+			fshader: self.program.fshader.clone(),
+			blend: BlendMode::Additive,
+		};
+		self.light_pipeline = Some(gfx.pipelines.get::<LightVertex>(&gfx.device, light_desc));
+		let normal_desc = PipelineDesc {
+			layout: self.normal_program.layout.clone(),
+			render_pass: lighting.normals_pass.clone(),
+			subpass: 0,
+			vshader: self.normal_program.vshader.clone(),
+			fshader: self.normal_program.fshader.clone(),
+			blend: BlendMode::Opaque,
+		};
+		let normal_pipeline = gfx.pipelines.get_instanced::<TriangleVertex, NormalInstance>(&gfx.device, normal_desc);
+		self.normal_pipeline = Some(normal_pipeline);
+		let sampler = gfx.sampler(SamplerDesc::default());
+		self.light_materials = lighting
+			.normals
+			.iter()
+			.map(|normals| {
+				let mut params = HashMap::new();
+				params.insert("normals".to_owned(), MaterialParam::Texture(normals.clone(), sampler.clone()));
+				Material::new(self.program.clone(), params).unwrap()
+			})
+			.collect();
+	}
+
+	/// Uploads the normals of every sprite back to front, so nearer sprites' normals win, and drops the bindings no
+	/// longer drawn. Returns `None` if no sprite has a normal map, since everything is flat then.
+	pub fn prepare_normals(
+		&mut self,
+		gfx: &Gfx,
+		sprites: &SpriteList,
+	) -> Option<(Arc<Buffer<[NormalInstance]>>, Vec<NormalBatch>)> {
+		let mut used = HashSet::new();
+		let mut used_pairs = HashSet::new();
+		let mut instances = vec![];
+		let mut batches: Vec<NormalBatch> = vec![];
+		let lit = sprites.sprites().any(|sprite| sprite.normal_map.is_some());
+		let (opaque, transparent) = if lit { sprites.sorted() } else { Default::default() };
+		for sprite in opaque.into_iter().rev().chain(transparent).filter_map(SpriteDraw::sprite) {
+			let texture = sprite.texture.as_ref().unwrap_or(gfx.default_texture());
+			let normal_map = sprite.normal_map.as_ref().unwrap_or(&self.flat);
+			let (textures, texture_slot, normal_slot) = match &mut self.textures {
+				Some(textures) => {
+					let bindings = (
+						textures.get(gfx, texture.image_view(), texture.sampler()),
+						textures.get(gfx, normal_map.image_view(), normal_map.sampler()),
+					);
+					let ((texture_key, texture_slot), (normal_key, normal_slot)) = match bindings {
+						(Ok(texture), Ok(normal_map)) => (texture, normal_map),
+						(Err(err), _) | (_, Err(err)) => {
+							warn!("skipped the normals of a sprite: {}", err);
+							continue;
+						},
+					};
+					used.extend(vec![texture_key, normal_key]);
+					(None, texture_slot, normal_slot)
+				},
+				None => {
+					let key = |subtex: &Subtex| (Arc::as_ptr(subtex.image_view()) as usize, subtex.sampler());
+					let pair = (key(texture), key(normal_map));
+					let program = &self.normal_program;
+					self.normal_materials.entry(pair).or_insert_with(|| {
+						let mut params = HashMap::new();
+						for (name, subtex) in vec![("tex", texture), ("normal_map", normal_map)] {
+							let (view, sampler) = (subtex.image_view().clone(), gfx.sampler(subtex.sampler()));
+							params.insert(name.to_owned(), MaterialParam::Texture(view, sampler));
+						}
+						Material::new(program.clone(), params).unwrap()
+					});
+					used_pairs.insert(pair);
+					(Some(pair), 0, 0)
+				},
+			};
+
+			let ((u, v), (w, h)) = (texture.pos(), texture.size());
+			let ((nu, nv), (nw, nh)) = (normal_map.pos(), normal_map.size());
+			match batches.last_mut() {
+				Some(last) if last.textures == textures => last.instance_count += 1,
+				_ => batches.push(NormalBatch { textures, first_instance: instances.len() as u32, instance_count: 1 }),
+			}
+			instances.push(NormalInstance {
+				pos: sprite.pos,
+				size: Vector2::new(w as f32, h as f32).component_mul(&sprite.scale),
+				uv_rect: [u as f32, v as f32, w as f32, h as f32],
+				normal_rect: [nu as f32, nv as f32, nw as f32, nh as f32],
+				texture: texture_slot,
+				normal_map: normal_slot,
+				rotation: sprite.rotation,
+			});
+		}
+
+		if let Some(textures) = &mut self.textures {
+			textures.retain(&used);
+		}
+		self.normal_materials.retain(|pair, _| used_pairs.contains(pair));
+		if instances.is_empty() {
+			return None;
+		}
+		let usage = BufferUsageFlags::VERTEX_BUFFER;
+		let buffer =
+			Buffer::init_slice(gfx.device.clone(), instances.len() as _, B1, usage).copy_from_slice(&instances);
+		validation::set_name(&gfx.device, &*buffer, "sprite normal instances");
+		Some((buffer, batches))
+	}
+
+	/// Builds the lit area of every light overlapping `view`, a world rectangle as min and max corners. Returns `None`
+	/// if no light is visible.
+	pub fn prepare(
+		&mut self,
+		gfx: &Gfx,
+		scene: &LightScene,
+		view: (Vector2<f32>, Vector2<f32>),
+	) -> Option<(Arc<Buffer<[LightVertex]>>, Vec<LightDraw>)> {
+		let mut verts = vec![];
+		let mut draws = vec![];
+		for light in &scene.lights {
+			let visible = light.pos.x + light.radius >= view.0.x
+				&& light.pos.x - light.radius <= view.1.x
+				&& light.pos.y + light.radius >= view.0.y
+				&& light.pos.y - light.radius <= view.1.y;
+			if !visible || light.intensity <= 0.0 || light.radius <= 0.0 {
+				continue;
+			}
+			let area = if light.shadows {
+				visibility(light.pos, light.radius, &scene.occluders)
+			} else {
+				let (min, max) = (light.pos.add_scalar(-light.radius), light.pos.add_scalar(light.radius));
+				let (tr, bl) = (Vector2::new(max.x, min.y), Vector2::new(min.x, max.y));
+				vec![min, tr, max, max, bl, min]
+			};
+			let first_vertex = verts.len() as u32;
+			verts.extend(area.into_iter().map(|pos| LightVertex { pos }));
+			draws.push(LightDraw { first_vertex, vertex_count: verts.len() as u32 - first_vertex, light: *light });
+		}
+
+		if verts.is_empty() {
+			return None;
+		}
+		let buffer = Buffer::init_slice(gfx.device.clone(), verts.len() as _, B1, BufferUsageFlags::VERTEX_BUFFER)
+			.copy_from_slice(&verts);
+		validation::set_name(&gfx.device, &*buffer, "light verts");
+		Some((buffer, draws))
+	}
+
+	/// Records what the last `prepare_normals` returned into the normals target. Expects the viewport and scissor to be
+	/// set.
+	pub fn record_normals(
+		&self,
+		gfx: &Gfx,
+		cmd: CommandBufferBuilder,
+		instances: &Arc<Buffer<[NormalInstance]>>,
+		batches: &[NormalBatch],
+		win_size: [f32; 2],
+		camera: &Camera,
+	) -> CommandBufferBuilder {
+		let program = &self.normal_program;
+		let pc = SpritePushConsts { win_size, view_pos: camera.pos.into(), zoom: camera.zoom };
+		let mut cmd = cmd
+			.bind_pipeline(self.normal_pipeline.clone().unwrap())
+			.bind_vertex_buffers(0, once(gfx.verts.clone() as _).chain(once(instances.clone() as _)), &[0, 0])
+			.push_constants(program.layout.clone(), program.push_constant_stages(), 0, &pc);
+		for batch in batches {
+			cmd = cmd
+				.bind_descriptor_sets(program.layout.clone(), 0, self.normal_desc_sets(batch).iter().cloned(), &[])
+				.draw(6, batch.instance_count, 0, batch.first_instance);
+		}
+		cmd
+	}
+
+	/// Records what the last `prepare` returned into the light buffer of frame in flight `frame`. Expects the viewport
+	/// and scissor to be set.
+	pub fn record_lights(
+		&self,
+		cmd: CommandBufferBuilder,
+		frame: usize,
+		verts: &Arc<Buffer<[LightVertex]>>,
+		draws: &[LightDraw],
+		win_size: [f32; 2],
+		camera: &Camera,
+	) -> CommandBufferBuilder {
+		let layout = self.program.layout.clone();
+		let stages = self.program.push_constant_stages();
+		let desc_sets = self.light_material(frame).desc_sets().iter().cloned();
+		let mut cmd = cmd
+			.bind_pipeline(self.light_pipeline.clone().unwrap())
+			.bind_vertex_buffers(0, once(verts.clone() as _), &[0])
+			.bind_descriptor_sets(layout.clone(), 0, desc_sets, &[]);
+		for draw in draws {
+			let pc = draw.push_consts(win_size, camera.pos.into(), camera.zoom);
+			cmd = cmd.push_constants(layout.clone(), stages, 0, &pc).draw(draw.vertex_count, 1, draw.first_vertex, 0);
+		}
+		cmd
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// total area of a triangle list
+	fn area(triangles: &[Vector2<f32>]) -> f32 {
+		let cross = |u: Vector2<f32>, v: Vector2<f32>| u.x * v.y - u.y * v.x;
+		triangles.chunks(3).map(|t| cross(t[1] - t[0], t[2] - t[0]).abs() / 2.0).sum()
+	}
+
+	fn covers(triangles: &[Vector2<f32>], point: Vector2<f32>) -> bool {
+		let cross = |u: Vector2<f32>, v: Vector2<f32>| u.x * v.y - u.y * v.x;
+		triangles.chunks(3).any(|t| {
+			let sides = [0, 1, 2].map(|i| cross(t[(i + 1) % 3] - t[i], point - t[i]));
+			sides.iter().all(|&s| s >= 0.0) || sides.iter().all(|&s| s <= 0.0)
+		})
+	}
+
+	#[test]
+	fn empty_scene() {
+		let center = Vector2::new(5.0, -3.0);
+		let triangles = visibility(center, 10.0, &[]);
+		assert_eq!(triangles.len() % 3, 0);
+		assert!((area(&triangles) - 400.0).abs() < 0.01, "{}", area(&triangles));
+		for point in triangles.iter().filter(|&&p| p != center) {
+			let offset = point - center;
+			assert!((offset.x.abs().max(offset.y.abs()) - 10.0).abs() < 1e-3, "{:?}", point);
+		}
+	}
+
+	#[test]
+	fn box_shadow() {
+		let occluder = Occluder::rect(Vector2::new(2.0, -1.0), Vector2::new(2.0, 2.0));
+		let triangles = visibility(Vector2::zeros(), 10.0, &[occluder]);
+		// the near face at x = 2 hides the wedge |y| <= x / 2 beyond it, 50 square pixels less the 2 in front of it
+		assert!((area(&triangles) - 352.0).abs() < 0.05, "{}", area(&triangles));
+		assert!(covers(&triangles, Vector2::new(1.5, 0.0)));
+		assert!(covers(&triangles, Vector2::new(8.0, 5.0)));
+		assert!(covers(&triangles, Vector2::new(-8.0, 0.0)));
+		assert!(!covers(&triangles, Vector2::new(3.0, 0.0)));
+		assert!(!covers(&triangles, Vector2::new(9.0, 3.0)));
+	}
+}
@@ -0,0 +1,199 @@
+use crate::{
+	fs::read_all_u8,
+	gfx::{sampler::SamplerDesc, shader::ShaderProgram, texture::upload_image, validation, Gfx},
+};
+use log::info;
+use std::{collections::HashMap, io, iter::once, path::Path, sync::Arc};
+use typenum::B1;
+use vulkan::{
+	buffer::Buffer,
+	descriptor::{DescriptorPool, DescriptorSet, DescriptorType},
+	device::BufferUsageFlags,
+	image::{ImageLayout, ImageView, Sampler},
+	sync::GpuFuture,
+};
+
+#[derive(Clone)]
+pub enum MaterialParam {
+	Texture(Arc<ImageView>, Arc<Sampler>),
+	Color([f32; 4]),
+	Float(f32),
+}
+impl MaterialParam {
+	fn bytes(&self) -> Vec<u8> {
+		match self {
+			Self::Texture(..) => vec![],
+			Self::Color(color) => color.iter().flat_map(|c| c.to_ne_bytes().to_vec()).collect(),
+			Self::Float(x) => x.to_ne_bytes().to_vec(),
+		}
+	}
+}
+
+pub struct Material {
+	program: Arc<ShaderProgram>,
+	params: HashMap<String, MaterialParam>,
+	desc_sets: Vec<Arc<DescriptorSet>>,
+	_uniforms: Vec<Arc<Buffer<[u8]>>>,
+}
+impl Material {
+	/// Fails if the program reads a texture missing from `params`, or a kind of descriptor materials can't provide.
+	pub fn new(program: Arc<ShaderProgram>, params: HashMap<String, MaterialParam>) -> io::Result<Self> {
+		let mut ret = Self { program, params, desc_sets: vec![], _uniforms: vec![] };
+		ret.update()?;
+		Ok(ret)
+	}
+
+	/// Loads a material description, where each line is either `shader = <name> [variant]` or `<param> = <type>
+	/// <value>` with type one of `texture <path> [sampler options]`, `color <r> <g> <b> <a>` or `float <x>`. See
+	/// `SamplerDesc::parse` for the options.
+	pub async fn load<P: AsRef<Path>>(gfx: &Arc<Gfx>, path: P) -> io::Result<Self> {
+		let path = path.as_ref();
+		let source = read_all_u8(path.to_owned()).await?;
+		let source = String::from_utf8(source).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+		let mut shader = None;
+		let mut params = HashMap::new();
+		let mut futures = vec![];
+		for (i, line) in source.lines().enumerate() {
+			let err = |msg: &str| {
+				io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", path.display(), i + 1, msg))
+			};
+
+			let line = line.split('#').next().unwrap().trim();
+			if line.is_empty() {
+				continue;
+			}
+
+			let mut kv = line.splitn(2, '=').map(str::trim);
+			let (key, value) = match (kv.next(), kv.next()) {
+				(Some(key), Some(value)) if !key.is_empty() => (key, value),
+				_ => return Err(err("expected `<key> = <value>`")),
+			};
+			if key == "shader" {
+				shader = Some(value.to_owned());
+				continue;
+			}
+
+			let mut words = value.split_whitespace();
+			let param = match words.next() {
+				Some("texture") => {
+					let tex_path = words.next().ok_or_else(|| err("expected texture path"))?;
+					let img = read_all_u8(tex_path.to_owned()).await?;
+					let img = image::load_from_memory(&img).map_err(|e| err(&e.to_string()))?.into_rgba();
+					let sampler = SamplerDesc::parse(words).map_err(|e| err(&e))?;
+					let (image_view, future) = upload_image(&gfx.queue, &gfx.cmdpool, img);
+					validation::set_name(&gfx.device, &*image_view, tex_path);
+					futures.push(future);
+					MaterialParam::Texture(image_view, gfx.sampler(sampler))
+				},
+				Some("color") => {
+					let c: Vec<f32> = words.map(str::parse).collect::<Result<_, _>>().map_err(|_| err("bad color"))?;
+					match c.len() {
+						3 => MaterialParam::Color([c[0], c[1], c[2], 1.0]),
+						4 => MaterialParam::Color([c[0], c[1], c[2], c[3]]),
+						_ => return Err(err("expected 3 or 4 color components")),
+					}
+				},
+				Some("float") => {
+					let x = words.next().and_then(|x| x.parse().ok()).ok_or_else(|| err("bad float"))?;
+					MaterialParam::Float(x)
+				},
+				_ => return Err(err("expected `texture`, `color` or `float`")),
+			};
+			params.insert(key.to_owned(), param);
+		}
+
+		let shader = shader.ok_or_else(|| {
+			io::Error::new(io::ErrorKind::InvalidData, format!("{}: missing `shader`", path.display()))
+		})?;
+		let mut shader = shader.split_whitespace();
+		let program = gfx
+			.program(shader.next().unwrap_or_default(), shader.next().unwrap_or_default())
+			.await
+			.map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+
+		for future in futures {
+			future.then_signal_fence().wait();
+		}
+
+		let material = Self::new(program, params)
+			.map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+		info!(target: "assets", "loaded material {}", path.display());
+		Ok(material)
+	}
+
+	pub fn program(&self) -> &Arc<ShaderProgram> {
+		&self.program
+	}
+
+	pub fn desc_sets(&self) -> &[Arc<DescriptorSet>] {
+		&self.desc_sets
+	}
+
+	pub fn get(&self, name: &str) -> Option<&MaterialParam> {
+		self.params.get(name)
+	}
+
+	/// Changes a parameter. Call `update` afterwards to apply the changes.
+	pub fn set(&mut self, name: &str, param: MaterialParam) {
+		self.params.insert(name.to_owned(), param);
+	}
+
+	/// Allocates fresh descriptor sets reflecting the current parameters. Sets still in use by in-flight frames are
+	/// kept alive by the command buffers referencing them. On failure the previous sets stay in use.
+	pub fn update(&mut self) -> io::Result<()> {
+		let err = |msg: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", self.program.name(), msg));
+		let device = self.program.layout.device().clone();
+		let reflection = self.program.reflection();
+
+		let mut sizes: HashMap<DescriptorType, u32> = HashMap::new();
+		for binding in &reflection.bindings {
+			*sizes.entry(binding.ty).or_default() += binding.count.max(1);
+		}
+		let sizes = sizes.into_iter().map(|size| size.into()).collect();
+		let pool = DescriptorPool::new(device.clone(), self.program.desc_layouts.len() as _, sizes);
+		let desc_sets: Vec<_> = DescriptorSet::alloc(pool, self.program.desc_layouts.clone()).collect();
+
+		let mut uniforms = vec![];
+		let mut update = DescriptorSet::update_builder(&device);
+		for binding in &reflection.bindings {
+			let desc_set = &desc_sets[binding.set as usize];
+			match binding.ty {
+				DescriptorType::COMBINED_IMAGE_SAMPLER => match self.params.get(&binding.name) {
+					Some(MaterialParam::Texture(image_view, sampler)) => {
+						update = update.write(
+							desc_set,
+							binding.binding,
+							binding.ty,
+							once((Some(sampler.clone()), image_view.clone(), ImageLayout::SHADER_READ_ONLY_OPTIMAL)),
+						);
+					},
+					_ => return Err(err(format!("missing texture parameter `{}`", binding.name))),
+				},
+				DescriptorType::UNIFORM_BUFFER => {
+					let mut data = vec![0; binding.size as usize];
+					for member in &binding.members {
+						if let Some(param) = self.params.get(&member.name) {
+							let bytes = param.bytes();
+							let len = bytes.len().min(member.size as usize);
+							let offset = member.offset as usize;
+							data[offset..offset + len].copy_from_slice(&bytes[..len]);
+						}
+					}
+
+					let buffer =
+						Buffer::init_slice(device.clone(), data.len() as _, B1, BufferUsageFlags::UNIFORM_BUFFER)
+							.copy_from_slice(&data);
+					update = update.write_buffers(desc_set, binding.binding, binding.ty, once(buffer.clone()));
+					uniforms.push(buffer);
+				},
+				ty => return Err(err(format!("unsupported material descriptor type {:?}", ty))),
+			}
+		}
+		update.submit();
+
+		self.desc_sets = desc_sets;
+		self._uniforms = uniforms;
+		Ok(())
+	}
+}
@@ -0,0 +1,216 @@
+use crate::{
+	gfx::{
+		bindless::{TextureKey, Textures},
+		camera::Camera,
+		pipeline::{BlendMode, PipelineDesc},
+		sampler::SamplerDesc,
+		shader::ShaderProgram,
+		sprite::layer_depth,
+		texture::upload_image,
+		validation,
+		vertex::vertex_input,
+		Gfx, TriangleVertex,
+	},
+	particles::ParticleSystem,
+};
+use futures::executor::block_on;
+use image::{Rgba, RgbaImage};
+use log::warn;
+use nalgebra::Vector2;
+use std::{
+	collections::{HashMap, HashSet},
+	iter::once,
+	sync::Arc,
+};
+use typenum::B1;
+use vulkan::{
+	buffer::Buffer, command::CommandBufferBuilder, descriptor::DescriptorSet, device::BufferUsageFlags,
+	image::ImageView, pipeline::GraphicsPipeline, render_pass::RenderPass, sync::GpuFuture,
+};
+
+vertex_input! {
+	/// One particle, drawn as an instance of the unit quad.
+	#[derive(Clone, Copy, Debug)]
+	pub struct ParticleInstance {
+		/// World pixels.
+		pub pos: Vector2<f32>,
+		/// Half the width and height, in world pixels.
+		pub half_size: Vector2<f32>,
+		/// Radians.
+		pub rotation: f32,
+		/// Position and size of the region drawn, in texels.
+		pub uv_rect: [f32; 4],
+		pub color: [f32; 4],
+		/// Slot in the texture array, if there is one.
+		pub texture: u32,
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct ParticlePushConsts {
+	pub win_size: [f32; 2],
+	pub view_pos: [f32; 2],
+	pub zoom: f32,
+	pub depth: f32,
+}
+
+/// The particles of one emitter, or of consecutive emitters sharing a layer and blend mode with a texture array.
+#[derive(Clone, Copy, Debug)]
+pub struct ParticleBatch {
+	// key into the renderer's textures
+	texture: Option<TextureKey>,
+	pub blend: BlendMode,
+	pub depth: f32,
+	pub first_instance: u32,
+	pub instance_count: u32,
+}
+
+/// Turns particle systems into one instance buffer per frame, drawn back to front by layer after transparent sprites.
+pub struct ParticleRenderer {
+	program: Arc<ShaderProgram>,
+	render_pass: Arc<RenderPass>,
+	pipelines: HashMap<BlendMode, Arc<GraphicsPipeline>>,
+	// stands in for the texture of untextured emitters
+	white: Arc<ImageView>,
+	textures: Textures,
+}
+impl ParticleRenderer {
+	pub fn new(gfx: &Gfx, render_pass: Arc<RenderPass>) -> Self {
+		let variant = if gfx.texture_array().is_some() { "bindless" } else { "" };
+		let program = block_on(gfx.program("particle", variant)).unwrap();
+		let (white, future) = upload_image(&gfx.queue, &gfx.cmdpool, RgbaImage::from_pixel(1, 1, Rgba([255; 4])));
+		future.then_signal_fence().wait();
+		let textures = Textures::new(gfx, program.clone());
+		Self { program, render_pass, pipelines: HashMap::new(), white, textures }
+	}
+
+	pub fn program(&self) -> &Arc<ShaderProgram> {
+		&self.program
+	}
+
+	/// Only valid for batches returned by the last `prepare`.
+	pub fn pipeline(&self, batch: &ParticleBatch) -> &Arc<GraphicsPipeline> {
+		&self.pipelines[&batch.blend]
+	}
+
+	/// Only valid for batches returned by the last `prepare`.
+	pub fn desc_sets(&self, batch: &ParticleBatch) -> &[Arc<DescriptorSet>] {
+		self.textures.desc_sets(batch.texture)
+	}
+
+	/// Uploads every live particle of `systems`. Returns `None` if there's nothing to draw.
+	pub fn prepare(
+		&mut self,
+		gfx: &Gfx,
+		systems: &[&ParticleSystem],
+	) -> Option<(Arc<Buffer<[ParticleInstance]>>, Vec<ParticleBatch>)> {
+		let mut emitters: Vec<_> = systems.iter().flat_map(|system| system.emitters()).collect();
+		// back to front, keeping the order emitters were added within a layer
+		emitters.sort_by_key(|emitter| emitter.config.layer);
+
+		let mut instances = vec![];
+		let mut batches: Vec<ParticleBatch> = vec![];
+		let mut used = HashSet::new();
+		for emitter in emitters.into_iter().filter(|emitter| !emitter.particles().is_empty()) {
+			let config = &emitter.config;
+			let (image_view, sampler, (u, v), (w, h)) = match &config.texture {
+				Some(texture) => (texture.image_view().clone(), texture.sampler(), texture.pos(), texture.size()),
+				None => (self.white.clone(), SamplerDesc::default(), (0, 0), (1, 1)),
+			};
+			let uv_rect = [u as f32, v as f32, w as f32, h as f32];
+			let aspect = h as f32 / w as f32;
+			let (key, slot) = match self.textures.get(gfx, &image_view, sampler) {
+				Ok(texture) => texture,
+				Err(err) => {
+					warn!("skipped an emitter: {}", err);
+					continue;
+				},
+			};
+			used.insert(key);
+
+			let first_instance = instances.len() as u32;
+			instances.extend(emitter.particles().iter().map(|particle| {
+				let progress = particle.progress();
+				let width = config.size.sample(progress);
+				ParticleInstance {
+					pos: particle.pos,
+					half_size: Vector2::new(width, width * aspect) / 2.0,
+					rotation: particle.rotation,
+					uv_rect,
+					color: config.color.sample(progress),
+					texture: slot,
+				}
+			}));
+
+			let batch = ParticleBatch {
+				texture: self.textures.batch_key(key),
+				blend: config.blend,
+				depth: layer_depth(config.layer),
+				first_instance,
+				instance_count: instances.len() as u32 - first_instance,
+			};
+			// consecutive emitters draw together when nothing bound changes in between
+			match batches.last_mut() {
+				Some(last) if (last.texture, last.blend, last.depth) == (batch.texture, batch.blend, batch.depth) => {
+					last.instance_count += batch.instance_count
+				},
+				_ => batches.push(batch),
+			}
+			if !self.pipelines.contains_key(&config.blend) {
+				let desc = PipelineDesc {
+					layout: self.program.layout.clone(),
+					render_pass: self.render_pass.clone(),
+					subpass: 0,
+					vshader: self.program.vshader.clone(),
+					fshader: self.program.fshader.clone(),
+					blend: config.blend,
+				};
+				self.pipelines.insert(
+					config.blend,
+					gfx.pipelines.get_instanced::<TriangleVertex, ParticleInstance>(&gfx.device, desc),
+				);
+			}
+		}
+
+		self.textures.retain(&used);
+		if instances.is_empty() {
+			return None;
+		}
+		let buffer = Buffer::init_slice(gfx.device.clone(), instances.len() as _, B1, BufferUsageFlags::VERTEX_BUFFER)
+			.copy_from_slice(&instances);
+		validation::set_name(&gfx.device, &*buffer, "particle instances");
+		Some((buffer, batches))
+	}
+
+	/// Records what the last `prepare` returned. Expects the viewport and scissor to be set.
+	pub fn record(
+		&self,
+		gfx: &Gfx,
+		cmd: CommandBufferBuilder,
+		instances: &Arc<Buffer<[ParticleInstance]>>,
+		batches: &[ParticleBatch],
+		win_size: [f32; 2],
+		camera: &Camera,
+	) -> CommandBufferBuilder {
+		let (view_pos, zoom) = (camera.pos.into(), camera.zoom);
+		let layout = self.program.layout.clone();
+		let stages = self.program.push_constant_stages();
+		let verts = once(gfx.verts.clone() as _).chain(once(instances.clone() as _));
+		let mut cmd = cmd.bind_vertex_buffers(0, verts, &[0, 0]);
+		let mut blend = None;
+		for batch in batches {
+			if blend != Some(batch.blend) {
+				blend = Some(batch.blend);
+				cmd = cmd.bind_pipeline(self.pipeline(batch).clone());
+			}
+			let pc = ParticlePushConsts { win_size, view_pos, zoom, depth: batch.depth };
+			let desc_sets = self.desc_sets(batch).iter().cloned();
+			cmd = cmd
+				.bind_descriptor_sets(layout.clone(), 0, desc_sets, &[])
+				.push_constants(layout.clone(), stages, 0, &pc)
+				.draw(6, batch.instance_count, 0, batch.first_instance);
+		}
+		cmd
+	}
+}
@@ -0,0 +1,154 @@
+use crate::gfx::{validation, vertex::VertexInput};
+use log::debug;
+use std::{
+	any::{type_name, TypeId},
+	collections::HashMap,
+	hash::{Hash, Hasher},
+	sync::{Arc, Mutex},
+};
+use vulkan::{
+	device::Device,
+	pipeline::{
+		BlendFactor, BlendOp, ColorComponentFlags, CompareOp, DynamicState, GraphicsPipeline,
+		PipelineCache as VkPipelineCache, PipelineColorBlendAttachmentState, PipelineDepthStencilStateCreateInfo,
+		PipelineLayout, VertexInputAttributeDescription, VertexInputBindingDescription,
+	},
+	render_pass::RenderPass,
+	shader::ShaderModule,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+	/// Depth tested and written, no blending.
+	Opaque,
+	/// Depth tested but not written, alpha blended.
+	Alpha,
+	/// Depth tested but not written, added to what's behind scaled by alpha. For glows, fire and sparks.
+	Additive,
+}
+
+#[derive(Clone)]
+pub struct PipelineDesc {
+	pub layout: Arc<PipelineLayout>,
+	pub render_pass: Arc<RenderPass>,
+	pub subpass: u32,
+	pub vshader: Arc<ShaderModule>,
+	pub fshader: Arc<ShaderModule>,
+	pub blend: BlendMode,
+}
+impl PartialEq for PipelineDesc {
+	fn eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.layout, &other.layout)
+			&& Arc::ptr_eq(&self.render_pass, &other.render_pass)
+			&& self.subpass == other.subpass
+			&& Arc::ptr_eq(&self.vshader, &other.vshader)
+			&& Arc::ptr_eq(&self.fshader, &other.fshader)
+			&& self.blend == other.blend
+	}
+}
+impl Eq for PipelineDesc {}
+impl Hash for PipelineDesc {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		Arc::as_ptr(&self.layout).hash(state);
+		Arc::as_ptr(&self.render_pass).hash(state);
+		self.subpass.hash(state);
+		Arc::as_ptr(&self.vshader).hash(state);
+		Arc::as_ptr(&self.fshader).hash(state);
+		self.blend.hash(state);
+	}
+}
+
+pub struct PipelineCache {
+	cache: Arc<VkPipelineCache>,
+	pipelines: Mutex<HashMap<(PipelineDesc, TypeId), Arc<GraphicsPipeline>>>,
+}
+impl PipelineCache {
+	pub fn new(device: Arc<Device>, initial_data: &[u8]) -> Self {
+		// the driver validates the header and ignores data from a different device or driver version
+		let cache = VkPipelineCache::new(device, initial_data);
+		Self { cache, pipelines: Mutex::default() }
+	}
+
+	/// A pipeline reading `V` per vertex from binding 0.
+	pub fn get<V: VertexInput>(&self, device: &Arc<Device>, desc: PipelineDesc) -> Arc<GraphicsPipeline> {
+		self.get_or_build::<V>(device, desc, || (vec![V::binding(0, false)], V::attributes(0, 0)))
+	}
+
+	/// A pipeline reading `V` per vertex from binding 0 and `I` per instance from binding 1, at the locations after
+	/// `V`'s.
+	pub fn get_instanced<V: VertexInput, I: VertexInput>(
+		&self,
+		device: &Arc<Device>,
+		desc: PipelineDesc,
+	) -> Arc<GraphicsPipeline> {
+		self.get_or_build::<(V, I)>(device, desc, || {
+			let mut attributes = V::attributes(0, 0);
+			attributes.extend(I::attributes(1, attributes.len() as _));
+			(vec![V::binding(0, false), I::binding(1, true)], attributes)
+		})
+	}
+
+	// `K` identifies the vertex input, which `vertex_input` describes
+	fn get_or_build<K: 'static>(
+		&self,
+		device: &Arc<Device>,
+		desc: PipelineDesc,
+		vertex_input: impl FnOnce() -> (Vec<VertexInputBindingDescription>, Vec<VertexInputAttributeDescription>),
+	) -> Arc<GraphicsPipeline> {
+		let key = (desc, TypeId::of::<K>());
+		let mut pipelines = self.pipelines.lock().unwrap();
+		if let Some(pipeline) = pipelines.get(&key) {
+			return pipeline.clone();
+		}
+
+		let desc = &key.0;
+		let depth_stencil = PipelineDepthStencilStateCreateInfo::builder()
+			.depth_test_enable(true)
+			.depth_write_enable(desc.blend == BlendMode::Opaque)
+			.depth_compare_op(CompareOp::LESS_OR_EQUAL)
+			.build();
+		let blend = match desc.blend {
+			BlendMode::Opaque => PipelineColorBlendAttachmentState::builder().blend_enable(false),
+			BlendMode::Alpha => PipelineColorBlendAttachmentState::builder()
+				.blend_enable(true)
+				.src_color_blend_factor(BlendFactor::SRC_ALPHA)
+				.dst_color_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+				.color_blend_op(BlendOp::ADD)
+				.src_alpha_blend_factor(BlendFactor::ONE)
+				.dst_alpha_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+				.alpha_blend_op(BlendOp::ADD),
+			BlendMode::Additive => PipelineColorBlendAttachmentState::builder()
+				.blend_enable(true)
+				.src_color_blend_factor(BlendFactor::SRC_ALPHA)
+				.dst_color_blend_factor(BlendFactor::ONE)
+				.color_blend_op(BlendOp::ADD)
+				.src_alpha_blend_factor(BlendFactor::ZERO)
+				.dst_alpha_blend_factor(BlendFactor::ONE)
+				.alpha_blend_op(BlendOp::ADD),
+		}
+		.color_write_mask(ColorComponentFlags::all())
+		.build();
+		let (bindings, attributes) = vertex_input();
+
+		let pipeline = device
+			.build_graphics_pipeline(desc.layout.clone(), desc.render_pass.clone())
+			.subpass(desc.subpass)
+			.cache(self.cache.clone())
+			.vertex_shader(desc.vshader.clone())
+			.fragment_shader(desc.fshader.clone())
+			.vertex_input_state(&bindings, &attributes)
+			.depth_stencil_state(depth_stencil)
+			.color_blend_attachments(&[blend])
+			.dynamic_states(&[DynamicState::VIEWPORT, DynamicState::SCISSOR])
+			.build();
+		let name = format!("{} {:?}", type_name::<K>(), desc.blend);
+		debug!("built pipeline {}", name);
+		validation::set_name(device, &*pipeline, &name);
+		pipelines.insert(key, pipeline.clone());
+		pipeline
+	}
+
+	pub fn data(&self) -> Vec<u8> {
+		self.cache.get_data()
+	}
+}
@@ -0,0 +1,171 @@
+use crate::gfx::{
+	graph::{FullscreenPass, RenderGraph, ResourceId},
+	material::MaterialParam,
+	sampler::SamplerDesc,
+	Gfx,
+};
+use futures::executor::block_on;
+use std::{io, sync::Arc};
+use vulkan::image::{Format, ImageView};
+
+/// A full-screen effect applied after the scene is drawn and before the GUI.
+pub trait PostEffect {
+	/// Adds passes reading `input` and returns the target holding the result. Fails if a program can't be loaded or a
+	/// pass doesn't fit the graph.
+	fn add_passes(&self, gfx: &Gfx, graph: &mut RenderGraph, input: ResourceId) -> io::Result<ResourceId>;
+}
+
+/// Makes bright parts of the image glow into their surroundings.
+#[derive(Clone, Copy, Debug)]
+pub struct Bloom {
+	/// Brightness above which pixels glow, 1 being white.
+	pub threshold: f32,
+	pub intensity: f32,
+}
+impl Default for Bloom {
+	fn default() -> Self {
+		Self { threshold: 0.8, intensity: 1.0 }
+	}
+}
+impl PostEffect for Bloom {
+	fn add_passes(&self, gfx: &Gfx, graph: &mut RenderGraph, input: ResourceId) -> io::Result<ResourceId> {
+		// blurred at half resolution, which is cheaper and spreads the glow further
+		let format = Format::R16G16B16A16_SFLOAT;
+		let bright = graph.target(format, 0.5);
+		let program = block_on(gfx.program("post_bloom_threshold", ""))?;
+		let pass = FullscreenPass::new("bloom threshold", program, bright)
+			.input("tex", input)
+			.param("threshold", MaterialParam::Float(self.threshold));
+		graph.add_pass(pass)?;
+
+		let blurred_h = graph.target(format, 0.5);
+		let program = block_on(gfx.program("post_blur", ""))?;
+		graph.add_pass(FullscreenPass::new("bloom blur h", program, blurred_h).input("tex", bright))?;
+		let blurred = graph.target(format, 0.5);
+		let program = block_on(gfx.program("post_blur", "vertical"))?;
+		graph.add_pass(FullscreenPass::new("bloom blur v", program, blurred).input("tex", blurred_h))?;
+
+		let output = graph.target(graph.desc(input).format, 1.0);
+		let program = block_on(gfx.program("post_bloom_combine", ""))?;
+		let pass = FullscreenPass::new("bloom combine", program, output)
+			.input("tex", input)
+			.input("bloom", blurred)
+			.param("intensity", MaterialParam::Float(self.intensity));
+		graph.add_pass(pass)?;
+		Ok(output)
+	}
+}
+
+/// Remaps colors through a lookup table, a strip of `size` square slices of `size` texels with blue increasing from
+/// slice to slice. An unmodified table leaves colors unchanged.
+#[derive(Clone)]
+pub struct ColorGrading {
+	pub lut: Arc<ImageView>,
+	/// How much of the graded color replaces the original, from 0 to 1.
+	pub amount: f32,
+}
+impl PostEffect for ColorGrading {
+	fn add_passes(&self, gfx: &Gfx, graph: &mut RenderGraph, input: ResourceId) -> io::Result<ResourceId> {
+		let output = graph.target(graph.desc(input).format, 1.0);
+		let program = block_on(gfx.program("post_color_grade", ""))?;
+		let pass = FullscreenPass::new("color grading", program, output)
+			.input("tex", input)
+			.param("lut", MaterialParam::Texture(self.lut.clone(), gfx.sampler(SamplerDesc::default())))
+			.param("amount", MaterialParam::Float(self.amount));
+		graph.add_pass(pass)?;
+		Ok(output)
+	}
+}
+
+/// Fades the edges of the screen into `color`.
+#[derive(Clone, Copy, Debug)]
+pub struct Vignette {
+	pub color: [f32; 4],
+	pub strength: f32,
+	/// Where the fade starts, as a fraction of the distance from the center to the top edge.
+	pub radius: f32,
+	/// Distance over which the fade goes from none to full `strength`.
+	pub softness: f32,
+}
+impl Default for Vignette {
+	fn default() -> Self {
+		Self { color: [0.0, 0.0, 0.0, 1.0], strength: 0.6, radius: 0.75, softness: 0.6 }
+	}
+}
+impl PostEffect for Vignette {
+	fn add_passes(&self, gfx: &Gfx, graph: &mut RenderGraph, input: ResourceId) -> io::Result<ResourceId> {
+		let output = graph.target(graph.desc(input).format, 1.0);
+		let program = block_on(gfx.program("post_vignette", ""))?;
+		let pass = FullscreenPass::new("vignette", program, output)
+			.input("tex", input)
+			.param("color", MaterialParam::Color(self.color))
+			.param("strength", MaterialParam::Float(self.strength))
+			.param("radius", MaterialParam::Float(self.radius))
+			.param("softness", MaterialParam::Float(self.softness));
+		graph.add_pass(pass)?;
+		Ok(output)
+	}
+}
+
+/// Imitates an old monitor with a curved screen and scanlines.
+#[derive(Clone, Copy, Debug)]
+pub struct Crt {
+	pub curvature: f32,
+	/// How dark the gaps between scanlines get, from 0 to 1.
+	pub scanlines: f32,
+	pub line_count: f32,
+}
+impl Default for Crt {
+	fn default() -> Self {
+		Self { curvature: 0.05, scanlines: 0.3, line_count: 240.0 }
+	}
+}
+impl PostEffect for Crt {
+	fn add_passes(&self, gfx: &Gfx, graph: &mut RenderGraph, input: ResourceId) -> io::Result<ResourceId> {
+		let output = graph.target(graph.desc(input).format, 1.0);
+		let program = block_on(gfx.program("post_crt", ""))?;
+		let pass = FullscreenPass::new("crt", program, output)
+			.input("tex", input)
+			.param("curvature", MaterialParam::Float(self.curvature))
+			.param("scanlines", MaterialParam::Float(self.scanlines))
+			.param("line_count", MaterialParam::Float(self.line_count));
+		graph.add_pass(pass)?;
+		Ok(output)
+	}
+}
+
+/// Multiplies the image by the light of `Window::lighting`, so only the ambient light reaches what no light shines on.
+#[derive(Clone, Copy, Debug)]
+pub struct Lighting {
+	/// Fraction of the window size the light buffer is drawn at.
+	pub resolution: f32,
+	/// Blurs the light buffer, softening shadow edges.
+	pub soft_shadows: bool,
+}
+impl Default for Lighting {
+	fn default() -> Self {
+		Self { resolution: 0.5, soft_shadows: true }
+	}
+}
+impl PostEffect for Lighting {
+	fn add_passes(&self, gfx: &Gfx, graph: &mut RenderGraph, input: ResourceId) -> io::Result<ResourceId> {
+		// light can exceed 1 where lights overlap
+		let format = Format::R16G16B16A16_SFLOAT;
+		let mut lights = graph.lights(format, self.resolution);
+		if self.soft_shadows {
+			let blurred_h = graph.target(format, self.resolution);
+			let program = block_on(gfx.program("post_blur", ""))?;
+			graph.add_pass(FullscreenPass::new("light blur h", program, blurred_h).input("tex", lights))?;
+			let blurred = graph.target(format, self.resolution);
+			let program = block_on(gfx.program("post_blur", "vertical"))?;
+			graph.add_pass(FullscreenPass::new("light blur v", program, blurred).input("tex", blurred_h))?;
+			lights = blurred;
+		}
+
+		let output = graph.target(graph.desc(input).format, 1.0);
+		let program = block_on(gfx.program("post_light_composite", ""))?;
+		let pass = FullscreenPass::new("light composite", program, output).input("tex", input).input("lights", lights);
+		graph.add_pass(pass)?;
+		Ok(output)
+	}
+}
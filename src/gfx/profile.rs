@@ -0,0 +1,101 @@
+use crate::{
+	gfx::Gfx,
+	profile::{self, Span},
+};
+use std::{
+	borrow::Cow,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+use vulkan::{
+	command::CommandBufferBuilder,
+	query::{QueryPool, QueryResultFlags, QueryType},
+	sync::PipelineStageFlags,
+};
+
+// enough for a mark before every pass of a large render graph
+const MAX_MARKS: u32 = 64;
+
+/// Timestamps written between the passes of one frame in flight, and read back into `profile` once its fence signals.
+/// Does nothing while profiling is disabled, or if the queue can't write timestamps.
+pub struct GpuTimer {
+	pool: Option<Arc<QueryPool>>,
+	// nanoseconds per tick
+	period: f64,
+	// each mark starts a region that lasts until the next, so the last one only ends the region before it
+	marks: Vec<Cow<'static, str>>,
+	active: bool,
+	frame: u64,
+	submitted: Option<Instant>,
+}
+impl GpuTimer {
+	pub fn new(gfx: &Gfx) -> Self {
+		let pool = gfx.timestamp_period.map(|_| QueryPool::new(gfx.device.clone(), QueryType::TIMESTAMP, MAX_MARKS));
+		let period = gfx.timestamp_period.unwrap_or(0.0) as f64;
+		Self { pool, period, marks: vec![], active: false, frame: 0, submitted: None }
+	}
+
+	/// Starts timing a frame. Record it first, outside of any render pass.
+	pub fn begin(&mut self, cmd: CommandBufferBuilder) -> CommandBufferBuilder {
+		self.marks.clear();
+		self.submitted = None;
+		self.active = profile::enabled();
+		match &self.pool {
+			Some(pool) if self.active => {
+				self.frame = profile::frame_number();
+				cmd.reset_query_pool(pool.clone(), 0, MAX_MARKS)
+			},
+			_ => cmd,
+		}
+	}
+
+	/// Starts a region named `name` once everything recorded before it has finished, ending the previous region.
+	pub fn mark(&mut self, cmd: CommandBufferBuilder, name: impl Into<Cow<'static, str>>) -> CommandBufferBuilder {
+		match &self.pool {
+			Some(pool) if self.active && (self.marks.len() as u32) < MAX_MARKS => {
+				let query = self.marks.len() as u32;
+				self.marks.push(name.into());
+				cmd.write_timestamp(PipelineStageFlags::BOTTOM_OF_PIPE, pool.clone(), query)
+			},
+			_ => cmd,
+		}
+	}
+
+	/// Ends the last region.
+	pub fn end(&mut self, cmd: CommandBufferBuilder) -> CommandBufferBuilder {
+		self.mark(cmd, "")
+	}
+
+	/// Call just before submitting the frame's commands.
+	pub fn submitted(&mut self) {
+		if self.active {
+			self.submitted = Some(Instant::now());
+		}
+	}
+
+	/// Hands the frame's regions to `profile`. Call once the frame's fence has signaled.
+	pub fn collect(&mut self) {
+		let (pool, submitted) = match (&self.pool, self.submitted.take()) {
+			(Some(pool), Some(submitted)) if self.marks.len() > 1 => (pool, submitted),
+			_ => return,
+		};
+		let count = self.marks.len() as u32;
+		let ticks: Vec<u64> = pool.get_results(0, count, QueryResultFlags::TYPE_64 | QueryResultFlags::WAIT);
+
+		// the GPU has its own clock, so line its first mark up with the submit, which it can't have started before
+		let period = self.period;
+		let time = |tick: u64| submitted + Duration::from_nanos((tick.saturating_sub(ticks[0]) as f64 * period) as u64);
+		let spans = self
+			.marks
+			.windows(2)
+			.zip(ticks.windows(2))
+			.map(|(names, ticks)| Span {
+				name: names[0].clone(),
+				thread: None,
+				start: time(ticks[0]),
+				end: time(ticks[1]),
+			})
+			.collect();
+		profile::add_gpu_spans(self.frame, spans);
+	}
+}
@@ -0,0 +1,171 @@
+use crate::gfx::{
+	camera::Camera,
+	gui::{draw::GuiVertex, render::GuiRenderer, Gui},
+	sprite::{PreparedSprites, Sprite, SpriteList, SpriteRenderer},
+	texture::Subtex,
+	validation,
+	window::{secondary, DEPTH_FORMAT},
+	Gfx,
+};
+use nalgebra::Vector2;
+use std::sync::Arc;
+use vulkan::{
+	buffer::Buffer,
+	command::{CommandBuffer, CommandPool},
+	image::{
+		ClearColorValue, Format, Framebuffer, Image, ImageAspectFlags, ImageLayout, ImageSubresourceRange, ImageType,
+		ImageUsageFlags, ImageView,
+	},
+	ordered_passes_renderpass,
+	render_pass::RenderPass,
+	sync::GpuFuture,
+	Extent2D,
+};
+
+/// An off-screen image sprites, shapes and GUIs can be drawn into, then sampled like any other texture, for minimaps,
+/// portals or cached panels, or shown with `sprite`. Drawing is recorded with `draw` and runs at the start of the next
+/// `Window::draw` it's passed to.
+pub struct RenderTexture {
+	gfx: Arc<Gfx>,
+	extent: Extent2D,
+	format: Format,
+	image_view: Arc<ImageView>,
+	render_pass: Arc<RenderPass>,
+	framebuffer: Arc<Framebuffer>,
+	sprite_renderer: SpriteRenderer,
+	gui_renderer: GuiRenderer,
+	cmdpool: Arc<CommandPool>,
+	/// Used for sprites, with the texture's size as the window size.
+	pub camera: Camera,
+	pub clear_color: [f32; 4],
+}
+impl RenderTexture {
+	pub fn new(gfx: Arc<Gfx>, width: u32, height: u32, format: Format) -> Self {
+		let usage = ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::SAMPLED | ImageUsageFlags::TRANSFER_DST;
+		// cleared up front, so sampling it before the first draw is valid
+		let (image, future) = Image::init(gfx.device.clone(), ImageType::TYPE_2D, width, height, 1, format, usage)
+			.clear(&gfx.queue, &gfx.cmdpool, ClearColorValue { float32: [0.0; 4] });
+		future.then_signal_fence().wait();
+		let range =
+			ImageSubresourceRange::builder().aspect_mask(ImageAspectFlags::COLOR).level_count(1).layer_count(1).build();
+		let image_view = ImageView::new(image, format, range);
+		validation::set_name(&gfx.device, &*image_view, "render texture");
+
+		let usage = ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT;
+		let depth = Image::new(gfx.device.clone(), ImageType::TYPE_2D, width, height, 1, DEPTH_FORMAT, usage);
+		let range =
+			ImageSubresourceRange::builder().aspect_mask(ImageAspectFlags::DEPTH).level_count(1).layer_count(1).build();
+		let depth_view = ImageView::new(depth, DEPTH_FORMAT, range);
+
+		let render_pass = ordered_passes_renderpass!(&gfx.device,
+			attachments: {
+				color: {
+					load: Clear,
+					store: Store,
+					format: format,
+					samples: 1,
+					initial_layout: ImageLayout::UNDEFINED,
+					final_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+				},
+				depth: { load: Clear, store: DontCare, format: DEPTH_FORMAT, samples: 1, }
+			},
+			passes: [{ color: [color], depth_stencil: {depth}, input: [] }]
+		);
+		let attachments = vec![image_view.clone(), depth_view];
+		let framebuffer = Framebuffer::new(gfx.device.clone(), render_pass.clone(), attachments, width, height);
+
+		let sprite_renderer = SpriteRenderer::new(&gfx, render_pass.clone());
+		let gui_renderer = GuiRenderer::new(&gfx, render_pass.clone());
+		let cmdpool = CommandPool::new(gfx.device.clone(), gfx.queue.family().clone(), true);
+
+		Self {
+			gfx,
+			extent: Extent2D { width, height },
+			format,
+			image_view,
+			render_pass,
+			framebuffer,
+			sprite_renderer,
+			gui_renderer,
+			cmdpool,
+			camera: Camera::default(),
+			clear_color: [0.0; 4],
+		}
+	}
+
+	pub fn size(&self) -> (u32, u32) {
+		(self.extent.width, self.extent.height)
+	}
+
+	pub fn format(&self) -> Format {
+		self.format
+	}
+
+	/// In `SHADER_READ_ONLY_OPTIMAL` layout outside of the window's frames.
+	pub fn image_view(&self) -> &Arc<ImageView> {
+		&self.image_view
+	}
+
+	/// The whole texture, for anything drawn from a `Subtex` like sprites, particles or tilesets. It can't be drawn
+	/// into this texture itself.
+	pub fn subtex(&self) -> Subtex {
+		Subtex::new(self.image_view.clone(), self.extent.width, self.extent.height)
+	}
+
+	/// A sprite showing the whole texture at `pos`, transparent if the clear color is.
+	pub fn sprite(&self, pos: Vector2<f32>, layer: u16) -> Sprite {
+		Sprite::new(pos, layer).texture(self.subtex()).transparent(self.clear_color[3] < 1.0)
+	}
+
+	/// Records clearing the texture and drawing `sprites` through `camera`, then `gui` on top. Pass the result to
+	/// `Window::draw_offscreen`, which runs it before the frame that samples it.
+	pub fn draw(&mut self, sprites: &SpriteList, gui: Option<&mut Gui>) -> OffscreenDraw {
+		let image_view = Arc::as_ptr(&self.image_view);
+		let reads_itself = sprites
+			.sprites()
+			.filter_map(|sprite| sprite.texture.as_ref())
+			.any(|texture| Arc::as_ptr(texture.image_view()) == image_view);
+		assert!(!reads_itself, "a render texture can't draw sprites of itself");
+		let extent = self.extent;
+		let win_size = [extent.width as f32, extent.height as f32];
+		let (cmdpool, render_pass, framebuffer) = (&self.cmdpool, &self.render_pass, &self.framebuffer);
+		let begin = || secondary(cmdpool, render_pass, framebuffer, extent);
+
+		let mut secondaries = vec![];
+		let sprites = self.sprite_renderer.prepare(&self.gfx, sprites);
+		let (renderer, camera) = (&self.sprite_renderer, &self.camera);
+		for &transparent in &[false, true] {
+			if !sprites.runs(transparent).is_empty() {
+				let cmd = renderer.record(&self.gfx, begin(), &sprites, transparent, win_size, camera);
+				secondaries.push(cmd.build());
+			}
+		}
+
+		let gui_verts = gui.and_then(|gui| Some((self.gui_renderer.prepare(&self.gfx, gui)?, gui)));
+		if let Some((verts, gui)) = &gui_verts {
+			secondaries.push(self.gui_renderer.record(begin(), verts, gui, extent).build());
+		}
+
+		OffscreenDraw {
+			render_pass: render_pass.clone(),
+			framebuffer: framebuffer.clone(),
+			extent,
+			clear_color: self.clear_color,
+			secondaries,
+			gui_verts: gui_verts.map(|(verts, _)| verts),
+			sprites,
+		}
+	}
+}
+
+/// Drawing into a `RenderTexture`, recorded but not run yet.
+pub struct OffscreenDraw {
+	pub(super) render_pass: Arc<RenderPass>,
+	pub(super) framebuffer: Arc<Framebuffer>,
+	pub(super) extent: Extent2D,
+	pub(super) clear_color: [f32; 4],
+	pub(super) secondaries: Vec<CommandBuffer>,
+	// kept alive until the frame running it finishes
+	pub(super) gui_verts: Option<Arc<Buffer<[GuiVertex]>>>,
+	pub(super) sprites: PreparedSprites,
+}
@@ -0,0 +1,181 @@
+use std::{
+	collections::HashMap,
+	sync::{Arc, Mutex},
+};
+use vulkan::{
+	device::Device,
+	image::{
+		BorderColor as VkBorderColor, Filter as VkFilter, Sampler, SamplerAddressMode, SamplerCreateInfo,
+		SamplerMipmapMode,
+	},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Filter {
+	/// Blocky, for pixel art.
+	Nearest,
+	Linear,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AddressMode {
+	/// Tiles the texture, for backgrounds and patterns.
+	Repeat,
+	MirroredRepeat,
+	ClampToEdge,
+	/// Reads `SamplerDesc::border` outside the texture.
+	ClampToBorder,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BorderColor {
+	TransparentBlack,
+	OpaqueBlack,
+	OpaqueWhite,
+}
+
+/// How a texture is read. The default filters linearly and clamps to the edge.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerDesc {
+	pub mag_filter: Filter,
+	pub min_filter: Filter,
+	/// Filtering between mip levels, for textures that have them.
+	pub mipmap: Filter,
+	/// Along x and y.
+	pub address: [AddressMode; 2],
+	pub border: BorderColor,
+	/// Samples taken along steep angles, 1 to disable. Capped at what the device supports.
+	pub anisotropy: u32,
+}
+impl Default for SamplerDesc {
+	fn default() -> Self {
+		Self {
+			mag_filter: Filter::Linear,
+			min_filter: Filter::Linear,
+			mipmap: Filter::Linear,
+			address: [AddressMode::ClampToEdge; 2],
+			border: BorderColor::TransparentBlack,
+			anisotropy: 1,
+		}
+	}
+}
+impl SamplerDesc {
+	pub fn nearest() -> Self {
+		Self::default().filter(Filter::Nearest)
+	}
+
+	pub fn linear() -> Self {
+		Self::default()
+	}
+
+	/// Same filter for magnifying, minifying and between mip levels.
+	pub fn filter(mut self, filter: Filter) -> Self {
+		self.mag_filter = filter;
+		self.min_filter = filter;
+		self.mipmap = filter;
+		self
+	}
+
+	/// Same address mode along both axes.
+	pub fn address(mut self, mode: AddressMode) -> Self {
+		self.address = [mode; 2];
+		self
+	}
+
+	pub fn border(mut self, color: BorderColor) -> Self {
+		self.border = color;
+		self
+	}
+
+	pub fn anisotropy(mut self, samples: u32) -> Self {
+		self.anisotropy = samples;
+		self
+	}
+
+	/// Parses space separated options as used by material files: `nearest`, `linear`, `repeat`, `mirror`, `clamp`,
+	/// `border <transparent|black|white>` and `anisotropy <samples>`, later ones overriding earlier ones.
+	pub fn parse<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<Self, String> {
+		let mut desc = Self::default();
+		while let Some(word) = words.next() {
+			desc = match word {
+				"nearest" => desc.filter(Filter::Nearest),
+				"linear" => desc.filter(Filter::Linear),
+				"repeat" => desc.address(AddressMode::Repeat),
+				"mirror" => desc.address(AddressMode::MirroredRepeat),
+				"clamp" => desc.address(AddressMode::ClampToEdge),
+				"border" => {
+					let color = match words.next() {
+						Some("transparent") => BorderColor::TransparentBlack,
+						Some("black") => BorderColor::OpaqueBlack,
+						Some("white") => BorderColor::OpaqueWhite,
+						_ => return Err("expected `transparent`, `black` or `white` after `border`".to_owned()),
+					};
+					desc.address(AddressMode::ClampToBorder).border(color)
+				},
+				"anisotropy" => {
+					let samples = words.next().and_then(|x| x.parse().ok()).ok_or("bad anisotropy")?;
+					desc.anisotropy(samples)
+				},
+				word => return Err(format!("unknown sampler option `{}`", word)),
+			};
+		}
+		Ok(desc)
+	}
+}
+
+/// Creates each distinct sampler once, since devices only allow a few thousand.
+pub struct SamplerCache {
+	device: Arc<Device>,
+	// 1 if the device doesn't support anisotropic filtering
+	max_anisotropy: f32,
+	samplers: Mutex<HashMap<SamplerDesc, Arc<Sampler>>>,
+}
+impl SamplerCache {
+	pub fn new(device: Arc<Device>, max_anisotropy: f32) -> Self {
+		Self { device, max_anisotropy, samplers: Mutex::default() }
+	}
+
+	pub fn get(&self, desc: SamplerDesc) -> Arc<Sampler> {
+		let mut samplers = self.samplers.lock().unwrap();
+		if let Some(sampler) = samplers.get(&desc) {
+			return sampler.clone();
+		}
+
+		let filter = |filter| match filter {
+			Filter::Nearest => VkFilter::NEAREST,
+			Filter::Linear => VkFilter::LINEAR,
+		};
+		let mipmap = match desc.mipmap {
+			Filter::Nearest => SamplerMipmapMode::NEAREST,
+			Filter::Linear => SamplerMipmapMode::LINEAR,
+		};
+		let address = |mode| match mode {
+			AddressMode::Repeat => SamplerAddressMode::REPEAT,
+			AddressMode::MirroredRepeat => SamplerAddressMode::MIRRORED_REPEAT,
+			AddressMode::ClampToEdge => SamplerAddressMode::CLAMP_TO_EDGE,
+			AddressMode::ClampToBorder => SamplerAddressMode::CLAMP_TO_BORDER,
+		};
+		let border = match desc.border {
+			BorderColor::TransparentBlack => VkBorderColor::FLOAT_TRANSPARENT_BLACK,
+			BorderColor::OpaqueBlack => VkBorderColor::FLOAT_OPAQUE_BLACK,
+			BorderColor::OpaqueWhite => VkBorderColor::FLOAT_OPAQUE_WHITE,
+		};
+		let anisotropy = (desc.anisotropy as f32).min(self.max_anisotropy);
+		let info = SamplerCreateInfo::builder()
+			.mag_filter(filter(desc.mag_filter))
+			.min_filter(filter(desc.min_filter))
+			.mipmap_mode(mipmap)
+			.address_mode_u(address(desc.address[0]))
+			.address_mode_v(address(desc.address[1]))
+			.address_mode_w(address(desc.address[0]))
+			.anisotropy_enable(anisotropy > 1.0)
+			.max_anisotropy(anisotropy.max(1.0))
+			.border_color(border)
+			// every mip level the image has
+			.max_lod(1000.0)
+			.build();
+		let sampler = Sampler::with_info(self.device.clone(), &info);
+		samplers.insert(desc, sampler.clone());
+		sampler
+	}
+}
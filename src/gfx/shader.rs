@@ -0,0 +1,200 @@
+pub mod reflect;
+
+#[cfg(feature = "hot-reload")]
+use crate::fs::read_all_u32;
+use crate::gfx::{bindless::array_layout, validation};
+#[cfg(feature = "hot-reload")]
+use log::debug;
+use reflect::{reflect, Reflection};
+use std::{borrow::Cow, io, iter::once, slice, sync::Arc};
+use vulkan::{
+	descriptor::DescriptorSetLayout,
+	device::Device,
+	pipeline::PipelineLayout,
+	shader::{ShaderModule, ShaderStageFlags},
+};
+
+include!(concat!(env!("OUT_DIR"), "/shaders.rs"));
+
+pub struct ShaderInfo {
+	pub name: &'static str,
+	pub stages: &'static [&'static str],
+	pub variants: &'static [VariantInfo],
+}
+
+pub struct VariantInfo {
+	pub name: &'static str,
+	pub defines: &'static [&'static str],
+	pub stages: &'static [StageInfo],
+}
+impl VariantInfo {
+	pub fn stage(&self, stage: &str) -> Option<&'static StageInfo> {
+		self.stages.iter().find(|info| info.stage == stage)
+	}
+}
+
+pub struct StageInfo {
+	pub stage: &'static str,
+	pub file: &'static str,
+	pub spirv: &'static Spirv<[u8]>,
+}
+
+// include_bytes! only guarantees byte alignment, so wrap it in something aligned for u32
+#[repr(C)]
+pub struct Spirv<B: ?Sized> {
+	_align: [u32; 0],
+	bytes: B,
+}
+impl Spirv<[u8]> {
+	pub fn words(&self) -> &[u32] {
+		unsafe { slice::from_raw_parts(self.bytes.as_ptr() as *const u32, self.bytes.len() / 4) }
+	}
+}
+
+pub fn find_shader(name: &str) -> Option<&'static ShaderInfo> {
+	SHADERS.iter().find(|shader| shader.name == name)
+}
+
+pub struct ShaderProgram {
+	name: String,
+	pub(super) vshader: Arc<ShaderModule>,
+	pub(super) fshader: Arc<ShaderModule>,
+	pub(super) layout: Arc<PipelineLayout>,
+	pub(super) desc_layouts: Vec<Arc<DescriptorSetLayout>>,
+	reflection: Reflection,
+}
+impl ShaderProgram {
+	/// Loads a program compiled by the build script. `variant` is empty for the default variant, otherwise the
+	/// lowercased permutation defines joined with `+`, e.g. `tint+alpha_test`.
+	pub async fn load(device: Arc<Device>, name: &str, variant: &str) -> io::Result<Arc<Self>> {
+		let shader = find_shader(name).ok_or_else(|| invalid(format!("unknown shader `{}`", name)))?;
+		let variant = shader
+			.variants
+			.iter()
+			.find(|v| v.name == variant)
+			.ok_or_else(|| invalid(format!("{}: unknown variant `{}`", name, variant)))?;
+
+		let stage = |stage| variant.stage(stage).ok_or_else(|| invalid(format!("{}: missing {} stage", name, stage)));
+		let vert_spv = load_spirv(stage("vert")?).await;
+		let frag_spv = load_spirv(stage("frag")?).await;
+		Self::new(device, name, &vert_spv, &frag_spv)
+	}
+
+	/// Fails if either stage can't be reflected, or its descriptors can't be laid out.
+	pub fn new(device: Arc<Device>, name: &str, vert_spv: &[u32], frag_spv: &[u32]) -> io::Result<Arc<Self>> {
+		let vreflection = reflect(vert_spv).map_err(|err| invalid(format!("{}.vert: {}", name, err)))?;
+		let freflection = reflect(frag_spv).map_err(|err| invalid(format!("{}.frag: {}", name, err)))?;
+		let reflection = vreflection.merge(freflection);
+		check_sets(&reflection).map_err(|msg| invalid(format!("{}: {}", name, msg)))?;
+
+		let vshader = unsafe { ShaderModule::new(device.clone(), vert_spv) };
+		let fshader = unsafe { ShaderModule::new(device.clone(), frag_spv) };
+		validation::set_name(&device, &*vshader, &format!("{}.vert", name));
+		validation::set_name(&device, &*fshader, &format!("{}.frag", name));
+
+		let desc_layouts: Vec<_> = (0..reflection.set_count())
+			.map(|set| {
+				let bindings = reflection.bindings.iter().filter(|b| b.set == set);
+				// unsized arrays are the texture array, which is shared between programs
+				if bindings.clone().any(|binding| binding.count == 0) {
+					return array_layout(device.clone());
+				}
+				let mut builder = DescriptorSetLayout::builder(device.clone());
+				for binding in bindings {
+					// no immutable samplers, each texture brings its own
+					builder = builder.desc(binding.ty, binding.count, binding.stages, vec![]);
+				}
+				builder.build()
+			})
+			.collect();
+
+		let push_constants =
+			once((reflection.push_constant_stages, 0, reflection.push_constant_size)).filter(|&(_, _, size)| size > 0);
+		let layout = PipelineLayout::new(device.clone(), desc_layouts.clone(), push_constants);
+		validation::set_name(&device, &*layout, name);
+
+		Ok(Arc::new(Self { name: name.to_owned(), vshader, fshader, layout, desc_layouts, reflection }))
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn reflection(&self) -> &Reflection {
+		&self.reflection
+	}
+
+	pub fn push_constant_stages(&self) -> ShaderStageFlags {
+		self.reflection.push_constant_stages
+	}
+}
+
+// a texture array must be alone in its set, and the layout builder numbers other bindings sequentially from 0
+fn check_sets(reflection: &Reflection) -> Result<(), String> {
+	for set in 0..reflection.set_count() {
+		let bindings: Vec<_> = reflection.bindings.iter().filter(|b| b.set == set).collect();
+		if bindings.iter().any(|binding| binding.count == 0) {
+			if bindings.len() != 1 {
+				return Err("a texture array must be alone in its set".to_owned());
+			}
+		} else if bindings.iter().enumerate().any(|(i, binding)| i as u32 != binding.binding) {
+			return Err("descriptor bindings must be contiguous".to_owned());
+		}
+	}
+	Ok(())
+}
+
+fn invalid(msg: String) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(not(feature = "hot-reload"))]
+async fn load_spirv(stage: &'static StageInfo) -> Cow<'static, [u32]> {
+	Cow::Borrowed(stage.spirv.words())
+}
+
+// prefer whatever the build script most recently wrote, so shaders can be rebuilt while the game is running
+#[cfg(feature = "hot-reload")]
+async fn load_spirv(stage: &'static StageInfo) -> Cow<'static, [u32]> {
+	let path = concat!(env!("OUT_DIR"), "/shaders/").to_owned() + stage.file;
+	match read_all_u32(path).await {
+		Ok(spirv) => {
+			debug!("loaded rebuilt {}", stage.file);
+			Cow::Owned(spirv)
+		},
+		Err(_) => Cow::Borrowed(stage.spirv.words()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn every_variant_loads() {
+		for shader in SHADERS {
+			for variant in shader.variants {
+				let stage = |stage| {
+					let info = variant.stage(stage).unwrap();
+					reflect(info.spirv.words()).unwrap_or_else(|err| panic!("{}: {}", info.file, err))
+				};
+				let reflection = stage("vert").merge(stage("frag"));
+				if let Err(msg) = check_sets(&reflection) {
+					panic!("{} `{}`: {}", shader.name, variant.name, msg);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn stages_shared_between_variants() {
+		let shader = find_shader("shader").unwrap();
+		let file = |variant: &str, stage| {
+			let variant = shader.variants.iter().find(|v| v.name == variant).unwrap();
+			variant.stage(stage).unwrap().file
+		};
+		assert_eq!(file("tint+alpha_test", "vert"), file("", "vert"));
+		assert_eq!(file("tint+bindless", "vert"), file("bindless", "vert"));
+		assert_ne!(file("tint", "frag"), file("", "frag"));
+	}
+}
@@ -0,0 +1,472 @@
+use std::{collections::HashMap, fmt};
+use vulkan::{descriptor::DescriptorType, shader::ShaderStageFlags};
+
+const MAGIC: u32 = 0x0723_0203;
+
+const OP_NAME: u16 = 5;
+const OP_MEMBER_NAME: u16 = 6;
+const OP_ENTRY_POINT: u16 = 15;
+const OP_TYPE_INT: u16 = 21;
+const OP_TYPE_FLOAT: u16 = 22;
+const OP_TYPE_VECTOR: u16 = 23;
+const OP_TYPE_MATRIX: u16 = 24;
+const OP_TYPE_IMAGE: u16 = 25;
+const OP_TYPE_SAMPLER: u16 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u16 = 27;
+const OP_TYPE_ARRAY: u16 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u16 = 29;
+const OP_TYPE_STRUCT: u16 = 30;
+const OP_TYPE_POINTER: u16 = 32;
+const OP_CONSTANT: u16 = 43;
+const OP_VARIABLE: u16 = 59;
+const OP_DECORATE: u16 = 71;
+const OP_MEMBER_DECORATE: u16 = 72;
+
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+const DIM_BUFFER: u32 = 5;
+
+#[derive(Debug)]
+pub enum ReflectError {
+	BadMagic,
+	Truncated,
+	Unsupported(&'static str),
+}
+impl fmt::Display for ReflectError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::BadMagic => write!(f, "not a SPIR-V module"),
+			Self::Truncated => write!(f, "truncated SPIR-V module"),
+			Self::Unsupported(what) => write!(f, "unsupported SPIR-V construct: {}", what),
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Reflection {
+	pub stages: ShaderStageFlags,
+	pub bindings: Vec<Binding>,
+	pub push_constant_stages: ShaderStageFlags,
+	pub push_constant_size: u32,
+}
+impl Reflection {
+	pub fn merge(mut self, other: Reflection) -> Self {
+		self.stages |= other.stages;
+		for binding in other.bindings {
+			match self.bindings.iter_mut().find(|b| b.set == binding.set && b.binding == binding.binding) {
+				Some(existing) => {
+					existing.stages |= binding.stages;
+					if existing.members.is_empty() {
+						existing.members = binding.members;
+					}
+				},
+				None => self.bindings.push(binding),
+			}
+		}
+		self.push_constant_stages |= other.push_constant_stages;
+		self.push_constant_size = self.push_constant_size.max(other.push_constant_size);
+		self.bindings.sort_by_key(|b| (b.set, b.binding));
+		self
+	}
+
+	pub fn set_count(&self) -> u32 {
+		self.bindings.iter().map(|b| b.set + 1).max().unwrap_or(0)
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Binding {
+	pub set: u32,
+	pub binding: u32,
+	pub name: String,
+	pub ty: DescriptorType,
+	/// 0 for runtime-sized arrays
+	pub count: u32,
+	pub stages: ShaderStageFlags,
+	pub size: u32,
+	pub members: Vec<Member>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Member {
+	pub name: String,
+	pub offset: u32,
+	pub size: u32,
+}
+
+enum Type {
+	Scalar { width: u32 },
+	Vector { component: u32, count: u32 },
+	Matrix { column: u32, count: u32 },
+	Image { dim: u32, sampled: u32 },
+	Sampler,
+	SampledImage,
+	Array { element: u32, length: u32 },
+	RuntimeArray { element: u32 },
+	Struct { members: Vec<u32> },
+	Pointer { pointee: u32 },
+}
+
+#[derive(Default)]
+struct Decorations {
+	block: bool,
+	buffer_block: bool,
+	binding: Option<u32>,
+	set: Option<u32>,
+	array_stride: Option<u32>,
+}
+
+#[derive(Default)]
+struct MemberDecorations {
+	offset: Option<u32>,
+	matrix_stride: Option<u32>,
+}
+
+pub fn reflect(spv: &[u32]) -> Result<Reflection, ReflectError> {
+	if spv.len() < 5 {
+		return Err(ReflectError::Truncated);
+	}
+	if spv[0] != MAGIC {
+		return Err(ReflectError::BadMagic);
+	}
+
+	let mut stages = ShaderStageFlags::empty();
+	let mut names = HashMap::new();
+	let mut member_names = HashMap::new();
+	let mut decorations: HashMap<u32, Decorations> = HashMap::new();
+	let mut member_decorations: HashMap<(u32, u32), MemberDecorations> = HashMap::new();
+	let mut types = HashMap::new();
+	let mut constants = HashMap::new();
+	let mut variables = vec![];
+
+	let mut i = 5;
+	while i < spv.len() {
+		let word_count = (spv[i] >> 16) as usize;
+		let opcode = (spv[i] & 0xFFFF) as u16;
+		if word_count == 0 || i + word_count > spv.len() {
+			return Err(ReflectError::Truncated);
+		}
+		let args = &spv[i + 1..i + word_count];
+		if args.len() < min_operands(opcode) {
+			return Err(ReflectError::Truncated);
+		}
+
+		match opcode {
+			OP_NAME => {
+				names.insert(args[0], read_string(&args[1..]));
+			},
+			OP_MEMBER_NAME => {
+				member_names.insert((args[0], args[1]), read_string(&args[2..]));
+			},
+			OP_ENTRY_POINT => {
+				stages |= match args[0] {
+					0 => ShaderStageFlags::VERTEX,
+					1 => ShaderStageFlags::TESSELLATION_CONTROL,
+					2 => ShaderStageFlags::TESSELLATION_EVALUATION,
+					3 => ShaderStageFlags::GEOMETRY,
+					4 => ShaderStageFlags::FRAGMENT,
+					5 => ShaderStageFlags::COMPUTE,
+					_ => return Err(ReflectError::Unsupported("execution model")),
+				}
+			},
+			OP_DECORATE => {
+				let decoration = decorations.entry(args[0]).or_default();
+				let literal = || args.get(2).cloned().ok_or(ReflectError::Truncated);
+				match args[1] {
+					DECORATION_BLOCK => decoration.block = true,
+					DECORATION_BUFFER_BLOCK => decoration.buffer_block = true,
+					DECORATION_ARRAY_STRIDE => decoration.array_stride = Some(literal()?),
+					DECORATION_BINDING => decoration.binding = Some(literal()?),
+					DECORATION_DESCRIPTOR_SET => decoration.set = Some(literal()?),
+					_ => (),
+				}
+			},
+			OP_MEMBER_DECORATE => {
+				let decoration = member_decorations.entry((args[0], args[1])).or_default();
+				let literal = || args.get(3).cloned().ok_or(ReflectError::Truncated);
+				match args[2] {
+					DECORATION_OFFSET => decoration.offset = Some(literal()?),
+					DECORATION_MATRIX_STRIDE => decoration.matrix_stride = Some(literal()?),
+					_ => (),
+				}
+			},
+			OP_TYPE_INT | OP_TYPE_FLOAT => {
+				types.insert(args[0], Type::Scalar { width: args[1] });
+			},
+			OP_TYPE_VECTOR => {
+				types.insert(args[0], Type::Vector { component: args[1], count: args[2] });
+			},
+			OP_TYPE_MATRIX => {
+				types.insert(args[0], Type::Matrix { column: args[1], count: args[2] });
+			},
+			OP_TYPE_IMAGE => {
+				types.insert(args[0], Type::Image { dim: args[2], sampled: args[6] });
+			},
+			OP_TYPE_SAMPLER => {
+				types.insert(args[0], Type::Sampler);
+			},
+			OP_TYPE_SAMPLED_IMAGE => {
+				types.insert(args[0], Type::SampledImage);
+			},
+			OP_TYPE_ARRAY => {
+				types.insert(args[0], Type::Array { element: args[1], length: args[2] });
+			},
+			OP_TYPE_RUNTIME_ARRAY => {
+				types.insert(args[0], Type::RuntimeArray { element: args[1] });
+			},
+			OP_TYPE_STRUCT => {
+				types.insert(args[0], Type::Struct { members: args[1..].to_vec() });
+			},
+			OP_TYPE_POINTER => {
+				types.insert(args[0], Type::Pointer { pointee: args[2] });
+			},
+			OP_CONSTANT => {
+				constants.insert(args[1], args[2]);
+			},
+			OP_VARIABLE => variables.push((args[0], args[1], args[2])),
+			_ => (),
+		}
+
+		i += word_count;
+	}
+
+	let refl = Reflector { types: &types, constants: &constants, decorations: &decorations, member_decorations };
+
+	let mut bindings = vec![];
+	let mut push_constant_stages = ShaderStageFlags::empty();
+	let mut push_constant_size = 0;
+	for (ty, id, storage) in variables {
+		let pointee = match types.get(&ty) {
+			Some(&Type::Pointer { pointee }) => pointee,
+			_ => continue,
+		};
+
+		match storage {
+			STORAGE_PUSH_CONSTANT => {
+				push_constant_stages = stages;
+				push_constant_size = push_constant_size.max(refl.size_of(pointee));
+			},
+			STORAGE_UNIFORM_CONSTANT | STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER => {
+				let decoration = match decorations.get(&id) {
+					Some(decoration) => decoration,
+					None => continue,
+				};
+				let (element, count) = refl.unwrap_array(pointee);
+				let ty = refl.descriptor_type(storage, element)?;
+
+				let (size, members) = match types.get(&element) {
+					Some(Type::Struct { members }) => {
+						let members = (0..members.len() as u32)
+							.map(|idx| Member {
+								name: member_names.get(&(element, idx)).cloned().unwrap_or_default(),
+								offset: refl.member_offset(element, idx),
+								size: refl.member_size(element, idx),
+							})
+							.collect();
+						(refl.size_of(element), members)
+					},
+					_ => (0, vec![]),
+				};
+
+				let name = names.get(&id).filter(|name| !name.is_empty()).or_else(|| names.get(&element)).cloned();
+				bindings.push(Binding {
+					set: decoration.set.unwrap_or(0),
+					binding: decoration.binding.unwrap_or(0),
+					name: name.unwrap_or_default(),
+					ty,
+					count,
+					stages,
+					size,
+					members,
+				});
+			},
+			_ => (),
+		}
+	}
+	bindings.sort_by_key(|b| (b.set, b.binding));
+
+	Ok(Reflection { stages, bindings, push_constant_stages, push_constant_size })
+}
+
+// operands an instruction needs before the ones it's read for can be indexed, not counting the opcode word
+fn min_operands(opcode: u16) -> usize {
+	match opcode {
+		OP_NAME | OP_ENTRY_POINT | OP_TYPE_SAMPLER | OP_TYPE_SAMPLED_IMAGE | OP_TYPE_STRUCT => 1,
+		OP_MEMBER_NAME | OP_DECORATE | OP_TYPE_INT | OP_TYPE_FLOAT | OP_TYPE_RUNTIME_ARRAY => 2,
+		OP_MEMBER_DECORATE | OP_TYPE_VECTOR | OP_TYPE_MATRIX | OP_TYPE_ARRAY | OP_TYPE_POINTER | OP_CONSTANT
+		| OP_VARIABLE => 3,
+		OP_TYPE_IMAGE => 7,
+		_ => 0,
+	}
+}
+
+struct Reflector<'a> {
+	types: &'a HashMap<u32, Type>,
+	constants: &'a HashMap<u32, u32>,
+	decorations: &'a HashMap<u32, Decorations>,
+	member_decorations: HashMap<(u32, u32), MemberDecorations>,
+}
+impl Reflector<'_> {
+	fn unwrap_array(&self, ty: u32) -> (u32, u32) {
+		match self.types.get(&ty) {
+			Some(&Type::Array { element, length }) => (element, self.constants.get(&length).cloned().unwrap_or(1)),
+			Some(&Type::RuntimeArray { element }) => (element, 0),
+			_ => (ty, 1),
+		}
+	}
+
+	fn descriptor_type(&self, storage: u32, ty: u32) -> Result<DescriptorType, ReflectError> {
+		let decoration = self.decorations.get(&ty);
+		let block = decoration.map(|d| d.block).unwrap_or(false);
+		let buffer_block = decoration.map(|d| d.buffer_block).unwrap_or(false);
+
+		Ok(match (storage, self.types.get(&ty)) {
+			(STORAGE_UNIFORM_CONSTANT, Some(Type::SampledImage)) => DescriptorType::COMBINED_IMAGE_SAMPLER,
+			(STORAGE_UNIFORM_CONSTANT, Some(Type::Sampler)) => DescriptorType::SAMPLER,
+			(STORAGE_UNIFORM_CONSTANT, Some(&Type::Image { dim: DIM_BUFFER, sampled: 2 })) => {
+				DescriptorType::STORAGE_TEXEL_BUFFER
+			},
+			(STORAGE_UNIFORM_CONSTANT, Some(&Type::Image { dim: DIM_BUFFER, .. })) => {
+				DescriptorType::UNIFORM_TEXEL_BUFFER
+			},
+			(STORAGE_UNIFORM_CONSTANT, Some(&Type::Image { sampled: 2, .. })) => DescriptorType::STORAGE_IMAGE,
+			(STORAGE_UNIFORM_CONSTANT, Some(Type::Image { .. })) => DescriptorType::SAMPLED_IMAGE,
+			(STORAGE_UNIFORM, _) if buffer_block => DescriptorType::STORAGE_BUFFER,
+			(STORAGE_UNIFORM, _) if block => DescriptorType::UNIFORM_BUFFER,
+			(STORAGE_STORAGE_BUFFER, _) => DescriptorType::STORAGE_BUFFER,
+			_ => return Err(ReflectError::Unsupported("descriptor type")),
+		})
+	}
+
+	fn size_of(&self, ty: u32) -> u32 {
+		match self.types.get(&ty) {
+			Some(&Type::Scalar { width }) => width / 8,
+			Some(&Type::Vector { component, count }) => self.size_of(component) * count,
+			Some(&Type::Matrix { column, count }) => self.size_of(column) * count,
+			Some(&Type::Array { element, length }) => {
+				let stride = self.decorations.get(&ty).and_then(|d| d.array_stride).unwrap_or(self.size_of(element));
+				stride * self.constants.get(&length).cloned().unwrap_or(1)
+			},
+			Some(Type::Struct { members }) => (0..members.len() as u32)
+				.map(|idx| self.member_offset(ty, idx) + self.member_size(ty, idx))
+				.max()
+				.unwrap_or(0),
+			_ => 0,
+		}
+	}
+
+	fn member_offset(&self, ty: u32, idx: u32) -> u32 {
+		self.member_decorations.get(&(ty, idx)).and_then(|d| d.offset).unwrap_or(0)
+	}
+
+	fn member_size(&self, ty: u32, idx: u32) -> u32 {
+		let member = match self.types.get(&ty) {
+			Some(Type::Struct { members }) => members[idx as usize],
+			_ => return 0,
+		};
+		match (self.types.get(&member), self.member_decorations.get(&(ty, idx)).and_then(|d| d.matrix_stride)) {
+			(Some(&Type::Matrix { count, .. }), Some(stride)) => stride * count,
+			_ => self.size_of(member),
+		}
+	}
+}
+
+fn read_string(words: &[u32]) -> String {
+	let bytes: Vec<u8> =
+		words.iter().flat_map(|word| word.to_le_bytes().to_vec()).take_while(|&byte| byte != 0).collect();
+	String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gfx::{light::LightPushConsts, shader::find_shader, sprite::SpritePushConsts};
+	use std::mem::size_of;
+
+	// both stages of a variant the build script compiled, merged like `ShaderProgram::new` does
+	fn program(name: &str, variant: &str) -> Reflection {
+		let shader = find_shader(name).unwrap();
+		let variant = shader.variants.iter().find(|v| v.name == variant).unwrap();
+		let stage = |stage| reflect(variant.stage(stage).unwrap().spirv.words()).unwrap();
+		stage("vert").merge(stage("frag"))
+	}
+
+	fn both() -> ShaderStageFlags {
+		ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT
+	}
+
+	#[test]
+	fn texture_read_by_both_stages() {
+		let reflection = program("shader", "");
+		assert_eq!(reflection.stages, both());
+		assert_eq!(reflection.bindings.len(), 1);
+		let tex = &reflection.bindings[0];
+		assert_eq!((tex.set, tex.binding, tex.name.as_str(), tex.count), (0, 0, "tex", 1));
+		assert_eq!(tex.ty, DescriptorType::COMBINED_IMAGE_SAMPLER);
+		assert_eq!(tex.stages, both());
+	}
+
+	#[test]
+	fn uniform_block_members() {
+		let reflection = program("shader", "tint");
+		let block = &reflection.bindings[1];
+		assert_eq!((block.set, block.binding, block.name.as_str()), (0, 1, "Material"));
+		assert_eq!(block.ty, DescriptorType::UNIFORM_BUFFER);
+		assert_eq!(block.stages, ShaderStageFlags::FRAGMENT);
+		assert_eq!(block.size, 16);
+		let members: Vec<_> = block.members.iter().map(|m| (m.name.as_str(), m.offset, m.size)).collect();
+		assert_eq!(members, vec![("tint", 0, 16)]);
+	}
+
+	#[test]
+	fn unsized_texture_array() {
+		let reflection = program("shader", "bindless");
+		assert_eq!(reflection.set_count(), 1);
+		assert_eq!(reflection.bindings.len(), 1);
+		let textures = &reflection.bindings[0];
+		assert_eq!((textures.binding, textures.name.as_str(), textures.count), (0, "textures", 0));
+		assert_eq!(textures.ty, DescriptorType::COMBINED_IMAGE_SAMPLER);
+	}
+
+	#[test]
+	fn push_constant_ranges() {
+		// renderers push their structs as they are, so the sizes have to match the blocks
+		let light = program("light", "");
+		assert_eq!(light.push_constant_size as usize, size_of::<LightPushConsts>());
+		assert_eq!(light.push_constant_stages, both());
+
+		let sprite = program("shader", "");
+		assert_eq!(sprite.push_constant_size as usize, size_of::<SpritePushConsts>());
+		assert_eq!(sprite.push_constant_stages, ShaderStageFlags::VERTEX);
+
+		let blur = program("post_blur", "");
+		assert_eq!((blur.push_constant_size, blur.push_constant_stages), (0, ShaderStageFlags::empty()));
+	}
+
+	#[test]
+	fn rejects_malformed_modules() {
+		assert!(matches!(reflect(&[0; 8]), Err(ReflectError::BadMagic)));
+		assert!(matches!(reflect(&[MAGIC]), Err(ReflectError::Truncated)));
+		// an instruction longer than what's left of the module
+		let header = [MAGIC, 0x0001_0000, 0, 8, 0];
+		let name = (3 << 16) | OP_NAME as u32;
+		assert!(matches!(reflect(&[&header[..], &[name, 1]].concat()), Err(ReflectError::Truncated)));
+		// an OpDecorate without its decoration, and a Binding decoration without its number
+		let decorate = (2 << 16) | OP_DECORATE as u32;
+		assert!(matches!(reflect(&[&header[..], &[decorate, 1]].concat()), Err(ReflectError::Truncated)));
+		let decorate = (3 << 16) | OP_DECORATE as u32;
+		let binding = [decorate, 1, DECORATION_BINDING];
+		assert!(matches!(reflect(&[&header[..], &binding[..]].concat()), Err(ReflectError::Truncated)));
+	}
+}
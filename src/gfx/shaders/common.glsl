@@ -0,0 +1 @@
+layout(binding = 0) uniform sampler2D tex;
@@ -0,0 +1,11 @@
+// shared vertex stage of post-processing programs, drawn with the unit quad in `Gfx::verts`
+
+layout(location = 0) in vec2 in_pos;
+
+layout(location = 0) out vec2 out_uv;
+
+void main() {
+	out_uv = in_pos;
+	// farthest depth, so anything drawn afterwards in the same pass lands on top
+	gl_Position = vec4(in_pos * 2 - 1, 1.0, 1.0);
+}
@@ -0,0 +1,43 @@
+#version 450
+
+layout(location = 0) in vec2 in_world;
+layout(location = 1) in vec2 in_screen_uv;
+
+layout(location = 0) out vec4 out_color;
+
+layout(binding = 0) uniform sampler2D normals;
+
+layout(push_constant) uniform PushConsts {
+	vec4 color;
+	vec2 center;
+	vec2 win_size;
+	vec2 view_pos;
+	vec2 direction;
+	float zoom;
+	float radius;
+	float height;
+	float cos_inner;
+	// -1 for point lights
+	float cos_outer;
+} pc;
+
+void main() {
+	vec2 to_pixel = in_world - pc.center;
+	float dist = length(to_pixel);
+	float falloff = clamp(1.0 - dist / pc.radius, 0.0, 1.0);
+	float light = falloff * falloff;
+
+	if (pc.cos_outer > -1.0) {
+		float cos_angle = dot(to_pixel / max(dist, 0.0001), pc.direction);
+		light *= smoothstep(pc.cos_outer, pc.cos_inner, cos_angle);
+	}
+
+	// normal maps point green up while world y points down
+	vec3 normal = texture(normals, in_screen_uv).xyz * 2.0 - 1.0;
+	normal.y = -normal.y;
+	vec3 to_light = normalize(vec3(-to_pixel, pc.height));
+	light *= max(dot(normalize(normal), to_light), 0.0);
+
+	// blended additively, so alpha only scales the contribution
+	out_color = vec4(pc.color.rgb * light, 1.0);
+}
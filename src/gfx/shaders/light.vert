@@ -0,0 +1,27 @@
+#version 450
+
+// world pixels
+layout(location = 0) in vec2 in_pos;
+
+layout(location = 0) out vec2 out_world;
+layout(location = 1) out vec2 out_screen_uv;
+
+layout(push_constant) uniform PushConsts {
+	vec4 color;
+	vec2 center;
+	vec2 win_size;
+	vec2 view_pos;
+	vec2 direction;
+	float zoom;
+	float radius;
+	float height;
+	float cos_inner;
+	float cos_outer;
+} pc;
+
+void main() {
+	out_world = in_pos;
+	vec2 screen = (in_pos - pc.view_pos) * pc.zoom;
+	out_screen_uv = screen / pc.win_size;
+	gl_Position = vec4(out_screen_uv * 2 - 1, 0.0, 1.0);
+}
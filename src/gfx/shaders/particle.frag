@@ -0,0 +1,17 @@
+#version 450
+#pragma permutation BINDLESS
+#ifdef BINDLESS
+#extension GL_EXT_nonuniform_qualifier : require
+#endif
+
+#include "textures.glsl"
+
+layout(location = 0) in vec2 in_uv;
+layout(location = 1) in vec4 in_color;
+layout(location = 2) flat in uint in_texture;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+	out_color = texture(TEX(in_texture), in_uv) * in_color;
+}
@@ -0,0 +1,39 @@
+#version 450
+#pragma permutation BINDLESS
+#ifdef BINDLESS
+#extension GL_EXT_nonuniform_qualifier : require
+#endif
+
+// the unit quad
+layout(location = 0) in vec2 in_pos;
+// per instance
+layout(location = 1) in vec2 in_center;
+layout(location = 2) in vec2 in_half_size;
+layout(location = 3) in float in_rotation;
+layout(location = 4) in vec4 in_uv_rect;
+layout(location = 5) in vec4 in_color;
+layout(location = 6) in uint in_texture;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec4 out_color;
+layout(location = 2) flat out uint out_texture;
+
+#include "textures.glsl"
+
+layout(push_constant) uniform PushConsts {
+	vec2 win_size;
+	vec2 view_pos;
+	float zoom;
+	float depth;
+} pc;
+
+void main() {
+	out_uv = (in_uv_rect.xy + in_pos * in_uv_rect.zw) / textureSize(TEX(in_texture), 0);
+	out_color = in_color;
+	out_texture = in_texture;
+	vec2 corner = (in_pos * 2 - 1) * in_half_size;
+	float c = cos(in_rotation), s = sin(in_rotation);
+	vec2 pos = in_center + mat2(c, s, -s, c) * corner;
+	vec2 screen = (pos - pc.view_pos) * pc.zoom;
+	gl_Position = vec4(screen / pc.win_size * 2 - 1, pc.depth, 1.0);
+}
@@ -0,0 +1,11 @@
+#version 450
+
+#include "common.glsl"
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+	out_color = texture(tex, in_uv);
+}
@@ -0,0 +1,18 @@
+#version 450
+
+#include "common.glsl"
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+layout(binding = 1) uniform sampler2D bloom;
+
+layout(binding = 2) uniform Params {
+	float intensity;
+};
+
+void main() {
+	vec4 color = texture(tex, in_uv);
+	out_color = vec4(color.rgb + texture(bloom, in_uv).rgb * intensity, color.a);
+}
@@ -0,0 +1,19 @@
+#version 450
+
+#include "common.glsl"
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+layout(binding = 1) uniform Params {
+	float threshold;
+};
+
+void main() {
+	vec3 color = texture(tex, in_uv).rgb;
+	float brightness = max(color.r, max(color.g, color.b));
+	// scaling instead of subtracting keeps the hue of what passes
+	float contribution = max(brightness - threshold, 0.0) / max(brightness, 0.0001);
+	out_color = vec4(color * contribution, 1.0);
+}
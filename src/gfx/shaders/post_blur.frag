@@ -0,0 +1,26 @@
+#version 450
+#pragma permutation VERTICAL
+
+#include "common.glsl"
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+// 9-tap gaussian folded into 5 bilinear fetches
+const float offsets[3] = float[](0.0, 1.3846153846, 3.2307692308);
+const float weights[3] = float[](0.2270270270, 0.3162162162, 0.0702702703);
+
+void main() {
+#ifdef VERTICAL
+	vec2 texel_step = vec2(0.0, 1.0) / textureSize(tex, 0);
+#else
+	vec2 texel_step = vec2(1.0, 0.0) / textureSize(tex, 0);
+#endif
+	vec4 sum = texture(tex, in_uv) * weights[0];
+	for (int i = 1; i < 3; i++) {
+		sum += texture(tex, in_uv + texel_step * offsets[i]) * weights[i];
+		sum += texture(tex, in_uv - texel_step * offsets[i]) * weights[i];
+	}
+	out_color = sum;
+}
@@ -0,0 +1,31 @@
+#version 450
+
+#include "common.glsl"
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+// a strip of `size` slices, each `size` texels square, with blue increasing from slice to slice
+layout(binding = 1) uniform sampler2D lut;
+
+layout(binding = 2) uniform Params {
+	float amount;
+};
+
+void main() {
+	vec4 color = texture(tex, in_uv);
+	vec3 rgb = clamp(color.rgb, 0.0, 1.0);
+	float size = textureSize(lut, 0).y;
+
+	float slice = rgb.b * (size - 1.0);
+	float slice0 = floor(slice);
+	float slice1 = min(slice0 + 1.0, size - 1.0);
+	// sample texel centers so neighbouring slices never bleed in
+	vec2 texel = (rgb.rg * (size - 1.0) + 0.5) / vec2(size * size, size);
+	vec3 graded0 = texture(lut, texel + vec2(slice0 / size, 0.0)).rgb;
+	vec3 graded1 = texture(lut, texel + vec2(slice1 / size, 0.0)).rgb;
+	vec3 graded = mix(graded0, graded1, slice - slice0);
+
+	out_color = vec4(mix(color.rgb, graded, amount), color.a);
+}
@@ -0,0 +1,29 @@
+#version 450
+
+#include "common.glsl"
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+layout(binding = 1) uniform Params {
+	float curvature;
+	float scanlines;
+	// scanlines over the height of the screen
+	float line_count;
+};
+
+void main() {
+	// barrel distortion around the center
+	vec2 centered = in_uv * 2.0 - 1.0;
+	centered *= 1.0 + curvature * dot(centered, centered);
+	vec2 uv = centered * 0.5 + 0.5;
+	if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+		out_color = vec4(0.0, 0.0, 0.0, 1.0);
+		return;
+	}
+
+	vec4 color = texture(tex, uv);
+	float line = sin(uv.y * line_count * 3.14159265) * 0.5 + 0.5;
+	out_color = vec4(color.rgb * mix(1.0, line, scanlines), color.a);
+}
@@ -0,0 +1,33 @@
+#version 450
+#pragma permutation SHARP
+
+#include "common.glsl"
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+// filtered here rather than by the sampler, so the result doesn't depend on its settings
+vec4 fetch(vec2 texel, vec2 size) {
+	return texelFetch(tex, ivec2(clamp(texel, vec2(0.0), size - 1.0)), 0);
+}
+
+void main() {
+	vec2 size = textureSize(tex, 0);
+	vec2 texel = in_uv * size;
+#ifdef SHARP
+	// nearest within each texel, blended across its edges over about one window pixel
+	vec2 scale = 1.0 / fwidth(texel);
+	vec2 from_center = fract(texel) - 0.5;
+	vec2 region = max(0.5 - 0.5 / scale, 0.0);
+	vec2 sharp = floor(texel) + 0.5 + (from_center - clamp(from_center, -region, region)) * scale;
+
+	vec2 corner = floor(sharp - 0.5);
+	vec2 weight = sharp - 0.5 - corner;
+	vec4 top = mix(fetch(corner, size), fetch(corner + vec2(1.0, 0.0), size), weight.x);
+	vec4 bottom = mix(fetch(corner + vec2(0.0, 1.0), size), fetch(corner + 1.0, size), weight.x);
+	out_color = mix(top, bottom, weight.y);
+#else
+	out_color = fetch(floor(texel), size);
+#endif
+}
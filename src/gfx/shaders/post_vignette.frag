@@ -0,0 +1,24 @@
+#version 450
+
+#include "common.glsl"
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+layout(binding = 1) uniform Params {
+	vec4 color;
+	float strength;
+	float radius;
+	float softness;
+};
+
+void main() {
+	vec4 scene = texture(tex, in_uv);
+	// distance from the center, corrected so the falloff is round on wide windows
+	vec2 size = textureSize(tex, 0);
+	vec2 offset = (in_uv - 0.5) * vec2(size.x / size.y, 1.0);
+	float dist = length(offset) * 2.0;
+	float amount = smoothstep(radius, radius + softness, dist) * strength;
+	out_color = vec4(mix(scene.rgb, color.rgb, amount), scene.a);
+}
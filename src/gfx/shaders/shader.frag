@@ -1,11 +1,36 @@
 #version 450
+#pragma permutation TINT ALPHA_TEST BINDLESS
+#ifdef BINDLESS
+#extension GL_EXT_nonuniform_qualifier : require
+#endif
 
-layout(location = 0) in vec2 vpos;
+#include "textures.glsl"
+
+layout(location = 0) in vec2 in_uv;
+layout(location = 1) in vec4 in_tint;
+layout(location = 2) flat in uint in_texture;
 
 layout(location = 0) out vec4 out_color;
 
-layout(binding = 0) uniform sampler2D tex;
+#ifdef TINT
+#ifdef BINDLESS
+// the texture array has set 0 to itself
+layout(set = 1, binding = 0) uniform Material {
+#else
+layout(binding = 1) uniform Material {
+#endif
+	vec4 tint;
+};
+#endif
 
 void main() {
-	out_color = texture(tex, vpos);
+	out_color = texture(TEX(in_texture), in_uv) * in_tint;
+#ifdef TINT
+	out_color *= tint;
+#endif
+#ifdef ALPHA_TEST
+	if (out_color.a < 0.5) {
+		discard;
+	}
+#endif
 }
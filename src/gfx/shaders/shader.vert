@@ -1,18 +1,39 @@
 #version 450
+#pragma permutation BINDLESS
+#ifdef BINDLESS
+#extension GL_EXT_nonuniform_qualifier : require
+#endif
 
+// the unit quad
 layout(location = 0) in vec2 in_pos;
+// per instance
+layout(location = 1) in vec2 in_corner;
+layout(location = 2) in vec2 in_size;
+layout(location = 3) in vec4 in_uv_rect;
+layout(location = 4) in vec4 in_tint;
+layout(location = 5) in uint in_texture;
+layout(location = 6) in float in_depth;
+layout(location = 7) in float in_rotation;
 
-layout(location = 0) out vec2 out_pos;
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec4 out_tint;
+layout(location = 2) flat out uint out_texture;
 
-layout(binding = 0) uniform usampler2D tex;
+#include "textures.glsl"
 
-// TODO: use ints
 layout(push_constant) uniform PushConsts {
-	vec2 pos;
 	vec2 win_size;
+	vec2 view_pos;
+	float zoom;
 } pc;
 
 void main() {
-	out_pos = in_pos;
-	gl_Position = vec4((in_pos * textureSize(tex, 0) + pc.pos) / pc.win_size * 2 - 1, 0.0, 1.0);
+	out_uv = (in_uv_rect.xy + in_pos * in_uv_rect.zw) / textureSize(TEX(in_texture), 0);
+	out_tint = in_tint;
+	out_texture = in_texture;
+	// turned about the corner, x towards y
+	vec2 axis = vec2(cos(in_rotation), sin(in_rotation));
+	vec2 world = in_corner + mat2(axis, vec2(-axis.y, axis.x)) * (in_pos * in_size);
+	vec2 screen = (world - pc.view_pos) * pc.zoom;
+	gl_Position = vec4(screen / pc.win_size * 2 - 1, in_depth, 1.0);
 }
@@ -0,0 +1,19 @@
+#version 450
+
+layout(location = 0) in vec2 in_pos;
+layout(location = 1) in vec4 in_color;
+
+layout(location = 0) out vec4 out_color;
+
+layout(push_constant) uniform PushConsts {
+	vec2 win_size;
+	vec2 view_pos;
+	float zoom;
+	float depth;
+} pc;
+
+void main() {
+	out_color = in_color;
+	vec2 screen = (in_pos - pc.view_pos) * pc.zoom;
+	gl_Position = vec4(screen / pc.win_size * 2 - 1, pc.depth, 1.0);
+}
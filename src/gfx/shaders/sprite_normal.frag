@@ -0,0 +1,28 @@
+#version 450
+#pragma permutation BINDLESS
+#ifdef BINDLESS
+#extension GL_EXT_nonuniform_qualifier : require
+#endif
+
+#include "sprite_normal.glsl"
+
+layout(location = 0) in vec2 in_uv;
+layout(location = 1) in vec2 in_normal_uv;
+layout(location = 2) flat in uint in_texture;
+layout(location = 3) flat in uint in_normal_map;
+layout(location = 4) flat in vec2 in_axis;
+layout(location = 5) flat in vec2 in_mirror;
+
+layout(location = 0) out vec4 out_normal;
+
+void main() {
+	// only where the sprite itself shows, so lighting follows its silhouette
+	if (texture(TEX(in_texture), in_uv).a < 0.5) {
+		discard;
+	}
+	// mirror and turn the normal with the sprite, flipping y around it since normal maps point green up
+	vec3 normal = texture(NORMAL_MAP(in_normal_map), in_normal_uv).xyz * 2.0 - 1.0;
+	vec2 xy = mat2(in_axis, vec2(-in_axis.y, in_axis.x)) * (vec2(normal.x, -normal.y) * in_mirror);
+	normal.xy = vec2(xy.x, -xy.y);
+	out_normal = vec4(normal * 0.5 + 0.5, 1.0);
+}
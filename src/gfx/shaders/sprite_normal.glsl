@@ -0,0 +1,9 @@
+// The sprite's texture is `TEX(slot)` from textures.glsl, and its normal map `NORMAL_MAP(slot)`. Without BINDLESS
+// both ignore the slot and read the material's `tex` and `normal_map`.
+#include "textures.glsl"
+#ifdef BINDLESS
+#define NORMAL_MAP(normal_slot) TEX(normal_slot)
+#else
+layout(binding = 1) uniform sampler2D normal_map;
+#define NORMAL_MAP(normal_slot) normal_map
+#endif
@@ -0,0 +1,44 @@
+#version 450
+#pragma permutation BINDLESS
+#ifdef BINDLESS
+#extension GL_EXT_nonuniform_qualifier : require
+#endif
+
+// the unit quad
+layout(location = 0) in vec2 in_pos;
+// per instance
+layout(location = 1) in vec2 in_corner;
+layout(location = 2) in vec2 in_size;
+layout(location = 3) in vec4 in_uv_rect;
+layout(location = 4) in vec4 in_normal_rect;
+layout(location = 5) in uint in_texture;
+layout(location = 6) in uint in_normal_map;
+layout(location = 7) in float in_rotation;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec2 out_normal_uv;
+layout(location = 2) flat out uint out_texture;
+layout(location = 3) flat out uint out_normal_map;
+layout(location = 4) flat out vec2 out_axis;
+layout(location = 5) flat out vec2 out_mirror;
+
+#include "sprite_normal.glsl"
+
+layout(push_constant) uniform PushConsts {
+	vec2 win_size;
+	vec2 view_pos;
+	float zoom;
+} pc;
+
+void main() {
+	out_uv = (in_uv_rect.xy + in_pos * in_uv_rect.zw) / textureSize(TEX(in_texture), 0);
+	out_normal_uv = (in_normal_rect.xy + in_pos * in_normal_rect.zw) / textureSize(NORMAL_MAP(in_normal_map), 0);
+	out_texture = in_texture;
+	out_normal_map = in_normal_map;
+	out_axis = vec2(cos(in_rotation), sin(in_rotation));
+	out_mirror = sign(in_size);
+	// turned about the corner, x towards y
+	vec2 world = in_corner + mat2(out_axis, vec2(-out_axis.y, out_axis.x)) * (in_pos * in_size);
+	vec2 screen = (world - pc.view_pos) * pc.zoom;
+	gl_Position = vec4(screen / pc.win_size * 2 - 1, 0.0, 1.0);
+}
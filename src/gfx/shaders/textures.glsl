@@ -0,0 +1,9 @@
+// Without BINDLESS the texture is `tex`, otherwise it's the texture array slot in `texture_slot`. Shaders including
+// this with BINDLESS must enable GL_EXT_nonuniform_qualifier first.
+#ifdef BINDLESS
+layout(binding = 0) uniform sampler2D textures[];
+#define TEX(texture_slot) textures[nonuniformEXT(texture_slot)]
+#else
+#include "common.glsl"
+#define TEX(texture_slot) tex
+#endif
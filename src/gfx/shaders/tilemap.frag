@@ -0,0 +1,30 @@
+#version 450
+#pragma permutation ALPHA_TEST BINDLESS
+#ifdef BINDLESS
+#extension GL_EXT_nonuniform_qualifier : require
+#endif
+
+#include "textures.glsl"
+
+layout(location = 0) in vec2 in_uv;
+layout(location = 1) flat in uint in_texture;
+
+layout(location = 0) out vec4 out_color;
+
+layout(push_constant) uniform PushConsts {
+	vec4 tint;
+	vec2 offset;
+	vec2 win_size;
+	vec2 view_pos;
+	float zoom;
+	float depth;
+} pc;
+
+void main() {
+	out_color = texture(TEX(in_texture), in_uv) * pc.tint;
+#ifdef ALPHA_TEST
+	if (out_color.a < 0.5) {
+		discard;
+	}
+#endif
+}
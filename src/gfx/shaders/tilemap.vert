@@ -0,0 +1,31 @@
+#version 450
+#pragma permutation BINDLESS
+#ifdef BINDLESS
+#extension GL_EXT_nonuniform_qualifier : require
+#endif
+
+layout(location = 0) in vec2 in_pos;
+layout(location = 1) in vec2 in_uv;
+layout(location = 2) in uint in_texture;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) flat out uint out_texture;
+
+#include "textures.glsl"
+
+layout(push_constant) uniform PushConsts {
+	vec4 tint;
+	vec2 offset;
+	vec2 win_size;
+	vec2 view_pos;
+	float zoom;
+	float depth;
+} pc;
+
+void main() {
+	// texel coordinates keep vertices independent of the texture's size
+	out_uv = in_uv / textureSize(TEX(in_texture), 0);
+	out_texture = in_texture;
+	vec2 screen = (in_pos + pc.offset - pc.view_pos) * pc.zoom;
+	gl_Position = vec4(screen / pc.win_size * 2 - 1, pc.depth, 1.0);
+}
@@ -0,0 +1,540 @@
+use crate::gfx::{
+	pipeline::{BlendMode, PipelineDesc},
+	shader::ShaderProgram,
+	sprite::SpriteList,
+	validation,
+	vertex::vertex_input,
+	Gfx,
+};
+use futures::executor::block_on;
+use nalgebra::Vector2;
+use std::{f32::consts::PI, sync::Arc};
+use typenum::B1;
+use vulkan::{buffer::Buffer, device::BufferUsageFlags, pipeline::GraphicsPipeline, render_pass::RenderPass};
+
+/// How far flattened curves may stray from the real ones, in world pixels.
+const TOLERANCE: f32 = 0.25;
+// keeps huge or degenerate curves from producing unbounded vertices
+const MAX_SEGMENTS: usize = 256;
+
+vertex_input! {
+	#[derive(Clone, Copy, Debug)]
+	pub struct ShapeVertex {
+		/// World pixels.
+		pub pos: Vector2<f32>,
+		pub color: [f32; 4],
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct ShapePushConsts {
+	pub win_size: [f32; 2],
+	pub view_pos: [f32; 2],
+	pub zoom: f32,
+	pub depth: f32,
+}
+
+/// Outlines made of straight lines, with curves flattened as they're added. Fill or stroke them with
+/// `SpriteList::fill` and `SpriteList::stroke`.
+#[derive(Clone, Debug, Default)]
+pub struct Path {
+	subpaths: Vec<Subpath>,
+}
+#[derive(Clone, Debug, Default)]
+struct Subpath {
+	points: Vec<Vector2<f32>>,
+	closed: bool,
+}
+impl Path {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn line(from: Vector2<f32>, to: Vector2<f32>) -> Self {
+		Self::polyline(&[from, to])
+	}
+
+	/// Connected lines, open at both ends.
+	pub fn polyline(points: &[Vector2<f32>]) -> Self {
+		Self { subpaths: vec![Subpath { points: points.to_vec(), closed: false }] }
+	}
+
+	/// Any simple polygon, convex or not.
+	pub fn polygon(points: &[Vector2<f32>]) -> Self {
+		Self { subpaths: vec![Subpath { points: points.to_vec(), closed: true }] }
+	}
+
+	/// `pos` is the top-left corner.
+	pub fn rect(pos: Vector2<f32>, size: Vector2<f32>) -> Self {
+		let (x, y) = (Vector2::new(size.x, 0.0), Vector2::new(0.0, size.y));
+		Self::polygon(&[pos, pos + x, pos + size, pos + y])
+	}
+
+	/// `pos` is the top-left corner. `radius` is clamped to half the shorter side.
+	pub fn rounded_rect(pos: Vector2<f32>, size: Vector2<f32>, radius: f32) -> Self {
+		let radius = radius.min(size.x / 2.0).min(size.y / 2.0).max(0.0);
+		let (min, max) = (pos.add_scalar(radius), (pos + size).add_scalar(-radius));
+		let corners = [(max.x, max.y, 0.0), (min.x, max.y, 0.5), (min.x, min.y, 1.0), (max.x, min.y, 1.5)];
+		let mut points = vec![];
+		for &(x, y, start) in &corners {
+			arc_points(&mut points, Vector2::new(x, y), radius, start * PI, (start + 0.5) * PI);
+		}
+		Self::polygon(&points)
+	}
+
+	pub fn circle(center: Vector2<f32>, radius: f32) -> Self {
+		Self::ellipse(center, Vector2::new(radius, radius))
+	}
+
+	pub fn ellipse(center: Vector2<f32>, radii: Vector2<f32>) -> Self {
+		let segments = arc_segments(radii.x.max(radii.y), 2.0 * PI);
+		let points: Vec<_> = (0..segments)
+			.map(|i| {
+				let angle = i as f32 / segments as f32 * 2.0 * PI;
+				center + Vector2::new(angle.cos() * radii.x, angle.sin() * radii.y)
+			})
+			.collect();
+		Self::polygon(&points)
+	}
+
+	/// Starts a new subpath at `pos`.
+	pub fn move_to(mut self, pos: Vector2<f32>) -> Self {
+		self.subpaths.push(Subpath { points: vec![pos], closed: false });
+		self
+	}
+
+	pub fn line_to(mut self, pos: Vector2<f32>) -> Self {
+		self.current().points.push(pos);
+		self
+	}
+
+	/// A quadratic bezier curve from the last point to `to`.
+	pub fn quad_to(mut self, ctrl: Vector2<f32>, to: Vector2<f32>) -> Self {
+		let from = self.last_point();
+		let segments = curve_segments((from - ctrl * 2.0 + to).norm() / 4.0);
+		let points = &mut self.current().points;
+		for i in 1..=segments {
+			let t = i as f32 / segments as f32;
+			let mt = 1.0 - t;
+			points.push(from * (mt * mt) + ctrl * (2.0 * mt * t) + to * (t * t));
+		}
+		self
+	}
+
+	/// A cubic bezier curve from the last point to `to`.
+	pub fn cubic_to(mut self, ctrl1: Vector2<f32>, ctrl2: Vector2<f32>, to: Vector2<f32>) -> Self {
+		let from = self.last_point();
+		let bend = (from - ctrl1 * 2.0 + ctrl2).norm().max((ctrl1 - ctrl2 * 2.0 + to).norm());
+		let segments = curve_segments(bend * 3.0 / 4.0);
+		let points = &mut self.current().points;
+		for i in 1..=segments {
+			let t = i as f32 / segments as f32;
+			let mt = 1.0 - t;
+			points.push(
+				from * (mt * mt * mt) + ctrl1 * (3.0 * mt * mt * t) + ctrl2 * (3.0 * mt * t * t) + to * (t * t * t),
+			);
+		}
+		self
+	}
+
+	/// Connects the last point back to the start of the subpath, so strokes join there instead of ending in caps.
+	pub fn close(mut self) -> Self {
+		self.current().closed = true;
+		self
+	}
+
+	/// Appends triangles covering the inside of every subpath, open or not, to `out`.
+	pub fn fill(&self, color: [f32; 4], out: &mut Vec<ShapeVertex>) {
+		for subpath in &self.subpaths {
+			let points = dedup(&subpath.points, true);
+			fill_polygon(&points, color, out);
+		}
+	}
+
+	/// Appends triangles covering the outline of every subpath to `out`.
+	pub fn stroke(&self, stroke: &Stroke, color: [f32; 4], out: &mut Vec<ShapeVertex>) {
+		for subpath in &self.subpaths {
+			let points = dedup(&subpath.points, subpath.closed);
+			stroke_polyline(&points, subpath.closed, stroke, color, out);
+		}
+	}
+
+	fn current(&mut self) -> &mut Subpath {
+		if self.subpaths.is_empty() {
+			self.subpaths.push(Subpath::default());
+		}
+		self.subpaths.last_mut().unwrap()
+	}
+
+	fn last_point(&self) -> Vector2<f32> {
+		self.subpaths.last().and_then(|subpath| subpath.points.last()).copied().unwrap_or_else(Vector2::zeros)
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LineJoin {
+	Miter,
+	Bevel,
+	Round,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LineCap {
+	/// Ends exactly at the end point.
+	Butt,
+	/// Extends past the end point by half the width.
+	Square,
+	Round,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stroke {
+	/// World pixels.
+	pub width: f32,
+	pub join: LineJoin,
+	pub cap: LineCap,
+	/// Miters longer than this many widths are beveled instead, like SVG's `stroke-miterlimit`.
+	pub miter_limit: f32,
+}
+impl Stroke {
+	pub fn new(width: f32) -> Self {
+		Self { width, join: LineJoin::Miter, cap: LineCap::Butt, miter_limit: 4.0 }
+	}
+
+	pub fn join(mut self, join: LineJoin) -> Self {
+		self.join = join;
+		self
+	}
+
+	pub fn cap(mut self, cap: LineCap) -> Self {
+		self.cap = cap;
+		self
+	}
+
+	pub fn miter_limit(mut self, miter_limit: f32) -> Self {
+		self.miter_limit = miter_limit;
+		self
+	}
+}
+
+/// Uploads the shapes of sprite lists, which are drawn between their sprites.
+pub struct ShapeRenderer {
+	program: Arc<ShaderProgram>,
+	opaque_pipeline: Arc<GraphicsPipeline>,
+	transparent_pipeline: Arc<GraphicsPipeline>,
+}
+impl ShapeRenderer {
+	pub fn new(gfx: &Gfx, render_pass: Arc<RenderPass>) -> Self {
+		let program = block_on(gfx.program("shape", "")).unwrap();
+		let desc = |blend| PipelineDesc {
+			layout: program.layout.clone(),
+			render_pass: render_pass.clone(),
+			subpass: 0,
+			vshader: program.vshader.clone(),
+			fshader: program.fshader.clone(),
+			blend,
+		};
+		let opaque_pipeline = gfx.pipelines.get::<ShapeVertex>(&gfx.device, desc(BlendMode::Opaque));
+		let transparent_pipeline = gfx.pipelines.get::<ShapeVertex>(&gfx.device, desc(BlendMode::Alpha));
+		Self { program, opaque_pipeline, transparent_pipeline }
+	}
+
+	pub fn program(&self) -> &Arc<ShaderProgram> {
+		&self.program
+	}
+
+	pub fn pipeline(&self, transparent: bool) -> &Arc<GraphicsPipeline> {
+		if transparent {
+			&self.transparent_pipeline
+		} else {
+			&self.opaque_pipeline
+		}
+	}
+
+	/// Returns `None` if `sprites` has no shapes.
+	pub fn prepare(&self, gfx: &Gfx, sprites: &SpriteList) -> Option<Arc<Buffer<[ShapeVertex]>>> {
+		let verts = sprites.shape_verts();
+		if verts.is_empty() {
+			return None;
+		}
+		let buffer = Buffer::init_slice(gfx.device.clone(), verts.len() as _, B1, BufferUsageFlags::VERTEX_BUFFER)
+			.copy_from_slice(verts);
+		validation::set_name(&gfx.device, &*buffer, "shape verts");
+		Some(buffer)
+	}
+}
+
+// enough segments for a curve whose chords stray `bend / segments²` from it
+fn curve_segments(bend: f32) -> usize {
+	((bend / TOLERANCE).sqrt().ceil() as usize).clamp(1, MAX_SEGMENTS)
+}
+
+fn arc_segments(radius: f32, angle: f32) -> usize {
+	if radius <= TOLERANCE {
+		return 4;
+	}
+	// each segment's chord strays `radius * (1 - cos(step / 2))` from the arc
+	let step = 2.0 * (1.0 - TOLERANCE / radius).acos();
+	((angle.abs() / step).ceil() as usize).clamp(1, MAX_SEGMENTS)
+}
+
+// points from `start` to `end` radians, inclusive
+fn arc_points(out: &mut Vec<Vector2<f32>>, center: Vector2<f32>, radius: f32, start: f32, end: f32) {
+	let segments = arc_segments(radius, end - start);
+	out.extend((0..=segments).map(|i| {
+		let angle = start + (end - start) * i as f32 / segments as f32;
+		center + Vector2::new(angle.cos(), angle.sin()) * radius
+	}));
+}
+
+// drops repeated points, which have no direction to join or cap along
+fn dedup(points: &[Vector2<f32>], closed: bool) -> Vec<Vector2<f32>> {
+	let mut ret: Vec<Vector2<f32>> = vec![];
+	for &point in points {
+		if ret.last().is_none_or(|&last| (point - last).norm_squared() > 1e-8) {
+			ret.push(point);
+		}
+	}
+	if closed && ret.len() > 1 && (ret[0] - ret[ret.len() - 1]).norm_squared() <= 1e-8 {
+		ret.pop();
+	}
+	ret
+}
+
+fn cross(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+	a.x * b.y - a.y * b.x
+}
+
+// `v` turned a quarter turn
+fn perp(v: Vector2<f32>) -> Vector2<f32> {
+	Vector2::new(-v.y, v.x)
+}
+
+fn tri(out: &mut Vec<ShapeVertex>, points: [Vector2<f32>; 3], color: [f32; 4]) {
+	out.extend(points.iter().map(|&pos| ShapeVertex { pos, color }));
+}
+
+fn fill_polygon(points: &[Vector2<f32>], color: [f32; 4], out: &mut Vec<ShapeVertex>) {
+	if points.len() < 3 {
+		return;
+	}
+	let n = points.len();
+	let turns = (0..n).map(|i| cross(points[(i + 1) % n] - points[i], points[(i + 2) % n] - points[(i + 1) % n]));
+	let (left, right) = turns.fold((false, false), |(left, right), turn| (left || turn > 0.0, right || turn < 0.0));
+	if !(left && right) {
+		// convex, so a fan covers it
+		for i in 1..n - 1 {
+			tri(out, [points[0], points[i], points[i + 1]], color);
+		}
+		return;
+	}
+
+	// ear clipping, with the polygon wound so ears turn left
+	let area: f32 = (0..n).map(|i| cross(points[i], points[(i + 1) % n])).sum();
+	let mut idx: Vec<_> = if area > 0.0 { (0..n).collect() } else { (0..n).rev().collect() };
+	let mut i = 0;
+	let mut misses = 0;
+	while idx.len() > 3 {
+		let len = idx.len();
+		let (prev, cur, next) = ((i + len - 1) % len, i % len, (i + 1) % len);
+		let (a, b, c) = (points[idx[prev]], points[idx[cur]], points[idx[next]]);
+		let convex = cross(b - a, c - b) > 0.0;
+		let ear = convex
+			&& idx
+				.iter()
+				.enumerate()
+				.all(|(j, &k)| j == prev || j == cur || j == next || !in_triangle(points[k], a, b, c));
+		// self-intersecting polygons can run out of ears, so clip whatever's left rather than loop forever
+		if ear || misses > len {
+			tri(out, [a, b, c], color);
+			idx.remove(cur);
+			i = cur;
+			misses = 0;
+		} else {
+			i = cur + 1;
+			misses += 1;
+		}
+	}
+	tri(out, [points[idx[0]], points[idx[1]], points[idx[2]]], color);
+}
+
+fn in_triangle(p: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>) -> bool {
+	cross(b - a, p - a) >= 0.0 && cross(c - b, p - b) >= 0.0 && cross(a - c, p - c) >= 0.0
+}
+
+fn stroke_polyline(
+	points: &[Vector2<f32>],
+	closed: bool,
+	stroke: &Stroke,
+	color: [f32; 4],
+	out: &mut Vec<ShapeVertex>,
+) {
+	if points.len() < 2 {
+		return;
+	}
+	let half = stroke.width / 2.0;
+	let n = points.len();
+	let segments = if closed { n } else { n - 1 };
+	let dir = |i: usize| (points[(i + 1) % n] - points[i]).normalize();
+
+	for i in 0..segments {
+		let (a, b) = (points[i], points[(i + 1) % n]);
+		let normal = perp(dir(i)) * half;
+		tri(out, [a + normal, b + normal, b - normal], color);
+		tri(out, [b - normal, a - normal, a + normal], color);
+	}
+
+	let joins = if closed { 0..n } else { 1..n - 1 };
+	for i in joins {
+		let (d0, d1) = (dir((i + n - 1) % n), dir(i));
+		join(points[i], d0, d1, half, stroke, color, out);
+	}
+
+	if !closed {
+		cap(points[0], -dir(0), half, stroke.cap, color, out);
+		cap(points[n - 1], dir(n - 2), half, stroke.cap, color, out);
+	}
+}
+
+// fills the gap on the outside of the turn at `p`, from the segment going `d0` to the one going `d1`
+fn join(
+	p: Vector2<f32>,
+	d0: Vector2<f32>,
+	d1: Vector2<f32>,
+	half: f32,
+	stroke: &Stroke,
+	color: [f32; 4],
+	out: &mut Vec<ShapeVertex>,
+) {
+	let turn = cross(d0, d1);
+	if turn.abs() < 1e-6 && d0.dot(&d1) > 0.0 {
+		return;
+	}
+	let side = if turn > 0.0 { -1.0 } else { 1.0 };
+	let (n0, n1) = (perp(d0) * side, perp(d1) * side);
+	let (o0, o1) = (p + n0 * half, p + n1 * half);
+	match stroke.join {
+		LineJoin::Round => {
+			let start = n0.y.atan2(n0.x);
+			// the short way around, which is the outside
+			let mut sweep = n1.y.atan2(n1.x) - start;
+			if sweep > PI {
+				sweep -= 2.0 * PI;
+			} else if sweep < -PI {
+				sweep += 2.0 * PI;
+			}
+			fan(p, half, start, sweep, color, out);
+		},
+		LineJoin::Miter => {
+			let sum = n0 + n1;
+			// the miter's length over half the width
+			let ratio = 2.0 / sum.norm();
+			if ratio.is_finite() && ratio <= stroke.miter_limit {
+				let tip = p + sum * (2.0 * half / sum.norm_squared());
+				tri(out, [p, o0, tip], color);
+				tri(out, [p, tip, o1], color);
+			} else {
+				tri(out, [p, o0, o1], color);
+			}
+		},
+		LineJoin::Bevel => tri(out, [p, o0, o1], color),
+	}
+}
+
+// the end of a line at `p`, pointing away from it along `dir`
+fn cap(p: Vector2<f32>, dir: Vector2<f32>, half: f32, cap: LineCap, color: [f32; 4], out: &mut Vec<ShapeVertex>) {
+	let normal = perp(dir) * half;
+	match cap {
+		LineCap::Butt => (),
+		LineCap::Square => {
+			let ahead = dir * half;
+			tri(out, [p + normal, p + normal + ahead, p - normal + ahead], color);
+			tri(out, [p - normal + ahead, p - normal, p + normal], color);
+		},
+		LineCap::Round => fan(p, half, normal.y.atan2(normal.x), -PI, color, out),
+	}
+}
+
+// a pie slice of `sweep` radians, from `start`
+fn fan(center: Vector2<f32>, radius: f32, start: f32, sweep: f32, color: [f32; 4], out: &mut Vec<ShapeVertex>) {
+	let mut points = vec![];
+	arc_points(&mut points, center, radius, start, start + sweep);
+	for pair in points.windows(2) {
+		tri(out, [center, pair[0], pair[1]], color);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn area(verts: &[ShapeVertex]) -> f32 {
+		verts.chunks(3).map(|tri| cross(tri[1].pos - tri[0].pos, tri[2].pos - tri[0].pos).abs() / 2.0).sum()
+	}
+
+	fn stroke(path: Path, stroke: Stroke) -> f32 {
+		let mut verts = vec![];
+		path.stroke(&stroke, [1.0; 4], &mut verts);
+		area(&verts)
+	}
+
+	#[test]
+	fn fill_concave() {
+		// an L, wound both ways
+		let l = [(0.0, 0.0), (4.0, 0.0), (4.0, 1.0), (1.0, 1.0), (1.0, 3.0), (0.0, 3.0)];
+		let mut points: Vec<_> = l.iter().map(|&(x, y)| Vector2::new(x, y)).collect();
+		for _ in 0..2 {
+			let mut verts = vec![];
+			Path::polygon(&points).fill([1.0; 4], &mut verts);
+			assert_eq!(verts.len(), (points.len() - 2) * 3);
+			// every triangle turns the same way, so none fold over the notch
+			let turns: Vec<_> =
+				verts.chunks(3).map(|tri| cross(tri[1].pos - tri[0].pos, tri[2].pos - tri[0].pos)).collect();
+			assert!(turns.iter().all(|&turn| turn * turns[0] > 0.0), "{:?}", turns);
+			assert!((area(&verts) - 6.0).abs() < 1e-4);
+			points.reverse();
+		}
+	}
+
+	#[test]
+	fn fill_degenerate() {
+		let mut verts = vec![];
+		let p = Vector2::new(1.0, 1.0);
+		Path::polygon(&[p, p, p * 2.0, p * 2.0]).fill([1.0; 4], &mut verts);
+		assert!(verts.is_empty());
+		Path::rect(p, p * 2.0).fill([1.0; 4], &mut verts);
+		assert_eq!(verts.len(), 6);
+		assert!((area(&verts) - 4.0).abs() < 1e-4);
+	}
+
+	#[test]
+	fn caps() {
+		let line = || Path::line(Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0));
+		assert!((stroke(line(), Stroke::new(2.0)) - 20.0).abs() < 1e-4);
+		// half the width past each end
+		assert!((stroke(line(), Stroke::new(2.0).cap(LineCap::Square)) - 24.0).abs() < 1e-4);
+		// a circle the width across split between the ends, short by no more than the tolerance around its edge
+		let round = stroke(line(), Stroke::new(2.0).cap(LineCap::Round));
+		assert!(round < 20.0 + PI && round > 20.0 + PI - 2.0 * PI * TOLERANCE, "{}", round);
+	}
+
+	#[test]
+	fn joins() {
+		let corner = || Path::polyline(&[Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0), Vector2::new(10.0, 10.0)]);
+		// both segments, plus the outside corner of the turn
+		let segments = 40.0;
+		assert!((stroke(corner(), Stroke::new(2.0)) - (segments + 1.0)).abs() < 1e-4);
+		assert!((stroke(corner(), Stroke::new(2.0).join(LineJoin::Bevel)) - (segments + 0.5)).abs() < 1e-4);
+		let round = stroke(corner(), Stroke::new(2.0).join(LineJoin::Round));
+		assert!(round < segments + PI / 4.0 && round > segments + 0.5, "{}", round);
+		// a right angle's miter is √2 widths long, so a lower limit bevels it
+		let limited = Stroke::new(2.0).miter_limit(1.4);
+		assert!((stroke(corner(), limited) - (segments + 0.5)).abs() < 1e-4);
+
+		// closing adds a segment and joins instead of caps
+		let square = Path::rect(Vector2::zeros(), Vector2::new(10.0, 10.0));
+		let square_area = stroke(square, Stroke::new(2.0).cap(LineCap::Square));
+		assert!((square_area - (80.0 + 4.0)).abs() < 1e-4, "{}", square_area);
+	}
+}
@@ -0,0 +1,461 @@
+use crate::gfx::{
+	bindless::{TextureKey, Textures},
+	camera::Camera,
+	pipeline::{BlendMode, PipelineDesc},
+	shader::ShaderProgram,
+	shape::{Path, ShapePushConsts, ShapeRenderer, ShapeVertex, Stroke},
+	texture::Subtex,
+	validation,
+	vertex::vertex_input,
+	Gfx, TriangleVertex,
+};
+use futures::executor::block_on;
+use log::warn;
+use nalgebra::Vector2;
+use std::{collections::HashSet, iter::once, sync::Arc};
+use typenum::B1;
+use vulkan::{
+	buffer::Buffer, command::CommandBufferBuilder, descriptor::DescriptorSet, device::BufferUsageFlags,
+	pipeline::GraphicsPipeline, render_pass::RenderPass,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sprite {
+	/// World position of the top-left corner.
+	pub pos: Vector2<f32>,
+	/// Radians about `pos`, turning x towards y.
+	pub rotation: f32,
+	/// Stretches the sprite away from `pos`, before it's turned. Negative components mirror it.
+	pub scale: Vector2<f32>,
+	/// Higher layers are drawn in front of lower ones. Sprites sharing a layer keep their submission order.
+	pub layer: u16,
+	pub transparent: bool,
+	/// Multiplies the texture color. Sprites that aren't fully opaque need `transparent` set.
+	pub tint: [f32; 4],
+	/// The region drawn, one world pixel per texel before `scale`. `None` draws `Gfx::default_texture`. Scene files
+	/// only store regions of textures loaded from a file.
+	pub texture: Option<Subtex>,
+	/// Normals of `texture`, a region the same size, used by the render graph's light buffer. `None` lights the sprite
+	/// as if flat. Saved in scene files like `texture`.
+	pub normal_map: Option<Subtex>,
+}
+impl Sprite {
+	pub fn new(pos: Vector2<f32>, layer: u16) -> Self {
+		Self {
+			pos,
+			rotation: 0.0,
+			scale: Vector2::new(1.0, 1.0),
+			layer,
+			transparent: false,
+			tint: [1.0; 4],
+			texture: None,
+			normal_map: None,
+		}
+	}
+
+	pub fn rotation(mut self, rotation: f32) -> Self {
+		self.rotation = rotation;
+		self
+	}
+
+	pub fn scale(mut self, scale: Vector2<f32>) -> Self {
+		self.scale = scale;
+		self
+	}
+
+	pub fn texture(mut self, texture: Subtex) -> Self {
+		self.texture = Some(texture);
+		self
+	}
+
+	pub fn normal_map(mut self, normal_map: Subtex) -> Self {
+		self.normal_map = Some(normal_map);
+		self
+	}
+
+	pub fn transparent(mut self, transparent: bool) -> Self {
+		self.transparent = transparent;
+		self
+	}
+
+	pub fn tint(mut self, tint: [f32; 4]) -> Self {
+		self.tint = tint;
+		self
+	}
+
+	pub fn depth(&self) -> f32 {
+		layer_depth(self.layer)
+	}
+}
+
+/// Depth buffer value for a draw layer. Anything else drawn in layers, like tilemaps, must use the same mapping.
+pub fn layer_depth(layer: u16) -> f32 {
+	// the depth buffer is cleared to 1.0, so keep every layer strictly in front of it
+	1.0 - (layer as f32 + 1.0) / (u16::MAX as f32 + 2.0)
+}
+
+/// Shape triangles sharing a layer, drawn like one sprite in it.
+#[derive(Clone, Copy, Debug)]
+pub struct ShapeBatch {
+	pub layer: u16,
+	pub transparent: bool,
+	/// Range in `SpriteList::shape_verts`.
+	pub first_vertex: u32,
+	pub vertex_count: u32,
+}
+impl ShapeBatch {
+	pub fn depth(&self) -> f32 {
+		layer_depth(self.layer)
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SpriteDraw<'a> {
+	Sprite(&'a Sprite),
+	Shapes(&'a ShapeBatch),
+}
+impl<'a> SpriteDraw<'a> {
+	pub fn sprite(self) -> Option<&'a Sprite> {
+		match self {
+			SpriteDraw::Sprite(sprite) => Some(sprite),
+			SpriteDraw::Shapes(_) => None,
+		}
+	}
+}
+
+enum Draw {
+	Sprite(Sprite),
+	Shapes(ShapeBatch),
+}
+impl Draw {
+	fn as_draw(&self) -> SpriteDraw<'_> {
+		match self {
+			Draw::Sprite(sprite) => SpriteDraw::Sprite(sprite),
+			Draw::Shapes(batch) => SpriteDraw::Shapes(batch),
+		}
+	}
+}
+
+/// Sprites and shapes to draw in one frame. Shapes are tessellated as they're added.
+#[derive(Default)]
+pub struct SpriteList {
+	draws: Vec<Draw>,
+	shape_verts: Vec<ShapeVertex>,
+}
+impl SpriteList {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn push(&mut self, sprite: Sprite) {
+		self.draws.push(Draw::Sprite(sprite));
+	}
+
+	/// The sprites, in submission order.
+	pub fn sprites(&self) -> impl Iterator<Item = &Sprite> {
+		self.draws.iter().filter_map(|draw| draw.as_draw().sprite())
+	}
+
+	/// Fills the inside of `path`. Shapes are transparent if `color` is.
+	pub fn fill(&mut self, path: &Path, layer: u16, color: [f32; 4]) {
+		self.push_shapes(layer, color[3] < 1.0, |verts| path.fill(color, verts));
+	}
+
+	/// Outlines `path`. Shapes are transparent if `color` is.
+	pub fn stroke(&mut self, path: &Path, stroke: &Stroke, layer: u16, color: [f32; 4]) {
+		self.push_shapes(layer, color[3] < 1.0, |verts| path.stroke(stroke, color, verts));
+	}
+
+	pub fn clear(&mut self) {
+		self.draws.clear();
+		self.shape_verts.clear();
+	}
+
+	/// Sprites plus shape batches.
+	pub fn len(&self) -> usize {
+		self.draws.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.draws.is_empty()
+	}
+
+	/// Every shape's triangles, indexed by `ShapeBatch`es.
+	pub fn shape_verts(&self) -> &[ShapeVertex] {
+		&self.shape_verts
+	}
+
+	/// Returns opaque sprites front-to-back, to make the most of early depth rejection, followed by transparent
+	/// sprites back-to-front, so blending composes correctly.
+	pub fn sorted(&self) -> (Vec<SpriteDraw<'_>>, Vec<SpriteDraw<'_>>) {
+		let mut keyed: Vec<_> = self
+			.draws
+			.iter()
+			.enumerate()
+			.map(|(i, draw)| {
+				let (layer, transparent) = match draw {
+					Draw::Sprite(sprite) => (sprite.layer, sprite.transparent),
+					Draw::Shapes(batch) => (batch.layer, batch.transparent),
+				};
+				(sort_key(layer, transparent, i as u32), transparent, draw.as_draw())
+			})
+			.collect();
+		keyed.sort_unstable_by_key(|&(key, ..)| key);
+
+		let split = keyed.iter().position(|&(_, transparent, _)| transparent).unwrap_or(keyed.len());
+		let transparent = keyed.split_off(split);
+		(keyed.into_iter().map(|(.., d)| d).collect(), transparent.into_iter().map(|(.., d)| d).collect())
+	}
+
+	// shapes added one after another in a layer share a batch
+	fn push_shapes(&mut self, layer: u16, transparent: bool, tessellate: impl FnOnce(&mut Vec<ShapeVertex>)) {
+		let first_vertex = self.shape_verts.len() as u32;
+		tessellate(&mut self.shape_verts);
+		let vertex_count = self.shape_verts.len() as u32 - first_vertex;
+		if vertex_count == 0 {
+			return;
+		}
+		match self.draws.last_mut() {
+			Some(Draw::Shapes(last)) if (last.layer, last.transparent) == (layer, transparent) => {
+				last.vertex_count += vertex_count
+			},
+			_ => self.draws.push(Draw::Shapes(ShapeBatch { layer, transparent, first_vertex, vertex_count })),
+		}
+	}
+}
+
+// bit 63: transparent, bits 32..48: layer (inverted for opaque), bits 0..32: submission order
+fn sort_key(layer: u16, transparent: bool, idx: u32) -> u64 {
+	let layer = if transparent { layer } else { u16::MAX - layer };
+	(transparent as u64) << 63 | (layer as u64) << 32 | idx as u64
+}
+
+vertex_input! {
+	/// One sprite, drawn as an instance of the unit quad.
+	#[derive(Clone, Copy, Debug)]
+	pub struct SpriteInstance {
+		/// World position of the top-left corner.
+		pub pos: Vector2<f32>,
+		/// World pixels, negative to mirror.
+		pub size: Vector2<f32>,
+		/// Position and size of the region drawn, in texels.
+		pub uv_rect: [f32; 4],
+		pub tint: [f32; 4],
+		/// Slot in the texture array, if there is one.
+		pub texture: u32,
+		pub depth: f32,
+		/// Radians about `pos`.
+		pub rotation: f32,
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub(super) struct SpritePushConsts {
+	pub win_size: [f32; 2],
+	pub view_pos: [f32; 2],
+	pub zoom: f32,
+}
+
+/// Sprites next to each other in draw order that share a texture binding, drawn as one instanced draw. With a texture
+/// array that's every sprite between two shape batches.
+#[derive(Clone, Copy, Debug)]
+pub struct SpriteBatch {
+	// key into the renderer's textures
+	texture: Option<TextureKey>,
+	pub first_instance: u32,
+	pub instance_count: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SpriteRun {
+	Sprites(SpriteBatch),
+	Shapes(ShapeBatch),
+}
+
+/// A `SpriteList` ready to draw, with every sprite in one instance buffer.
+pub struct PreparedSprites {
+	/// `None` if there are only shapes.
+	pub instances: Option<Arc<Buffer<[SpriteInstance]>>>,
+	/// `None` if there are no shapes.
+	pub shape_verts: Option<Arc<Buffer<[ShapeVertex]>>>,
+	/// Front to back, drawn first.
+	pub opaque: Vec<SpriteRun>,
+	/// Back to front.
+	pub transparent: Vec<SpriteRun>,
+}
+impl PreparedSprites {
+	pub fn runs(&self, transparent: bool) -> &[SpriteRun] {
+		if transparent {
+			&self.transparent
+		} else {
+			&self.opaque
+		}
+	}
+
+	/// Sprites, not counting shapes.
+	pub fn sprite_count(&self) -> u32 {
+		let runs = self.opaque.iter().chain(&self.transparent);
+		runs.map(|run| if let SpriteRun::Sprites(batch) = run { batch.instance_count } else { 0 }).sum()
+	}
+}
+
+/// Turns the sprites of a `SpriteList` into one instance buffer per frame, batched by texture, and its shapes into one
+/// vertex buffer.
+pub struct SpriteRenderer {
+	program: Arc<ShaderProgram>,
+	opaque_pipeline: Arc<GraphicsPipeline>,
+	transparent_pipeline: Arc<GraphicsPipeline>,
+	default_texture: Subtex,
+	textures: Textures,
+	shapes: ShapeRenderer,
+}
+impl SpriteRenderer {
+	pub fn new(gfx: &Gfx, render_pass: Arc<RenderPass>) -> Self {
+		let variant = if gfx.texture_array().is_some() { "bindless" } else { "" };
+		let program = block_on(gfx.program("shader", variant)).unwrap();
+		let desc = |blend| PipelineDesc {
+			layout: program.layout.clone(),
+			render_pass: render_pass.clone(),
+			subpass: 0,
+			vshader: program.vshader.clone(),
+			fshader: program.fshader.clone(),
+			blend,
+		};
+		let pipeline = |blend| gfx.pipelines.get_instanced::<TriangleVertex, SpriteInstance>(&gfx.device, desc(blend));
+		let (opaque_pipeline, transparent_pipeline) = (pipeline(BlendMode::Opaque), pipeline(BlendMode::Alpha));
+		let default_texture = gfx.default_texture().clone();
+		let textures = Textures::new(gfx, program.clone());
+		let shapes = ShapeRenderer::new(gfx, render_pass);
+		Self { program, opaque_pipeline, transparent_pipeline, default_texture, textures, shapes }
+	}
+
+	pub fn program(&self) -> &Arc<ShaderProgram> {
+		&self.program
+	}
+
+	pub fn pipeline(&self, transparent: bool) -> &Arc<GraphicsPipeline> {
+		if transparent {
+			&self.transparent_pipeline
+		} else {
+			&self.opaque_pipeline
+		}
+	}
+
+	/// Only valid for batches returned by the last `prepare`.
+	pub fn desc_sets(&self, batch: &SpriteBatch) -> &[Arc<DescriptorSet>] {
+		self.textures.desc_sets(batch.texture)
+	}
+
+	/// Uploads every sprite of `sprites` in draw order, and drops the textures no longer drawn.
+	pub fn prepare(&mut self, gfx: &Gfx, sprites: &SpriteList) -> PreparedSprites {
+		let (opaque, transparent) = sprites.sorted();
+		let mut instances = vec![];
+		let mut used = HashSet::new();
+		let mut to_runs = |draws: Vec<SpriteDraw>| {
+			let mut runs: Vec<SpriteRun> = vec![];
+			for draw in draws {
+				let sprite = match draw {
+					SpriteDraw::Sprite(sprite) => sprite,
+					SpriteDraw::Shapes(batch) => {
+						runs.push(SpriteRun::Shapes(*batch));
+						continue;
+					},
+				};
+				let texture = sprite.texture.as_ref().unwrap_or(&self.default_texture);
+				let (key, slot) = match self.textures.get(gfx, texture.image_view(), texture.sampler()) {
+					Ok(texture) => texture,
+					Err(err) => {
+						warn!("skipped a sprite: {}", err);
+						continue;
+					},
+				};
+				used.insert(key);
+				let ((u, v), (w, h)) = (texture.pos(), texture.size());
+				let batch = SpriteBatch {
+					texture: self.textures.batch_key(key),
+					first_instance: instances.len() as u32,
+					instance_count: 1,
+				};
+				instances.push(SpriteInstance {
+					pos: sprite.pos,
+					size: Vector2::new(w as f32, h as f32).component_mul(&sprite.scale),
+					uv_rect: [u as f32, v as f32, w as f32, h as f32],
+					tint: sprite.tint,
+					texture: slot,
+					depth: sprite.depth(),
+					rotation: sprite.rotation,
+				});
+				match runs.last_mut() {
+					Some(SpriteRun::Sprites(last)) if last.texture == batch.texture => last.instance_count += 1,
+					_ => runs.push(SpriteRun::Sprites(batch)),
+				}
+			}
+			runs
+		};
+		let (opaque, transparent) = (to_runs(opaque), to_runs(transparent));
+
+		self.textures.retain(&used);
+		let instances = if instances.is_empty() {
+			None
+		} else {
+			let usage = BufferUsageFlags::VERTEX_BUFFER;
+			let buffer =
+				Buffer::init_slice(gfx.device.clone(), instances.len() as _, B1, usage).copy_from_slice(&instances);
+			validation::set_name(&gfx.device, &*buffer, "sprite instances");
+			Some(buffer)
+		};
+		let shape_verts = self.shapes.prepare(gfx, sprites);
+		PreparedSprites { instances, shape_verts, opaque, transparent }
+	}
+
+	/// Records the opaque or transparent runs of `prepared`, which must come from the last `prepare`. Expects the
+	/// viewport and scissor to be set.
+	pub fn record(
+		&self,
+		gfx: &Gfx,
+		mut cmd: CommandBufferBuilder,
+		prepared: &PreparedSprites,
+		transparent: bool,
+		win_size: [f32; 2],
+		camera: &Camera,
+	) -> CommandBufferBuilder {
+		let (view_pos, zoom) = (camera.pos.into(), camera.zoom);
+		let shapes = self.shapes.program();
+		// sprites and shapes interleave by layer, so only rebind when switching between them
+		let mut bound_shapes = None;
+		for run in prepared.runs(transparent) {
+			match run {
+				SpriteRun::Sprites(batch) => {
+					if bound_shapes != Some(false) {
+						bound_shapes = Some(false);
+						let instances = prepared.instances.clone().unwrap();
+						let verts = once(gfx.verts.clone() as _).chain(once(instances as _));
+						let pc = SpritePushConsts { win_size, view_pos, zoom };
+						cmd = cmd
+							.bind_pipeline(self.pipeline(transparent).clone())
+							.bind_vertex_buffers(0, verts, &[0, 0])
+							.push_constants(self.program.layout.clone(), self.program.push_constant_stages(), 0, &pc);
+					}
+					let desc_sets = self.desc_sets(batch).iter().cloned();
+					cmd = cmd
+						.bind_descriptor_sets(self.program.layout.clone(), 0, desc_sets, &[])
+						.draw(6, batch.instance_count, 0, batch.first_instance);
+				},
+				SpriteRun::Shapes(batch) => {
+					if bound_shapes != Some(true) {
+						bound_shapes = Some(true);
+						let verts = prepared.shape_verts.clone().unwrap();
+						cmd = cmd
+							.bind_pipeline(self.shapes.pipeline(transparent).clone())
+							.bind_vertex_buffers(0, once(verts as _), &[0]);
+					}
+					let pc = ShapePushConsts { win_size, view_pos, zoom, depth: batch.depth() };
+					cmd = cmd.push_constants(shapes.layout.clone(), shapes.push_constant_stages(), 0, &pc);
+					cmd = cmd.draw(batch.vertex_count, 1, batch.first_vertex, 0);
+				},
+			}
+		}
+		cmd
+	}
+}
@@ -1,13 +1,38 @@
-use std::sync::Arc;
+use crate::{
+	fs::read_all_u8,
+	gfx::{sampler::SamplerDesc, validation, Gfx},
+};
+use image::RgbaImage;
+use std::{fmt, io, path::Path, sync::Arc};
+use typenum::B1;
 use vulkan::{
+	buffer::Buffer,
 	command::CommandPool,
-	device::Queue,
+	device::{BufferUsageFlags, Queue},
 	image::{
 		ClearColorValue, Format, Image, ImageAspectFlags, ImageSubresourceRange, ImageType, ImageUsageFlags, ImageView,
 	},
 	sync::{GpuFuture, NowFuture},
 };
 
+pub fn upload_image(
+	queue: &Arc<Queue>,
+	pool: &Arc<CommandPool>,
+	img: RgbaImage,
+) -> (Arc<ImageView>, Box<dyn GpuFuture>) {
+	let (width, height) = (img.width(), img.height());
+	let data: Vec<_> = img.pixels().cloned().collect();
+	let pixels = Buffer::init_slice(queue.device().clone(), data.len() as _, B1, BufferUsageFlags::TRANSFER_SRC)
+		.copy_from_slice(&data);
+	let format = Format::R8G8B8A8_UNORM;
+	let usage = ImageUsageFlags::TRANSFER_DST | ImageUsageFlags::SAMPLED;
+	let (image, future) = Image::init(queue.device().clone(), ImageType::TYPE_2D, width, height, 1, format, usage)
+		.copy_from_buffer(queue, pool, pixels);
+	let subresource =
+		ImageSubresourceRange::builder().aspect_mask(ImageAspectFlags::COLOR).level_count(1).layer_count(1).build();
+	(ImageView::new(image, format, subresource), Box::new(future))
+}
+
 pub struct TexAtlas {
 	queue: Arc<Queue>,
 	pool: Arc<CommandPool>,
@@ -59,16 +84,93 @@ impl TexAtlas {
 			(image_view, rect, Box::new(future) as _)
 		};
 
-		(Subtex { image_view, rect }, future)
+		(Subtex { image_view, rect, sampler: SamplerDesc::default(), path: None }, future)
 	}
 }
 
+/// A rectangular region of a texture, in texels.
+#[derive(Clone)]
 pub struct Subtex {
 	image_view: Arc<ImageView>,
 	rect: Rect,
+	sampler: SamplerDesc,
+	path: Option<Arc<str>>,
+}
+impl Subtex {
+	/// The whole of a `w` by `h` texture, filtered linearly.
+	pub fn new(image_view: Arc<ImageView>, w: u32, h: u32) -> Self {
+		Self { image_view, rect: Rect::new(0, 0, w, h), sampler: SamplerDesc::default(), path: None }
+	}
+
+	/// The whole of the image at `path`, which it remembers so scenes can refer to it.
+	pub async fn load<P: AsRef<Path>>(gfx: &Gfx, path: P) -> io::Result<Self> {
+		let path = path.as_ref();
+		let img = read_all_u8(path.to_owned()).await?;
+		let img = image::load_from_memory(&img)
+			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?
+			.into_rgba();
+		let (w, h) = img.dimensions();
+		let (image_view, future) = upload_image(&gfx.queue, &gfx.cmdpool, img);
+		let path = path.to_string_lossy();
+		validation::set_name(&gfx.device, &*image_view, &path);
+		future.then_signal_fence().wait();
+		Ok(Self { path: Some(path.into()), ..Self::new(image_view, w, h) })
+	}
+
+	/// A region of this one, relative to its top-left corner, read with the same sampler.
+	pub fn sub(&self, x: u32, y: u32, w: u32, h: u32) -> Self {
+		assert!(x + w <= self.rect.w && y + h <= self.rect.h, "region out of bounds");
+		let rect = Rect::new(self.rect.x + x, self.rect.y + y, w, h);
+		Self { rect, ..self.clone() }
+	}
+
+	/// Reads the texture with `sampler` instead, e.g. `SamplerDesc::nearest()` for pixel art.
+	pub fn with_sampler(mut self, sampler: SamplerDesc) -> Self {
+		self.sampler = sampler;
+		self
+	}
+
+	pub fn sampler(&self) -> SamplerDesc {
+		self.sampler
+	}
+
+	pub fn image_view(&self) -> &Arc<ImageView> {
+		&self.image_view
+	}
+
+	pub fn pos(&self) -> (u32, u32) {
+		(self.rect.x, self.rect.y)
+	}
+
+	pub fn size(&self) -> (u32, u32) {
+		(self.rect.w, self.rect.h)
+	}
+
+	/// The image file this was loaded from, if any.
+	pub fn path(&self) -> Option<&str> {
+		self.path.as_deref()
+	}
+}
+// the same region of the same image, read the same way
+impl PartialEq for Subtex {
+	fn eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.image_view, &other.image_view) && self.rect == other.rect && self.sampler == other.sampler
+	}
+}
+impl fmt::Debug for Subtex {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("Subtex")
+			.field("image_view", &Arc::as_ptr(&self.image_view))
+			.field("pos", &self.pos())
+			.field("size", &self.size())
+			.field("sampler", &self.sampler)
+			.field("path", &self.path)
+			.finish()
+	}
 }
 
 // TODO: move to a math module?
+#[derive(Clone, Copy, PartialEq)]
 struct Rect {
 	x: u32,
 	y: u32,
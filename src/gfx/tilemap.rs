@@ -0,0 +1,323 @@
+use crate::{
+	gfx::{
+		bindless::{TextureKey, Textures},
+		camera::Camera,
+		pipeline::{BlendMode, PipelineDesc},
+		shader::ShaderProgram,
+		sprite::layer_depth,
+		validation,
+		vertex::vertex_input,
+		Gfx,
+	},
+	tilemap::{
+		layer::{Chunk, TileLayer, CHUNK_SIZE},
+		Layer, Tilemap,
+	},
+};
+use futures::executor::block_on;
+use log::warn;
+use nalgebra::Vector2;
+use std::{
+	collections::{HashMap, HashSet},
+	iter::once,
+	sync::Arc,
+};
+use typenum::B1;
+use vulkan::{
+	buffer::Buffer, command::CommandBufferBuilder, descriptor::DescriptorSet, device::BufferUsageFlags,
+	pipeline::GraphicsPipeline, render_pass::RenderPass,
+};
+
+vertex_input! {
+	#[derive(Clone, Copy, Debug)]
+	pub struct TileVertex {
+		/// Pixels, relative to the layer.
+		pub pos: Vector2<f32>,
+		/// Texels.
+		pub uv: Vector2<f32>,
+		/// Slot in the texture array, if there is one.
+		pub texture: u32,
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct TilemapPushConsts {
+	pub tint: [f32; 4],
+	pub offset: [f32; 2],
+	pub win_size: [f32; 2],
+	pub view_pos: [f32; 2],
+	pub zoom: f32,
+	pub depth: f32,
+}
+
+/// Tiles of one chunk sharing a texture, or all of them with a texture array.
+#[derive(Clone, Copy, Debug)]
+pub struct Batch {
+	// key into the renderer's textures
+	texture: Option<TextureKey>,
+	pub first_vertex: u32,
+	pub vertex_count: u32,
+}
+
+/// A visible chunk, ready to draw.
+pub struct ChunkDraw {
+	pub buffer: Arc<Buffer<[TileVertex]>>,
+	pub batches: Vec<Batch>,
+	pub offset: Vector2<f32>,
+	pub tint: [f32; 4],
+	pub depth: f32,
+	pub transparent: bool,
+}
+
+// cache key: map id, layer index and chunk coordinates
+type ChunkKey = (u64, usize, (i32, i32));
+
+struct ChunkMesh {
+	version: u64,
+	// `Tilemap::anim_version` at build time, for chunks with animated tiles
+	anim_version: Option<u64>,
+	buffer: Option<Arc<Buffer<[TileVertex]>>>,
+	batches: Vec<Batch>,
+	textures: Vec<TextureKey>,
+}
+
+/// Keeps a vertex buffer per chunk of each tile layer drawn, rebuilding it only when the chunk changes or shows a
+/// different animation frame. Chunks of maps and layers that weren't drawn in a frame are dropped.
+pub struct TilemapRenderer {
+	program: Arc<ShaderProgram>,
+	opaque_pipeline: Arc<GraphicsPipeline>,
+	transparent_pipeline: Arc<GraphicsPipeline>,
+	textures: Textures,
+	meshes: HashMap<ChunkKey, ChunkMesh>,
+}
+impl TilemapRenderer {
+	pub fn new(gfx: &Gfx, render_pass: Arc<RenderPass>) -> Self {
+		let (program, alpha_test) = match gfx.texture_array() {
+			Some(_) => ("bindless", "alpha_test+bindless"),
+			None => ("", "alpha_test"),
+		};
+		let program = block_on(gfx.program("tilemap", program)).unwrap();
+		let alpha_test = block_on(gfx.program("tilemap", alpha_test)).unwrap();
+		// both variants have the same layout, so the same descriptor sets serve both
+		let desc = |program: &ShaderProgram, blend| PipelineDesc {
+			layout: program.layout.clone(),
+			render_pass: render_pass.clone(),
+			subpass: 0,
+			vshader: program.vshader.clone(),
+			fshader: program.fshader.clone(),
+			blend,
+		};
+		let mut opaque = desc(&alpha_test, BlendMode::Opaque);
+		opaque.layout = program.layout.clone();
+		let opaque_pipeline = gfx.pipelines.get::<TileVertex>(&gfx.device, opaque);
+		let transparent_pipeline = gfx.pipelines.get::<TileVertex>(&gfx.device, desc(&program, BlendMode::Alpha));
+		let textures = Textures::new(gfx, program.clone());
+		Self { program, opaque_pipeline, transparent_pipeline, textures, meshes: HashMap::new() }
+	}
+
+	pub fn program(&self) -> &Arc<ShaderProgram> {
+		&self.program
+	}
+
+	pub fn pipeline(&self, transparent: bool) -> &Arc<GraphicsPipeline> {
+		if transparent {
+			&self.transparent_pipeline
+		} else {
+			&self.opaque_pipeline
+		}
+	}
+
+	pub fn desc_sets(&self, batch: &Batch) -> &[Arc<DescriptorSet>] {
+		self.textures.desc_sets(batch.texture)
+	}
+
+	/// Records `draws`, which must come from the last `prepare` and all share `transparent`. Expects the viewport and
+	/// scissor to be set.
+	pub fn record(
+		&self,
+		cmd: CommandBufferBuilder,
+		draws: &[ChunkDraw],
+		transparent: bool,
+		win_size: [f32; 2],
+		camera: &Camera,
+	) -> CommandBufferBuilder {
+		let layout = self.program.layout.clone();
+		let stages = self.program.push_constant_stages();
+		let mut cmd = cmd.bind_pipeline(self.pipeline(transparent).clone());
+		for draw in draws {
+			let pc = TilemapPushConsts {
+				tint: draw.tint,
+				offset: draw.offset.into(),
+				win_size,
+				view_pos: camera.pos.into(),
+				zoom: camera.zoom,
+				depth: draw.depth,
+			};
+			cmd = cmd
+				.bind_vertex_buffers(0, once(draw.buffer.clone() as _), &[0])
+				.push_constants(layout.clone(), stages, 0, &pc);
+			for batch in &draw.batches {
+				let desc_sets = self.desc_sets(batch).iter().cloned();
+				cmd = cmd
+					.bind_descriptor_sets(layout.clone(), 0, desc_sets, &[])
+					.draw(batch.vertex_count, 1, batch.first_vertex, 0);
+			}
+		}
+		cmd
+	}
+
+	/// Returns the chunks of `maps` that overlap `view`, a world rectangle as min and max corners, rebuilding those
+	/// that changed.
+	pub fn prepare(&mut self, gfx: &Gfx, maps: &[&Tilemap], view: (Vector2<f32>, Vector2<f32>)) -> Vec<ChunkDraw> {
+		let mut draws = vec![];
+		let mut seen = HashSet::new();
+		for map in maps {
+			// tiles bigger than a cell stick out of their chunk up and to the right
+			let overhang = map.tilesets.iter().fold(Vector2::zeros(), |overhang: Vector2<f32>, tileset| {
+				let size = tileset.tile_size.zip_map(&map.tile_size, |tile, cell| tile.saturating_sub(cell) as f32);
+				overhang.sup(&size)
+			});
+			let chunk_size = map.tile_size.map(|x| (x as i32 * CHUNK_SIZE) as f32);
+
+			for (layer_idx, layer) in map.layers.iter().enumerate() {
+				let layer = match layer {
+					Layer::Tiles(layer) if layer.visible && layer.tint[3] > 0.0 => layer,
+					_ => continue,
+				};
+				for (coords, chunk) in layer.chunks() {
+					let key = (map.id(), layer_idx, coords);
+					seen.insert(key);
+
+					let min = layer.offset + Vector2::new(coords.0 as f32, coords.1 as f32).component_mul(&chunk_size);
+					let max = min + chunk_size;
+					if max.x + overhang.x < view.0.x
+						|| min.x > view.1.x
+						|| max.y < view.0.y
+						|| min.y - overhang.y > view.1.y
+					{
+						continue;
+					}
+
+					let mesh = self.meshes.get(&key);
+					let stale = mesh.is_none_or(|mesh| {
+						mesh.version != chunk.version() || mesh.anim_version.is_some_and(|v| v != map.anim_version())
+					});
+					if stale {
+						let mesh = self.build(gfx, map, chunk, coords);
+						self.meshes.insert(key, mesh);
+					}
+					let mesh = &self.meshes[&key];
+					if let Some(buffer) = &mesh.buffer {
+						draws.push(ChunkDraw {
+							buffer: buffer.clone(),
+							batches: mesh.batches.clone(),
+							offset: layer.offset,
+							tint: layer.tint,
+							depth: layer_depth(layer.layer),
+							transparent: is_transparent(layer),
+						});
+					}
+				}
+			}
+		}
+
+		self.meshes.retain(|key, _| seen.contains(key));
+		let used: HashSet<_> = self.meshes.values().flat_map(|mesh| mesh.textures.iter().cloned()).collect();
+		self.textures.retain(&used);
+
+		// opaque front to back, transparent back to front, like sprites
+		draws.sort_by(|a, b| match (a.transparent, b.transparent) {
+			(false, false) => a.depth.partial_cmp(&b.depth).unwrap(),
+			(true, true) => b.depth.partial_cmp(&a.depth).unwrap(),
+			(a, b) => a.cmp(&b),
+		});
+		draws
+	}
+
+	fn build(&mut self, gfx: &Gfx, map: &Tilemap, chunk: &Chunk, (cx, cy): (i32, i32)) -> ChunkMesh {
+		let mut animated = false;
+		let mut per_tileset: Vec<Vec<TileVertex>> = map.tilesets.iter().map(|_| vec![]).collect();
+		let cell = map.tile_size.map(|x| x as f32);
+		for (i, &tile) in chunk.tiles().iter().enumerate() {
+			let (idx, tileset) = match map.tileset_for(tile) {
+				Some((idx, tileset)) if tileset.texture.is_some() => (idx, tileset),
+				_ => continue,
+			};
+			let id = tile.gid() - tileset.first_gid;
+			animated |= tileset.is_animated(id);
+			let (u, v, w, h) = tileset.tile_rect(tileset.displayed(id));
+			let (u, v, w, h) = (u as f32, v as f32, w as f32, h as f32);
+
+			let x = cx * CHUNK_SIZE + i as i32 % CHUNK_SIZE;
+			let y = cy * CHUNK_SIZE + i as i32 / CHUNK_SIZE;
+			// anchored at the cell's bottom-left corner, like in Tiled
+			let pos = Vector2::new(x as f32 * cell.x, (y + 1) as f32 * cell.y - h);
+			let corner = |cx: f32, cy: f32| {
+				let (mut sx, mut sy) = if tile.flip_d() { (cy, cx) } else { (cx, cy) };
+				if tile.flip_h() {
+					sx = 1.0 - sx;
+				}
+				if tile.flip_v() {
+					sy = 1.0 - sy;
+				}
+				let uv = Vector2::new(u + sx * w, v + sy * h);
+				TileVertex { pos: pos + Vector2::new(cx * w, cy * h), uv, texture: 0 }
+			};
+			let (tl, tr, br, bl) = (corner(0.0, 0.0), corner(1.0, 0.0), corner(1.0, 1.0), corner(0.0, 1.0));
+			per_tileset[idx].extend_from_slice(&[tl, tr, br, br, bl, tl]);
+		}
+
+		let mut verts = vec![];
+		let mut batches: Vec<Batch> = vec![];
+		let mut textures = vec![];
+		for (tileset, mut tile_verts) in map.tilesets.iter().zip(per_tileset) {
+			if tile_verts.is_empty() {
+				continue;
+			}
+			let subtex = tileset.texture.as_ref().unwrap();
+			let (key, slot) = match self.textures.get(gfx, subtex.image_view(), subtex.sampler()) {
+				Ok(texture) => texture,
+				Err(err) => {
+					warn!("skipped a tileset: {}", err);
+					continue;
+				},
+			};
+			for vert in &mut tile_verts {
+				vert.texture = slot;
+			}
+			textures.push(key);
+			let texture = self.textures.batch_key(key);
+			match batches.last_mut() {
+				// tilesets sharing an atlas page, or any tilesets with a texture array, draw together
+				Some(last) if last.texture == texture => last.vertex_count += tile_verts.len() as u32,
+				_ => batches.push(Batch {
+					texture,
+					first_vertex: verts.len() as u32,
+					vertex_count: tile_verts.len() as u32,
+				}),
+			}
+			verts.extend(tile_verts);
+		}
+
+		let buffer = if verts.is_empty() {
+			None
+		} else {
+			let buffer = Buffer::init_slice(gfx.device.clone(), verts.len() as _, B1, BufferUsageFlags::VERTEX_BUFFER)
+				.copy_from_slice(&verts);
+			validation::set_name(&gfx.device, &*buffer, "tile chunk verts");
+			Some(buffer)
+		};
+		ChunkMesh {
+			version: chunk.version(),
+			anim_version: Some(map.anim_version()).filter(|_| animated),
+			buffer,
+			batches,
+			textures,
+		}
+	}
+}
+
+fn is_transparent(layer: &TileLayer) -> bool {
+	layer.tint[3] < 1.0
+}
@@ -0,0 +1,110 @@
+use nalgebra::Vector2;
+use vulkan::{Extent2D, Offset2D, Rect2D};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Upscale {
+	/// Whole multiples of the virtual resolution only, so every texel covers the same number of window pixels.
+	/// Falls back to `SharpBilinear` in windows smaller than the virtual resolution.
+	Integer,
+	/// Fills as much of the window as the aspect ratio allows, keeping texel edges crisp.
+	SharpBilinear,
+}
+
+/// Renders the scene at a fixed size and scales it up to the window, with black bars around it. Sprites, tilemaps and
+/// particles are laid out in virtual pixels; the GUI still uses window pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VirtualResolution {
+	pub width: u32,
+	pub height: u32,
+	pub upscale: Upscale,
+}
+impl VirtualResolution {
+	pub fn new(width: u32, height: u32, upscale: Upscale) -> Self {
+		assert!(width > 0 && height > 0, "virtual resolution must not be empty");
+		Self { width, height, upscale }
+	}
+
+	pub fn extent(&self) -> Extent2D {
+		Extent2D { width: self.width, height: self.height }
+	}
+
+	/// Where the scene lands in a window of `extent`, centered.
+	pub fn viewport(&self, extent: Extent2D) -> Rect2D {
+		let (width, height) = (self.width as f32, self.height as f32);
+		let fit = (extent.width as f32 / width).min(extent.height as f32 / height);
+		let scale = match self.upscale {
+			Upscale::Integer if fit >= 1.0 => fit.floor(),
+			_ => fit,
+		};
+		let size = Extent2D {
+			width: ((width * scale).round() as u32).clamp(1, extent.width.max(1)),
+			height: ((height * scale).round() as u32).clamp(1, extent.height.max(1)),
+		};
+		let offset = Offset2D {
+			x: (extent.width.saturating_sub(size.width) / 2) as _,
+			y: (extent.height.saturating_sub(size.height) / 2) as _,
+		};
+		Rect2D::builder().offset(offset).extent(size).build()
+	}
+
+	/// Converts window pixels, like a cursor position, to virtual pixels. Points on the bars land outside the virtual
+	/// resolution.
+	pub fn window_to_virtual(&self, extent: Extent2D, pos: Vector2<f32>) -> Vector2<f32> {
+		let viewport = self.viewport(extent);
+		let offset = Vector2::new(viewport.offset.x as f32, viewport.offset.y as f32);
+		let scale = self.width as f32 / viewport.extent.width as f32;
+		(pos - offset) * scale
+	}
+
+	/// Converts virtual pixels to window pixels, the inverse of `window_to_virtual`.
+	pub fn virtual_to_window(&self, extent: Extent2D, pos: Vector2<f32>) -> Vector2<f32> {
+		let viewport = self.viewport(extent);
+		let offset = Vector2::new(viewport.offset.x as f32, viewport.offset.y as f32);
+		let scale = viewport.extent.width as f32 / self.width as f32;
+		pos * scale + offset
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn viewport(res: VirtualResolution, width: u32, height: u32) -> ((i32, i32), (u32, u32)) {
+		let rect = res.viewport(Extent2D { width, height });
+		((rect.offset.x, rect.offset.y), (rect.extent.width, rect.extent.height))
+	}
+
+	#[test]
+	fn integer() {
+		let res = VirtualResolution::new(320, 180, Upscale::Integer);
+		assert_eq!(viewport(res, 1280, 720), ((0, 0), (1280, 720)));
+		// 4.27x rounds down, with the rest as bars on every side
+		assert_eq!(viewport(res, 1366, 768), ((43, 24), (1280, 720)));
+		// too small for a whole multiple, so scaled down instead
+		assert_eq!(viewport(res, 160, 90), ((0, 0), (160, 90)));
+	}
+
+	#[test]
+	fn sharp_bilinear() {
+		let res = VirtualResolution::new(320, 180, Upscale::SharpBilinear);
+		assert_eq!(viewport(res, 1366, 768), ((0, 0), (1365, 768)));
+		// letterboxed in a taller window, pillarboxed in a wider one
+		assert_eq!(viewport(res, 1280, 1024), ((0, 152), (1280, 720)));
+		assert_eq!(viewport(res, 2560, 720), ((640, 0), (1280, 720)));
+		// a minimized window still gets a viewport
+		assert_eq!(viewport(res, 0, 0), ((0, 0), (1, 1)));
+	}
+
+	#[test]
+	fn window_virtual_round_trip() {
+		let res = VirtualResolution::new(320, 180, Upscale::Integer);
+		let extent = Extent2D { width: 1366, height: 768 };
+		assert_eq!(res.window_to_virtual(extent, Vector2::new(43.0, 24.0)), Vector2::new(0.0, 0.0));
+		assert_eq!(res.window_to_virtual(extent, Vector2::new(1323.0, 744.0)), Vector2::new(320.0, 180.0));
+		// the bars are outside the virtual resolution
+		assert!(res.window_to_virtual(extent, Vector2::new(0.0, 0.0)).iter().all(|&x| x < 0.0));
+
+		let pos = Vector2::new(100.5, 42.25);
+		assert_eq!(res.window_to_virtual(extent, res.virtual_to_window(extent, pos)), pos);
+	}
+}
@@ -0,0 +1,100 @@
+use log::{log, warn, Level};
+use std::{
+	env,
+	sync::atomic::{AtomicBool, Ordering},
+};
+use vulkan::{
+	debug::{
+		DebugUtilsMessageSeverityFlags, DebugUtilsMessageTypeFlags, DebugUtilsMessenger,
+		DebugUtilsMessengerCallbackData, DebugUtilsObject,
+	},
+	device::Device,
+	instance::Instance,
+	Vulkan,
+};
+
+pub const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+pub const DEBUG_UTILS_EXTENSION: &str = "VK_EXT_debug_utils";
+
+// set once the instance has debug utils, since naming objects without it is an error
+static NAMES: AtomicBool = AtomicBool::new(false);
+
+/// On in debug builds. `VULKAN_VALIDATION=0` or `=1` overrides it.
+pub fn default_enabled() -> bool {
+	match env::var("VULKAN_VALIDATION").as_deref() {
+		Ok("0") => false,
+		Ok(_) => true,
+		Err(_) => cfg!(debug_assertions),
+	}
+}
+
+/// The layers and instance extensions to enable for validation, skipping any that aren't installed.
+pub(super) fn instance_requirements(vulkan: &Vulkan) -> (Vec<&'static str>, Vec<&'static str>) {
+	let mut layers = vec![];
+	if vulkan.enumerate_instance_layer_properties().iter().any(|layer| layer.layer_name() == VALIDATION_LAYER) {
+		layers.push(VALIDATION_LAYER);
+	} else {
+		warn!("{} isn't installed, so validation is off", VALIDATION_LAYER);
+	}
+
+	let mut extensions = vec![];
+	let available = vulkan.enumerate_instance_extension_properties(&layers);
+	if available.iter().any(|ext| ext.extension_name() == DEBUG_UTILS_EXTENSION) {
+		extensions.push(DEBUG_UTILS_EXTENSION);
+	} else {
+		warn!("{} isn't available, so there are no validation messages or object names", DEBUG_UTILS_EXTENSION);
+	}
+	(layers, extensions)
+}
+
+/// Routes validation and driver messages into the log, under this module's target. Messages stop when it's dropped.
+pub(super) fn messenger(instance: &Instance) -> DebugUtilsMessenger {
+	NAMES.store(true, Ordering::Relaxed);
+	let severity = DebugUtilsMessageSeverityFlags::ERROR
+		| DebugUtilsMessageSeverityFlags::WARNING
+		| DebugUtilsMessageSeverityFlags::INFO
+		| DebugUtilsMessageSeverityFlags::VERBOSE;
+	let types = DebugUtilsMessageTypeFlags::GENERAL
+		| DebugUtilsMessageTypeFlags::VALIDATION
+		| DebugUtilsMessageTypeFlags::PERFORMANCE;
+	DebugUtilsMessenger::new(instance, severity, types, Box::new(message))
+}
+
+fn message(
+	severity: DebugUtilsMessageSeverityFlags,
+	ty: DebugUtilsMessageTypeFlags,
+	data: &DebugUtilsMessengerCallbackData,
+) {
+	// the validation layer's info messages are mostly about loading itself
+	let level = if severity.contains(DebugUtilsMessageSeverityFlags::ERROR) {
+		Level::Error
+	} else if severity.contains(DebugUtilsMessageSeverityFlags::WARNING) {
+		Level::Warn
+	} else if severity.contains(DebugUtilsMessageSeverityFlags::INFO) {
+		Level::Debug
+	} else {
+		Level::Trace
+	};
+	let kind = if ty.contains(DebugUtilsMessageTypeFlags::VALIDATION) {
+		"validation"
+	} else if ty.contains(DebugUtilsMessageTypeFlags::PERFORMANCE) {
+		"performance"
+	} else {
+		"general"
+	};
+	let id = data.message_id_name().unwrap_or("");
+	let objects: Vec<_> = data.objects().filter_map(|object| object.object_name()).collect();
+	if objects.is_empty() {
+		log!(level, "{} {}: {}", kind, id, data.message());
+	} else {
+		log!(level, "{} {} ({}): {}", kind, id, objects.join(", "), data.message());
+	}
+}
+
+/// Names a buffer, image, pipeline or other object for validation messages and debuggers like RenderDoc. Does nothing
+/// without validation.
+pub fn set_name(device: &Device, object: &impl DebugUtilsObject, name: &str) {
+	if NAMES.load(Ordering::Relaxed) {
+		device.set_debug_utils_object_name(object, name);
+	}
+}
@@ -0,0 +1,135 @@
+use nalgebra::{Vector2, Vector3};
+use std::mem::size_of;
+use vulkan::{
+	image::Format,
+	pipeline::{VertexInputAttributeDescription, VertexInputBindingDescription, VertexInputRate},
+};
+
+/// A struct read by vertex shaders, one attribute per field. Implement it with `vertex_input!`.
+pub trait VertexInput: Copy + 'static {
+	/// The fields' attributes, read from `binding` at consecutive locations starting at `first_location`.
+	fn attributes(binding: u32, first_location: u32) -> Vec<VertexInputAttributeDescription>;
+
+	/// Read once per vertex, or once per instance with `instanced`.
+	fn binding(binding: u32, instanced: bool) -> VertexInputBindingDescription {
+		let rate = if instanced { VertexInputRate::INSTANCE } else { VertexInputRate::VERTEX };
+		VertexInputBindingDescription::builder()
+			.binding(binding)
+			.stride(size_of::<Self>() as _)
+			.input_rate(rate)
+			.build()
+	}
+}
+
+/// Field types `vertex_input!` knows the attribute format of.
+pub trait VertexFormat {
+	const FORMAT: Format;
+}
+impl VertexFormat for f32 {
+	const FORMAT: Format = Format::R32_SFLOAT;
+}
+impl VertexFormat for u32 {
+	const FORMAT: Format = Format::R32_UINT;
+}
+impl VertexFormat for [f32; 2] {
+	const FORMAT: Format = Format::R32G32_SFLOAT;
+}
+impl VertexFormat for [f32; 3] {
+	const FORMAT: Format = Format::R32G32B32_SFLOAT;
+}
+impl VertexFormat for [f32; 4] {
+	const FORMAT: Format = Format::R32G32B32A32_SFLOAT;
+}
+impl VertexFormat for Vector2<f32> {
+	const FORMAT: Format = Format::R32G32_SFLOAT;
+}
+impl VertexFormat for Vector3<f32> {
+	const FORMAT: Format = Format::R32G32B32_SFLOAT;
+}
+
+/// Declares a `#[repr(C)]` struct and implements `VertexInput` for it, with the attribute format of each field taken
+/// from its type's `VertexFormat`. Other crates can use it without depending on `vulkan` or `memoffset`:
+///
+/// ```ignore
+/// use game_engine::gfx::vertex::vertex_input;
+///
+/// vertex_input! {
+///     #[derive(Clone, Copy, Debug)]
+///     pub struct MyVertex {
+///         pub pos: Vector2<f32>,
+///         pub color: [f32; 4],
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! vertex_input {
+	(
+		$(#[$meta:meta])*
+		$vis:vis struct $name:ident {
+			$($(#[$field_meta:meta])* $field_vis:vis $field:ident: $ty:ty),* $(,)?
+		}
+	) => {
+		$(#[$meta])*
+		#[repr(C)]
+		$vis struct $name {
+			$($(#[$field_meta])* $field_vis $field: $ty),*
+		}
+		impl $crate::gfx::vertex::VertexInput for $name {
+			fn attributes(
+				binding: u32,
+				first_location: u32,
+			) -> Vec<$crate::vulkan::pipeline::VertexInputAttributeDescription> {
+				let formats = [$(<$ty as $crate::gfx::vertex::VertexFormat>::FORMAT),*];
+				let offsets = [$($crate::memoffset::offset_of!($name, $field) as u32),*];
+				formats
+					.iter()
+					.zip(&offsets)
+					.enumerate()
+					.map(|(i, (&format, &offset))| {
+						$crate::vulkan::pipeline::VertexInputAttributeDescription::builder()
+							.binding(binding)
+							.location(first_location + i as u32)
+							.format(format)
+							.offset(offset)
+							.build()
+					})
+					.collect()
+			}
+		}
+	};
+}
+pub use crate::vertex_input;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	vertex_input! {
+		#[derive(Clone, Copy)]
+		struct Mixed {
+			pos: Vector2<f32>,
+			layer: u32,
+			color: [f32; 4],
+			depth: f32,
+			normal: Vector3<f32>,
+		}
+	}
+
+	#[test]
+	fn mixed_fields() {
+		let attributes: Vec<_> = Mixed::attributes(1, 3)
+			.iter()
+			.map(|attribute| (attribute.binding, attribute.location, attribute.format, attribute.offset))
+			.collect();
+		assert_eq!(attributes, [
+			(1, 3, Format::R32G32_SFLOAT, 0),
+			(1, 4, Format::R32_UINT, 8),
+			(1, 5, Format::R32G32B32A32_SFLOAT, 12),
+			(1, 6, Format::R32_SFLOAT, 28),
+			(1, 7, Format::R32G32B32_SFLOAT, 32),
+		]);
+
+		let binding = Mixed::binding(2, true);
+		assert_eq!((binding.binding, binding.stride, binding.input_rate), (2, 44, VertexInputRate::INSTANCE));
+	}
+}
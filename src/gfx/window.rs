@@ -1,20 +1,50 @@
-use crate::gfx::{Gfx, TriangleVertex};
+use crate::{
+	gfx::{
+		camera::Camera,
+		debug::{DebugOverlay, FrameStats},
+		graph::{CompiledGraph, CompiledLighting, RenderGraph},
+		gui::{draw::GuiVertex, render::GuiRenderer, Gui},
+		light::{LightDraw, LightRenderer, LightScene, LightVertex, NormalBatch, NormalInstance},
+		material::Material,
+		particles::{ParticleBatch, ParticleInstance, ParticleRenderer},
+		pipeline::{BlendMode, PipelineDesc},
+		post::PostEffect,
+		profile::GpuTimer,
+		render_texture::OffscreenDraw,
+		shader::ShaderProgram,
+		sprite::{PreparedSprites, SpriteList, SpriteRenderer, SpriteRun},
+		tilemap::{ChunkDraw, TilemapRenderer},
+		upscale::{Upscale, VirtualResolution},
+		validation, Gfx, TriangleVertex,
+	},
+	particles::ParticleSystem,
+	profile,
+	tilemap::Tilemap,
+};
+use futures::executor::block_on;
+use log::{debug, info, warn};
+use nalgebra::Vector2;
 use std::{
 	cmp::{max, min},
+	io,
 	iter::{empty, once},
+	mem,
 	sync::Arc,
 	u32,
 };
 use vulkan::{
-	command::{ClearValue, CommandPool, InheritanceInfo},
-	image::{ClearColorValue, Format, Framebuffer, ImageAspectFlags, ImageSubresourceRange, ImageView},
+	buffer::Buffer,
+	command::{ClearValue, CommandBuffer, CommandBufferBuilder, CommandPool, InheritanceInfo},
+	image::{
+		ClearColorValue, ClearDepthStencilValue, Format, Framebuffer, Image, ImageAspectFlags, ImageSubresourceRange,
+		ImageType, ImageUsageFlags, ImageView,
+	},
 	ordered_passes_renderpass,
 	pipeline::{GraphicsPipeline, Viewport},
 	render_pass::RenderPass,
-	shader::ShaderStageFlags,
 	surface::{ColorSpace, PresentMode, Surface, SurfaceCapabilities, SurfaceFormat},
 	swapchain::{CompositeAlphaFlags, Swapchain},
-	sync::{Fence, GpuFuture},
+	sync::{AccessFlags, DependencyFlags, Fence, GpuFuture, MemoryBarrier, PipelineStageFlags},
 	Extent2D, Rect2D, VkResult,
 };
 use winit::{
@@ -23,6 +53,8 @@ use winit::{
 	window::{Window as IWindow, WindowBuilder},
 };
 
+pub(super) const DEPTH_FORMAT: Format = Format::D32_SFLOAT;
+
 pub struct Window {
 	pub(super) gfx: Arc<Gfx>,
 	surface: Arc<Surface<IWindow>>,
@@ -32,10 +64,27 @@ pub struct Window {
 	image_extent: Extent2D,
 	present_mode: PresentMode,
 	swapchain: Arc<Swapchain<IWindow>>,
-	pub(super) pipeline: Arc<GraphicsPipeline>,
+	sprite_renderer: SpriteRenderer,
+	gui_renderer: GuiRenderer,
+	tilemap_renderer: TilemapRenderer,
+	particle_renderer: ParticleRenderer,
+	light_renderer: LightRenderer,
+	// copies the graph's output into the swapchain image
+	present_program: Arc<ShaderProgram>,
+	present_pipeline: Arc<GraphicsPipeline>,
+	graph: Option<RenderGraph>,
+	virtual_res: Option<VirtualResolution>,
+	compiled: Option<CompiledGraph>,
+	offscreen: Vec<OffscreenDraw>,
+	depth_view: Arc<ImageView>,
 	pub(super) framebuffers: Vec<Arc<Framebuffer>>,
 	frame: bool,
 	recreate_swapchain: bool,
+	pub camera: Camera,
+	/// Drawn when the render graph has a light buffer, like with the `post::Lighting` effect.
+	pub lighting: LightScene,
+	/// Drawn over the GUI when shown. Pass it window events to toggle it.
+	pub debug: DebugOverlay,
 }
 impl Window {
 	pub fn new(gfx: Arc<Gfx>, event_loop: &EventLoop<()>) -> Self {
@@ -54,8 +103,11 @@ impl Window {
 			.unwrap();
 
 		let render_pass = ordered_passes_renderpass!(&gfx.device,
-			attachments: { color: { load: Clear, store: Store, format: surface_format.format, samples: 1, } },
-			passes: [{ color: [color], depth_stencil: {}, input: [] }]
+			attachments: {
+				color: { load: Clear, store: Store, format: surface_format.format, samples: 1, },
+				depth: { load: Clear, store: DontCare, format: DEPTH_FORMAT, samples: 1, }
+			},
+			passes: [{ color: [color], depth_stencil: {depth}, input: [] }]
 		);
 
 		let (caps, image_extent) = get_caps(&gfx, &surface);
@@ -75,8 +127,16 @@ impl Window {
 
 		let (swapchain, image_views) =
 			create_swapchain(&gfx, surface.clone(), &caps, &surface_format, image_extent, present_mode, None);
-		let pipeline = create_pipeline(&gfx, image_extent, render_pass.clone());
-		let framebuffers = create_framebuffers(&render_pass, image_views, image_extent);
+		let (width, height) = (image_extent.width, image_extent.height);
+		info!("swapchain {}x{}, {:?}, {:?}", width, height, surface_format.format, present_mode);
+		let sprite_renderer = SpriteRenderer::new(&gfx, render_pass.clone());
+		let gui_renderer = GuiRenderer::new(&gfx, render_pass.clone());
+		let tilemap_renderer = TilemapRenderer::new(&gfx, render_pass.clone());
+		let particle_renderer = ParticleRenderer::new(&gfx, render_pass.clone());
+		let light_renderer = LightRenderer::new(&gfx);
+		let (present_program, present_pipeline) = create_present_pipeline(&gfx, render_pass.clone(), None);
+		let depth_view = create_depth(&gfx, image_extent);
+		let framebuffers = create_framebuffers(&render_pass, image_views, &depth_view, image_extent);
 
 		let frame_data = [FrameData::new(&gfx), FrameData::new(&gfx)];
 
@@ -89,14 +149,114 @@ impl Window {
 			image_extent,
 			present_mode,
 			swapchain,
-			pipeline,
+			sprite_renderer,
+			gui_renderer,
+			tilemap_renderer,
+			particle_renderer,
+			light_renderer,
+			present_program,
+			present_pipeline,
+			graph: None,
+			virtual_res: None,
+			compiled: None,
+			offscreen: vec![],
+			depth_view,
 			framebuffers,
 			frame: false,
 			recreate_swapchain: false,
+			camera: Camera::default(),
+			lighting: LightScene::default(),
+			debug: DebugOverlay::new(),
+		}
+	}
+
+	/// Size of the drawable area in physical pixels.
+	pub fn size(&self) -> Vector2<f32> {
+		let size: [f32; 2] = self.surface.window().inner_size().into();
+		size.into()
+	}
+
+	pub fn scale_factor(&self) -> f32 {
+		self.surface.window().scale_factor() as _
+	}
+
+	/// Size the scene is drawn at in physical pixels: the virtual resolution if there is one, otherwise the window
+	/// size.
+	pub fn scene_size(&self) -> Vector2<f32> {
+		let extent = self.scene_extent();
+		Vector2::new(extent.width as _, extent.height as _)
+	}
+
+	/// Converts window pixels, like a cursor position, to scene pixels. Use it before `Camera::screen_to_world`.
+	pub fn window_to_scene(&self, pos: Vector2<f32>) -> Vector2<f32> {
+		match self.virtual_res {
+			Some(res) => res.window_to_virtual(self.image_extent, pos),
+			None => pos,
 		}
 	}
 
-	pub fn draw(&mut self) {
+	/// Converts scene pixels, like `Camera::world_to_screen` returns, to window pixels.
+	pub fn scene_to_window(&self, pos: Vector2<f32>) -> Vector2<f32> {
+		match self.virtual_res {
+			Some(res) => res.virtual_to_window(self.image_extent, pos),
+			None => pos,
+		}
+	}
+
+	/// Draws the scene at a fixed size, scaled up to fit the window. `None` draws at the window size again. If the
+	/// render graph's materials can't be built at the new size, the window draws without the graph and returns the
+	/// error, like `set_render_graph`.
+	pub fn set_virtual_resolution(&mut self, res: Option<VirtualResolution>) -> io::Result<()> {
+		self.wait_frames();
+		self.virtual_res = res;
+		self.compile_graph()
+	}
+
+	/// Format of the swapchain images, which the scene target of a render graph must use.
+	pub fn color_format(&self) -> Format {
+		self.surface_format.format
+	}
+
+	/// Applies `effects` in order to everything but the GUI. An empty list draws straight to the window again. Fails
+	/// without changing anything if an effect can't add its passes.
+	pub fn set_effects(&mut self, effects: &[Box<dyn PostEffect>]) -> io::Result<()> {
+		let graph = if effects.is_empty() {
+			None
+		} else {
+			let mut graph = RenderGraph::new(self.color_format());
+			let output = effects
+				.iter()
+				.try_fold(RenderGraph::SCENE, |input, effect| effect.add_passes(&self.gfx, &mut graph, input))?;
+			graph.present(output);
+			Some(graph)
+		};
+		self.set_render_graph(graph)
+	}
+
+	/// Replaces the passes run between drawing the scene and drawing the GUI. If a pass's material can't be built, like
+	/// when its shader reads a texture it wasn't given, the window draws without the graph and returns the error. A
+	/// graph whose scene target doesn't use `color_format` is rejected without changing anything.
+	pub fn set_render_graph(&mut self, graph: Option<RenderGraph>) -> io::Result<()> {
+		if let Some(graph) = &graph {
+			if graph.desc(RenderGraph::SCENE).format != self.color_format() {
+				let msg = "the scene target must use `Window::color_format`";
+				return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+			}
+		}
+		self.wait_frames();
+		self.graph = graph;
+		self.compile_graph()
+	}
+
+	/// Runs drawing into a render texture at the start of the next frame, before anything samples it. Draws into the
+	/// same texture run in the order they're passed.
+	pub fn draw_offscreen(&mut self, draw: OffscreenDraw) {
+		self.offscreen.push(draw);
+	}
+
+	/// Draws tilemaps, sprites and particles through `camera` and the render graph, with the GUI on top.
+	pub fn draw(&mut self, sprites: &SpriteList, tilemaps: &[&Tilemap], particles: &[&ParticleSystem], gui: &mut Gui) {
+		let scope = profile::scope("draw");
 		if self.recreate_swapchain {
 			self.recreate_swapchain();
 		}
@@ -115,66 +275,249 @@ impl Window {
 			},
 			Err(err) => panic!(err),
 		};
-		let image_uidx = image_idx as usize;
 
 		let frame = self.frame as usize;
 		if let Some(fence) = self.frame_data[frame].fence.take() {
+			let _scope = profile::scope("wait for gpu");
 			fence.wait();
 		}
+		self.frame_data[frame].timer.collect();
 		self.frame = !self.frame;
-
-		let framebuffer = &self.framebuffers[image_uidx];
-
+		if let Some(textures) = self.gfx.texture_array() {
+			textures.next_frame(self.frame_data.len());
+		}
 		self.frame_data[frame].cmdpool.reset(false);
 
-		let win_size: [f32; 2] = self.surface.window().inner_size().into();
-		let pc = ([100.0f32, 100.0], win_size);
-
-		// TODO: replace with real sprites
-		let secondaries = (0..2).map(|_| {
-			let inherit = InheritanceInfo {
-				render_pass: self.render_pass.clone(),
-				subpass: 0,
-				framebuffer: Some(framebuffer.clone()),
-			};
-			self.frame_data[frame]
-				.cmdpool
-				.record_secondary(true, false, Some(inherit))
-				.bind_pipeline(self.pipeline.clone())
-				.bind_vertex_buffers(0, once(self.gfx.verts.clone() as _), &[0])
-				.bind_descriptor_sets(self.gfx.layout.clone(), 0, once(self.gfx.desc_set.clone()), &[])
-				.push_constants(self.gfx.layout.clone(), ShaderStageFlags::VERTEX, 0, &pc)
-				.draw(6, 1, 0, 0)
-				.build()
-		});
+		let drawn = self.prepare(sprites, tilemaps, particles, gui);
+		let framebuffer = self.framebuffers[image_idx as usize].clone();
+		let primary = self.record(frame, &framebuffer, &drawn, gui);
+		let stats = self.frame_stats(&drawn, gui);
+		self.debug.end_frame(stats);
 
-		let primary = self.frame_data[frame]
-			.cmdpool
-			.record(true, false)
-			.begin_render_pass(
-				self.render_pass.clone(),
-				framebuffer.clone(),
-				Rect2D::builder().extent(self.image_extent).build(),
-				&[ClearValue { color: ClearColorValue { float32: [0.0, 0.0, 0.0, 1.0] } }],
-			)
-			.execute_commands(secondaries)
-			.end_render_pass()
-			.build();
+		self.frame_data[frame].timer.submitted();
 		let (signal, wait) = self.gfx.queue.submit_after(future, primary).then_signal_semaphore();
 		let fence = signal.then_signal_fence();
 		self.frame_data[frame].fence = Some(fence);
+		self.frame_data[frame].drawn = Some(drawn);
 
 		match Swapchain::present_after(vec![wait], self.gfx.queue.clone(), &[self.swapchain.clone()], &[image_idx]) {
 			Ok(true) | Err(VkResult::ERROR_OUT_OF_DATE_KHR) => self.recreate_swapchain = true,
 			Ok(false) => (),
 			Err(err) => panic!(err),
 		}
+		drop(scope);
+		profile::end_frame();
+	}
+
+	// uploads everything drawn this frame, along with the debug overlay
+	fn prepare(
+		&mut self,
+		sprites: &SpriteList,
+		tilemaps: &[&Tilemap],
+		particles: &[&ParticleSystem],
+		gui: &mut Gui,
+	) -> Drawn {
+		let extent = self.scene_extent();
+		let win_size = [extent.width as f32, extent.height as f32];
+		let zoom = self.camera.zoom;
+		// whole virtual pixels, or everything shimmers as the camera moves
+		let pos = match self.virtual_res {
+			Some(_) => (self.camera.pos * zoom).map(f32::round) / zoom,
+			None => self.camera.pos,
+		};
+		let camera = Camera { pos, zoom };
+		let view = camera.view(win_size.into());
+		let tiles = self.tilemap_renderer.prepare(&self.gfx, tilemaps, view);
+		let lit = self.compiled.as_ref().is_some_and(|compiled| compiled.lighting.is_some());
+		let lights = if lit { self.light_renderer.prepare(&self.gfx, &self.lighting, view) } else { None };
+		let normals = if lit { self.light_renderer.prepare_normals(&self.gfx, sprites) } else { None };
+		let particles = self.particle_renderer.prepare(&self.gfx, particles);
+
+		let (virtual_res, image_extent) = (self.virtual_res, self.image_extent);
+		self.debug.draw(gui, |pos| {
+			let pos = camera.world_to_screen(pos);
+			virtual_res.map_or(pos, |res| res.virtual_to_window(image_extent, pos))
+		});
+		let gui_verts = self.gui_renderer.prepare(&self.gfx, gui);
+		let sprites = self.sprite_renderer.prepare(&self.gfx, sprites);
+		Drawn { camera, win_size, tiles, sprites, particles, lights, normals, gui_verts }
+	}
+
+	// offscreen draws, the scene, the lighting and graph passes, then the GUI over the graph's output
+	fn record(&mut self, frame: usize, framebuffer: &Arc<Framebuffer>, drawn: &Drawn, gui: &Gui) -> CommandBuffer {
+		let cmdpool = &self.frame_data[frame].cmdpool;
+		// with a render graph the scene is drawn into its first target instead of the window
+		let (scene_pass, scene_framebuffer) = match &self.compiled {
+			Some(compiled) => (&compiled.scene_pass, &compiled.scene_framebuffers[frame]),
+			None => (&self.render_pass, framebuffer),
+		};
+		let scene = self.scene_cmds(frame, scene_pass, scene_framebuffer, drawn);
+		let gui_cmd = drawn.gui_verts.as_ref().map(|verts| {
+			let cmd = secondary(cmdpool, &self.render_pass, framebuffer, self.image_extent);
+			self.gui_renderer.record(cmd, verts, gui, self.image_extent).build()
+		});
+		let lighting = self.compiled.as_ref().and_then(|compiled| compiled.lighting.as_ref());
+		let lighting_cmds = lighting.map(|lighting| self.lighting_cmds(frame, lighting, drawn));
+		let passes: Vec<_> = self
+			.compiled
+			.iter()
+			.flat_map(|compiled| &compiled.passes)
+			.map(|pass| {
+				let cmd = secondary(cmdpool, &pass.render_pass, &pass.framebuffers[frame], pass.extent);
+				pass.record(&self.gfx, cmd, frame).build()
+			})
+			.collect();
+		let blit = self
+			.compiled
+			.as_ref()
+			.map(|compiled| self.present_cmd(frame, framebuffer, &compiled.present_materials[frame]));
+
+		let clear = [
+			ClearValue { color: ClearColorValue { float32: [0.0, 0.0, 0.0, 1.0] } },
+			ClearValue { depth_stencil: ClearDepthStencilValue { depth: 1.0, stencil: 0 } },
+		];
+		let window_area = Rect2D::builder().extent(self.image_extent).build();
+		let scene_area = Rect2D::builder().extent(self.scene_extent()).build();
+		let mut offscreen = mem::take(&mut self.offscreen);
+		let data = &mut self.frame_data[frame];
+		let cmd = data.cmdpool.record(true, false);
+		let cmd = data.timer.begin(cmd);
+		let cmd = record_offscreen(cmd, &mut data.timer, &mut offscreen);
+		data.offscreen = offscreen;
+		let cmd = data.timer.mark(cmd, "scene");
+		let cmd = match &self.compiled {
+			None => cmd
+				.begin_render_pass(self.render_pass.clone(), framebuffer.clone(), window_area, &clear)
+				.execute_commands(scene.into_iter().chain(gui_cmd))
+				.end_render_pass(),
+			Some(compiled) => {
+				let mut cmd = cmd
+					.begin_render_pass(scene_pass.clone(), scene_framebuffer.clone(), scene_area, &clear)
+					.execute_commands(scene)
+					.end_render_pass();
+				if let (Some(lighting), Some((normals, lights))) = (&compiled.lighting, lighting_cmds) {
+					let flat = [ClearValue { color: ClearColorValue { float32: [0.5, 0.5, 1.0, 1.0] } }];
+					let [r, g, b] = self.lighting.ambient;
+					let ambient = [ClearValue { color: ClearColorValue { float32: [r, g, b, 1.0] } }];
+					let lights_area = Rect2D::builder().extent(lighting.lights_extent).build();
+					cmd = data.timer.mark(cmd, "lighting");
+					cmd = cmd
+						.begin_render_pass(
+							lighting.normals_pass.clone(),
+							lighting.normals_framebuffers[frame].clone(),
+							scene_area,
+							&flat,
+						)
+						.execute_commands(normals)
+						.end_render_pass();
+					let lights_framebuffer = &lighting.lights_framebuffers[frame];
+					cmd = sampled_pass(cmd, &lighting.lights_pass, lights_framebuffer, lights_area, &ambient, lights);
+				}
+				for (pass, pass_cmd) in compiled.passes.iter().zip(passes) {
+					let area = Rect2D::builder().extent(pass.extent).build();
+					cmd = data.timer.mark(cmd, pass.program.name().to_owned());
+					cmd = sampled_pass(cmd, &pass.render_pass, &pass.framebuffers[frame], area, &[], once(pass_cmd));
+				}
+				cmd = data.timer.mark(cmd, "present");
+				sampled_pass(cmd, &self.render_pass, framebuffer, window_area, &clear, blit.into_iter().chain(gui_cmd))
+			},
+		};
+		data.timer.end(cmd).build()
+	}
+
+	// tiles go under sprites sharing their layer, and each blended pass goes after every opaque one
+	fn scene_cmds(
+		&self,
+		frame: usize,
+		render_pass: &Arc<RenderPass>,
+		framebuffer: &Arc<Framebuffer>,
+		drawn: &Drawn,
+	) -> Vec<CommandBuffer> {
+		let (win_size, camera) = (drawn.win_size, &drawn.camera);
+		let extent = self.scene_extent();
+		let begin = || secondary(&self.frame_data[frame].cmdpool, render_pass, framebuffer, extent);
+		let split = drawn.tiles.iter().position(|draw| draw.transparent).unwrap_or(drawn.tiles.len());
+		let tiles = |draws: &[ChunkDraw], transparent: bool| {
+			let renderer = &self.tilemap_renderer;
+			(!draws.is_empty()).then(|| renderer.record(begin(), draws, transparent, win_size, camera).build())
+		};
+		let sprites = |transparent: bool| {
+			let (renderer, prepared) = (&self.sprite_renderer, &drawn.sprites);
+			let cmd = || renderer.record(&self.gfx, begin(), prepared, transparent, win_size, camera).build();
+			(!prepared.runs(transparent).is_empty()).then(cmd)
+		};
+		let particles = drawn.particles.as_ref().map(|(instances, batches)| {
+			let renderer = &self.particle_renderer;
+			renderer.record(&self.gfx, begin(), instances, batches, win_size, camera).build()
+		});
+		tiles(&drawn.tiles[..split], false)
+			.into_iter()
+			.chain(sprites(false))
+			.chain(tiles(&drawn.tiles[split..], true))
+			.chain(sprites(true))
+			.chain(particles)
+			.collect()
+	}
+
+	// the normals of sprites with normal maps and flat ones for the sprites in front of them, then the lights
+	fn lighting_cmds(
+		&self,
+		frame: usize,
+		lighting: &CompiledLighting,
+		drawn: &Drawn,
+	) -> (Option<CommandBuffer>, Option<CommandBuffer>) {
+		let (win_size, camera) = (drawn.win_size, &drawn.camera);
+		let (cmdpool, renderer) = (&self.frame_data[frame].cmdpool, &self.light_renderer);
+		let normals = drawn.normals.as_ref().map(|(instances, batches)| {
+			let framebuffer = &lighting.normals_framebuffers[frame];
+			let cmd = secondary(cmdpool, &lighting.normals_pass, framebuffer, self.scene_extent());
+			renderer.record_normals(&self.gfx, cmd, instances, batches, win_size, camera).build()
+		});
+		let lights = drawn.lights.as_ref().map(|(verts, draws)| {
+			let framebuffer = &lighting.lights_framebuffers[frame];
+			let cmd = secondary(cmdpool, &lighting.lights_pass, framebuffer, lighting.lights_extent);
+			renderer.record_lights(cmd, frame, verts, draws, win_size, camera).build()
+		});
+		(normals, lights)
+	}
+
+	fn frame_stats(&self, drawn: &Drawn, gui: &Gui) -> FrameStats {
+		let runs = drawn.sprites.opaque.iter().chain(&drawn.sprites.transparent);
+		let shape_batches = runs.clone().filter(|run| matches!(run, SpriteRun::Shapes(_))).count() as u32;
+		let sprite_draws = runs.count() as u32;
+		let tile_batches: usize = drawn.tiles.iter().map(|draw| draw.batches.len()).sum();
+		let particle_batches = drawn.particles.as_ref().map_or(&[][..], |(_, batches)| batches);
+		let light_draws = drawn.lights.as_ref().map_or(&[][..], |(_, draws)| draws);
+		let normal_batches = drawn.normals.as_ref().map_or(0, |(_, batches)| batches.len() as u32);
+		let graph_draws = self.compiled.as_ref().map_or(0, |compiled| compiled.passes.len() as u32 + 1);
+		FrameStats {
+			draw_calls: sprite_draws
+				+ tile_batches as u32
+				+ particle_batches.len() as u32
+				+ gui.draw_list().cmds().len() as u32
+				+ light_draws.len() as u32
+				+ normal_batches
+				+ graph_draws,
+			sprites: drawn.sprites.sprite_count(),
+			shape_batches,
+			tile_chunks: drawn.tiles.len() as u32,
+			particles: particle_batches.iter().map(|batch| batch.instance_count).sum(),
+			lights: light_draws.len() as u32,
+			glyph_atlas: gui.atlas_usage(),
+			texture_slots: self.gfx.texture_array().map(|textures| textures.used()),
+			memory: self.gfx.memory_usage(),
+			swapchain_extent: self.image_extent,
+			swapchain_images: self.framebuffers.len() as u32,
+			swapchain_format: self.surface_format.format,
+			present_mode: self.present_mode,
+		}
 	}
 
 	fn recreate_swapchain(&mut self) {
 		self.frame_data[(!self.frame) as usize].fence.as_ref().unwrap().wait();
 
 		let (caps, image_extent) = get_caps(&self.gfx, &self.surface);
+		debug!("recreating swapchain at {}x{}", image_extent.width, image_extent.height);
 		let (swapchain, image_views) = create_swapchain(
 			&self.gfx,
 			self.surface.clone(),
@@ -186,24 +529,211 @@ impl Window {
 		);
 		self.swapchain = swapchain;
 
-		self.pipeline = create_pipeline(&self.gfx, image_extent, self.render_pass.clone());
-		self.framebuffers = create_framebuffers(&self.render_pass, image_views, image_extent);
+		self.depth_view = create_depth(&self.gfx, image_extent);
+		self.framebuffers = create_framebuffers(&self.render_pass, image_views, &self.depth_view, image_extent);
 
 		self.image_extent = image_extent;
+		if let Err(err) = self.compile_graph() {
+			warn!("dropped the render graph after resizing: {}", err);
+		}
 
 		self.recreate_swapchain = false;
 	}
+
+	fn scene_extent(&self) -> Extent2D {
+		self.virtual_res.map_or(self.image_extent, |res| res.extent())
+	}
+
+	// in-flight frames may still read targets about to be replaced
+	fn wait_frames(&self) {
+		for data in &self.frame_data {
+			if let Some(fence) = &data.fence {
+				fence.wait();
+			}
+		}
+	}
+
+	// allocates the graph's targets for the current size, dropping the graph if its materials can't be built
+	fn compile_graph(&mut self) -> io::Result<()> {
+		let (program, pipeline) = create_present_pipeline(&self.gfx, self.render_pass.clone(), self.virtual_res);
+		self.present_program = program;
+		self.present_pipeline = pipeline;
+
+		// scaling up needs the scene in a target, even without effects
+		let plain;
+		let graph = match &self.graph {
+			Some(graph) => Some(graph),
+			None if self.virtual_res.is_some() => {
+				plain = RenderGraph::new(self.color_format());
+				Some(&plain)
+			},
+			None => None,
+		};
+		let extent = self.scene_extent();
+		let depth_view = match self.virtual_res {
+			Some(_) => create_depth(&self.gfx, extent),
+			None => self.depth_view.clone(),
+		};
+		let frames = self.frame_data.len();
+		let compiled =
+			graph.map(|graph| CompiledGraph::new(&self.gfx, graph, &depth_view, extent, frames, &self.present_program));
+		match compiled.transpose() {
+			Ok(compiled) => self.compiled = compiled,
+			// without it the scene is drawn as if no graph was ever set
+			Err(err) if self.graph.is_some() => {
+				self.graph = None;
+				self.compile_graph()?;
+				return Err(err);
+			},
+			Err(err) => return Err(err),
+		}
+		let lighting = self.compiled.as_ref().and_then(|compiled| compiled.lighting.as_ref());
+		self.light_renderer.bind(&self.gfx, lighting);
+		Ok(())
+	}
+
+	// draws the graph's output into the window, letterboxed if there's a virtual resolution
+	fn present_cmd(&self, frame: usize, framebuffer: &Arc<Framebuffer>, material: &Material) -> CommandBuffer {
+		let inherit = InheritanceInfo {
+			render_pass: self.render_pass.clone(),
+			subpass: 0,
+			framebuffer: Some(framebuffer.clone()),
+		};
+		let scissor = match self.virtual_res {
+			Some(res) => res.viewport(self.image_extent),
+			None => Rect2D::builder().extent(self.image_extent).build(),
+		};
+		let viewport = Viewport::builder()
+			.x(scissor.offset.x as _)
+			.y(scissor.offset.y as _)
+			.width(scissor.extent.width as _)
+			.height(scissor.extent.height as _)
+			.max_depth(1.0)
+			.build();
+		let layout = self.present_program.layout.clone();
+		self.frame_data[frame]
+			.cmdpool
+			.record_secondary(true, false, Some(inherit))
+			.bind_pipeline(self.present_pipeline.clone())
+			.set_viewport(0, &[viewport])
+			.set_scissor(0, &[scissor])
+			.bind_vertex_buffers(0, once(self.gfx.verts.clone() as _), &[0])
+			.bind_descriptor_sets(layout, 0, material.desc_sets().iter().cloned(), &[])
+			.draw(6, 1, 0, 0)
+			.build()
+	}
+}
+
+// everything uploaded for a frame, kept alive until its fence signals since it's rebuilt or dropped every frame
+struct Drawn {
+	// the camera as drawn, with the view snapped
+	camera: Camera,
+	win_size: [f32; 2],
+	tiles: Vec<ChunkDraw>,
+	sprites: PreparedSprites,
+	particles: Option<(Arc<Buffer<[ParticleInstance]>>, Vec<ParticleBatch>)>,
+	lights: Option<(Arc<Buffer<[LightVertex]>>, Vec<LightDraw>)>,
+	normals: Option<(Arc<Buffer<[NormalInstance]>>, Vec<NormalBatch>)>,
+	gui_verts: Option<Arc<Buffer<[GuiVertex]>>>,
 }
 
 struct FrameData {
 	cmdpool: Arc<CommandPool>,
 	fence: Option<Fence>,
+	drawn: Option<Drawn>,
+	// drawing into render textures, run before the scene
+	offscreen: Vec<OffscreenDraw>,
+	timer: GpuTimer,
 }
 impl FrameData {
 	fn new(gfx: &Arc<Gfx>) -> Self {
 		let cmdpool = CommandPool::new(gfx.device.clone(), gfx.queue.family().clone(), true);
-		Self { cmdpool, fence: None }
+		Self { cmdpool, fence: None, drawn: None, offscreen: vec![], timer: GpuTimer::new(gfx) }
+	}
+}
+
+/// Starts a secondary command buffer for `render_pass`, with the viewport and scissor covering `extent`.
+pub(super) fn secondary(
+	cmdpool: &Arc<CommandPool>,
+	render_pass: &Arc<RenderPass>,
+	framebuffer: &Arc<Framebuffer>,
+	extent: Extent2D,
+) -> CommandBufferBuilder {
+	let framebuffer = Some(framebuffer.clone());
+	let inherit = InheritanceInfo { render_pass: render_pass.clone(), subpass: 0, framebuffer };
+	let viewport = Viewport::builder().width(extent.width as _).height(extent.height as _).max_depth(1.0).build();
+	let scissor = Rect2D::builder().extent(extent).build();
+	cmdpool.record_secondary(true, false, Some(inherit)).set_viewport(0, &[viewport]).set_scissor(0, &[scissor])
+}
+
+// runs drawing into render textures, keeping what it draws in `draws` without its commands
+fn record_offscreen(
+	mut cmd: CommandBufferBuilder,
+	timer: &mut GpuTimer,
+	draws: &mut [OffscreenDraw],
+) -> CommandBufferBuilder {
+	if draws.is_empty() {
+		return cmd;
+	}
+	cmd = timer.mark(cmd, "offscreen");
+	// earlier frames may still be sampling the textures
+	cmd = cmd.pipeline_barrier(
+		PipelineStageFlags::FRAGMENT_SHADER,
+		PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+		DependencyFlags::empty(),
+		&[],
+		&[],
+		&[],
+	);
+	for draw in draws {
+		let area = Rect2D::builder().extent(draw.extent).build();
+		let clear = [
+			ClearValue { color: ClearColorValue { float32: draw.clear_color } },
+			ClearValue { depth_stencil: ClearDepthStencilValue { depth: 1.0, stencil: 0 } },
+		];
+		cmd = cmd
+			.begin_render_pass(draw.render_pass.clone(), draw.framebuffer.clone(), area, &clear)
+			.execute_commands(mem::take(&mut draw.secondaries))
+			.end_render_pass();
 	}
+	cmd.pipeline_barrier(
+		PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+		PipelineStageFlags::FRAGMENT_SHADER,
+		DependencyFlags::empty(),
+		&[attachment_read_barrier()],
+		&[],
+		&[],
+	)
+}
+
+// a render pass whose shaders sample what the passes before it drew
+fn sampled_pass(
+	cmd: CommandBufferBuilder,
+	render_pass: &Arc<RenderPass>,
+	framebuffer: &Arc<Framebuffer>,
+	area: Rect2D,
+	clear: &[ClearValue],
+	secondaries: impl IntoIterator<Item = CommandBuffer>,
+) -> CommandBufferBuilder {
+	cmd.pipeline_barrier(
+		PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+		PipelineStageFlags::FRAGMENT_SHADER,
+		DependencyFlags::empty(),
+		&[attachment_read_barrier()],
+		&[],
+		&[],
+	)
+	.begin_render_pass(render_pass.clone(), framebuffer.clone(), area, clear)
+	.execute_commands(secondaries)
+	.end_render_pass()
+}
+
+// makes a pass's color writes visible to the fragment shaders of the passes sampling them
+fn attachment_read_barrier() -> MemoryBarrier {
+	MemoryBarrier::builder()
+		.src_access_mask(AccessFlags::COLOR_ATTACHMENT_WRITE)
+		.dst_access_mask(AccessFlags::SHADER_READ)
+		.build()
 }
 
 fn get_caps(gfx: &Gfx, surface: &Surface<IWindow>) -> (SurfaceCapabilities, Extent2D) {
@@ -245,36 +775,60 @@ fn create_swapchain(
 	);
 
 	let image_views = images
-		.map(|image| {
+		.enumerate()
+		.map(|(i, image)| {
 			let range = ImageSubresourceRange::builder()
 				.aspect_mask(ImageAspectFlags::COLOR)
 				.level_count(1)
 				.layer_count(1)
 				.build();
-			ImageView::new(image, surface_format.format, range)
+			let view = ImageView::new(image, surface_format.format, range);
+			validation::set_name(&gfx.device, &*view, &format!("swapchain image {}", i));
+			view
 		})
 		.collect();
 
 	(swapchain, image_views)
 }
 
-fn create_pipeline(gfx: &Gfx, image_extent: Extent2D, render_pass: Arc<RenderPass>) -> Arc<GraphicsPipeline> {
-	gfx.device
-		.build_graphics_pipeline(gfx.layout.clone(), render_pass)
-		.vertex_shader(gfx.vshader.clone())
-		.fragment_shader(gfx.fshader.clone())
-		.vertex_input::<TriangleVertex>()
-		.viewports(&[Viewport::builder()
-			.width(image_extent.width as _)
-			.height(image_extent.height as _)
-			.max_depth(1.0)
-			.build()])
-		.build()
+// copies the graph's output as is, or scales it up to the window
+fn create_present_pipeline(
+	gfx: &Gfx,
+	render_pass: Arc<RenderPass>,
+	virtual_res: Option<VirtualResolution>,
+) -> (Arc<ShaderProgram>, Arc<GraphicsPipeline>) {
+	let program = match virtual_res.map(|res| res.upscale) {
+		None => block_on(gfx.program("post_blit", "")).unwrap(),
+		Some(Upscale::Integer) => block_on(gfx.program("post_upscale", "")).unwrap(),
+		Some(Upscale::SharpBilinear) => block_on(gfx.program("post_upscale", "sharp")).unwrap(),
+	};
+	let desc = PipelineDesc {
+		layout: program.layout.clone(),
+		render_pass,
+		subpass: 0,
+		vshader: program.vshader.clone(),
+		fshader: program.fshader.clone(),
+		blend: BlendMode::Opaque,
+	};
+	let pipeline = gfx.pipelines.get::<TriangleVertex>(&gfx.device, desc);
+	(program, pipeline)
+}
+
+fn create_depth(gfx: &Gfx, image_extent: Extent2D) -> Arc<ImageView> {
+	let usage = ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT;
+	let (width, height) = (image_extent.width, image_extent.height);
+	let image = Image::new(gfx.device.clone(), ImageType::TYPE_2D, width, height, 1, DEPTH_FORMAT, usage);
+	let range =
+		ImageSubresourceRange::builder().aspect_mask(ImageAspectFlags::DEPTH).level_count(1).layer_count(1).build();
+	let view = ImageView::new(image, DEPTH_FORMAT, range);
+	validation::set_name(&gfx.device, &*view, "depth");
+	view
 }
 
 fn create_framebuffers(
 	render_pass: &Arc<RenderPass>,
 	image_views: Vec<Arc<ImageView>>,
+	depth_view: &Arc<ImageView>,
 	image_extent: Extent2D,
 ) -> Vec<Arc<Framebuffer>> {
 	image_views
@@ -283,7 +837,7 @@ fn create_framebuffers(
 			Framebuffer::new(
 				render_pass.device().clone(),
 				render_pass.clone(),
-				vec![view],
+				vec![view, depth_view.clone()],
 				image_extent.width,
 				image_extent.height,
 			)
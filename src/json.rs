@@ -0,0 +1,357 @@
+use std::{char, fmt, io, str};
+
+/// A parsed JSON document. Objects keep their keys in file order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Json>),
+	Object(Vec<(String, Json)>),
+}
+impl Json {
+	pub fn parse(src: &str) -> io::Result<Self> {
+		let mut parser = Parser { src: src.as_bytes(), pos: 0 };
+		let value = parser.value()?;
+		parser.skip_whitespace();
+		if parser.pos < parser.src.len() {
+			return Err(parser.err("trailing characters"));
+		}
+		Ok(value)
+	}
+
+	/// Looks up `key` if this is an object.
+	pub fn get(&self, key: &str) -> Option<&Json> {
+		self.as_object()?.iter().find(|(k, _)| k == key).map(|(_, value)| value)
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match self {
+			Self::Bool(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	pub fn as_f64(&self) -> Option<f64> {
+		match self {
+			Self::Number(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	pub fn as_u32(&self) -> Option<u32> {
+		self.as_f64().filter(|&value| value >= 0.0 && value <= u32::MAX as f64).map(|value| value as u32)
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			Self::String(value) => Some(value),
+			_ => None,
+		}
+	}
+
+	pub fn as_array(&self) -> Option<&[Json]> {
+		match self {
+			Self::Array(values) => Some(values),
+			_ => None,
+		}
+	}
+
+	pub fn as_object(&self) -> Option<&[(String, Json)]> {
+		match self {
+			Self::Object(members) => Some(members),
+			_ => None,
+		}
+	}
+}
+// compact, with no whitespace between tokens
+impl fmt::Display for Json {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Null => f.write_str("null"),
+			Self::Bool(value) => write!(f, "{}", value),
+			// JSON has no NaN or infinities
+			Self::Number(value) if !value.is_finite() => f.write_str("null"),
+			Self::Number(value) => write!(f, "{}", value),
+			Self::String(value) => write_string(f, value),
+			Self::Array(values) => {
+				f.write_str("[")?;
+				for (i, value) in values.iter().enumerate() {
+					if i > 0 {
+						f.write_str(",")?;
+					}
+					write!(f, "{}", value)?;
+				}
+				f.write_str("]")
+			},
+			Self::Object(members) => {
+				f.write_str("{")?;
+				for (i, (key, value)) in members.iter().enumerate() {
+					if i > 0 {
+						f.write_str(",")?;
+					}
+					write_string(f, key)?;
+					write!(f, ":{}", value)?;
+				}
+				f.write_str("}")
+			},
+		}
+	}
+}
+
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+	f.write_str("\"")?;
+	for ch in value.chars() {
+		match ch {
+			'"' => f.write_str("\\\"")?,
+			'\\' => f.write_str("\\\\")?,
+			'\n' => f.write_str("\\n")?,
+			'\r' => f.write_str("\\r")?,
+			'\t' => f.write_str("\\t")?,
+			ch if (ch as u32) < 0x20 => write!(f, "\\u{:04x}", ch as u32)?,
+			ch => write!(f, "{}", ch)?,
+		}
+	}
+	f.write_str("\"")
+}
+
+struct Parser<'a> {
+	src: &'a [u8],
+	pos: usize,
+}
+impl<'a> Parser<'a> {
+	fn value(&mut self) -> io::Result<Json> {
+		self.skip_whitespace();
+		match self.peek() {
+			Some(b'{') => self.object(),
+			Some(b'[') => self.array(),
+			Some(b'"') => Ok(Json::String(self.string()?)),
+			Some(b't') => self.keyword("true", Json::Bool(true)),
+			Some(b'f') => self.keyword("false", Json::Bool(false)),
+			Some(b'n') => self.keyword("null", Json::Null),
+			Some(b'-') | Some(b'0'..=b'9') => self.number(),
+			Some(_) => Err(self.err("unexpected character")),
+			None => Err(self.err("unexpected end of input")),
+		}
+	}
+
+	fn object(&mut self) -> io::Result<Json> {
+		self.pos += 1;
+		let mut members = vec![];
+		self.skip_whitespace();
+		if self.eat(b'}') {
+			return Ok(Json::Object(members));
+		}
+		loop {
+			self.skip_whitespace();
+			if self.peek() != Some(b'"') {
+				return Err(self.err("expected a key"));
+			}
+			let key = self.string()?;
+			self.skip_whitespace();
+			if !self.eat(b':') {
+				return Err(self.err("expected `:`"));
+			}
+			members.push((key, self.value()?));
+			self.skip_whitespace();
+			if self.eat(b'}') {
+				return Ok(Json::Object(members));
+			}
+			if !self.eat(b',') {
+				return Err(self.err("expected `,` or `}`"));
+			}
+		}
+	}
+
+	fn array(&mut self) -> io::Result<Json> {
+		self.pos += 1;
+		let mut values = vec![];
+		self.skip_whitespace();
+		if self.eat(b']') {
+			return Ok(Json::Array(values));
+		}
+		loop {
+			values.push(self.value()?);
+			self.skip_whitespace();
+			if self.eat(b']') {
+				return Ok(Json::Array(values));
+			}
+			if !self.eat(b',') {
+				return Err(self.err("expected `,` or `]`"));
+			}
+		}
+	}
+
+	fn string(&mut self) -> io::Result<String> {
+		self.pos += 1;
+		let mut out = String::new();
+		loop {
+			// copy runs of plain characters at once, which also keeps multi-byte characters intact
+			let start = self.pos;
+			while self.peek().is_some_and(|byte| byte != b'"' && byte != b'\\') {
+				self.pos += 1;
+			}
+			out.push_str(str::from_utf8(&self.src[start..self.pos]).unwrap());
+
+			match self.next() {
+				Some(b'"') => return Ok(out),
+				Some(b'\\') => {
+					let ch = match self.next() {
+						Some(b'"') => '"',
+						Some(b'\\') => '\\',
+						Some(b'/') => '/',
+						Some(b'b') => '\u{8}',
+						Some(b'f') => '\u{c}',
+						Some(b'n') => '\n',
+						Some(b'r') => '\r',
+						Some(b't') => '\t',
+						Some(b'u') => self.unicode_escape()?,
+						_ => return Err(self.err("invalid escape")),
+					};
+					out.push(ch);
+				},
+				_ => return Err(self.err("unterminated string")),
+			}
+		}
+	}
+
+	fn unicode_escape(&mut self) -> io::Result<char> {
+		let high = self.hex4()?;
+		if !(0xd800..0xdc00).contains(&high) {
+			return char::from_u32(high).ok_or_else(|| self.err("invalid unicode escape"));
+		}
+		// characters outside the BMP are written as a surrogate pair
+		if !(self.eat(b'\\') && self.eat(b'u')) {
+			return Err(self.err("unpaired surrogate"));
+		}
+		let low = self.hex4()?;
+		if !(0xdc00..0xe000).contains(&low) {
+			return Err(self.err("unpaired surrogate"));
+		}
+		char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+			.ok_or_else(|| self.err("invalid unicode escape"))
+	}
+
+	fn hex4(&mut self) -> io::Result<u32> {
+		let digits = self.src.get(self.pos..self.pos + 4).ok_or_else(|| self.err("invalid unicode escape"))?;
+		let digits = str::from_utf8(digits).map_err(|_| self.err("invalid unicode escape"))?;
+		let value = u32::from_str_radix(digits, 16).map_err(|_| self.err("invalid unicode escape"))?;
+		self.pos += 4;
+		Ok(value)
+	}
+
+	fn number(&mut self) -> io::Result<Json> {
+		let start = self.pos;
+		while self.peek().is_some_and(|byte| matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+			self.pos += 1;
+		}
+		let number = str::from_utf8(&self.src[start..self.pos]).unwrap();
+		number.parse().map(Json::Number).map_err(|_| self.err("invalid number"))
+	}
+
+	fn keyword(&mut self, keyword: &str, value: Json) -> io::Result<Json> {
+		if !self.src[self.pos..].starts_with(keyword.as_bytes()) {
+			return Err(self.err("unexpected character"));
+		}
+		self.pos += keyword.len();
+		Ok(value)
+	}
+
+	fn skip_whitespace(&mut self) {
+		while self.peek().is_some_and(|byte| byte.is_ascii_whitespace()) {
+			self.pos += 1;
+		}
+	}
+
+	fn peek(&self) -> Option<u8> {
+		self.src.get(self.pos).copied()
+	}
+
+	fn next(&mut self) -> Option<u8> {
+		let byte = self.peek()?;
+		self.pos += 1;
+		Some(byte)
+	}
+
+	fn eat(&mut self, byte: u8) -> bool {
+		let matches = self.peek() == Some(byte);
+		if matches {
+			self.pos += 1;
+		}
+		matches
+	}
+
+	fn err(&self, msg: &str) -> io::Error {
+		io::Error::new(io::ErrorKind::InvalidData, format!("json at byte {}: {}", self.pos, msg))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn values() {
+		let json = Json::parse(r#" { "b": [1, -2.5e3, true, false, null], "a": { }, "c": [] } "#).unwrap();
+		let expected = Json::Object(vec![
+			(
+				"b".to_owned(),
+				Json::Array(vec![
+					Json::Number(1.0),
+					Json::Number(-2500.0),
+					Json::Bool(true),
+					Json::Bool(false),
+					Json::Null,
+				]),
+			),
+			("a".to_owned(), Json::Object(vec![])),
+			("c".to_owned(), Json::Array(vec![])),
+		]);
+		assert_eq!(json, expected);
+		// keys stay in file order
+		let keys: Vec<_> = json.as_object().unwrap().iter().map(|(key, _)| key.as_str()).collect();
+		assert_eq!(keys, ["b", "a", "c"]);
+		assert_eq!(json.get("b").and_then(Json::as_array).map(<[_]>::len), Some(5));
+		assert_eq!(json.get("missing"), None);
+	}
+
+	#[test]
+	fn accessors() {
+		assert_eq!(Json::Number(7.0).as_u32(), Some(7));
+		assert_eq!(Json::Number(-1.0).as_u32(), None);
+		assert_eq!(Json::Number(5e9).as_u32(), None);
+		assert_eq!(Json::String("7".to_owned()).as_u32(), None);
+		assert_eq!(Json::Null.get("a"), None);
+	}
+
+	#[test]
+	fn escapes() {
+		let json = Json::parse(r#""a\"b\\c\/d\n\té😀 ü""#).unwrap();
+		assert_eq!(json, Json::String("a\"b\\c/d\n\t\u{e9}\u{1f600} ü".to_owned()));
+
+		assert!(Json::parse(r#""\x""#).is_err());
+		assert!(Json::parse(r#""\ud83d""#).is_err());
+		assert!(Json::parse(r#""\ud83dA""#).is_err());
+		assert!(Json::parse(r#""\u12""#).is_err());
+		assert!(Json::parse(r#""open"#).is_err());
+	}
+
+	#[test]
+	fn display_round_trips() {
+		let src = r#"{"s":"q\"\\\n\u0001é","n":[0.5,-3,1e21],"e":{},"z":null}"#;
+		let json = Json::parse(src).unwrap();
+		assert_eq!(Json::parse(&json.to_string()).unwrap(), json);
+		assert_eq!(Json::Array(vec![Json::Number(f64::NAN), Json::Bool(true)]).to_string(), "[null,true]");
+	}
+
+	#[test]
+	fn malformed() {
+		for src in ["", "[1,]", "[1 2]", "{\"a\" 1}", "{a: 1}", "{\"a\": 1,}", "tru", "-", "1.2.3", "[] []"] {
+			assert!(Json::parse(src).is_err(), "{:?}", src);
+		}
+		let err = Json::parse("[1, x]").unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+		assert!(err.to_string().starts_with("json at byte 4:"), "{}", err);
+	}
+}
@@ -0,0 +1,18 @@
+pub mod animation;
+pub mod audio;
+mod fs;
+pub mod gfx;
+mod json;
+pub mod logger;
+pub mod particles;
+pub mod physics;
+pub mod profile;
+pub mod scene;
+mod threads;
+pub mod tilemap;
+pub mod tween;
+mod xml;
+
+// for `vertex_input!` in crates that don't depend on these themselves
+#[doc(hidden)]
+pub use {memoffset, vulkan};
@@ -0,0 +1,174 @@
+use crate::json::Json;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::{
+	cmp::Reverse,
+	env,
+	fs::File,
+	io::{self, Write},
+	path::Path,
+	sync::Mutex,
+	thread,
+	time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+// module paths are logged without it, so `game_engine::gfx::window` is `gfx::window`
+const CRATE_PREFIX: &str = concat!(env!("CARGO_CRATE_NAME"), "::");
+
+/// Which records are logged and where. Targets are module paths within the engine, like `gfx` or `gfx::validation`,
+/// plus `fs`, `input` and `assets`, and filters apply to a target and everything under it.
+pub struct LogConfig {
+	level: LevelFilter,
+	// most specific first
+	targets: Vec<(String, LevelFilter)>,
+	json: Option<File>,
+}
+impl LogConfig {
+	/// Logs info and up, and debug and up in debug builds.
+	pub fn new() -> Self {
+		let level = if cfg!(debug_assertions) { LevelFilter::Debug } else { LevelFilter::Info };
+		Self { level, targets: vec![], json: None }
+	}
+
+	/// `new`, with filters from `GAME_LOG` if it's set, like `warn,gfx=debug,gfx::validation=trace`.
+	pub fn from_env() -> Self {
+		Self::new().filters(&env::var("GAME_LOG").unwrap_or_default())
+	}
+
+	fn filters(mut self, filters: &str) -> Self {
+		for filter in filters.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+			let mut kv = filter.splitn(2, '=');
+			match (kv.next(), kv.next().map(str::parse)) {
+				(Some(target), Some(Ok(level))) => self = self.target(target, level),
+				(Some(level), None) => match level.parse() {
+					Ok(level) => self = self.level(level),
+					Err(_) => eprintln!("GAME_LOG: invalid level `{}`", level),
+				},
+				_ => eprintln!("GAME_LOG: invalid filter `{}`", filter),
+			}
+		}
+		self
+	}
+
+	/// The level for targets without a filter of their own.
+	pub fn level(mut self, level: LevelFilter) -> Self {
+		self.level = level;
+		self
+	}
+
+	pub fn target(mut self, target: &str, level: LevelFilter) -> Self {
+		self.targets.retain(|(t, _)| t != target);
+		self.targets.push((target.to_owned(), level));
+		self.targets.sort_by_key(|(target, _)| Reverse(target.len()));
+		self
+	}
+
+	/// Also writes every record to `path` as JSON, one object per line.
+	pub fn json_file(mut self, path: impl AsRef<Path>) -> io::Result<Self> {
+		self.json = Some(File::create(path)?);
+		Ok(self)
+	}
+
+	fn level_for(&self, target: &str) -> LevelFilter {
+		let matches =
+			|filter: &str| target.strip_prefix(filter).is_some_and(|rest| rest.is_empty() || rest.starts_with("::"));
+		self.targets.iter().find(|(filter, _)| matches(filter)).map_or(self.level, |&(_, level)| level)
+	}
+}
+impl Default for LogConfig {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Sends records from the `log` macros to stderr, and to a JSON file if configured. Only the first call does anything.
+pub fn init(mut config: LogConfig) {
+	let max = config.targets.iter().map(|&(_, level)| level).chain(Some(config.level)).max().unwrap();
+	let logger = Logger { start: Instant::now(), json: config.json.take().map(Mutex::new), config };
+	if log::set_boxed_logger(Box::new(logger)).is_ok() {
+		log::set_max_level(max);
+	}
+}
+
+struct Logger {
+	config: LogConfig,
+	start: Instant,
+	json: Option<Mutex<File>>,
+}
+impl Log for Logger {
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		metadata.level() <= self.config.level_for(target(metadata.target()))
+	}
+
+	fn log(&self, record: &Record) {
+		if !self.enabled(record.metadata()) {
+			return;
+		}
+
+		let target = target(record.target());
+		let elapsed = self.start.elapsed().as_secs_f32();
+		eprintln!("[{:9.3} {:5} {}] {}", elapsed, record.level(), target, record.args());
+
+		if let Some(file) = &self.json {
+			let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+			let thread = thread::current();
+			let mut fields = vec![
+				("time".to_owned(), Json::Number(time)),
+				("level".to_owned(), Json::String(record.level().to_string())),
+				("target".to_owned(), Json::String(target.to_owned())),
+				("thread".to_owned(), thread.name().map_or(Json::Null, |name| Json::String(name.to_owned()))),
+				("message".to_owned(), Json::String(record.args().to_string())),
+			];
+			if let (Some(path), Some(line)) = (record.file(), record.line()) {
+				fields.push(("location".to_owned(), Json::String(format!("{}:{}", path, line))));
+			}
+			// logging mustn't fail whatever it was logging about
+			let _ = writeln!(file.lock().unwrap(), "{}", Json::Object(fields));
+		}
+	}
+
+	fn flush(&self) {
+		if let Some(file) = &self.json {
+			let _ = file.lock().unwrap().flush();
+		}
+	}
+}
+
+fn target(target: &str) -> &str {
+	target.strip_prefix(CRATE_PREFIX).unwrap_or(target)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn filters() {
+		let config = LogConfig::new().filters(" warn, gfx=debug ,gfx::validation=trace,,fs=off");
+		assert_eq!(config.level, LevelFilter::Warn);
+		let targets: Vec<_> = config.targets.iter().map(|(target, level)| (target.as_str(), *level)).collect();
+		let expected = [("gfx::validation", LevelFilter::Trace), ("gfx", LevelFilter::Debug), ("fs", LevelFilter::Off)];
+		assert_eq!(targets, expected);
+
+		// bad filters are skipped, and later filters replace earlier ones
+		let config = LogConfig::new().filters("loud,gfx=loud,=,gfx=info,error,gfx=warn");
+		assert_eq!(config.level, LevelFilter::Error);
+		assert_eq!(config.targets, [("gfx".to_owned(), LevelFilter::Warn)]);
+	}
+
+	#[test]
+	fn level_for() {
+		let config = LogConfig::new()
+			.level(LevelFilter::Warn)
+			.target("gfx", LevelFilter::Debug)
+			.target("gfx::validation", LevelFilter::Trace);
+		assert_eq!(config.level_for("gfx"), LevelFilter::Debug);
+		assert_eq!(config.level_for("gfx::window"), LevelFilter::Debug);
+		assert_eq!(config.level_for("gfx::validation"), LevelFilter::Trace);
+		assert_eq!(config.level_for("gfx::validation::names"), LevelFilter::Trace);
+		// prefixes only match whole path segments
+		assert_eq!(config.level_for("gfxtra"), LevelFilter::Warn);
+		assert_eq!(config.level_for("gfx::validations"), LevelFilter::Debug);
+		assert_eq!(config.level_for("audio"), LevelFilter::Warn);
+		assert_eq!(config.level_for(target(concat!(env!("CARGO_CRATE_NAME"), "::gfx::sprite"))), LevelFilter::Debug);
+	}
+}
@@ -1,15 +1,22 @@
-mod fs;
-mod gfx;
-mod threads;
-
 use futures::executor::block_on;
-use gfx::{window::Window, Gfx};
+use game_engine::{
+	gfx::{
+		gui::{layout::Rect, Gui},
+		sprite::{Sprite, SpriteList},
+		window::Window,
+		Gfx,
+	},
+	logger::{self, LogConfig},
+	profile,
+};
+use log::{error, info};
 use winit::{
-	event::{Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+	event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
 	event_loop::{ControlFlow, EventLoop},
 };
 
 fn main() {
+	logger::init(LogConfig::from_env());
 	block_on(amain());
 }
 
@@ -18,22 +25,46 @@ async fn amain() {
 
 	let event_loop = EventLoop::new();
 	let mut window = Window::new(gfx.clone(), &event_loop);
+	let mut gui = Gui::new(window.scale_factor());
+
+	// TODO: replace with real sprites
+	let mut sprites = SpriteList::new();
+	sprites.push(Sprite::new([100.0, 100.0].into(), 0));
+	sprites.push(Sprite::new([300.0, 200.0].into(), 1).transparent(true));
 
 	event_loop.run(move |event, _window, control| {
 		*control = ControlFlow::Poll;
 
 		match event {
+			Event::WindowEvent { event, .. } if window.debug.handle_event(&event) => (),
+			Event::WindowEvent { event, .. } if gui.handle_event(&event) => (),
 			Event::WindowEvent { event, .. } => match event {
 				WindowEvent::CloseRequested => *control = ControlFlow::Exit,
-				WindowEvent::KeyboardInput { input: KeyboardInput { virtual_keycode, .. }, .. } => {
+				WindowEvent::KeyboardInput { input: KeyboardInput { state, virtual_keycode, .. }, .. } => {
 					match virtual_keycode {
 						Some(VirtualKeyCode::Escape) => *control = ControlFlow::Exit,
+						Some(VirtualKeyCode::F5) if state == ElementState::Pressed => {
+							match block_on(profile::export_chrome_trace("trace.json")) {
+								Ok(()) => info!(target: "fs", "wrote profile to trace.json"),
+								Err(err) => error!(target: "fs", "couldn't write trace.json: {}", err),
+							}
+						},
 						_ => (),
 					}
 				},
 				_ => (),
 			},
-			Event::MainEventsCleared => window.draw(),
+			Event::MainEventsCleared => {
+				let scope = profile::scope("gui");
+				gui.frame(window.size(), |ui| {
+					ui.window("Debug", Rect::new(10.0, 10.0, 200.0, 80.0), |ui| {
+						ui.label(&format!("sprites: {}", sprites.len()));
+					});
+				});
+				drop(scope);
+				window.draw(&sprites, &[], &[], &mut gui);
+			},
+			Event::LoopDestroyed => gfx.save_pipeline_cache(),
 			_ => (),
 		};
 	});
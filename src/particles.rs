@@ -0,0 +1,293 @@
+pub mod config;
+
+use config::{EmitterConfig, Shape};
+use nalgebra::{Rotation2, Vector2};
+use std::{
+	f32::consts::PI,
+	sync::{
+		atomic::{AtomicU32, Ordering},
+		Arc,
+	},
+	time::Duration,
+};
+
+// gives every emitter a different random sequence
+static NEXT_SEED: AtomicU32 = AtomicU32::new(0x9e37_79b9);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particle {
+	/// World pixels, the center of the particle.
+	pub pos: Vector2<f32>,
+	pub vel: Vector2<f32>,
+	pub rotation: f32,
+	pub spin: f32,
+	/// Seconds.
+	pub age: f32,
+	pub lifetime: f32,
+}
+impl Particle {
+	/// How far along its life the particle is, from 0 to 1.
+	pub fn progress(&self) -> f32 {
+		(self.age / self.lifetime).min(1.0)
+	}
+}
+
+/// Spawns and simulates particles. Particles move in world space once spawned, so moving the emitter leaves a trail.
+pub struct Emitter {
+	pub config: Arc<EmitterConfig>,
+	pub pos: Vector2<f32>,
+	/// Whether new particles are spawned at `config.rate`. Bursts ignore it.
+	pub emitting: bool,
+	particles: Vec<Particle>,
+	// fractional particles owed by the continuous rate
+	pending: f32,
+	// seconds spent emitting
+	elapsed: f32,
+	rng: Rng,
+}
+impl Emitter {
+	pub fn new(config: Arc<EmitterConfig>, pos: Vector2<f32>) -> Self {
+		let seed = NEXT_SEED.fetch_add(0x6d2b_79f5, Ordering::Relaxed);
+		Self { config, pos, emitting: true, particles: vec![], pending: 0.0, elapsed: 0.0, rng: Rng(seed | 1) }
+	}
+
+	/// Spawns `count` particles at once, for explosions and impacts.
+	pub fn burst(&mut self, count: u32) {
+		let free = self.config.max_particles.saturating_sub(self.particles.len());
+		for _ in 0..(count as usize).min(free) {
+			self.spawn(0.0);
+		}
+	}
+
+	pub fn particles(&self) -> &[Particle] {
+		&self.particles
+	}
+
+	/// Done emitting and every particle has died, so the emitter can be dropped.
+	pub fn finished(&self) -> bool {
+		let emitting = self.emitting && self.config.rate > 0.0 && self.config.duration.is_none_or(|d| self.elapsed < d);
+		!emitting && self.particles.is_empty()
+	}
+
+	pub fn update(&mut self, dt: Duration) {
+		let dt = dt.as_secs_f32();
+		let config = &*self.config;
+		for particle in &mut self.particles {
+			step(config, particle, dt);
+		}
+		self.particles.retain(|particle| particle.age < particle.lifetime);
+
+		if !self.emitting || config.rate <= 0.0 {
+			return;
+		}
+		// only the part of the frame still within the duration emits
+		let active = match config.duration {
+			Some(duration) => dt.min(duration - self.elapsed).max(0.0),
+			None => dt,
+		};
+		self.elapsed += dt;
+		let before = self.pending;
+		self.pending += active * config.rate;
+		let count = self.pending.floor();
+		self.pending -= count;
+		// after a long frame only the last particles owed can still be alive, and only as many as fit are spawned
+		let (count, rate) = (count as usize, config.rate);
+		let free = config.max_particles.saturating_sub(self.particles.len());
+		for i in count - count.min(free)..count {
+			// spread spawn times over the frame, so low frame rates don't make particles clump
+			let spawned_at = (i as f32 + 1.0 - before) / rate;
+			self.spawn(dt - spawned_at);
+		}
+	}
+
+	// `age` is how long ago in this frame the particle was born
+	fn spawn(&mut self, age: f32) {
+		let config = &self.config;
+		if self.particles.len() >= config.max_particles {
+			return;
+		}
+		let rng = &mut self.rng;
+		let offset = match config.shape {
+			Shape::Point => Vector2::zeros(),
+			Shape::Circle(radius) => {
+				// square root keeps the density even across the disc
+				let (angle, dist) = (rng.range(0.0, PI * 2.0), radius * rng.next().sqrt());
+				Vector2::new(angle.cos(), angle.sin()) * dist
+			},
+			Shape::Box(half) => Vector2::new(rng.range(-half.x, half.x), rng.range(-half.y, half.y)),
+			Shape::Line(to) => to * rng.next(),
+		};
+		let angle = config.direction + rng.range(-config.spread, config.spread);
+		let speed = rng.range(config.speed.0, config.speed.1);
+		let mut particle = Particle {
+			pos: self.pos + offset,
+			vel: Rotation2::new(angle) * Vector2::new(speed, 0.0),
+			rotation: rng.range(0.0, PI * 2.0),
+			spin: rng.range(config.spin.0, config.spin.1),
+			age: 0.0,
+			lifetime: rng.range(config.lifetime.0, config.lifetime.1),
+		};
+		if age > 0.0 {
+			step(config, &mut particle, age);
+		}
+		if particle.age < particle.lifetime {
+			self.particles.push(particle);
+		}
+	}
+}
+
+fn step(config: &EmitterConfig, particle: &mut Particle, dt: f32) {
+	particle.vel += config.gravity * dt;
+	particle.vel *= (-config.drag * dt).exp();
+	particle.pos += particle.vel * dt;
+	particle.rotation += particle.spin * dt;
+	particle.age += dt;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EmitterId(u64);
+
+/// Emitters updated and drawn together. Emitters that finish are removed unless kept.
+#[derive(Default)]
+pub struct ParticleSystem {
+	emitters: Vec<(EmitterId, Emitter, bool)>,
+	next_id: u64,
+}
+impl ParticleSystem {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds an emitter that is removed once it finishes, for one-off effects.
+	pub fn add(&mut self, emitter: Emitter) -> EmitterId {
+		self.insert(emitter, false)
+	}
+
+	/// Adds an emitter that stays until removed, for effects that are turned on and off.
+	pub fn add_persistent(&mut self, emitter: Emitter) -> EmitterId {
+		self.insert(emitter, true)
+	}
+
+	pub fn remove(&mut self, id: EmitterId) -> Option<Emitter> {
+		let idx = self.emitters.iter().position(|(emitter_id, ..)| *emitter_id == id)?;
+		Some(self.emitters.remove(idx).1)
+	}
+
+	pub fn get(&self, id: EmitterId) -> Option<&Emitter> {
+		self.emitters.iter().find(|(emitter_id, ..)| *emitter_id == id).map(|(_, emitter, _)| emitter)
+	}
+
+	pub fn get_mut(&mut self, id: EmitterId) -> Option<&mut Emitter> {
+		self.emitters.iter_mut().find(|(emitter_id, ..)| *emitter_id == id).map(|(_, emitter, _)| emitter)
+	}
+
+	pub fn emitters(&self) -> impl Iterator<Item = &Emitter> {
+		self.emitters.iter().map(|(_, emitter, _)| emitter)
+	}
+
+	pub fn particle_count(&self) -> usize {
+		self.emitters().map(|emitter| emitter.particles.len()).sum()
+	}
+
+	pub fn update(&mut self, dt: Duration) {
+		for (_, emitter, _) in &mut self.emitters {
+			emitter.update(dt);
+		}
+		self.emitters.retain(|(_, emitter, persistent)| *persistent || !emitter.finished());
+	}
+
+	fn insert(&mut self, emitter: Emitter, persistent: bool) -> EmitterId {
+		let id = EmitterId(self.next_id);
+		self.next_id += 1;
+		self.emitters.push((id, emitter, persistent));
+		id
+	}
+}
+
+// xorshift, plenty for effects
+struct Rng(u32);
+impl Rng {
+	// in 0..1
+	fn next(&mut self) -> f32 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 17;
+		self.0 ^= self.0 << 5;
+		(self.0 >> 8) as f32 / (1 << 24) as f32
+	}
+
+	fn range(&mut self, min: f32, max: f32) -> f32 {
+		min + (max - min) * self.next()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn start(config: EmitterConfig) -> Emitter {
+		Emitter::new(Arc::new(config.lifetime(10.0, 10.0)), Vector2::zeros())
+	}
+
+	#[test]
+	fn rate() {
+		let mut emitter = start(EmitterConfig::new(None).rate(8.0));
+		emitter.update(Duration::from_millis(250));
+		assert_eq!(emitter.particles().len(), 2);
+		for _ in 0..3 {
+			emitter.update(Duration::from_millis(250));
+		}
+		assert_eq!(emitter.particles().len(), 8);
+		// spawned evenly across frames, an eighth of a second apart with the newest just born
+		let mut ages: Vec<_> = emitter.particles().iter().map(|particle| particle.age).collect();
+		ages.sort_by(f32::total_cmp);
+		for (i, age) in ages.iter().enumerate() {
+			assert!((age - i as f32 / 8.0).abs() < 1e-4, "{:?}", ages);
+		}
+	}
+
+	#[test]
+	fn duration() {
+		let mut emitter = start(EmitterConfig::new(None).rate(8.0).duration(0.5));
+		for _ in 0..8 {
+			emitter.update(Duration::from_millis(250));
+		}
+		assert_eq!(emitter.particles().len(), 4);
+
+		// the duration ending partway through a frame only emits for the part before it
+		let mut emitter = start(EmitterConfig::new(None).rate(8.0).duration(0.5));
+		emitter.update(Duration::from_secs(1));
+		assert_eq!(emitter.particles().len(), 4);
+	}
+
+	#[test]
+	fn finished() {
+		let mut emitter = start(EmitterConfig::new(None).rate(8.0).duration(0.5));
+		assert!(!emitter.finished());
+		emitter.update(Duration::from_secs(1));
+		// done emitting but particles are alive
+		assert!(!emitter.finished());
+		emitter.update(Duration::from_secs(10));
+		assert!(emitter.finished());
+
+		// never finishes on its own without a duration, unless stopped
+		let mut emitter = start(EmitterConfig::new(None).rate(8.0));
+		emitter.update(Duration::from_secs(100));
+		assert!(!emitter.finished());
+		emitter.emitting = false;
+		emitter.update(Duration::from_secs(10));
+		assert!(emitter.finished());
+	}
+
+	#[test]
+	fn max_particles() {
+		let mut emitter = start(EmitterConfig::new(None).max_particles(5));
+		emitter.burst(u32::MAX);
+		assert_eq!(emitter.particles().len(), 5);
+
+		let mut emitter = start(EmitterConfig::new(None).rate(1e6).max_particles(5));
+		emitter.update(Duration::from_secs(1000));
+		assert_eq!(emitter.particles().len(), 5);
+		// the newest particles are the ones kept
+		assert!(emitter.particles().iter().all(|particle| particle.age < 1e-3));
+	}
+}
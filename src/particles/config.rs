@@ -0,0 +1,147 @@
+use crate::{
+	gfx::{pipeline::BlendMode, texture::Subtex},
+	tween::curve::{Curve, Interp},
+};
+use nalgebra::Vector2;
+
+/// Where particles appear, relative to the emitter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+	Point,
+	/// Anywhere inside the circle.
+	Circle(f32),
+	/// Anywhere inside a box of these half extents.
+	Box(Vector2<f32>),
+	/// Anywhere on the segment from the emitter to this offset.
+	Line(Vector2<f32>),
+}
+
+/// Everything about how an emitter spawns and moves particles. Ranges are `(min, max)`, picked uniformly for each
+/// particle.
+#[derive(Clone)]
+pub struct EmitterConfig {
+	/// Drawn untextured when `None`.
+	pub texture: Option<Subtex>,
+	pub blend: BlendMode,
+	/// Draw order, shared with `Sprite::layer`.
+	pub layer: u16,
+	pub shape: Shape,
+	/// Particles per second while emitting.
+	pub rate: f32,
+	/// Seconds to keep emitting, or forever for `None`.
+	pub duration: Option<f32>,
+	pub max_particles: usize,
+	/// Seconds.
+	pub lifetime: (f32, f32),
+	/// Pixels per second.
+	pub speed: (f32, f32),
+	/// Radians, clockwise from +x.
+	pub direction: f32,
+	/// Radians either side of `direction`.
+	pub spread: f32,
+	/// Pixels per second squared.
+	pub gravity: Vector2<f32>,
+	/// Fraction of velocity lost per second.
+	pub drag: f32,
+	/// Radians per second.
+	pub spin: (f32, f32),
+	/// Over each particle's lifetime, from 0 to 1.
+	pub color: Curve<[f32; 4]>,
+	/// Width in pixels over each particle's lifetime, from 0 to 1. The height follows the texture's aspect ratio.
+	pub size: Curve<f32>,
+}
+impl EmitterConfig {
+	pub fn new(texture: Option<Subtex>) -> Self {
+		Self {
+			texture,
+			blend: BlendMode::Alpha,
+			layer: 0,
+			shape: Shape::Point,
+			rate: 0.0,
+			duration: None,
+			max_particles: 1000,
+			lifetime: (1.0, 1.0),
+			speed: (0.0, 0.0),
+			direction: 0.0,
+			spread: std::f32::consts::PI,
+			gravity: Vector2::zeros(),
+			drag: 0.0,
+			spin: (0.0, 0.0),
+			color: Curve::new(0.0, [1.0; 4], Interp::Linear),
+			size: Curve::new(0.0, 8.0, Interp::Linear),
+		}
+	}
+
+	pub fn blend(mut self, blend: BlendMode) -> Self {
+		self.blend = blend;
+		self
+	}
+
+	pub fn layer(mut self, layer: u16) -> Self {
+		self.layer = layer;
+		self
+	}
+
+	pub fn shape(mut self, shape: Shape) -> Self {
+		self.shape = shape;
+		self
+	}
+
+	pub fn rate(mut self, rate: f32) -> Self {
+		self.rate = rate;
+		self
+	}
+
+	pub fn duration(mut self, duration: f32) -> Self {
+		self.duration = Some(duration);
+		self
+	}
+
+	pub fn max_particles(mut self, max_particles: usize) -> Self {
+		self.max_particles = max_particles;
+		self
+	}
+
+	pub fn lifetime(mut self, min: f32, max: f32) -> Self {
+		self.lifetime = (min, max);
+		self
+	}
+
+	pub fn speed(mut self, min: f32, max: f32) -> Self {
+		self.speed = (min, max);
+		self
+	}
+
+	pub fn direction(mut self, direction: f32, spread: f32) -> Self {
+		self.direction = direction;
+		self.spread = spread;
+		self
+	}
+
+	pub fn gravity(mut self, gravity: Vector2<f32>) -> Self {
+		self.gravity = gravity;
+		self
+	}
+
+	pub fn drag(mut self, drag: f32) -> Self {
+		self.drag = drag;
+		self
+	}
+
+	pub fn spin(mut self, min: f32, max: f32) -> Self {
+		self.spin = (min, max);
+		self
+	}
+
+	/// Keys are at times from 0 to 1.
+	pub fn color(mut self, color: Curve<[f32; 4]>) -> Self {
+		self.color = color;
+		self
+	}
+
+	/// Keys are at times from 0 to 1.
+	pub fn size(mut self, size: Curve<f32>) -> Self {
+		self.size = size;
+		self
+	}
+}
@@ -0,0 +1,510 @@
+pub mod body;
+mod broadphase;
+pub mod narrowphase;
+pub mod query;
+pub mod shape;
+mod solver;
+
+use body::{BodyType, Collider, RigidBody};
+use broadphase::SpatialHash;
+use nalgebra::{Isometry2, Point2, Vector2};
+use narrowphase::{collide, Manifold};
+use shape::Aabb;
+use solver::{ContactConstraint, SolverBody};
+use std::{collections::BTreeSet, time::Duration};
+
+// don't let a long frame turn into a spiral of ever more catch-up steps
+const MAX_STEPS_PER_UPDATE: u32 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BodyHandle(Key);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ColliderHandle(Key);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerEvent {
+	Enter { sensor: ColliderHandle, other: ColliderHandle },
+	Exit { sensor: ColliderHandle, other: ColliderHandle },
+}
+
+/// Simulates rigid bodies with a fixed timestep. Given the same inputs and the same sequence of steps, results are
+/// identical, so replays only need to record inputs.
+pub struct World {
+	pub gravity: Vector2<f32>,
+	pub velocity_iterations: usize,
+	timestep: f32,
+	accumulator: f32,
+	bodies: Slots<RigidBody>,
+	colliders: Slots<Collider>,
+	broadphase: SpatialHash,
+	// set when colliders may have moved or changed since the broadphase was filled
+	broadphase_stale: bool,
+	contacts: Vec<(ColliderHandle, ColliderHandle, Manifold)>,
+	// pairs with at least one sensor, lower handle first
+	overlaps: BTreeSet<(ColliderHandle, ColliderHandle)>,
+	events: Vec<TriggerEvent>,
+}
+impl World {
+	pub fn new(gravity: Vector2<f32>, timestep: f32) -> Self {
+		Self {
+			gravity,
+			velocity_iterations: 8,
+			timestep,
+			accumulator: 0.0,
+			bodies: Slots::new(),
+			colliders: Slots::new(),
+			broadphase: SpatialHash::new(64.0),
+			broadphase_stale: true,
+			contacts: vec![],
+			overlaps: BTreeSet::new(),
+			events: vec![],
+		}
+	}
+
+	pub fn timestep(&self) -> f32 {
+		self.timestep
+	}
+
+	/// Grid cell size for the broadphase. Roughly the size of a typical collider works best.
+	pub fn set_cell_size(&mut self, cell_size: f32) {
+		self.broadphase.set_cell_size(cell_size);
+		self.broadphase_stale = true;
+	}
+
+	pub fn add_body(&mut self, body: RigidBody) -> BodyHandle {
+		BodyHandle(self.bodies.insert(body))
+	}
+
+	/// Removes the body along with its colliders.
+	pub fn remove_body(&mut self, handle: BodyHandle) -> Option<RigidBody> {
+		let body = self.bodies.remove(handle.0)?;
+		self.broadphase_stale = true;
+		for &collider in &body.colliders {
+			self.detach_collider(collider);
+		}
+		Some(body)
+	}
+
+	pub fn body(&self, handle: BodyHandle) -> Option<&RigidBody> {
+		self.bodies.get(handle.0)
+	}
+
+	pub fn body_mut(&mut self, handle: BodyHandle) -> Option<&mut RigidBody> {
+		self.broadphase_stale = true;
+		self.bodies.get_mut(handle.0)
+	}
+
+	pub fn bodies(&self) -> impl Iterator<Item = (BodyHandle, &RigidBody)> {
+		self.bodies.iter().map(|(key, body)| (BodyHandle(key), body))
+	}
+
+	pub fn add_collider(&mut self, body: BodyHandle, mut collider: Collider) -> ColliderHandle {
+		collider.body = Some(body);
+		self.broadphase_stale = true;
+		let handle = ColliderHandle(self.colliders.insert(collider));
+		self.bodies.get_mut(body.0).unwrap().colliders.push(handle);
+		self.update_mass(body);
+		handle
+	}
+
+	pub fn remove_collider(&mut self, handle: ColliderHandle) -> Option<Collider> {
+		let collider = self.detach_collider(handle)?;
+		self.broadphase_stale = true;
+		let body = collider.body();
+		self.bodies.get_mut(body.0).unwrap().colliders.retain(|&c| c != handle);
+		self.update_mass(body);
+		Some(collider)
+	}
+
+	pub fn collider(&self, handle: ColliderHandle) -> Option<&Collider> {
+		self.colliders.get(handle.0)
+	}
+
+	/// Call `update_mass` after changing the collider's shape, offset, density or sensor flag.
+	pub fn collider_mut(&mut self, handle: ColliderHandle) -> Option<&mut Collider> {
+		self.broadphase_stale = true;
+		self.colliders.get_mut(handle.0)
+	}
+
+	pub fn colliders(&self) -> impl Iterator<Item = (ColliderHandle, &Collider)> {
+		self.colliders.iter().map(|(key, collider)| (ColliderHandle(key), collider))
+	}
+
+	/// Recomputes the body's mass from its colliders.
+	pub fn update_mass(&mut self, handle: BodyHandle) {
+		let body = match self.bodies.get(handle.0) {
+			Some(body) => body,
+			None => return,
+		};
+
+		let props: Vec<_> = body
+			.colliders
+			.iter()
+			.map(|&c| self.colliders.get(c.0).unwrap())
+			.filter(|collider| !collider.sensor)
+			.map(|collider| {
+				let props = collider.shape.mass_properties(collider.density);
+				(props.mass, collider.offset * Point2::from(props.center), props.inertia)
+			})
+			.collect();
+
+		let mass: f32 = props.iter().map(|(mass, ..)| mass).sum();
+		let center = if mass > 0.0 {
+			props.iter().fold(Vector2::zeros(), |center, (m, c, _)| center + c.coords * *m) / mass
+		} else {
+			Vector2::zeros()
+		};
+		let inertia = props.iter().map(|(m, c, i)| i + m * (c.coords - center).norm_squared()).sum();
+
+		self.bodies.get_mut(handle.0).unwrap().set_mass(mass, center, inertia);
+	}
+
+	/// Contacts found during the last step, between non-sensor colliders.
+	pub fn contacts(&self) -> impl Iterator<Item = (ColliderHandle, ColliderHandle, &Manifold)> {
+		self.contacts.iter().map(|(a, b, manifold)| (*a, *b, manifold))
+	}
+
+	/// Trigger events since the last call.
+	pub fn drain_events(&mut self) -> impl Iterator<Item = TriggerEvent> + '_ {
+		self.events.drain(..)
+	}
+
+	/// Advances by as many fixed steps as fit in `dt` and returns how many were taken. Time left over carries into
+	/// the next update.
+	pub fn update(&mut self, dt: Duration) -> u32 {
+		self.accumulator += dt.as_secs_f32();
+		let mut steps = 0;
+		while self.accumulator >= self.timestep {
+			if steps == MAX_STEPS_PER_UPDATE {
+				self.accumulator = 0.0;
+				break;
+			}
+			self.step();
+			self.accumulator -= self.timestep;
+			steps += 1;
+		}
+		steps
+	}
+
+	/// How far between the last step and the next one the leftover time is, for interpolating rendering.
+	pub fn alpha(&self) -> f32 {
+		self.accumulator / self.timestep
+	}
+
+	pub fn step(&mut self) {
+		let dt = self.timestep;
+		for (_, body) in self.bodies.iter_mut() {
+			body.integrate_velocity(self.gravity, dt);
+		}
+
+		if self.broadphase_stale {
+			self.fill_broadphase();
+		}
+		let mut shapes = Vec::with_capacity(self.colliders.capacity());
+		shapes.resize_with(self.colliders.capacity(), || None);
+		for (key, collider) in self.colliders.iter() {
+			shapes[key.idx as usize] = Some(collider.shape.to_world(&self.collider_isometry(collider)));
+		}
+
+		let mut solver_bodies = Vec::with_capacity(self.bodies.capacity());
+		solver_bodies.resize(self.bodies.capacity(), SolverBody {
+			linvel: Vector2::zeros(),
+			angvel: 0.0,
+			inv_mass: 0.0,
+			inv_inertia: 0.0,
+			center: Vector2::zeros(),
+		});
+		for (key, body) in self.bodies.iter() {
+			solver_bodies[key.idx as usize] = SolverBody {
+				linvel: body.linvel,
+				angvel: body.angvel,
+				inv_mass: body.inv_mass,
+				inv_inertia: body.inv_inertia(),
+				center: body.center(),
+			};
+		}
+
+		self.contacts.clear();
+		let mut constraints = vec![];
+		let mut overlaps = BTreeSet::new();
+		for (a, b) in self.broadphase.pairs() {
+			let (a, b) = (self.colliders.key(a), self.colliders.key(b));
+			let (ca, cb) = (self.colliders.get(a).unwrap(), self.colliders.get(b).unwrap());
+			let (body_a, body_b) = (ca.body(), cb.body());
+			if body_a == body_b || !ca.interacts_with(cb) {
+				continue;
+			}
+
+			let sensor = ca.sensor || cb.sensor;
+			let type_a = self.bodies.get(body_a.0).unwrap().body_type();
+			let type_b = self.bodies.get(body_b.0).unwrap().body_type();
+			let dynamic = type_a == BodyType::Dynamic || type_b == BodyType::Dynamic;
+			if !dynamic && !(sensor && (type_a != BodyType::Static || type_b != BodyType::Static)) {
+				continue;
+			}
+
+			let shape = |key: Key| shapes[key.idx as usize].as_ref().unwrap();
+			let manifold = match collide(shape(a), shape(b)) {
+				Some(manifold) => manifold,
+				None => continue,
+			};
+
+			if sensor {
+				overlaps.insert((ColliderHandle(a), ColliderHandle(b)));
+				continue;
+			}
+
+			constraints.push(ContactConstraint::new(
+				body_a.0.idx as usize,
+				body_b.0.idx as usize,
+				&solver_bodies,
+				&manifold,
+				(ca.friction * cb.friction).sqrt(),
+				ca.restitution.max(cb.restitution),
+				dt,
+			));
+			self.contacts.push((ColliderHandle(a), ColliderHandle(b), manifold));
+		}
+
+		for _ in 0..self.velocity_iterations {
+			for constraint in &mut constraints {
+				constraint.solve(&mut solver_bodies);
+			}
+		}
+
+		for (key, body) in self.bodies.iter_mut() {
+			if body.body_type() == BodyType::Dynamic {
+				body.linvel = solver_bodies[key.idx as usize].linvel;
+				body.angvel = solver_bodies[key.idx as usize].angvel;
+			}
+			body.integrate_position(dt);
+		}
+		// ready for queries and the next step, unless bodies are changed in between
+		self.fill_broadphase();
+
+		let entered: Vec<_> = overlaps.difference(&self.overlaps).copied().collect();
+		let exited: Vec<_> = self.overlaps.difference(&overlaps).copied().collect();
+		for (a, b) in entered {
+			self.push_trigger_events(a, b, |sensor, other| TriggerEvent::Enter { sensor, other });
+		}
+		for (a, b) in exited {
+			self.push_trigger_events(a, b, |sensor, other| TriggerEvent::Exit { sensor, other });
+		}
+		self.overlaps = overlaps;
+	}
+
+	fn fill_broadphase(&mut self) {
+		let bounds: Vec<_> = self
+			.colliders
+			.iter()
+			.map(|(key, collider)| (key.idx, collider.shape.bounds(&self.collider_isometry(collider))))
+			.collect();
+		self.broadphase.clear();
+		for (idx, aabb) in bounds {
+			self.broadphase.insert(idx, aabb);
+		}
+		self.broadphase_stale = false;
+	}
+
+	// colliders that may overlap `bounds`, in handle order
+	fn candidates(&self, bounds: &Aabb) -> impl Iterator<Item = (ColliderHandle, &Collider)> {
+		// after bodies were changed it's the broadphase that can't be trusted, not the colliders
+		let ids: Vec<_> = if self.broadphase_stale {
+			self.colliders.iter().map(|(key, _)| key.idx).collect()
+		} else {
+			self.broadphase.query(bounds)
+		};
+		ids.into_iter().map(move |idx| {
+			let key = self.colliders.key(idx);
+			(ColliderHandle(key), self.colliders.get(key).unwrap())
+		})
+	}
+
+	fn collider_isometry(&self, collider: &Collider) -> Isometry2<f32> {
+		self.bodies.get(collider.body().0).unwrap().isometry() * collider.offset
+	}
+
+	fn push_trigger_events(
+		&mut self,
+		a: ColliderHandle,
+		b: ColliderHandle,
+		event: impl Fn(ColliderHandle, ColliderHandle) -> TriggerEvent,
+	) {
+		if self.colliders.get(a.0).is_some_and(|collider| collider.sensor) {
+			self.events.push(event(a, b));
+		}
+		if self.colliders.get(b.0).is_some_and(|collider| collider.sensor) {
+			self.events.push(event(b, a));
+		}
+	}
+
+	// removes the collider from storage, ending any overlaps it was part of
+	fn detach_collider(&mut self, handle: ColliderHandle) -> Option<Collider> {
+		let ended: Vec<_> = self.overlaps.iter().copied().filter(|&(a, b)| a == handle || b == handle).collect();
+		for (a, b) in ended {
+			self.push_trigger_events(a, b, |sensor, other| TriggerEvent::Exit { sensor, other });
+			self.overlaps.remove(&(a, b));
+		}
+		self.contacts.retain(|&(a, b, _)| a != handle && b != handle);
+		self.colliders.remove(handle.0)
+	}
+}
+
+// a slot index along with how many times the slot had been freed, so handles to removed items don't reach whatever
+// reuses their slot
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Key {
+	idx: u32,
+	generation: u32,
+}
+
+// stable indices with slot reuse, always taking the most recently freed slot so reuse is deterministic
+struct Slots<T> {
+	items: Vec<(u32, Option<T>)>,
+	free: Vec<u32>,
+}
+impl<T> Slots<T> {
+	fn new() -> Self {
+		Self { items: vec![], free: vec![] }
+	}
+
+	fn capacity(&self) -> usize {
+		self.items.len()
+	}
+
+	fn insert(&mut self, item: T) -> Key {
+		match self.free.pop() {
+			Some(idx) => {
+				let slot = &mut self.items[idx as usize];
+				slot.1 = Some(item);
+				Key { idx, generation: slot.0 }
+			},
+			None => {
+				self.items.push((0, Some(item)));
+				Key { idx: self.items.len() as u32 - 1, generation: 0 }
+			},
+		}
+	}
+
+	fn remove(&mut self, key: Key) -> Option<T> {
+		let slot = self.items.get_mut(key.idx as usize).filter(|slot| slot.0 == key.generation)?;
+		let item = slot.1.take()?;
+		slot.0 = slot.0.wrapping_add(1);
+		self.free.push(key.idx);
+		Some(item)
+	}
+
+	fn get(&self, key: Key) -> Option<&T> {
+		self.items.get(key.idx as usize).filter(|slot| slot.0 == key.generation)?.1.as_ref()
+	}
+
+	fn get_mut(&mut self, key: Key) -> Option<&mut T> {
+		self.items.get_mut(key.idx as usize).filter(|slot| slot.0 == key.generation)?.1.as_mut()
+	}
+
+	// the key for whatever currently occupies a slot
+	fn key(&self, idx: u32) -> Key {
+		Key { idx, generation: self.items[idx as usize].0 }
+	}
+
+	fn iter(&self) -> impl Iterator<Item = (Key, &T)> {
+		self.items.iter().enumerate().filter_map(|(idx, (generation, item))| {
+			item.as_ref().map(|item| (Key { idx: idx as u32, generation: *generation }, item))
+		})
+	}
+
+	fn iter_mut(&mut self) -> impl Iterator<Item = (Key, &mut T)> {
+		self.items.iter_mut().enumerate().filter_map(|(idx, (generation, item))| {
+			item.as_mut().map(|item| (Key { idx: idx as u32, generation: *generation }, item))
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use body::Collider;
+	use shape::Shape;
+
+	// boxes, triangles and balls dropped onto the ground, already spinning
+	fn pile() -> World {
+		let mut world = World::new(Vector2::new(0.0, 500.0), 1.0 / 60.0);
+		let ground = world.add_body(RigidBody::fixed(Vector2::new(0.0, 200.0)));
+		world.add_collider(ground, Collider::new(Shape::aabb(Vector2::new(400.0, 10.0))));
+		let triangle = [Vector2::new(-8.0, 6.0), Vector2::new(8.0, 6.0), Vector2::new(0.0, -8.0)];
+		for i in 0..24 {
+			let pos = Vector2::new((i % 6) as f32 * 17.0 - 45.0, (i / 6) as f32 * -30.0);
+			let body = world.add_body(RigidBody::dynamic(pos));
+			let shape = match i % 3 {
+				0 => Shape::circle(8.0),
+				1 => Shape::polygon(&triangle).unwrap(),
+				_ => Shape::aabb(Vector2::new(7.0, 5.0)),
+			};
+			world.add_collider(body, Collider::new(shape).friction(0.6).restitution(0.2));
+			world.body_mut(body).unwrap().angvel = i as f32 * 0.25 - 3.0;
+		}
+		world
+	}
+
+	fn state(world: &World) -> Vec<(BodyHandle, [u32; 6])> {
+		let bits = |body: &RigidBody| {
+			let (pos, vel) = (body.pos, body.linvel);
+			[pos.x, pos.y, body.angle, vel.x, vel.y, body.angvel].map(f32::to_bits)
+		};
+		world.bodies().map(|(handle, body)| (handle, bits(body))).collect()
+	}
+
+	#[test]
+	fn identical_worlds_stay_identical() {
+		let (mut a, mut b) = (pile(), pile());
+		for _ in 0..300 {
+			a.step();
+			b.step();
+		}
+		assert_eq!(state(&a), state(&b));
+		// otherwise nothing touched and there was little to diverge
+		assert!(a.contacts().count() > 0);
+	}
+
+	#[test]
+	fn queries_follow_moved_bodies() {
+		let mut world = World::new(Vector2::zeros(), 1.0 / 60.0);
+		let body = world.add_body(RigidBody::fixed(Vector2::new(0.0, 0.0)));
+		let collider = world.add_collider(body, Collider::new(Shape::circle(5.0)));
+		world.step();
+		assert_eq!(world.intersect_point(Vector2::new(1.0, 0.0), !0), [collider]);
+
+		// before the next step the broadphase is out of date, so queries must not rely on it
+		world.body_mut(body).unwrap().pos = Vector2::new(500.0, 0.0);
+		assert!(world.intersect_point(Vector2::new(1.0, 0.0), !0).is_empty());
+		assert_eq!(world.intersect_point(Vector2::new(501.0, 0.0), !0), [collider]);
+		world.step();
+		assert_eq!(world.intersect_point(Vector2::new(501.0, 0.0), !0), [collider]);
+
+		let hit = world.raycast(Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0), f32::INFINITY, !0).unwrap();
+		assert_eq!((hit.collider, hit.toi), (collider, 495.0));
+		assert!(world.raycast(Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0), 100.0, !0).is_none());
+	}
+
+	#[test]
+	fn stale_handles_rejected() {
+		let mut world = World::new(Vector2::zeros(), 1.0 / 60.0);
+		let old_body = world.add_body(RigidBody::dynamic(Vector2::zeros()));
+		let old_collider = world.add_collider(old_body, Collider::new(Shape::circle(5.0)));
+		world.remove_body(old_body).unwrap();
+
+		// both slots are reused
+		let body = world.add_body(RigidBody::fixed(Vector2::new(10.0, 0.0)));
+		let collider = world.add_collider(body, Collider::new(Shape::circle(3.0)));
+		assert_ne!(body, old_body);
+		assert_ne!(collider, old_collider);
+		assert!(world.body(old_body).is_none());
+		assert!(world.body_mut(old_body).is_none());
+		assert!(world.collider(old_collider).is_none());
+		assert!(world.remove_collider(old_collider).is_none());
+		assert!(world.remove_body(old_body).is_none());
+		assert_eq!(world.body(body).unwrap().pos, Vector2::new(10.0, 0.0));
+		assert_eq!(world.colliders().map(|(handle, _)| handle).collect::<Vec<_>>(), [collider]);
+	}
+}
@@ -0,0 +1,219 @@
+use crate::physics::{shape::Shape, BodyHandle, ColliderHandle};
+use nalgebra::{Isometry2, Rotation2, Vector2};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyType {
+	/// Never moves.
+	Static,
+	/// Moves only by its velocity, which is set by the game. Pushes dynamic bodies but isn't pushed back.
+	Kinematic,
+	/// Moved by gravity, forces and collisions.
+	Dynamic,
+}
+
+#[derive(Clone, Debug)]
+pub struct RigidBody {
+	body_type: BodyType,
+	pub pos: Vector2<f32>,
+	pub angle: f32,
+	pub linvel: Vector2<f32>,
+	pub angvel: f32,
+	pub gravity_scale: f32,
+	pub linear_damping: f32,
+	pub angular_damping: f32,
+	/// Stops collisions from rotating the body.
+	pub fixed_rotation: bool,
+	force: Vector2<f32>,
+	torque: f32,
+	pub(super) inv_mass: f32,
+	inv_inertia: f32,
+	// center of mass relative to pos, before rotation
+	pub(super) local_center: Vector2<f32>,
+	pub(super) colliders: Vec<ColliderHandle>,
+}
+impl RigidBody {
+	pub fn new(body_type: BodyType, pos: Vector2<f32>) -> Self {
+		Self {
+			body_type,
+			pos,
+			angle: 0.0,
+			linvel: Vector2::zeros(),
+			angvel: 0.0,
+			gravity_scale: 1.0,
+			linear_damping: 0.0,
+			angular_damping: 0.0,
+			fixed_rotation: false,
+			force: Vector2::zeros(),
+			torque: 0.0,
+			inv_mass: 0.0,
+			inv_inertia: 0.0,
+			local_center: Vector2::zeros(),
+			colliders: vec![],
+		}
+	}
+
+	pub fn fixed(pos: Vector2<f32>) -> Self {
+		Self::new(BodyType::Static, pos)
+	}
+
+	pub fn kinematic(pos: Vector2<f32>) -> Self {
+		Self::new(BodyType::Kinematic, pos)
+	}
+
+	pub fn dynamic(pos: Vector2<f32>) -> Self {
+		Self::new(BodyType::Dynamic, pos)
+	}
+
+	pub fn body_type(&self) -> BodyType {
+		self.body_type
+	}
+
+	pub fn colliders(&self) -> &[ColliderHandle] {
+		&self.colliders
+	}
+
+	pub fn mass(&self) -> f32 {
+		if self.inv_mass == 0.0 {
+			0.0
+		} else {
+			1.0 / self.inv_mass
+		}
+	}
+
+	pub fn isometry(&self) -> Isometry2<f32> {
+		Isometry2::new(self.pos, self.angle)
+	}
+
+	/// Center of mass in world space.
+	pub fn center(&self) -> Vector2<f32> {
+		self.pos + Rotation2::new(self.angle) * self.local_center
+	}
+
+	/// Applies `force` at the center of mass until the next step.
+	pub fn apply_force(&mut self, force: Vector2<f32>) {
+		self.force += force;
+	}
+
+	pub fn apply_torque(&mut self, torque: f32) {
+		self.torque += torque;
+	}
+
+	/// Instantly changes velocity as if `impulse` was applied at `point` in world space.
+	pub fn apply_impulse(&mut self, impulse: Vector2<f32>, point: Vector2<f32>) {
+		self.linvel += impulse * self.inv_mass;
+		self.angvel += self.inv_inertia() * (point - self.center()).perp(&impulse);
+	}
+
+	pub(super) fn set_mass(&mut self, mass: f32, center: Vector2<f32>, inertia: f32) {
+		self.local_center = center;
+		if self.body_type != BodyType::Dynamic || mass <= 0.0 {
+			self.inv_mass = 0.0;
+			self.inv_inertia = 0.0;
+			return;
+		}
+		self.inv_mass = 1.0 / mass;
+		self.inv_inertia = if inertia > 0.0 { 1.0 / inertia } else { 0.0 };
+	}
+
+	pub(super) fn inv_inertia(&self) -> f32 {
+		if self.fixed_rotation {
+			0.0
+		} else {
+			self.inv_inertia
+		}
+	}
+
+	pub(super) fn integrate_velocity(&mut self, gravity: Vector2<f32>, dt: f32) {
+		if self.body_type == BodyType::Dynamic {
+			self.linvel += (gravity * self.gravity_scale + self.force * self.inv_mass) * dt;
+			self.angvel += self.torque * self.inv_inertia() * dt;
+			self.linvel /= 1.0 + dt * self.linear_damping;
+			self.angvel /= 1.0 + dt * self.angular_damping;
+			if self.fixed_rotation {
+				self.angvel = 0.0;
+			}
+		}
+		self.force = Vector2::zeros();
+		self.torque = 0.0;
+	}
+
+	pub(super) fn integrate_position(&mut self, dt: f32) {
+		if self.body_type == BodyType::Static {
+			return;
+		}
+		// rotate about the center of mass rather than the origin
+		let center = self.center() + self.linvel * dt;
+		self.angle += self.angvel * dt;
+		self.pos = center - Rotation2::new(self.angle) * self.local_center;
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Collider {
+	pub shape: Shape,
+	/// Placement relative to the body.
+	pub offset: Isometry2<f32>,
+	pub density: f32,
+	pub friction: f32,
+	pub restitution: f32,
+	/// Sensors report overlaps as trigger events instead of colliding.
+	pub sensor: bool,
+	/// Two colliders only interact if each one's groups intersect the other's mask.
+	pub groups: u32,
+	pub mask: u32,
+	pub(super) body: Option<BodyHandle>,
+}
+impl Collider {
+	pub fn new(shape: Shape) -> Self {
+		Self {
+			shape,
+			offset: Isometry2::identity(),
+			density: 1.0,
+			friction: 0.5,
+			restitution: 0.0,
+			sensor: false,
+			groups: !0,
+			mask: !0,
+			body: None,
+		}
+	}
+
+	pub fn offset(mut self, offset: Vector2<f32>, angle: f32) -> Self {
+		self.offset = Isometry2::new(offset, angle);
+		self
+	}
+
+	pub fn density(mut self, density: f32) -> Self {
+		self.density = density;
+		self
+	}
+
+	pub fn friction(mut self, friction: f32) -> Self {
+		self.friction = friction;
+		self
+	}
+
+	pub fn restitution(mut self, restitution: f32) -> Self {
+		self.restitution = restitution;
+		self
+	}
+
+	pub fn sensor(mut self, sensor: bool) -> Self {
+		self.sensor = sensor;
+		self
+	}
+
+	pub fn filter(mut self, groups: u32, mask: u32) -> Self {
+		self.groups = groups;
+		self.mask = mask;
+		self
+	}
+
+	pub fn body(&self) -> BodyHandle {
+		self.body.unwrap()
+	}
+
+	pub(super) fn interacts_with(&self, other: &Collider) -> bool {
+		self.groups & other.mask != 0 && other.groups & self.mask != 0
+	}
+}
@@ -0,0 +1,188 @@
+use crate::physics::shape::Aabb;
+use std::collections::HashMap;
+
+// bounds spanning more cells than this along either axis go in the overflow list instead of the grid
+const MAX_SPAN: f32 = 64.0;
+
+/// Buckets bounds into a uniform grid so only objects sharing a cell are tested against each other.
+pub(super) struct SpatialHash {
+	cell_size: f32,
+	cells: HashMap<(i32, i32), Vec<u32>>,
+	// too big for the grid, so checked against everything
+	overflow: Vec<u32>,
+	bounds: Vec<(u32, Aabb)>,
+}
+impl SpatialHash {
+	pub(super) fn new(cell_size: f32) -> Self {
+		Self { cell_size, cells: HashMap::new(), overflow: vec![], bounds: vec![] }
+	}
+
+	pub(super) fn set_cell_size(&mut self, cell_size: f32) {
+		self.cell_size = cell_size;
+		self.clear();
+	}
+
+	pub(super) fn clear(&mut self) {
+		// keep the cells filled last time around, since the same ones tend to be filled again, but drop the rest so
+		// bodies moving through the world don't leave a trail of empty cells
+		self.cells.retain(|_, cell| !cell.is_empty());
+		for cell in self.cells.values_mut() {
+			cell.clear();
+		}
+		self.overflow.clear();
+		self.bounds.clear();
+	}
+
+	/// Bounds that aren't finite, like those of a body that has fallen out of the world, overlap nothing and are
+	/// skipped.
+	pub(super) fn insert(&mut self, id: u32, aabb: Aabb) {
+		if !is_finite(&aabb) {
+			return;
+		}
+		let idx = self.bounds.len() as u32;
+		self.bounds.push((id, aabb));
+		if self.too_big(&aabb) {
+			self.overflow.push(idx);
+			return;
+		}
+
+		let (min, max) = self.cell_range(&aabb);
+		for x in min.0..=max.0 {
+			for y in min.1..=max.1 {
+				self.cells.entry((x, y)).or_default().push(idx);
+			}
+		}
+	}
+
+	/// Every pair of ids whose bounds overlap, with the lower id first, sorted so results don't depend on hash
+	/// order.
+	pub(super) fn pairs(&self) -> Vec<(u32, u32)> {
+		let mut pairs = vec![];
+		let mut check = |a: u32, b: u32| {
+			let (id_a, aabb_a) = &self.bounds[a as usize];
+			let (id_b, aabb_b) = &self.bounds[b as usize];
+			if aabb_a.intersects(aabb_b) {
+				pairs.push((*id_a.min(id_b), *id_a.max(id_b)));
+			}
+		};
+		for cell in self.cells.values() {
+			for (i, &a) in cell.iter().enumerate() {
+				for &b in &cell[i + 1..] {
+					check(a, b);
+				}
+			}
+		}
+		for &a in &self.overflow {
+			for b in (0..self.bounds.len() as u32).filter(|&b| b != a) {
+				check(a, b);
+			}
+		}
+		pairs.sort_unstable();
+		pairs.dedup();
+		pairs
+	}
+
+	/// Ids whose bounds overlap `aabb`, sorted. Bounds that aren't finite, like those of an endless ray, match every
+	/// id, leaving the exact test to the caller.
+	pub(super) fn query(&self, aabb: &Aabb) -> Vec<u32> {
+		let mut ids: Vec<_> = if !is_finite(aabb) {
+			self.bounds.iter().map(|&(id, _)| id).collect()
+		} else if self.too_big(aabb) {
+			self.bounds.iter().filter(|(_, bounds)| bounds.intersects(aabb)).map(|&(id, _)| id).collect()
+		} else {
+			let (min, max) = self.cell_range(aabb);
+			let cells = (min.0..=max.0).flat_map(|x| (min.1..=max.1).map(move |y| (x, y)));
+			cells
+				.filter_map(|cell| self.cells.get(&cell))
+				.flatten()
+				.chain(&self.overflow)
+				.map(|&idx| &self.bounds[idx as usize])
+				.filter(|(_, bounds)| bounds.intersects(aabb))
+				.map(|&(id, _)| id)
+				.collect()
+		};
+		ids.sort_unstable();
+		ids.dedup();
+		ids
+	}
+
+	fn too_big(&self, aabb: &Aabb) -> bool {
+		let cell = |v: f32| (v / self.cell_size).floor();
+		cell(aabb.max.x) - cell(aabb.min.x) > MAX_SPAN || cell(aabb.max.y) - cell(aabb.min.y) > MAX_SPAN
+	}
+
+	fn cell_range(&self, aabb: &Aabb) -> ((i32, i32), (i32, i32)) {
+		let cell = |v: f32| (v / self.cell_size).floor() as i32;
+		((cell(aabb.min.x), cell(aabb.min.y)), (cell(aabb.max.x), cell(aabb.max.y)))
+	}
+}
+
+fn is_finite(aabb: &Aabb) -> bool {
+	aabb.min.x.is_finite() && aabb.min.y.is_finite() && aabb.max.x.is_finite() && aabb.max.y.is_finite()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::Vector2;
+
+	fn aabb(min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Aabb {
+		Aabb::new(Vector2::new(min_x, min_y), Vector2::new(max_x, max_y))
+	}
+
+	#[test]
+	fn pairs_sharing_cells() {
+		let mut grid = SpatialHash::new(10.0);
+		grid.insert(3, aabb(0.0, 0.0, 5.0, 5.0));
+		grid.insert(1, aabb(4.0, 4.0, 25.0, 6.0));
+		grid.insert(2, aabb(20.0, 0.0, 30.0, 10.0));
+		// shares a cell with 1 but doesn't overlap it
+		grid.insert(4, aabb(12.0, 7.0, 14.0, 9.0));
+		assert_eq!(grid.pairs(), [(1, 2), (1, 3)]);
+	}
+
+	#[test]
+	fn skips_non_finite_bounds() {
+		let mut grid = SpatialHash::new(10.0);
+		grid.insert(0, aabb(0.0, 0.0, 5.0, 5.0));
+		grid.insert(1, aabb(f32::NAN, 0.0, 5.0, 5.0));
+		grid.insert(2, aabb(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::INFINITY, f32::INFINITY));
+		assert_eq!(grid.cells.values().map(Vec::len).sum::<usize>(), 1);
+		assert!(grid.pairs().is_empty());
+	}
+
+	#[test]
+	fn huge_bounds_overflow() {
+		let mut grid = SpatialHash::new(10.0);
+		grid.insert(0, aabb(-1e30, -1e30, 1e30, 1e30));
+		grid.insert(1, aabb(0.0, 0.0, 5.0, 5.0));
+		grid.insert(2, aabb(1e6, 1e6, 1e6 + 5.0, 1e6 + 5.0));
+		grid.insert(3, aabb(-5e29, 0.0, 5e29, 1.0));
+		assert_eq!(grid.overflow.len(), 2);
+		assert_eq!(grid.pairs(), [(0, 1), (0, 2), (0, 3), (1, 3)]);
+	}
+
+	#[test]
+	fn moving_bounds_drop_cells() {
+		let mut grid = SpatialHash::new(10.0);
+		for step in 0..1000 {
+			grid.clear();
+			let x = step as f32 * 7.0;
+			grid.insert(0, aabb(x, 0.0, x + 5.0, 5.0));
+		}
+		// the cells from the step before, and the ones just filled
+		assert!(grid.cells.len() <= 4, "{}", grid.cells.len());
+	}
+
+	#[test]
+	fn query_bounds() {
+		let mut grid = SpatialHash::new(10.0);
+		grid.insert(5, aabb(0.0, 0.0, 5.0, 5.0));
+		grid.insert(2, aabb(30.0, 0.0, 35.0, 5.0));
+		grid.insert(7, aabb(-1e30, 40.0, 1e30, 41.0));
+		assert_eq!(grid.query(&aabb(4.0, 4.0, 31.0, 6.0)), [2, 5]);
+		assert_eq!(grid.query(&aabb(100.0, 35.0, 110.0, 45.0)), [7]);
+		assert_eq!(grid.query(&aabb(-1e9, -1.0, 1e9, 1.0)), [2, 5]);
+		assert_eq!(grid.query(&aabb(0.0, 0.0, f32::INFINITY, 1.0)), [2, 5, 7]);
+	}
+}
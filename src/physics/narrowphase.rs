@@ -0,0 +1,286 @@
+use crate::physics::shape::WorldShape;
+use nalgebra::Vector2;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Contact {
+	/// The point of the second shape deepest inside the first.
+	pub point: Vector2<f32>,
+	pub depth: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Manifold {
+	/// Points from the first shape towards the second.
+	pub normal: Vector2<f32>,
+	pub contacts: Vec<Contact>,
+}
+impl Manifold {
+	// swaps which shape is first
+	fn flip(mut self) -> Self {
+		for contact in &mut self.contacts {
+			contact.point += self.normal * contact.depth;
+		}
+		self.normal = -self.normal;
+		self
+	}
+}
+
+pub(super) fn collide(a: &WorldShape, b: &WorldShape) -> Option<Manifold> {
+	match (a, b) {
+		(WorldShape::Circle { center: ca, radius: ra }, WorldShape::Circle { center: cb, radius: rb }) => {
+			circle_circle(*ca, *ra, *cb, *rb)
+		},
+		(WorldShape::Polygon { verts, normals }, WorldShape::Circle { center, radius }) => {
+			polygon_circle(verts, normals, *center, *radius)
+		},
+		(WorldShape::Circle { center, radius }, WorldShape::Polygon { verts, normals }) => {
+			polygon_circle(verts, normals, *center, *radius).map(Manifold::flip)
+		},
+		(WorldShape::Polygon { verts: va, normals: na }, WorldShape::Polygon { verts: vb, normals: nb }) => {
+			polygon_polygon(va, na, vb, nb)
+		},
+	}
+}
+
+fn circle_circle(ca: Vector2<f32>, ra: f32, cb: Vector2<f32>, rb: f32) -> Option<Manifold> {
+	let offset = cb - ca;
+	let distance = offset.norm();
+	if distance > ra + rb {
+		return None;
+	}
+
+	// concentric circles have no meaningful normal, so pick one consistently
+	let normal = if distance > f32::EPSILON { offset / distance } else { Vector2::new(0.0, 1.0) };
+	let contact = Contact { point: cb - normal * rb, depth: ra + rb - distance };
+	Some(Manifold { normal, contacts: vec![contact] })
+}
+
+fn polygon_circle(
+	verts: &[Vector2<f32>],
+	normals: &[Vector2<f32>],
+	center: Vector2<f32>,
+	radius: f32,
+) -> Option<Manifold> {
+	let mut face = 0;
+	let mut separation = f32::MIN;
+	for (i, (v, n)) in verts.iter().zip(normals).enumerate() {
+		let s = n.dot(&(center - v));
+		if s > radius {
+			return None;
+		}
+		if s > separation {
+			separation = s;
+			face = i;
+		}
+	}
+
+	let v1 = verts[face];
+	let v2 = verts[(face + 1) % verts.len()];
+	let (normal, depth) = if separation <= 0.0 {
+		// center is inside the polygon
+		(normals[face], radius - separation)
+	} else if (center - v1).dot(&(v2 - v1)) <= 0.0 {
+		corner_normal(v1, center, radius)?
+	} else if (center - v2).dot(&(v1 - v2)) <= 0.0 {
+		corner_normal(v2, center, radius)?
+	} else {
+		(normals[face], radius - separation)
+	};
+
+	Some(Manifold { normal, contacts: vec![Contact { point: center - normal * radius, depth }] })
+}
+
+fn corner_normal(corner: Vector2<f32>, center: Vector2<f32>, radius: f32) -> Option<(Vector2<f32>, f32)> {
+	let offset = center - corner;
+	let distance = offset.norm();
+	if distance > radius {
+		None
+	} else {
+		Some((offset / distance, radius - distance))
+	}
+}
+
+fn polygon_polygon(
+	va: &[Vector2<f32>],
+	na: &[Vector2<f32>],
+	vb: &[Vector2<f32>],
+	nb: &[Vector2<f32>],
+) -> Option<Manifold> {
+	let (face_a, sep_a) = max_separation(va, na, vb);
+	if sep_a > 0.0 {
+		return None;
+	}
+	let (face_b, sep_b) = max_separation(vb, nb, va);
+	if sep_b > 0.0 {
+		return None;
+	}
+
+	// prefer the first polygon as the reference so results don't flicker between nearly equal axes
+	const TOLERANCE: f32 = 0.0005;
+	if sep_b > sep_a + TOLERANCE {
+		clip_faces(vb, nb, face_b, va, na).map(Manifold::flip)
+	} else {
+		clip_faces(va, na, face_a, vb, nb)
+	}
+}
+
+// the face of `verts` along which `other` is furthest away, and that distance
+fn max_separation(verts: &[Vector2<f32>], normals: &[Vector2<f32>], other: &[Vector2<f32>]) -> (usize, f32) {
+	let mut face = 0;
+	let mut separation = f32::MIN;
+	for (i, (v, n)) in verts.iter().zip(normals).enumerate() {
+		let s = other.iter().map(|o| n.dot(&(o - v))).fold(f32::MAX, f32::min);
+		if s > separation {
+			separation = s;
+			face = i;
+		}
+	}
+	(face, separation)
+}
+
+fn clip_faces(
+	ref_verts: &[Vector2<f32>],
+	ref_normals: &[Vector2<f32>],
+	ref_face: usize,
+	inc_verts: &[Vector2<f32>],
+	inc_normals: &[Vector2<f32>],
+) -> Option<Manifold> {
+	let normal = ref_normals[ref_face];
+
+	// the incident face is the one most anti-parallel to the reference face
+	let inc_face = (0..inc_normals.len())
+		.min_by(|&i, &j| normal.dot(&inc_normals[i]).partial_cmp(&normal.dot(&inc_normals[j])).unwrap())
+		.unwrap();
+	let incident = [inc_verts[inc_face], inc_verts[(inc_face + 1) % inc_verts.len()]];
+
+	let v1 = ref_verts[ref_face];
+	let v2 = ref_verts[(ref_face + 1) % ref_verts.len()];
+	let tangent = (v2 - v1).normalize();
+
+	let clipped = clip_segment(incident, -tangent, -tangent.dot(&v1))?;
+	let clipped = clip_segment(clipped, tangent, tangent.dot(&v2))?;
+
+	let front = normal.dot(&v1);
+	let contacts: Vec<_> = clipped
+		.iter()
+		.map(|&point| Contact { point, depth: front - normal.dot(&point) })
+		.filter(|contact| contact.depth >= 0.0)
+		.collect();
+	if contacts.is_empty() {
+		None
+	} else {
+		Some(Manifold { normal, contacts })
+	}
+}
+
+// keeps the part of the segment where `normal . p <= offset`
+fn clip_segment(points: [Vector2<f32>; 2], normal: Vector2<f32>, offset: f32) -> Option<[Vector2<f32>; 2]> {
+	let d0 = normal.dot(&points[0]) - offset;
+	let d1 = normal.dot(&points[1]) - offset;
+	match (d0 <= 0.0, d1 <= 0.0) {
+		(true, true) => Some(points),
+		(false, false) => None,
+		_ => {
+			let crossing = points[0] + (points[1] - points[0]) * (d0 / (d0 - d1));
+			if d0 <= 0.0 {
+				Some([points[0], crossing])
+			} else {
+				Some([crossing, points[1]])
+			}
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::physics::shape::Shape;
+	use nalgebra::Isometry2;
+
+	fn at(shape: Shape, x: f32, y: f32) -> WorldShape {
+		shape.to_world(&Isometry2::translation(x, y))
+	}
+
+	fn square(x: f32, y: f32) -> WorldShape {
+		at(Shape::aabb(Vector2::new(10.0, 10.0)), x, y)
+	}
+
+	fn circle(x: f32, y: f32) -> WorldShape {
+		at(Shape::circle(5.0), x, y)
+	}
+
+	fn assert_close(a: Vector2<f32>, b: Vector2<f32>) {
+		assert!((a - b).norm() < 1e-4, "{:?} != {:?}", a, b);
+	}
+
+	// contact points and depths, sorted along x
+	fn contacts(manifold: &Manifold) -> Vec<(Vector2<f32>, f32)> {
+		let mut contacts: Vec<_> = manifold.contacts.iter().map(|c| (c.point, c.depth)).collect();
+		contacts.sort_by(|a, b| a.0.x.partial_cmp(&b.0.x).unwrap());
+		contacts
+	}
+
+	#[test]
+	fn box_box_face() {
+		// b overlaps the bottom of a by 2, shifted right so only part of its top face is inside
+		let manifold = collide(&square(0.0, 0.0), &square(5.0, 18.0)).unwrap();
+		assert_close(manifold.normal, Vector2::new(0.0, 1.0));
+		let contacts = contacts(&manifold);
+		assert_eq!(contacts.len(), 2);
+		assert_close(contacts[0].0, Vector2::new(-5.0, 8.0));
+		assert_close(contacts[1].0, Vector2::new(10.0, 8.0));
+		assert!(contacts.iter().all(|&(_, depth)| (depth - 2.0).abs() < 1e-4));
+	}
+
+	#[test]
+	fn box_box_swapped() {
+		// the normal still points from the first shape to the second, and the points lie on the second
+		let manifold = collide(&square(5.0, 18.0), &square(0.0, 0.0)).unwrap();
+		assert_close(manifold.normal, Vector2::new(0.0, -1.0));
+		let contacts = contacts(&manifold);
+		assert_eq!(contacts.len(), 2);
+		assert_close(contacts[0].0, Vector2::new(-5.0, 10.0));
+		assert_close(contacts[1].0, Vector2::new(10.0, 10.0));
+		assert!(contacts.iter().all(|&(_, depth)| (depth - 2.0).abs() < 1e-4));
+	}
+
+	#[test]
+	fn box_box_apart() {
+		assert!(collide(&square(0.0, 0.0), &square(0.0, 20.5)).is_none());
+		assert!(collide(&square(0.0, 0.0), &square(21.0, 21.0)).is_none());
+	}
+
+	#[test]
+	fn polygon_circle_face() {
+		let manifold = collide(&square(0.0, 0.0), &circle(0.0, 14.0)).unwrap();
+		assert_close(manifold.normal, Vector2::new(0.0, 1.0));
+		assert_eq!(manifold.contacts.len(), 1);
+		assert_close(manifold.contacts[0].point, Vector2::new(0.0, 9.0));
+		assert!((manifold.contacts[0].depth - 1.0).abs() < 1e-4);
+	}
+
+	#[test]
+	fn circle_polygon_face() {
+		let manifold = collide(&circle(0.0, 14.0), &square(0.0, 0.0)).unwrap();
+		assert_close(manifold.normal, Vector2::new(0.0, -1.0));
+		assert_eq!(manifold.contacts.len(), 1);
+		assert_close(manifold.contacts[0].point, Vector2::new(0.0, 10.0));
+		assert!((manifold.contacts[0].depth - 1.0).abs() < 1e-4);
+	}
+
+	#[test]
+	fn polygon_circle_corner() {
+		// past the corner, the normal points from it to the center instead of along a face
+		let manifold = collide(&square(0.0, 0.0), &circle(13.0, 13.0)).unwrap();
+		assert_close(manifold.normal, Vector2::new(1.0, 1.0).normalize());
+		assert!((manifold.contacts[0].depth - (5.0 - 18f32.sqrt())).abs() < 1e-4);
+		assert!(collide(&square(0.0, 0.0), &circle(14.0, 14.0)).is_none());
+	}
+
+	#[test]
+	fn polygon_circle_center_inside() {
+		let manifold = collide(&square(0.0, 0.0), &circle(0.0, 8.0)).unwrap();
+		assert_close(manifold.normal, Vector2::new(0.0, 1.0));
+		assert!((manifold.contacts[0].depth - 7.0).abs() < 1e-4);
+	}
+}
@@ -0,0 +1,111 @@
+use crate::physics::{
+	narrowphase::collide,
+	shape::{Aabb, Shape, WorldShape},
+	ColliderHandle, World,
+};
+use nalgebra::{Isometry2, Vector2};
+
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+	pub collider: ColliderHandle,
+	/// Distance along the ray, in multiples of its direction.
+	pub toi: f32,
+	pub point: Vector2<f32>,
+	pub normal: Vector2<f32>,
+}
+
+/// Queries see colliders where their bodies are now, including changes made since the last step. They find them through
+/// the broadphase, but check every collider after bodies or colliders were changed, until the next step. Only
+/// colliders whose groups intersect `mask` are considered.
+impl World {
+	/// The closest collider hit by a ray, ignoring colliders that contain `origin`.
+	pub fn raycast(&self, origin: Vector2<f32>, dir: Vector2<f32>, max_toi: f32, mask: u32) -> Option<RayHit> {
+		let end = origin + dir * max_toi;
+		let mut closest: Option<RayHit> = None;
+		for (handle, collider) in self.candidates(&Aabb::new(origin.inf(&end), origin.sup(&end))) {
+			if collider.groups & mask == 0 {
+				continue;
+			}
+
+			let iso = self.collider_isometry(collider);
+			let max_toi = closest.map_or(max_toi, |hit| hit.toi);
+			if let Some((toi, normal)) = ray_shape(&collider.shape.to_world(&iso), origin, dir, max_toi) {
+				closest = Some(RayHit { collider: handle, toi, point: origin + dir * toi, normal });
+			}
+		}
+		closest
+	}
+
+	/// Every collider overlapping `shape` placed at `iso`.
+	pub fn intersect_shape(&self, shape: &Shape, iso: &Isometry2<f32>, mask: u32) -> Vec<ColliderHandle> {
+		let bounds = shape.bounds(iso);
+		let shape = shape.to_world(iso);
+		self.candidates(&bounds)
+			.filter(|(_, collider)| collider.groups & mask != 0)
+			.filter(|(_, collider)| {
+				let iso = self.collider_isometry(collider);
+				collider.shape.bounds(&iso).intersects(&bounds)
+					&& collide(&shape, &collider.shape.to_world(&iso)).is_some()
+			})
+			.map(|(handle, _)| handle)
+			.collect()
+	}
+
+	/// Every collider containing `point`.
+	pub fn intersect_point(&self, point: Vector2<f32>, mask: u32) -> Vec<ColliderHandle> {
+		self.candidates(&Aabb::new(point, point))
+			.filter(|(_, collider)| collider.groups & mask != 0)
+			.filter(|(_, collider)| {
+				let iso = self.collider_isometry(collider);
+				collider.shape.bounds(&iso).contains(point) && collider.shape.to_world(&iso).contains(point)
+			})
+			.map(|(handle, _)| handle)
+			.collect()
+	}
+}
+
+fn ray_shape(shape: &WorldShape, origin: Vector2<f32>, dir: Vector2<f32>, max_toi: f32) -> Option<(f32, Vector2<f32>)> {
+	match shape {
+		WorldShape::Circle { center, radius } => {
+			let offset = origin - center;
+			let a = dir.norm_squared();
+			let b = offset.dot(&dir);
+			let c = offset.norm_squared() - radius * radius;
+			if c <= 0.0 || a == 0.0 {
+				return None;
+			}
+			let discriminant = b * b - a * c;
+			if discriminant < 0.0 {
+				return None;
+			}
+			let toi = (-b - discriminant.sqrt()) / a;
+			if toi < 0.0 || toi > max_toi {
+				return None;
+			}
+			Some((toi, (offset + dir * toi) / *radius))
+		},
+		WorldShape::Polygon { verts, normals } => {
+			// clip the ray against each face's half-plane
+			let (mut lower, mut upper) = (0.0, max_toi);
+			let mut face = None;
+			for (i, (v, n)) in verts.iter().zip(normals).enumerate() {
+				let numerator = n.dot(&(v - origin));
+				let denominator = n.dot(&dir);
+				if denominator == 0.0 {
+					if numerator < 0.0 {
+						return None;
+					}
+				} else if denominator < 0.0 && numerator < lower * denominator {
+					lower = numerator / denominator;
+					face = Some(i);
+				} else if denominator > 0.0 && numerator < upper * denominator {
+					upper = numerator / denominator;
+				}
+				if upper < lower {
+					return None;
+				}
+			}
+			face.map(|face| (lower, normals[face]))
+		},
+	}
+}
@@ -0,0 +1,227 @@
+use nalgebra::{Isometry2, Vector2};
+use std::f32::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+	pub min: Vector2<f32>,
+	pub max: Vector2<f32>,
+}
+impl Aabb {
+	pub fn new(min: Vector2<f32>, max: Vector2<f32>) -> Self {
+		Self { min, max }
+	}
+
+	pub fn from_center(center: Vector2<f32>, half_extents: Vector2<f32>) -> Self {
+		Self { min: center - half_extents, max: center + half_extents }
+	}
+
+	pub fn center(&self) -> Vector2<f32> {
+		(self.min + self.max) * 0.5
+	}
+
+	pub fn intersects(&self, other: &Aabb) -> bool {
+		self.min.x <= other.max.x && self.max.x >= other.min.x && self.min.y <= other.max.y && self.max.y >= other.min.y
+	}
+
+	pub fn contains(&self, point: Vector2<f32>) -> bool {
+		self.min.x <= point.x && point.x <= self.max.x && self.min.y <= point.y && point.y <= self.max.y
+	}
+
+	pub fn merge(&self, other: &Aabb) -> Aabb {
+		Aabb { min: min(self.min, other.min), max: max(self.max, other.max) }
+	}
+}
+
+#[derive(Clone, Debug)]
+pub enum Shape {
+	/// A box that stays axis-aligned however its body rotates. Useful for characters and tiles.
+	Aabb {
+		half_extents: Vector2<f32>,
+	},
+	Circle {
+		radius: f32,
+	},
+	Polygon(ConvexPolygon),
+}
+impl Shape {
+	pub fn aabb(half_extents: Vector2<f32>) -> Self {
+		Self::Aabb { half_extents }
+	}
+
+	pub fn circle(radius: f32) -> Self {
+		Self::Circle { radius }
+	}
+
+	/// Returns `None` if `points` don't span any area.
+	pub fn polygon(points: &[Vector2<f32>]) -> Option<Self> {
+		ConvexPolygon::new(points).map(Self::Polygon)
+	}
+
+	/// Bounds of the shape placed at `iso`.
+	pub fn bounds(&self, iso: &Isometry2<f32>) -> Aabb {
+		match self {
+			Self::Aabb { half_extents } => Aabb::from_center(iso.translation.vector, *half_extents),
+			Self::Circle { radius } => Aabb::from_center(iso.translation.vector, Vector2::new(*radius, *radius)),
+			Self::Polygon(poly) => {
+				let mut verts = poly.verts.iter().map(|v| iso.rotation * v + iso.translation.vector);
+				let first = verts.next().unwrap();
+				verts.fold(Aabb::new(first, first), |aabb, v| Aabb::new(min(aabb.min, v), max(aabb.max, v)))
+			},
+		}
+	}
+
+	pub fn mass_properties(&self, density: f32) -> MassProperties {
+		match self {
+			Self::Aabb { half_extents: h } => {
+				let mass = density * 4.0 * h.x * h.y;
+				MassProperties { mass, center: Vector2::zeros(), inertia: mass * h.norm_squared() / 3.0 }
+			},
+			Self::Circle { radius } => {
+				let mass = density * PI * radius * radius;
+				MassProperties { mass, center: Vector2::zeros(), inertia: mass * radius * radius * 0.5 }
+			},
+			Self::Polygon(poly) => poly.mass_properties(density),
+		}
+	}
+
+	pub(super) fn to_world(&self, iso: &Isometry2<f32>) -> WorldShape {
+		let pos = iso.translation.vector;
+		match self {
+			Self::Aabb { half_extents: h } => WorldShape::Polygon {
+				verts: vec![
+					pos + Vector2::new(-h.x, -h.y),
+					pos + Vector2::new(h.x, -h.y),
+					pos + Vector2::new(h.x, h.y),
+					pos + Vector2::new(-h.x, h.y),
+				],
+				normals: vec![
+					Vector2::new(0.0, -1.0),
+					Vector2::new(1.0, 0.0),
+					Vector2::new(0.0, 1.0),
+					Vector2::new(-1.0, 0.0),
+				],
+			},
+			Self::Circle { radius } => WorldShape::Circle { center: pos, radius: *radius },
+			Self::Polygon(poly) => WorldShape::Polygon {
+				verts: poly.verts.iter().map(|v| iso.rotation * v + pos).collect(),
+				normals: poly.normals.iter().map(|n| iso.rotation * n).collect(),
+			},
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MassProperties {
+	pub mass: f32,
+	/// Center of mass in the shape's local space.
+	pub center: Vector2<f32>,
+	/// Rotational inertia about the center of mass.
+	pub inertia: f32,
+}
+
+/// A convex polygon with counter-clockwise winding.
+#[derive(Clone, Debug)]
+pub struct ConvexPolygon {
+	verts: Vec<Vector2<f32>>,
+	normals: Vec<Vector2<f32>>,
+}
+impl ConvexPolygon {
+	/// Builds the convex hull of `points`. Returns `None` if they don't span any area.
+	pub fn new(points: &[Vector2<f32>]) -> Option<Self> {
+		let verts = convex_hull(points);
+		if verts.len() < 3 {
+			return None;
+		}
+
+		let normals = (0..verts.len())
+			.map(|i| {
+				let edge = verts[(i + 1) % verts.len()] - verts[i];
+				Vector2::new(edge.y, -edge.x).normalize()
+			})
+			.collect();
+		Some(Self { verts, normals })
+	}
+
+	pub fn verts(&self) -> &[Vector2<f32>] {
+		&self.verts
+	}
+
+	pub fn normals(&self) -> &[Vector2<f32>] {
+		&self.normals
+	}
+
+	fn mass_properties(&self, density: f32) -> MassProperties {
+		// split into triangles fanning out from the first vertex, relative to it for precision
+		let origin = self.verts[0];
+		let mut area = 0.0;
+		let mut center = Vector2::zeros();
+		let mut inertia = 0.0;
+		for i in 1..self.verts.len() - 1 {
+			let e1 = self.verts[i] - origin;
+			let e2 = self.verts[i + 1] - origin;
+			let d = e1.perp(&e2);
+			let tri_area = d * 0.5;
+			area += tri_area;
+			center += (e1 + e2) * (tri_area / 3.0);
+			let intx2 = e1.x * e1.x + e2.x * e1.x + e2.x * e2.x;
+			let inty2 = e1.y * e1.y + e2.y * e1.y + e2.y * e2.y;
+			inertia += (0.25 / 3.0 * d) * (intx2 + inty2);
+		}
+
+		let mass = density * area;
+		center /= area;
+		// inertia about the origin vertex, shifted to the center of mass
+		let inertia = density * inertia - mass * center.norm_squared();
+		MassProperties { mass, center: center + origin, inertia }
+	}
+}
+
+// Andrew's monotone chain, counter-clockwise with collinear points removed
+fn convex_hull(points: &[Vector2<f32>]) -> Vec<Vector2<f32>> {
+	let mut points = points.to_vec();
+	points.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap().then(a.y.partial_cmp(&b.y).unwrap()));
+	points.dedup();
+	if points.len() < 3 {
+		return points;
+	}
+
+	let mut hull: Vec<Vector2<f32>> = Vec::with_capacity(points.len() * 2);
+	for pass in 0..2 {
+		let start = hull.len();
+		for &p in &points {
+			while hull.len() >= start + 2
+				&& (hull[hull.len() - 1] - hull[hull.len() - 2]).perp(&(p - hull[hull.len() - 2])) <= 0.0
+			{
+				hull.pop();
+			}
+			hull.push(p);
+		}
+		hull.pop();
+		if pass == 0 {
+			points.reverse();
+		}
+	}
+	hull
+}
+
+fn min(a: Vector2<f32>, b: Vector2<f32>) -> Vector2<f32> {
+	Vector2::new(a.x.min(b.x), a.y.min(b.y))
+}
+
+fn max(a: Vector2<f32>, b: Vector2<f32>) -> Vector2<f32> {
+	Vector2::new(a.x.max(b.x), a.y.max(b.y))
+}
+
+/// A shape transformed into world space, ready for collision tests.
+pub(super) enum WorldShape {
+	Circle { center: Vector2<f32>, radius: f32 },
+	Polygon { verts: Vec<Vector2<f32>>, normals: Vec<Vector2<f32>> },
+}
+impl WorldShape {
+	pub(super) fn contains(&self, point: Vector2<f32>) -> bool {
+		match self {
+			Self::Circle { center, radius } => (point - center).norm_squared() <= radius * radius,
+			Self::Polygon { verts, normals } => verts.iter().zip(normals).all(|(v, n)| n.dot(&(point - v)) <= 0.0),
+		}
+	}
+}
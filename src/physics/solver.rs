@@ -0,0 +1,126 @@
+use crate::physics::narrowphase::Manifold;
+use nalgebra::Vector2;
+
+// fraction of the penetration resolved per step
+const BAUMGARTE: f32 = 0.2;
+// penetration allowed before correcting, which keeps resting contacts from jittering
+const SLOP: f32 = 0.01;
+// approach speeds below this don't bounce, so resting bodies settle
+const RESTITUTION_THRESHOLD: f32 = 1.0;
+
+/// The parts of a rigid body the solver works with, copied out so constraints can borrow two at once.
+#[derive(Clone, Copy)]
+pub(super) struct SolverBody {
+	pub(super) linvel: Vector2<f32>,
+	pub(super) angvel: f32,
+	pub(super) inv_mass: f32,
+	pub(super) inv_inertia: f32,
+	pub(super) center: Vector2<f32>,
+}
+impl SolverBody {
+	fn velocity_at(&self, r: Vector2<f32>) -> Vector2<f32> {
+		self.linvel + Vector2::new(-self.angvel * r.y, self.angvel * r.x)
+	}
+
+	fn apply(&mut self, impulse: Vector2<f32>, r: Vector2<f32>) {
+		self.linvel += impulse * self.inv_mass;
+		self.angvel += self.inv_inertia * r.perp(&impulse);
+	}
+}
+
+struct ConstraintPoint {
+	ra: Vector2<f32>,
+	rb: Vector2<f32>,
+	normal_mass: f32,
+	tangent_mass: f32,
+	bias: f32,
+	normal_impulse: f32,
+	tangent_impulse: f32,
+}
+
+pub(super) struct ContactConstraint {
+	a: usize,
+	b: usize,
+	normal: Vector2<f32>,
+	friction: f32,
+	points: Vec<ConstraintPoint>,
+}
+impl ContactConstraint {
+	pub(super) fn new(
+		a: usize,
+		b: usize,
+		bodies: &[SolverBody],
+		manifold: &Manifold,
+		friction: f32,
+		restitution: f32,
+		dt: f32,
+	) -> Self {
+		let (body_a, body_b) = (&bodies[a], &bodies[b]);
+		let normal = manifold.normal;
+		let tangent = Vector2::new(-normal.y, normal.x);
+		let effective_mass = |ra: Vector2<f32>, rb: Vector2<f32>, dir: Vector2<f32>| {
+			let (rna, rnb) = (ra.perp(&dir), rb.perp(&dir));
+			let k = body_a.inv_mass + body_b.inv_mass + body_a.inv_inertia * rna * rna + body_b.inv_inertia * rnb * rnb;
+			if k > 0.0 {
+				1.0 / k
+			} else {
+				0.0
+			}
+		};
+
+		let points = manifold
+			.contacts
+			.iter()
+			.map(|contact| {
+				let ra = contact.point - body_a.center;
+				let rb = contact.point - body_b.center;
+
+				let mut bias = BAUMGARTE / dt * (contact.depth - SLOP).max(0.0);
+				let approach = (body_b.velocity_at(rb) - body_a.velocity_at(ra)).dot(&normal);
+				if approach < -RESTITUTION_THRESHOLD {
+					bias = bias.max(-restitution * approach);
+				}
+
+				ConstraintPoint {
+					ra,
+					rb,
+					normal_mass: effective_mass(ra, rb, normal),
+					tangent_mass: effective_mass(ra, rb, tangent),
+					bias,
+					normal_impulse: 0.0,
+					tangent_impulse: 0.0,
+				}
+			})
+			.collect();
+
+		Self { a, b, normal, friction, points }
+	}
+
+	pub(super) fn solve(&mut self, bodies: &mut [SolverBody]) {
+		let (mut body_a, mut body_b) = (bodies[self.a], bodies[self.b]);
+		let normal = self.normal;
+		let tangent = Vector2::new(-normal.y, normal.x);
+
+		for point in &mut self.points {
+			// friction first, since it's less important than not penetrating
+			let dv = body_b.velocity_at(point.rb) - body_a.velocity_at(point.ra);
+			let max_friction = self.friction * point.normal_impulse;
+			let total =
+				(point.tangent_impulse - point.tangent_mass * dv.dot(&tangent)).clamp(-max_friction, max_friction);
+			let impulse = tangent * (total - point.tangent_impulse);
+			point.tangent_impulse = total;
+			body_a.apply(-impulse, point.ra);
+			body_b.apply(impulse, point.rb);
+
+			let dv = body_b.velocity_at(point.rb) - body_a.velocity_at(point.ra);
+			let total = (point.normal_impulse + point.normal_mass * (point.bias - dv.dot(&normal))).max(0.0);
+			let impulse = normal * (total - point.normal_impulse);
+			point.normal_impulse = total;
+			body_a.apply(-impulse, point.ra);
+			body_b.apply(impulse, point.rb);
+		}
+
+		bodies[self.a] = body_a;
+		bodies[self.b] = body_b;
+	}
+}
@@ -0,0 +1,312 @@
+use crate::{fs::write_all, json::Json};
+use lazy_static::lazy_static;
+use std::{
+	borrow::Cow,
+	collections::VecDeque,
+	io, mem,
+	path::Path,
+	sync::{
+		atomic::{AtomicBool, AtomicU32, Ordering},
+		Mutex,
+	},
+	thread,
+	time::{Duration, Instant},
+};
+
+// frames kept for the trace, about 5 seconds at 60 fps
+const HISTORY: usize = 300;
+
+lazy_static! {
+	static ref EPOCH: Instant = Instant::now();
+	static ref STATE: Mutex<State> = Mutex::default();
+}
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static NEXT_THREAD: AtomicU32 = AtomicU32::new(0);
+
+thread_local! {
+	static THREAD: u32 = register_thread();
+}
+
+#[derive(Default)]
+struct State {
+	frame: u64,
+	frame_start: Option<Instant>,
+	spans: Vec<Span>,
+	frames: VecDeque<Frame>,
+	// indexed by thread id
+	threads: Vec<String>,
+}
+
+/// A timed region, on a CPU thread or the GPU.
+#[derive(Clone, Debug)]
+pub struct Span {
+	pub name: Cow<'static, str>,
+	/// The thread it ran on, or `None` on the GPU.
+	pub thread: Option<u32>,
+	pub start: Instant,
+	pub end: Instant,
+}
+impl Span {
+	pub fn duration(&self) -> Duration {
+		self.end - self.start
+	}
+}
+
+/// Everything recorded between two calls to `end_frame`.
+#[derive(Clone, Debug)]
+pub struct Frame {
+	pub number: u64,
+	pub start: Instant,
+	pub end: Instant,
+	pub cpu: Vec<Span>,
+	/// Filled in a couple of frames late, once the GPU finishes the frame. Empty if timestamps aren't supported.
+	pub gpu: Vec<Span>,
+}
+impl Frame {
+	/// Total time of the GPU's passes, if it reported them.
+	pub fn gpu_time(&self) -> Option<Duration> {
+		let first = self.gpu.iter().map(|span| span.start).min()?;
+		let last = self.gpu.iter().map(|span| span.end).max()?;
+		Some(last - first)
+	}
+
+	/// Total time of the CPU scopes with this name, across all threads.
+	pub fn cpu_time(&self, name: &str) -> Duration {
+		self.cpu.iter().filter(|span| span.name == name).map(Span::duration).sum()
+	}
+}
+
+/// Starts or stops recording scopes, and GPU timings from windows. Stopping drops what was recorded.
+pub fn set_enabled(enabled: bool) {
+	// traces are timed from here, so nothing can start before it
+	lazy_static::initialize(&EPOCH);
+	ENABLED.store(enabled, Ordering::Relaxed);
+	if !enabled {
+		let mut state = STATE.lock().unwrap();
+		state.spans.clear();
+		state.frames.clear();
+	}
+}
+
+pub fn enabled() -> bool {
+	ENABLED.load(Ordering::Relaxed)
+}
+
+/// Times from now until the returned guard is dropped, on any thread. Free while profiling is disabled.
+pub fn scope(name: &'static str) -> Scope {
+	Scope { name, start: if enabled() { Some(Instant::now()) } else { None } }
+}
+
+#[must_use = "the scope ends when this is dropped"]
+pub struct Scope {
+	name: &'static str,
+	start: Option<Instant>,
+}
+impl Drop for Scope {
+	fn drop(&mut self) {
+		if let Some(start) = self.start {
+			let thread = THREAD.with(|&thread| thread);
+			let span = Span { name: self.name.into(), thread: Some(thread), start, end: Instant::now() };
+			STATE.lock().unwrap().spans.push(span);
+		}
+	}
+}
+
+/// The number of the frame being recorded, to hand back to `add_gpu_spans` later.
+pub fn frame_number() -> u64 {
+	STATE.lock().unwrap().frame
+}
+
+/// Ends the frame, moving the scopes recorded since the last call into the history. Called by `Window::draw`.
+pub fn end_frame() {
+	let now = Instant::now();
+	let mut state = STATE.lock().unwrap();
+	let number = state.frame;
+	state.frame += 1;
+	let start = state.frame_start.replace(now);
+	if !enabled() {
+		return;
+	}
+
+	let cpu = mem::take(&mut state.spans);
+	let frame = Frame { number, start: start.unwrap_or(now), end: now, cpu, gpu: vec![] };
+	if state.frames.len() == HISTORY {
+		state.frames.pop_front();
+	}
+	state.frames.push_back(frame);
+}
+
+/// Attaches GPU timings to an earlier frame, if it's still in the history.
+pub fn add_gpu_spans(number: u64, spans: Vec<Span>) {
+	let mut state = STATE.lock().unwrap();
+	if let Some(frame) = state.frames.iter_mut().find(|frame| frame.number == number) {
+		frame.gpu = spans;
+	}
+}
+
+/// The most recent frames, oldest first.
+pub fn frames() -> Vec<Frame> {
+	STATE.lock().unwrap().frames.iter().cloned().collect()
+}
+
+/// The newest frame the GPU has reported on, or just the newest frame if it never does.
+pub fn last_frame() -> Option<Frame> {
+	let state = STATE.lock().unwrap();
+	let mut frames = state.frames.iter().rev();
+	frames.clone().find(|frame| !frame.gpu.is_empty()).or_else(|| frames.next()).cloned()
+}
+
+/// Writes the recorded frames as a Chrome trace, for `chrome://tracing` or Perfetto. Each CPU thread and the GPU get
+/// their own track.
+pub async fn export_chrome_trace<P: AsRef<Path> + Send + 'static>(path: P) -> io::Result<()> {
+	let trace = chrome_trace();
+	write_all(path, trace.to_string().into_bytes()).await
+}
+
+fn chrome_trace() -> Json {
+	let state = STATE.lock().unwrap();
+	let gpu_thread = state.threads.len() as u32;
+	let micros = |time: Instant| time.saturating_duration_since(*EPOCH).as_secs_f64() * 1_000_000.0;
+
+	let thread_names = state.threads.iter().map(|name| name.as_str()).chain(Some("gpu"));
+	let mut events: Vec<_> = thread_names
+		.enumerate()
+		.map(|(tid, name)| {
+			Json::Object(vec![
+				("name".to_owned(), Json::String("thread_name".to_owned())),
+				("ph".to_owned(), Json::String("M".to_owned())),
+				("pid".to_owned(), Json::Number(0.0)),
+				("tid".to_owned(), Json::Number(tid as f64)),
+				("args".to_owned(), Json::Object(vec![("name".to_owned(), Json::String(name.to_owned()))])),
+			])
+		})
+		.collect();
+
+	for frame in &state.frames {
+		for span in frame.cpu.iter().chain(&frame.gpu) {
+			let (category, tid) = match span.thread {
+				Some(thread) => ("cpu", thread),
+				None => ("gpu", gpu_thread),
+			};
+			events.push(Json::Object(vec![
+				("name".to_owned(), Json::String(span.name.to_string())),
+				("cat".to_owned(), Json::String(category.to_owned())),
+				("ph".to_owned(), Json::String("X".to_owned())),
+				("ts".to_owned(), Json::Number(micros(span.start))),
+				("dur".to_owned(), Json::Number(micros(span.end) - micros(span.start))),
+				("pid".to_owned(), Json::Number(0.0)),
+				("tid".to_owned(), Json::Number(tid as f64)),
+			]));
+		}
+	}
+
+	Json::Object(vec![
+		("traceEvents".to_owned(), Json::Array(events)),
+		("displayTimeUnit".to_owned(), Json::String("ms".to_owned())),
+	])
+}
+
+fn register_thread() -> u32 {
+	let id = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+	let current = thread::current();
+	let name = current.name().map_or_else(|| format!("thread {}", id), |name| name.to_owned());
+	let mut state = STATE.lock().unwrap();
+	// threads can register out of order, since ids are handed out before taking the lock
+	if state.threads.len() <= id as usize {
+		state.threads.resize(id as usize + 1, String::new());
+	}
+	state.threads[id as usize] = name;
+	id
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	lazy_static! {
+		// the profiler is global, so its tests take turns
+		static ref LOCK: Mutex<()> = Mutex::default();
+	}
+
+	fn names(spans: &[Span]) -> Vec<&str> {
+		spans.iter().map(|span| &*span.name).filter(|name| name.starts_with("test")).collect()
+	}
+
+	#[test]
+	fn history() {
+		let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+		set_enabled(true);
+		let first = frame_number();
+		drop(scope("test a"));
+		drop(scope("test b"));
+		end_frame();
+		let recorded = frames();
+		assert_eq!(recorded.len(), 1);
+		assert_eq!(recorded[0].number, first);
+		assert_eq!(names(&recorded[0].cpu), ["test a", "test b"]);
+
+		// old frames fall out of the history, and so can't be given GPU timings anymore
+		for _ in 0..HISTORY {
+			end_frame();
+		}
+		let recorded = frames();
+		assert_eq!(recorded.len(), HISTORY);
+		assert_eq!(recorded[0].number, first + 1);
+		assert!(recorded.windows(2).all(|pair| pair[1].number == pair[0].number + 1));
+		assert!(recorded.iter().all(|frame| names(&frame.cpu).is_empty()));
+		let now = Instant::now();
+		let gpu = |name: &'static str| vec![Span { name: name.into(), thread: None, start: now, end: now }];
+		add_gpu_spans(first, gpu("test dropped"));
+		assert!(frames().iter().all(|frame| frame.gpu.is_empty()));
+
+		// the newest frame until the GPU reports on one, then the newest it has reported on
+		assert_eq!(last_frame().unwrap().number, first + HISTORY as u64);
+		add_gpu_spans(first + 10, gpu("test gpu"));
+		end_frame();
+		let last = last_frame().unwrap();
+		assert_eq!(last.number, first + 10);
+		assert_eq!(last.gpu_time(), Some(Duration::from_secs(0)));
+
+		set_enabled(false);
+		end_frame();
+		assert!(frames().is_empty());
+	}
+
+	#[test]
+	fn chrome_trace_events() {
+		let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+		set_enabled(true);
+		let worker = thread::Builder::new().name("test worker".to_owned());
+		worker.spawn(|| drop(scope("test worker"))).unwrap().join().unwrap();
+		let number = frame_number();
+		drop(scope("test main"));
+		end_frame();
+		let now = Instant::now();
+		let pass = Span { name: "test pass".into(), thread: None, start: now, end: now + Duration::from_millis(2) };
+		add_gpu_spans(number, vec![pass]);
+
+		let trace = Json::parse(&chrome_trace().to_string()).unwrap();
+		set_enabled(false);
+		let events = trace.get("traceEvents").unwrap().as_array().unwrap();
+		let str = |event: &Json, key| event.get(key).and_then(Json::as_str).map(str::to_owned);
+		let tid = |event: &Json| event.get("tid").and_then(Json::as_u32).unwrap();
+		// every thread is named, with the GPU after the CPU threads
+		let thread = |name| {
+			let mut meta = events.iter().filter(|event| str(event, "ph").as_deref() == Some("M"));
+			tid(meta.find(|event| str(event.get("args").unwrap(), "name").as_deref() == Some(name)).unwrap())
+		};
+		let gpu = thread("gpu");
+		assert!(thread("test worker") < gpu);
+
+		let span = |name| events.iter().find(|event| str(event, "name").as_deref() == Some(name)).unwrap();
+		assert_eq!(tid(span("test worker")), thread("test worker"));
+		assert_eq!(str(span("test main"), "cat").as_deref(), Some("cpu"));
+		assert_ne!(tid(span("test main")), gpu);
+		let pass = span("test pass");
+		assert_eq!(str(pass, "cat").as_deref(), Some("gpu"));
+		assert_eq!(str(pass, "ph").as_deref(), Some("X"));
+		assert_eq!(tid(pass), gpu);
+		let dur = pass.get("dur").and_then(Json::as_f64).unwrap();
+		assert!((dur - 2000.0).abs() < 1.0, "{}", dur);
+	}
+}
@@ -0,0 +1,452 @@
+mod binary;
+pub mod component;
+pub mod document;
+mod text;
+pub mod transform;
+
+use crate::gfx::sprite::{Sprite, SpriteList};
+use component::Component;
+use nalgebra::{Matrix3, Point2, Vector2};
+use transform::Transform;
+
+/// Ids of removed nodes stay invalid after their slot is reused by a new node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId {
+	idx: u32,
+	// how many times the slot had been freed when the node was added
+	generation: u32,
+}
+
+pub struct Node {
+	pub name: String,
+	/// Drawn at the node's world position. The sprite's `pos` is an offset in the node's local space.
+	pub sprite: Option<Sprite>,
+	/// Half extents of a local-space rectangle centered on the node, used for picking.
+	pub bounds: Option<Vector2<f32>>,
+	pub components: Vec<Component>,
+	local: Transform,
+	visible: bool,
+	parent: Option<NodeId>,
+	children: Vec<NodeId>,
+	// cached by `Scene::update`
+	world: Matrix3<f32>,
+	world_visible: bool,
+	dirty: bool,
+}
+impl Node {
+	pub fn transform(&self) -> &Transform {
+		&self.local
+	}
+
+	pub fn visible(&self) -> bool {
+		self.visible
+	}
+
+	/// Whether this node and all of its ancestors are visible, as of the last `Scene::update`.
+	pub fn visible_in_tree(&self) -> bool {
+		self.world_visible
+	}
+
+	pub fn parent(&self) -> Option<NodeId> {
+		self.parent
+	}
+
+	pub fn children(&self) -> &[NodeId] {
+		&self.children
+	}
+
+	/// Local to world space, as of the last `Scene::update`.
+	pub fn world(&self) -> &Matrix3<f32> {
+		&self.world
+	}
+
+	pub fn world_pos(&self) -> Vector2<f32> {
+		self.world.transform_point(&Point2::origin()).coords
+	}
+}
+
+/// A hierarchy of nodes whose transforms and visibility are relative to their parents. Changes are cached lazily:
+/// call `update` once they're done for the frame, before reading world transforms.
+#[derive(Default)]
+pub struct Scene {
+	// generation and node per slot
+	nodes: Vec<(u32, Option<Node>)>,
+	free: Vec<u32>,
+	roots: Vec<NodeId>,
+	dirty: bool,
+}
+impl Scene {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn add(&mut self, name: impl Into<String>, transform: Transform, parent: Option<NodeId>) -> NodeId {
+		let node = Node {
+			name: name.into(),
+			sprite: None,
+			bounds: None,
+			components: vec![],
+			local: transform,
+			visible: true,
+			parent,
+			children: vec![],
+			world: Matrix3::identity(),
+			world_visible: true,
+			dirty: true,
+		};
+		let id = match self.free.pop() {
+			Some(idx) => {
+				let slot = &mut self.nodes[idx as usize];
+				slot.1 = Some(node);
+				NodeId { idx, generation: slot.0 }
+			},
+			None => {
+				self.nodes.push((0, Some(node)));
+				NodeId { idx: self.nodes.len() as u32 - 1, generation: 0 }
+			},
+		};
+		self.siblings_mut(parent).push(id);
+		self.dirty = true;
+		id
+	}
+
+	/// Removes `id` along with all of its descendants.
+	pub fn remove(&mut self, id: NodeId) {
+		let parent = match self.node(id) {
+			Some(node) => node.parent,
+			None => return,
+		};
+		self.siblings_mut(parent).retain(|&child| child != id);
+
+		let mut stack = vec![id];
+		while let Some(id) = stack.pop() {
+			let slot = &mut self.nodes[id.idx as usize];
+			let node = slot.1.take().unwrap();
+			slot.0 = slot.0.wrapping_add(1);
+			stack.extend(node.children);
+			self.free.push(id.idx);
+		}
+	}
+
+	pub fn node(&self, id: NodeId) -> Option<&Node> {
+		self.nodes.get(id.idx as usize).filter(|slot| slot.0 == id.generation)?.1.as_ref()
+	}
+
+	pub fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+		self.nodes.get_mut(id.idx as usize).filter(|slot| slot.0 == id.generation)?.1.as_mut()
+	}
+
+	pub fn nodes(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+		self.nodes.iter().enumerate().filter_map(|(idx, (generation, node))| {
+			node.as_ref().map(|node| (NodeId { idx: idx as u32, generation: *generation }, node))
+		})
+	}
+
+	pub fn roots(&self) -> &[NodeId] {
+		&self.roots
+	}
+
+	/// Finds the first node called `name` among the descendants of `parent`, or in the whole scene for `None`.
+	pub fn find(&self, parent: Option<NodeId>, name: &str) -> Option<NodeId> {
+		let mut stack: Vec<_> = self.siblings(parent).iter().rev().copied().collect();
+		while let Some(id) = stack.pop() {
+			let node = self.get(id);
+			if node.name == name {
+				return Some(id);
+			}
+			stack.extend(node.children.iter().rev());
+		}
+		None
+	}
+
+	pub fn set_transform(&mut self, id: NodeId, transform: Transform) {
+		let node = self.get_mut(id);
+		node.local = transform;
+		node.dirty = true;
+		self.dirty = true;
+	}
+
+	pub fn set_visible(&mut self, id: NodeId, visible: bool) {
+		let node = self.get_mut(id);
+		node.visible = visible;
+		node.dirty = true;
+		self.dirty = true;
+	}
+
+	/// Moves `id` under `parent`, keeping its local transform, so it jumps to the same place relative to its new
+	/// parent. Panics if `parent` is `id` or one of its descendants.
+	pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) {
+		if let Some(parent) = parent {
+			assert!(!self.is_ancestor(id, parent), "a node can't be moved under itself");
+		}
+		let old = self.get(id).parent;
+		self.siblings_mut(old).retain(|&child| child != id);
+		self.siblings_mut(parent).push(id);
+
+		let node = self.get_mut(id);
+		node.parent = parent;
+		node.dirty = true;
+		self.dirty = true;
+	}
+
+	/// Whether `ancestor` is `id` or one of its ancestors.
+	pub fn is_ancestor(&self, ancestor: NodeId, mut id: NodeId) -> bool {
+		loop {
+			if id == ancestor {
+				return true;
+			}
+			match self.get(id).parent {
+				Some(parent) => id = parent,
+				None => return false,
+			}
+		}
+	}
+
+	/// Recomputes the cached world transforms and visibility of every node changed since the last update, along with
+	/// their descendants.
+	pub fn update(&mut self) {
+		if !self.dirty {
+			return;
+		}
+		self.dirty = false;
+
+		let mut stack: Vec<_> = self.roots.iter().map(|&id| (id, Matrix3::identity(), true, false)).collect();
+		while let Some((id, parent_world, parent_visible, parent_dirty)) = stack.pop() {
+			let node = self.nodes[id.idx as usize].1.as_mut().unwrap();
+			let dirty = node.dirty || parent_dirty;
+			if dirty {
+				node.world = parent_world * node.local.to_matrix();
+				node.world_visible = parent_visible && node.visible;
+				node.dirty = false;
+			}
+			let (world, visible) = (node.world, node.world_visible);
+			stack.extend(node.children.iter().map(|&child| (child, world, visible, dirty)));
+		}
+	}
+
+	/// Visits visible nodes depth-first, parents before children and siblings in the order they were added, which is
+	/// also back to front among nodes sharing a sprite layer. Invisible nodes are skipped along with their subtrees.
+	pub fn traverse(&self, mut f: impl FnMut(NodeId, &Node)) {
+		let mut stack: Vec<_> = self.roots.iter().rev().copied().collect();
+		while let Some(id) = stack.pop() {
+			let node = self.get(id);
+			if !node.visible {
+				continue;
+			}
+			f(id, node);
+			stack.extend(node.children.iter().rev());
+		}
+	}
+
+	/// Queues the sprites of every visible node, turned and scaled along with it. Skew from scaling a child of a
+	/// turned node unevenly is dropped, since sprites can't show it.
+	pub fn draw(&self, sprites: &mut SpriteList) {
+		self.traverse(|_, node| {
+			let sprite = match &node.sprite {
+				Some(sprite) => sprite,
+				None => return,
+			};
+			// the node's local axes, turned and scaled into world space
+			let world = &node.world;
+			let x_axis = Vector2::new(world[(0, 0)], world[(1, 0)]);
+			let y_axis = Vector2::new(world[(0, 1)], world[(1, 1)]);
+			let width = x_axis.norm();
+			if width == 0.0 {
+				return;
+			}
+			let pos = world.transform_point(&Point2::from(sprite.pos)).coords;
+			let rotation = x_axis.y.atan2(x_axis.x) + sprite.rotation;
+			let scale = Vector2::new(width, x_axis.perp(&y_axis) / width).component_mul(&sprite.scale);
+			sprites.push(Sprite { pos, rotation, scale, ..sprite.clone() });
+		});
+	}
+
+	/// Returns the topmost visible node whose bounds contain `point`, in world space.
+	pub fn pick(&self, point: Vector2<f32>) -> Option<NodeId> {
+		let point = Point2::from(point);
+		let mut hit = None;
+		let mut order = 0u32;
+		self.traverse(|id, node| {
+			order += 1;
+			let half_extents = match node.bounds {
+				Some(half_extents) => half_extents,
+				None => return,
+			};
+			let local = match node.world.try_inverse() {
+				Some(inverse) => inverse.transform_point(&point),
+				None => return,
+			};
+			if local.x.abs() <= half_extents.x && local.y.abs() <= half_extents.y {
+				let layer = node.sprite.as_ref().map_or(0, |sprite| sprite.layer);
+				if hit.is_none_or(|(key, _)| (layer, order) > key) {
+					hit = Some(((layer, order), id));
+				}
+			}
+		});
+		hit.map(|(_, id)| id)
+	}
+
+	fn get(&self, id: NodeId) -> &Node {
+		self.node(id).expect("invalid node")
+	}
+
+	fn get_mut(&mut self, id: NodeId) -> &mut Node {
+		self.node_mut(id).expect("invalid node")
+	}
+
+	fn siblings(&self, parent: Option<NodeId>) -> &[NodeId] {
+		match parent {
+			Some(parent) => &self.get(parent).children,
+			None => &self.roots,
+		}
+	}
+
+	fn siblings_mut(&mut self, parent: Option<NodeId>) -> &mut Vec<NodeId> {
+		match parent {
+			Some(parent) => &mut self.get_mut(parent).children,
+			None => &mut self.roots,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f32::consts::FRAC_PI_2;
+
+	fn at(x: f32, y: f32) -> Transform {
+		Transform::new(Vector2::new(x, y), 0.0, Vector2::new(1.0, 1.0))
+	}
+
+	fn world_pos(scene: &Scene, id: NodeId) -> Vector2<f32> {
+		scene.node(id).unwrap().world_pos()
+	}
+
+	fn near(a: Vector2<f32>, b: Vector2<f32>) -> bool {
+		(a - b).norm() < 0.0001
+	}
+
+	#[test]
+	fn dirty_propagates_to_descendants() {
+		let mut scene = Scene::new();
+		let root = scene.add("root", at(10.0, 0.0), None);
+		let child = scene.add("child", at(0.0, 5.0), Some(root));
+		let grandchild = scene.add("grandchild", at(1.0, 0.0), Some(child));
+		let other = scene.add("other", at(-3.0, 0.0), None);
+		scene.update();
+		assert_eq!(world_pos(&scene, grandchild), Vector2::new(11.0, 5.0));
+		assert!(scene.nodes().all(|(_, node)| !node.dirty));
+
+		// cached until the next update
+		scene.set_transform(root, Transform::new(Vector2::zeros(), FRAC_PI_2, Vector2::new(2.0, 2.0)));
+		assert!(scene.node(root).unwrap().dirty && !scene.node(child).unwrap().dirty);
+		assert_eq!(world_pos(&scene, grandchild), Vector2::new(11.0, 5.0));
+		scene.update();
+		assert!(near(world_pos(&scene, child), Vector2::new(-10.0, 0.0)));
+		assert!(near(world_pos(&scene, grandchild), Vector2::new(-10.0, 2.0)));
+		assert_eq!(world_pos(&scene, other), Vector2::new(-3.0, 0.0));
+
+		scene.set_visible(child, false);
+		scene.update();
+		assert!(scene.node(root).unwrap().visible_in_tree());
+		assert!(!scene.node(grandchild).unwrap().visible_in_tree());
+		scene.set_visible(child, true);
+		scene.update();
+		assert!(scene.node(grandchild).unwrap().visible_in_tree());
+	}
+
+	#[test]
+	fn set_parent_keeps_local_transform() {
+		let mut scene = Scene::new();
+		let a = scene.add("a", at(10.0, 0.0), None);
+		let b = scene.add("b", at(0.0, 20.0), None);
+		let child = scene.add("child", at(1.0, 1.0), Some(a));
+		let grandchild = scene.add("grandchild", at(1.0, 0.0), Some(child));
+		scene.update();
+
+		scene.set_parent(child, Some(b));
+		scene.update();
+		assert_eq!(scene.node(a).unwrap().children(), []);
+		assert_eq!(scene.node(b).unwrap().children(), [child]);
+		assert_eq!(scene.node(child).unwrap().parent(), Some(b));
+		assert_eq!(world_pos(&scene, child), Vector2::new(1.0, 21.0));
+		assert_eq!(world_pos(&scene, grandchild), Vector2::new(2.0, 21.0));
+
+		scene.set_parent(child, None);
+		scene.update();
+		assert_eq!(scene.roots(), [a, b, child]);
+		assert_eq!(world_pos(&scene, grandchild), Vector2::new(2.0, 1.0));
+		assert_eq!(scene.find(None, "grandchild"), Some(grandchild));
+		assert_eq!(scene.find(Some(a), "grandchild"), None);
+	}
+
+	#[test]
+	#[should_panic(expected = "a node can't be moved under itself")]
+	fn set_parent_under_descendant() {
+		let mut scene = Scene::new();
+		let root = scene.add("root", Transform::identity(), None);
+		let child = scene.add("child", Transform::identity(), Some(root));
+		let grandchild = scene.add("grandchild", Transform::identity(), Some(child));
+		scene.set_parent(root, Some(grandchild));
+	}
+
+	#[test]
+	fn pick_topmost() {
+		let mut scene = Scene::new();
+		let back = scene.add("back", at(0.0, 0.0), None);
+		scene.node_mut(back).unwrap().bounds = Some(Vector2::new(10.0, 10.0));
+		let front = scene.add("front", at(5.0, 0.0), None);
+		scene.node_mut(front).unwrap().bounds = Some(Vector2::new(2.0, 2.0));
+		// bounds are in local space, so this one is 2 wide and 20 tall
+		let transform = Transform::new(Vector2::new(30.0, 0.0), FRAC_PI_2, Vector2::new(2.0, 1.0));
+		let turned = scene.add("turned", transform, None);
+		scene.node_mut(turned).unwrap().bounds = Some(Vector2::new(5.0, 1.0));
+		scene.update();
+
+		assert_eq!(scene.pick(Vector2::new(-5.0, 0.0)), Some(back));
+		assert_eq!(scene.pick(Vector2::new(6.0, 1.0)), Some(front));
+		assert_eq!(scene.pick(Vector2::new(30.0, 9.0)), Some(turned));
+		assert_eq!(scene.pick(Vector2::new(33.0, 0.0)), None);
+
+		scene.set_visible(front, false);
+		scene.update();
+		assert_eq!(scene.pick(Vector2::new(6.0, 1.0)), Some(back));
+	}
+
+	#[test]
+	fn remove_subtree() {
+		let mut scene = Scene::new();
+		let root = scene.add("root", Transform::identity(), None);
+		let child = scene.add("child", Transform::identity(), Some(root));
+		let grandchild = scene.add("grandchild", Transform::identity(), Some(child));
+		let sibling = scene.add("sibling", Transform::identity(), Some(root));
+
+		scene.remove(child);
+		assert!(scene.node(child).is_none());
+		assert!(scene.node(grandchild).is_none());
+		assert_eq!(scene.node(root).unwrap().children(), [sibling]);
+		assert_eq!(scene.nodes().map(|(id, _)| id).collect::<Vec<_>>(), [root, sibling]);
+
+		let mut visited = vec![];
+		scene.update();
+		scene.traverse(|id, _| visited.push(id));
+		assert_eq!(visited, [root, sibling]);
+	}
+
+	#[test]
+	fn stale_ids_rejected() {
+		let mut scene = Scene::new();
+		let old = scene.add("old", Transform::identity(), None);
+		scene.remove(old);
+		let new = scene.add("new", Transform::identity(), None);
+		assert_eq!(new.idx, old.idx);
+		assert_ne!(new, old);
+
+		assert!(scene.node(old).is_none());
+		assert!(scene.node_mut(old).is_none());
+		// removing a stale id leaves the node in its slot alone
+		scene.remove(old);
+		assert_eq!(scene.node(new).unwrap().name, "new");
+		assert_eq!(scene.roots(), [new]);
+	}
+}
@@ -0,0 +1,253 @@
+use crate::scene::{
+	component::{Component, Value},
+	document::{Document, NodeDesc, SpriteDesc, TextureRef, FORMAT_VERSION},
+	transform::Transform,
+};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use nalgebra::Vector2;
+use std::io::{self, Read};
+
+pub(super) const MAGIC: &[u8] = b"SCNB";
+
+const VISIBLE: u8 = 1 << 0;
+const SPRITE: u8 = 1 << 1;
+const TRANSPARENT: u8 = 1 << 2;
+const BOUNDS: u8 = 1 << 3;
+const TINT: u8 = 1 << 4;
+const TEXTURE: u8 = 1 << 5;
+const NORMAL_MAP: u8 = 1 << 6;
+
+const NO_PARENT: u32 = u32::MAX;
+
+pub(super) fn write(doc: &Document) -> Vec<u8> {
+	// writing to a Vec can't fail
+	let mut out = MAGIC.to_vec();
+	out.write_u32::<LittleEndian>(FORMAT_VERSION).unwrap();
+	out.write_u32::<LittleEndian>(doc.version).unwrap();
+	out.write_u32::<LittleEndian>(doc.nodes.len() as _).unwrap();
+	for node in &doc.nodes {
+		write_str(&mut out, &node.name);
+		out.write_u32::<LittleEndian>(node.parent.unwrap_or(NO_PARENT)).unwrap();
+		write_vec2(&mut out, node.transform.translation());
+		out.write_f32::<LittleEndian>(node.transform.rotation()).unwrap();
+		write_vec2(&mut out, node.transform.scale);
+
+		let mut flags = 0;
+		if node.visible {
+			flags |= VISIBLE;
+		}
+		if let Some(sprite) = &node.sprite {
+			flags |= SPRITE;
+			if sprite.transparent {
+				flags |= TRANSPARENT;
+			}
+			if sprite.tint != [1.0; 4] {
+				flags |= TINT;
+			}
+			if sprite.texture.is_some() {
+				flags |= TEXTURE;
+			}
+			if sprite.normal_map.is_some() {
+				flags |= NORMAL_MAP;
+			}
+		}
+		if node.bounds.is_some() {
+			flags |= BOUNDS;
+		}
+		out.write_u8(flags).unwrap();
+		if let Some(sprite) = &node.sprite {
+			write_vec2(&mut out, sprite.pos);
+			out.write_f32::<LittleEndian>(sprite.rotation).unwrap();
+			write_vec2(&mut out, sprite.scale);
+			out.write_u16::<LittleEndian>(sprite.layer).unwrap();
+			if flags & TINT != 0 {
+				for &channel in &sprite.tint {
+					out.write_f32::<LittleEndian>(channel).unwrap();
+				}
+			}
+			for texture in sprite.texture.iter().chain(&sprite.normal_map) {
+				write_str(&mut out, &texture.path);
+				for &n in &[texture.pos.0, texture.pos.1, texture.size.0, texture.size.1] {
+					out.write_u32::<LittleEndian>(n).unwrap();
+				}
+			}
+		}
+		if let Some(bounds) = node.bounds {
+			write_vec2(&mut out, bounds);
+		}
+
+		out.write_u32::<LittleEndian>(node.components.len() as _).unwrap();
+		for component in &node.components {
+			write_str(&mut out, &component.kind);
+			out.write_u32::<LittleEndian>(component.props.len() as _).unwrap();
+			for (name, value) in &component.props {
+				write_str(&mut out, name);
+				match value {
+					Value::Bool(value) => {
+						out.write_u8(0).unwrap();
+						out.write_u8(*value as _).unwrap();
+					},
+					Value::Int(value) => {
+						out.write_u8(1).unwrap();
+						out.write_i64::<LittleEndian>(*value).unwrap();
+					},
+					Value::Float(value) => {
+						out.write_u8(2).unwrap();
+						out.write_f32::<LittleEndian>(*value).unwrap();
+					},
+					Value::Vec2(value) => {
+						out.write_u8(3).unwrap();
+						write_vec2(&mut out, *value);
+					},
+					Value::Str(value) => {
+						out.write_u8(4).unwrap();
+						write_str(&mut out, value);
+					},
+					Value::Asset(value) => {
+						out.write_u8(5).unwrap();
+						write_str(&mut out, value);
+					},
+				}
+			}
+		}
+	}
+	out
+}
+
+pub(super) fn read(mut data: &[u8]) -> io::Result<Document> {
+	let mut magic = [0; 4];
+	data.read_exact(&mut magic)?;
+	if magic != MAGIC {
+		return Err(invalid_data("not a binary scene"));
+	}
+	let format = data.read_u32::<LittleEndian>()?;
+	if format > FORMAT_VERSION {
+		return Err(invalid_data("scene format is newer than this engine supports"));
+	}
+	let version = data.read_u32::<LittleEndian>()?;
+
+	let count = data.read_u32::<LittleEndian>()?;
+	// don't trust the count with a huge allocation
+	let mut nodes = Vec::with_capacity(count.min(1024) as usize);
+	for _ in 0..count {
+		let name = read_str(&mut data)?;
+		let parent = match data.read_u32::<LittleEndian>()? {
+			NO_PARENT => None,
+			parent => Some(parent),
+		};
+		let translation = read_vec2(&mut data)?;
+		let rotation = data.read_f32::<LittleEndian>()?;
+		let transform = Transform::new(translation, rotation, read_vec2(&mut data)?);
+
+		let flags = data.read_u8()?;
+		let sprite = if flags & SPRITE != 0 {
+			let pos = read_vec2(&mut data)?;
+			// format 2 sprites had neither rotation nor scale
+			let (rotation, scale) = if format >= 3 {
+				(data.read_f32::<LittleEndian>()?, read_vec2(&mut data)?)
+			} else {
+				(0.0, Vector2::new(1.0, 1.0))
+			};
+			let layer = data.read_u16::<LittleEndian>()?;
+			let transparent = flags & TRANSPARENT != 0;
+			let mut sprite = SpriteDesc { rotation, scale, transparent, ..SpriteDesc::new(pos, layer) };
+			if flags & TINT != 0 {
+				for channel in &mut sprite.tint {
+					*channel = data.read_f32::<LittleEndian>()?;
+				}
+			}
+			if flags & TEXTURE != 0 {
+				sprite.texture = Some(read_texture(&mut data)?);
+			}
+			if flags & NORMAL_MAP != 0 {
+				sprite.normal_map = Some(read_texture(&mut data)?);
+			}
+			Some(sprite)
+		} else {
+			None
+		};
+		let bounds = if flags & BOUNDS != 0 { Some(read_vec2(&mut data)?) } else { None };
+
+		let mut components = vec![];
+		for _ in 0..data.read_u32::<LittleEndian>()? {
+			let mut component = Component::new(read_str(&mut data)?);
+			for _ in 0..data.read_u32::<LittleEndian>()? {
+				let name = read_str(&mut data)?;
+				let value = match data.read_u8()? {
+					0 => Value::Bool(data.read_u8()? != 0),
+					1 => Value::Int(data.read_i64::<LittleEndian>()?),
+					2 => Value::Float(data.read_f32::<LittleEndian>()?),
+					3 => Value::Vec2(read_vec2(&mut data)?),
+					4 => Value::Str(read_str(&mut data)?),
+					5 => Value::Asset(read_str(&mut data)?),
+					_ => return Err(invalid_data("unknown value type")),
+				};
+				component.props.push((name, value));
+			}
+			components.push(component);
+		}
+
+		nodes.push(NodeDesc { name, parent, transform, visible: flags & VISIBLE != 0, sprite, bounds, components });
+	}
+	Ok(Document { version, nodes })
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+	out.write_u32::<LittleEndian>(s.len() as _).unwrap();
+	out.extend_from_slice(s.as_bytes());
+}
+
+fn write_vec2(out: &mut Vec<u8>, v: Vector2<f32>) {
+	out.write_f32::<LittleEndian>(v.x).unwrap();
+	out.write_f32::<LittleEndian>(v.y).unwrap();
+}
+
+fn read_str(data: &mut &[u8]) -> io::Result<String> {
+	let len = data.read_u32::<LittleEndian>()? as usize;
+	if len > data.len() {
+		return Err(io::ErrorKind::UnexpectedEof.into());
+	}
+	let (s, rest) = data.split_at(len);
+	*data = rest;
+	String::from_utf8(s.to_vec()).map_err(|_| invalid_data("invalid utf-8"))
+}
+
+fn read_vec2(data: &mut &[u8]) -> io::Result<Vector2<f32>> {
+	Ok(Vector2::new(data.read_f32::<LittleEndian>()?, data.read_f32::<LittleEndian>()?))
+}
+
+fn read_texture(data: &mut &[u8]) -> io::Result<TextureRef> {
+	let path = read_str(data)?;
+	let mut read = || data.read_u32::<LittleEndian>();
+	Ok(TextureRef { path, pos: (read()?, read()?), size: (read()?, read()?) })
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reads_format_2() {
+		let mut data = MAGIC.to_vec();
+		for &n in &[2, 1, 1] {
+			data.write_u32::<LittleEndian>(n).unwrap();
+		}
+		write_str(&mut data, "player");
+		data.write_u32::<LittleEndian>(NO_PARENT).unwrap();
+		for &n in &[0.0, 0.0, 0.0, 1.0, 1.0] {
+			data.write_f32::<LittleEndian>(n).unwrap();
+		}
+		data.write_u8(VISIBLE | SPRITE | TRANSPARENT).unwrap();
+		write_vec2(&mut data, Vector2::new(4.0, 8.0));
+		data.write_u16::<LittleEndian>(2).unwrap();
+		data.write_u32::<LittleEndian>(0).unwrap();
+
+		let doc = read(&data).unwrap();
+		let sprite = SpriteDesc { transparent: true, ..SpriteDesc::new(Vector2::new(4.0, 8.0), 2) };
+		assert_eq!(doc.nodes[0].sprite, Some(sprite));
+	}
+}
@@ -0,0 +1,53 @@
+use nalgebra::Vector2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+	Bool(bool),
+	Int(i64),
+	Float(f32),
+	Vec2(Vector2<f32>),
+	Str(String),
+	/// Path of an asset to load alongside the scene.
+	Asset(String),
+}
+
+/// Game-defined data attached to a node, saved and loaded with the scene without the engine knowing its meaning.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Component {
+	pub kind: String,
+	pub props: Vec<(String, Value)>,
+}
+impl Component {
+	pub fn new(kind: impl Into<String>) -> Self {
+		Self { kind: kind.into(), props: vec![] }
+	}
+
+	pub fn with(mut self, name: impl Into<String>, value: Value) -> Self {
+		self.set(name, value);
+		self
+	}
+
+	pub fn get(&self, name: &str) -> Option<&Value> {
+		self.props.iter().find(|(prop, _)| prop == name).map(|(_, value)| value)
+	}
+
+	/// Replaces the property called `name`, or adds it if there isn't one.
+	pub fn set(&mut self, name: impl Into<String>, value: Value) {
+		let name = name.into();
+		match self.props.iter_mut().find(|(prop, _)| *prop == name) {
+			Some((_, old)) => *old = value,
+			None => self.props.push((name, value)),
+		}
+	}
+
+	pub fn remove(&mut self, name: &str) -> Option<Value> {
+		let idx = self.props.iter().position(|(prop, _)| prop == name)?;
+		Some(self.props.remove(idx).1)
+	}
+
+	pub fn rename(&mut self, from: &str, to: impl Into<String>) {
+		if let Some((prop, _)) = self.props.iter_mut().find(|(prop, _)| prop == from) {
+			*prop = to.into();
+		}
+	}
+}
@@ -0,0 +1,342 @@
+use crate::{
+	fs::{read_all_u8, write_all},
+	gfx::{sprite::Sprite, texture::Subtex, Gfx},
+	scene::{
+		binary,
+		component::{Component, Value},
+		text,
+		transform::Transform,
+		NodeId, Scene,
+	},
+};
+use nalgebra::Vector2;
+use std::{collections::HashMap, io, path::Path};
+
+/// Version of the file syntax itself. Files written by a newer engine are rejected, and older ones are upgraded as
+/// they're read.
+pub const FORMAT_VERSION: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+	/// Line based and diff friendly, for files kept in source control.
+	Text,
+	/// Compact, for shipping and save games.
+	Binary,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeDesc {
+	pub name: String,
+	/// Index of the parent in `Document::nodes`. Parents always come before their children.
+	pub parent: Option<u32>,
+	pub transform: Transform,
+	pub visible: bool,
+	pub sprite: Option<SpriteDesc>,
+	pub bounds: Option<Vector2<f32>>,
+	pub components: Vec<Component>,
+}
+
+/// A `Sprite` as stored on disk, with its textures referred to by the images they come from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteDesc {
+	pub pos: Vector2<f32>,
+	pub rotation: f32,
+	pub scale: Vector2<f32>,
+	pub layer: u16,
+	pub transparent: bool,
+	pub tint: [f32; 4],
+	pub texture: Option<TextureRef>,
+	pub normal_map: Option<TextureRef>,
+}
+impl SpriteDesc {
+	pub fn new(pos: Vector2<f32>, layer: u16) -> Self {
+		Self {
+			pos,
+			rotation: 0.0,
+			scale: Vector2::new(1.0, 1.0),
+			layer,
+			transparent: false,
+			tint: [1.0; 4],
+			texture: None,
+			normal_map: None,
+		}
+	}
+
+	/// Textures that weren't loaded from a file, like render textures, are left out.
+	pub fn from_sprite(sprite: &Sprite) -> Self {
+		Self {
+			pos: sprite.pos,
+			rotation: sprite.rotation,
+			scale: sprite.scale,
+			layer: sprite.layer,
+			transparent: sprite.transparent,
+			tint: sprite.tint,
+			texture: sprite.texture.as_ref().and_then(TextureRef::new),
+			normal_map: sprite.normal_map.as_ref().and_then(TextureRef::new),
+		}
+	}
+
+	/// `textures` holds the whole of every image referred to, by path.
+	pub fn to_sprite(&self, textures: &HashMap<String, Subtex>) -> io::Result<Sprite> {
+		let resolve = |texture: &Option<TextureRef>| texture.as_ref().map(|tex| tex.resolve(textures)).transpose();
+		Ok(Sprite {
+			pos: self.pos,
+			rotation: self.rotation,
+			scale: self.scale,
+			layer: self.layer,
+			transparent: self.transparent,
+			tint: self.tint,
+			texture: resolve(&self.texture)?,
+			normal_map: resolve(&self.normal_map)?,
+		})
+	}
+}
+
+/// A region of an image file, in texels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureRef {
+	pub path: String,
+	pub pos: (u32, u32),
+	pub size: (u32, u32),
+}
+impl TextureRef {
+	/// `None` if `texture` wasn't loaded from a file.
+	pub fn new(texture: &Subtex) -> Option<Self> {
+		Some(Self { path: texture.path()?.to_owned(), pos: texture.pos(), size: texture.size() })
+	}
+
+	fn resolve(&self, textures: &HashMap<String, Subtex>) -> io::Result<Subtex> {
+		let err = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("texture {}: {}", self.path, msg));
+		let texture = textures.get(&self.path).ok_or_else(|| err("not loaded"))?;
+		let ((x, y), (w, h), (tex_w, tex_h)) = (self.pos, self.size, texture.size());
+		let fits = |start: u32, len, max| start.checked_add(len).is_some_and(|end| end <= max);
+		if !fits(x, w, tex_w) || !fits(y, h, tex_h) {
+			return Err(err("region outside of the image"));
+		}
+		Ok(texture.sub(x, y, w, h))
+	}
+}
+
+/// A scene as stored on disk.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Document {
+	/// Version of the game's data, upgraded on load by `Migrations`.
+	pub version: u32,
+	pub nodes: Vec<NodeDesc>,
+}
+impl Document {
+	pub fn from_scene(scene: &Scene, version: u32) -> Self {
+		let mut nodes = vec![];
+		let mut indices = HashMap::new();
+		let mut stack: Vec<NodeId> = scene.roots().iter().rev().copied().collect();
+		while let Some(id) = stack.pop() {
+			let node = scene.node(id).unwrap();
+			indices.insert(id, nodes.len() as u32);
+			nodes.push(NodeDesc {
+				name: node.name.clone(),
+				parent: node.parent().map(|parent| indices[&parent]),
+				transform: *node.transform(),
+				visible: node.visible(),
+				sprite: node.sprite.as_ref().map(SpriteDesc::from_sprite),
+				bounds: node.bounds,
+				components: node.components.clone(),
+			});
+			stack.extend(node.children().iter().rev());
+		}
+		Self { version, nodes }
+	}
+
+	/// `textures` holds the whole of every image in `textures()`, by path.
+	pub fn into_scene(self, textures: &HashMap<String, Subtex>) -> io::Result<Scene> {
+		let mut scene = Scene::new();
+		let mut ids = Vec::with_capacity(self.nodes.len());
+		for (idx, desc) in self.nodes.into_iter().enumerate() {
+			let parent = match desc.parent {
+				Some(parent) if parent as usize >= idx => {
+					return Err(io::Error::new(io::ErrorKind::InvalidData, "node listed before its parent"));
+				},
+				parent => parent.map(|parent| ids[parent as usize]),
+			};
+			let sprite = desc.sprite.map(|sprite| sprite.to_sprite(textures)).transpose()?;
+			let id = scene.add(desc.name, desc.transform, parent);
+			scene.set_visible(id, desc.visible);
+			let node = scene.node_mut(id).unwrap();
+			node.sprite = sprite;
+			node.bounds = desc.bounds;
+			node.components = desc.components;
+			ids.push(id);
+		}
+		Ok(scene)
+	}
+
+	/// Every asset referenced by a component, so they can be loaded before the scene is shown.
+	pub fn assets(&self) -> impl Iterator<Item = &str> {
+		self.nodes.iter().flat_map(|node| &node.components).flat_map(|component| &component.props).filter_map(
+			|(_, value)| match value {
+				Value::Asset(path) => Some(path.as_str()),
+				_ => None,
+			},
+		)
+	}
+
+	/// Every image used by a sprite, possibly more than once.
+	pub fn textures(&self) -> impl Iterator<Item = &str> {
+		self.nodes
+			.iter()
+			.filter_map(|node| node.sprite.as_ref())
+			.flat_map(|sprite| sprite.texture.iter().chain(&sprite.normal_map))
+			.map(|texture| texture.path.as_str())
+	}
+
+	/// Detects the format from the contents.
+	pub fn decode(data: &[u8]) -> io::Result<Self> {
+		if data.starts_with(binary::MAGIC) {
+			binary::read(data)
+		} else {
+			let data = std::str::from_utf8(data).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+			text::read(data)
+		}
+	}
+
+	pub fn encode(&self, format: Format) -> Vec<u8> {
+		match format {
+			Format::Text => text::write(self).into_bytes(),
+			Format::Binary => binary::write(self),
+		}
+	}
+
+	pub async fn load<P: AsRef<Path> + Send + 'static>(path: P) -> io::Result<Self> {
+		Self::decode(&read_all_u8(path).await?)
+	}
+
+	pub async fn save<P: AsRef<Path> + Send + 'static>(&self, path: P, format: Format) -> io::Result<()> {
+		write_all(path, self.encode(format)).await
+	}
+}
+
+type Migration = Box<dyn Fn(&mut Document) + Send + Sync>;
+
+/// Upgrades documents saved by older versions of the game, one version at a time.
+#[derive(Default)]
+pub struct Migrations {
+	steps: Vec<(u32, Migration)>,
+}
+impl Migrations {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Registers `f` to upgrade documents at `version` to `version + 1`.
+	pub fn add(mut self, version: u32, f: impl Fn(&mut Document) + Send + Sync + 'static) -> Self {
+		self.steps.push((version, Box::new(f)));
+		self
+	}
+
+	/// The version documents end up at once migrated.
+	pub fn current(&self) -> u32 {
+		self.steps.iter().map(|&(version, _)| version + 1).max().unwrap_or(0)
+	}
+
+	pub fn migrate(&self, doc: &mut Document) -> io::Result<()> {
+		let current = self.current();
+		if doc.version > current {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "scene saved by a newer version of the game"));
+		}
+		while doc.version < current {
+			// versions without a step didn't change the data
+			if let Some((_, f)) = self.steps.iter().find(|&&(version, _)| version == doc.version) {
+				f(doc);
+			}
+			doc.version += 1;
+		}
+		Ok(())
+	}
+}
+
+impl Scene {
+	/// Loads the images used by its sprites too.
+	pub async fn load<P: AsRef<Path> + Send + 'static>(
+		gfx: &Gfx,
+		path: P,
+		migrations: &Migrations,
+	) -> io::Result<Self> {
+		let mut doc = Document::load(path).await?;
+		migrations.migrate(&mut doc)?;
+		let mut textures = HashMap::new();
+		for path in doc.textures() {
+			if !textures.contains_key(path) {
+				textures.insert(path.to_owned(), Subtex::load(gfx, path).await?);
+			}
+		}
+		doc.into_scene(&textures)
+	}
+
+	/// Saves every node, visible or not. `version` is usually `Migrations::current`.
+	pub async fn save<P: AsRef<Path> + Send + 'static>(&self, path: P, format: Format, version: u32) -> io::Result<()> {
+		Document::from_scene(self, version).save(path, format).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn texture(path: &str, x: u32) -> Option<TextureRef> {
+		Some(TextureRef { path: path.to_owned(), pos: (x, 0), size: (16, 32) })
+	}
+
+	fn doc() -> Document {
+		let sprite = SpriteDesc {
+			rotation: 0.5,
+			scale: Vector2::new(-1.0, 2.0),
+			transparent: true,
+			tint: [1.0, 0.5, 0.25, 0.75],
+			texture: texture("sprites/player.png", 16),
+			normal_map: texture("sprites/player_normals.png", 16),
+			..SpriteDesc::new(Vector2::new(-4.0, 2.5), 3)
+		};
+		let player = NodeDesc {
+			name: "player \"one\"".to_owned(),
+			parent: None,
+			transform: Transform::new(Vector2::new(100.0, -20.0), 0.0, Vector2::new(2.0, 3.0)),
+			visible: true,
+			sprite: Some(sprite),
+			bounds: Some(Vector2::new(8.0, 16.0)),
+			components: vec![Component::new("health")
+				.with("max", Value::Int(100))
+				.with("icon", Value::Asset("ui/heart.png".to_owned()))],
+		};
+		let sword = NodeDesc {
+			name: "sword".to_owned(),
+			parent: Some(0),
+			transform: Transform::identity(),
+			visible: false,
+			sprite: Some(SpriteDesc {
+				texture: texture("sprites/player.png", 0),
+				..SpriteDesc::new(Vector2::zeros(), 0)
+			}),
+			bounds: None,
+			components: vec![],
+		};
+		Document { version: 7, nodes: vec![player, sword] }
+	}
+
+	#[test]
+	fn text_round_trip() {
+		let doc = doc();
+		assert_eq!(Document::decode(&doc.encode(Format::Text)).unwrap(), doc);
+	}
+
+	#[test]
+	fn binary_round_trip() {
+		let doc = doc();
+		assert_eq!(Document::decode(&doc.encode(Format::Binary)).unwrap(), doc);
+	}
+
+	#[test]
+	fn textures() {
+		let doc = doc();
+		let textures: Vec<_> = doc.textures().collect();
+		assert_eq!(textures, ["sprites/player.png", "sprites/player_normals.png", "sprites/player.png"]);
+	}
+}
@@ -0,0 +1,311 @@
+use crate::scene::{
+	component::{Component, Value},
+	document::{Document, NodeDesc, SpriteDesc, TextureRef, FORMAT_VERSION},
+	transform::Transform,
+};
+use nalgebra::Vector2;
+use std::{fmt::Write, io, str::FromStr};
+
+// One statement per line. Everything after `node` up to the next `node` describes that node, and every `prop` belongs
+// to the last `component`. Indentation is only there for readability.
+//
+//   format 3
+//   version 0
+//   node "player"
+//   	transform 100 0 0 1 1
+//   	sprite 0 0 0 1 1 1 opaque
+//   	texture "player.png" 0 0 16 16
+//   	tint 1 0.5 0.5 1
+//   	component "health"
+//   		prop "max" int 100
+//   node "sword"
+//   	parent 0
+//   	hidden
+
+pub(super) fn write(doc: &Document) -> String {
+	let mut out = String::new();
+	writeln!(out, "format {}", FORMAT_VERSION).unwrap();
+	writeln!(out, "version {}", doc.version).unwrap();
+	for node in &doc.nodes {
+		writeln!(out, "node {}", quote(&node.name)).unwrap();
+		if let Some(parent) = node.parent {
+			writeln!(out, "\tparent {}", parent).unwrap();
+		}
+		let transform = &node.transform;
+		let (translation, scale) = (transform.translation(), transform.scale);
+		writeln!(
+			out,
+			"\ttransform {} {} {} {} {}",
+			translation.x,
+			translation.y,
+			transform.rotation(),
+			scale.x,
+			scale.y
+		)
+		.unwrap();
+		if !node.visible {
+			writeln!(out, "\thidden").unwrap();
+		}
+		if let Some(sprite) = &node.sprite {
+			let blend = if sprite.transparent { "transparent" } else { "opaque" };
+			let (pos, scale) = (sprite.pos, sprite.scale);
+			writeln!(
+				out,
+				"\tsprite {} {} {} {} {} {} {}",
+				pos.x,
+				pos.y,
+				sprite.rotation,
+				scale.x,
+				scale.y,
+				sprite.layer,
+				blend
+			)
+			.unwrap();
+			for &(keyword, texture) in &[("texture", &sprite.texture), ("normal_map", &sprite.normal_map)] {
+				if let Some(texture) = texture {
+					let ((x, y), (w, h)) = (texture.pos, texture.size);
+					writeln!(out, "\t{} {} {} {} {} {}", keyword, quote(&texture.path), x, y, w, h).unwrap();
+				}
+			}
+			if sprite.tint != [1.0; 4] {
+				let [r, g, b, a] = sprite.tint;
+				writeln!(out, "\ttint {} {} {} {}", r, g, b, a).unwrap();
+			}
+		}
+		if let Some(bounds) = node.bounds {
+			writeln!(out, "\tbounds {} {}", bounds.x, bounds.y).unwrap();
+		}
+		for component in &node.components {
+			writeln!(out, "\tcomponent {}", quote(&component.kind)).unwrap();
+			for (name, value) in &component.props {
+				let value = match value {
+					Value::Bool(value) => format!("bool {}", value),
+					Value::Int(value) => format!("int {}", value),
+					Value::Float(value) => format!("float {}", value),
+					Value::Vec2(value) => format!("vec2 {} {}", value.x, value.y),
+					Value::Str(value) => format!("str {}", quote(value)),
+					Value::Asset(value) => format!("asset {}", quote(value)),
+				};
+				writeln!(out, "\t\tprop {} {}", quote(name), value).unwrap();
+			}
+		}
+	}
+	out
+}
+
+pub(super) fn read(data: &str) -> io::Result<Document> {
+	let mut doc = Document { version: 0, nodes: vec![] };
+	let mut format = FORMAT_VERSION;
+	for (idx, line) in data.lines().enumerate() {
+		let err = |msg: String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", idx + 1, msg));
+		let mut line = Line::new(line).map_err(err)?;
+		read_statement(&mut doc, &mut format, &mut line).and_then(|()| line.end()).map_err(err)?;
+	}
+	Ok(doc)
+}
+
+fn read_statement(doc: &mut Document, format: &mut u32, line: &mut Line) -> Result<(), String> {
+	let keyword = match line.next() {
+		Some(Token::Word(keyword)) => keyword,
+		Some(Token::Str(_)) => return Err("expected a keyword".to_owned()),
+		None => return Ok(()),
+	};
+	match keyword.as_str() {
+		"format" => {
+			*format = line.parse()?;
+			if *format > FORMAT_VERSION {
+				return Err(format!("format {} is newer than this engine supports", format));
+			}
+		},
+		"version" => doc.version = line.parse()?,
+		"node" => doc.nodes.push(NodeDesc {
+			name: line.string()?,
+			parent: None,
+			transform: Transform::identity(),
+			visible: true,
+			sprite: None,
+			bounds: None,
+			components: vec![],
+		}),
+		_ => {
+			let node = doc.nodes.last_mut().ok_or_else(|| format!("{} outside of a node", keyword))?;
+			read_node_statement(node, *format, &keyword, line)?;
+		},
+	}
+	Ok(())
+}
+
+fn read_node_statement(node: &mut NodeDesc, format: u32, keyword: &str, line: &mut Line) -> Result<(), String> {
+	match keyword {
+		"parent" => node.parent = Some(line.parse()?),
+		"transform" => {
+			let translation = line.vec2()?;
+			let rotation = line.parse()?;
+			node.transform = Transform::new(translation, rotation, line.vec2()?);
+		},
+		"hidden" => node.visible = false,
+		"sprite" => {
+			let pos = line.vec2()?;
+			// format 2 sprites had neither rotation nor scale
+			let (rotation, scale) =
+				if format >= 3 { (line.parse()?, line.vec2()?) } else { (0.0, Vector2::new(1.0, 1.0)) };
+			let mut sprite = SpriteDesc { rotation, scale, ..SpriteDesc::new(pos, line.parse()?) };
+			sprite.transparent = match line.word()?.as_str() {
+				"opaque" => false,
+				"transparent" => true,
+				blend => return Err(format!("unknown blending {}", blend)),
+			};
+			node.sprite = Some(sprite);
+		},
+		"tint" => {
+			let sprite = node.sprite.as_mut().ok_or("tint without a sprite")?;
+			sprite.tint = [line.parse()?, line.parse()?, line.parse()?, line.parse()?];
+		},
+		"texture" | "normal_map" => {
+			let sprite = node.sprite.as_mut().ok_or_else(|| format!("{} without a sprite", keyword))?;
+			let path = line.string()?;
+			let pos = (line.parse()?, line.parse()?);
+			let texture = Some(TextureRef { path, pos, size: (line.parse()?, line.parse()?) });
+			if keyword == "texture" {
+				sprite.texture = texture;
+			} else {
+				sprite.normal_map = texture;
+			}
+		},
+		"bounds" => node.bounds = Some(line.vec2()?),
+		"component" => node.components.push(Component::new(line.string()?)),
+		"prop" => {
+			let component = node.components.last_mut().ok_or("prop outside of a component")?;
+			let name = line.string()?;
+			let value = match line.word()?.as_str() {
+				"bool" => Value::Bool(line.parse()?),
+				"int" => Value::Int(line.parse()?),
+				"float" => Value::Float(line.parse()?),
+				"vec2" => Value::Vec2(line.vec2()?),
+				"str" => Value::Str(line.string()?),
+				"asset" => Value::Asset(line.string()?),
+				kind => return Err(format!("unknown value type {}", kind)),
+			};
+			component.props.push((name, value));
+		},
+		_ => return Err(format!("unknown keyword {}", keyword)),
+	}
+	Ok(())
+}
+
+fn quote(s: &str) -> String {
+	let mut out = String::with_capacity(s.len() + 2);
+	out.push('"');
+	for ch in s.chars() {
+		match ch {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			'\t' => out.push_str("\\t"),
+			ch => out.push(ch),
+		}
+	}
+	out.push('"');
+	out
+}
+
+enum Token {
+	Word(String),
+	Str(String),
+}
+
+struct Line {
+	tokens: std::vec::IntoIter<Token>,
+}
+impl Line {
+	fn new(line: &str) -> Result<Self, String> {
+		let mut tokens = vec![];
+		let mut chars = line.chars().peekable();
+		while let Some(&ch) = chars.peek() {
+			if ch.is_whitespace() {
+				chars.next();
+			} else if ch == '#' {
+				break;
+			} else if ch == '"' {
+				chars.next();
+				let mut s = String::new();
+				loop {
+					match chars.next().ok_or("unterminated string")? {
+						'"' => break,
+						'\\' => s.push(match chars.next().ok_or("unterminated string")? {
+							'n' => '\n',
+							't' => '\t',
+							ch => ch,
+						}),
+						ch => s.push(ch),
+					}
+				}
+				tokens.push(Token::Str(s));
+			} else {
+				let mut s = String::new();
+				while let Some(&ch) = chars.peek() {
+					if ch.is_whitespace() {
+						break;
+					}
+					s.push(ch);
+					chars.next();
+				}
+				tokens.push(Token::Word(s));
+			}
+		}
+		Ok(Self { tokens: tokens.into_iter() })
+	}
+
+	fn next(&mut self) -> Option<Token> {
+		self.tokens.next()
+	}
+
+	fn word(&mut self) -> Result<String, String> {
+		match self.next() {
+			Some(Token::Word(word)) => Ok(word),
+			_ => Err("expected a word".to_owned()),
+		}
+	}
+
+	fn string(&mut self) -> Result<String, String> {
+		match self.next() {
+			Some(Token::Str(s)) => Ok(s),
+			_ => Err("expected a quoted string".to_owned()),
+		}
+	}
+
+	fn parse<T: FromStr>(&mut self) -> Result<T, String> {
+		let word = self.word()?;
+		word.parse().map_err(|_| format!("invalid value {}", word))
+	}
+
+	fn vec2(&mut self) -> Result<Vector2<f32>, String> {
+		Ok(Vector2::new(self.parse()?, self.parse()?))
+	}
+
+	fn end(&mut self) -> Result<(), String> {
+		match self.next() {
+			Some(_) => Err("unexpected trailing values".to_owned()),
+			None => Ok(()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reads_format_2() {
+		let doc = read("format 2\nversion 1\nnode \"player\"\n\tsprite 4 8 2 transparent\n\ttint 1 1 1 0.5\n").unwrap();
+		let tint = [1.0, 1.0, 1.0, 0.5];
+		let sprite = SpriteDesc { transparent: true, tint, ..SpriteDesc::new(Vector2::new(4.0, 8.0), 2) };
+		assert_eq!(doc.nodes[0].sprite, Some(sprite));
+		assert!(read("format 2\nnode \"player\"\n\tsprite 4 8 0 1 1 2 opaque\n").is_err());
+	}
+
+	#[test]
+	fn texture_needs_sprite() {
+		assert!(read("node \"player\"\n\ttexture \"player.png\" 0 0 16 16\n").is_err());
+	}
+}
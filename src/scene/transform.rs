@@ -0,0 +1,39 @@
+use nalgebra::{Isometry2, Matrix3, Vector2};
+
+/// Placement of a node relative to its parent. Scale is applied before rotation and translation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+	pub isometry: Isometry2<f32>,
+	pub scale: Vector2<f32>,
+}
+impl Transform {
+	pub fn new(translation: Vector2<f32>, rotation: f32, scale: Vector2<f32>) -> Self {
+		Self { isometry: Isometry2::new(translation, rotation), scale }
+	}
+
+	pub fn identity() -> Self {
+		Self { isometry: Isometry2::identity(), scale: Vector2::new(1.0, 1.0) }
+	}
+
+	pub fn translation(&self) -> Vector2<f32> {
+		self.isometry.translation.vector
+	}
+
+	pub fn rotation(&self) -> f32 {
+		self.isometry.rotation.angle()
+	}
+
+	pub fn to_matrix(self) -> Matrix3<f32> {
+		self.isometry.to_homogeneous() * Matrix3::new_nonuniform_scaling(&self.scale)
+	}
+}
+impl Default for Transform {
+	fn default() -> Self {
+		Self::identity()
+	}
+}
+impl From<Isometry2<f32>> for Transform {
+	fn from(isometry: Isometry2<f32>) -> Self {
+		Self { isometry, scale: Vector2::new(1.0, 1.0) }
+	}
+}
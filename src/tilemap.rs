@@ -0,0 +1,127 @@
+pub mod layer;
+pub mod object;
+pub mod tiled;
+pub mod tileset;
+
+use crate::scene::component::Value;
+use layer::{Tile, TileLayer};
+use nalgebra::Vector2;
+use object::ObjectLayer;
+use std::{
+	sync::atomic::{AtomicU64, Ordering},
+	time::Duration,
+};
+use tileset::Tileset;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+pub enum Layer {
+	Tiles(TileLayer),
+	Objects(ObjectLayer),
+}
+impl Layer {
+	pub fn name(&self) -> &str {
+		match self {
+			Self::Tiles(layer) => &layer.name,
+			Self::Objects(layer) => &layer.name,
+		}
+	}
+}
+
+/// A grid of tiles in layers, plus free-standing objects. Positions are in world pixels, with cell `(0, 0)` at the
+/// origin.
+pub struct Tilemap {
+	// tells the renderer's cached chunks apart from other maps'
+	id: u64,
+	/// Size of a grid cell in pixels. Tiles from tilesets with bigger tiles stick out above and to the right.
+	pub tile_size: Vector2<u32>,
+	/// Sorted by `first_gid`.
+	pub tilesets: Vec<Tileset>,
+	/// Bottom to top.
+	pub layers: Vec<Layer>,
+	pub properties: Vec<(String, Value)>,
+	time: f32,
+	anim_version: u64,
+}
+impl Tilemap {
+	pub fn new(tile_size: Vector2<u32>) -> Self {
+		Self {
+			id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+			tile_size,
+			tilesets: vec![],
+			layers: vec![],
+			properties: vec![],
+			time: 0.0,
+			anim_version: 0,
+		}
+	}
+
+	pub fn id(&self) -> u64 {
+		self.id
+	}
+
+	/// Adds a tileset after the existing ones, numbering its tiles from the next free gid, which is returned.
+	pub fn add_tileset(&mut self, mut tileset: Tileset) -> u32 {
+		tileset.first_gid = self.tilesets.last().map_or(1, |last| last.first_gid + last.tile_count);
+		let first_gid = tileset.first_gid;
+		self.tilesets.push(tileset);
+		first_gid
+	}
+
+	/// The tileset `tile` comes from, along with its index.
+	pub fn tileset_for(&self, tile: Tile) -> Option<(usize, &Tileset)> {
+		if tile.is_empty() {
+			return None;
+		}
+		let idx = self.tilesets.iter().rposition(|tileset| tileset.first_gid <= tile.gid())?;
+		Some((idx, &self.tilesets[idx])).filter(|(_, tileset)| tileset.contains(tile.gid()))
+	}
+
+	pub fn layer(&self, name: &str) -> Option<&Layer> {
+		self.layers.iter().find(|layer| layer.name() == name)
+	}
+
+	pub fn tile_layer(&self, name: &str) -> Option<&TileLayer> {
+		self.layers.iter().find_map(|layer| match layer {
+			Layer::Tiles(layer) if layer.name == name => Some(layer),
+			_ => None,
+		})
+	}
+
+	pub fn tile_layer_mut(&mut self, name: &str) -> Option<&mut TileLayer> {
+		self.layers.iter_mut().find_map(|layer| match layer {
+			Layer::Tiles(layer) if layer.name == name => Some(layer),
+			_ => None,
+		})
+	}
+
+	pub fn object_layer(&self, name: &str) -> Option<&ObjectLayer> {
+		self.layers.iter().find_map(|layer| match layer {
+			Layer::Objects(layer) if layer.name == name => Some(layer),
+			_ => None,
+		})
+	}
+
+	/// The cell containing a world position, ignoring layer offsets.
+	pub fn cell_at(&self, pos: Vector2<f32>) -> (i32, i32) {
+		((pos.x / self.tile_size.x as f32).floor() as i32, (pos.y / self.tile_size.y as f32).floor() as i32)
+	}
+
+	/// Advances tile animations.
+	pub fn update(&mut self, dt: Duration) {
+		self.time += dt.as_secs_f32();
+		let time = self.time;
+		let mut changed = false;
+		for tileset in &mut self.tilesets {
+			changed |= tileset.animate(time);
+		}
+		if changed {
+			self.anim_version += 1;
+		}
+	}
+
+	/// Changes whenever an animated tile shows a different frame, so chunks holding animated tiles can be rebuilt.
+	pub fn anim_version(&self) -> u64 {
+		self.anim_version
+	}
+}
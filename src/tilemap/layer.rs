@@ -0,0 +1,262 @@
+use crate::scene::component::Value;
+use nalgebra::Vector2;
+use std::{
+	collections::HashMap,
+	sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Width and height of a chunk in cells.
+pub const CHUNK_SIZE: i32 = 16;
+
+// chunk versions are unique across every layer, so a cache keyed loosely can't mistake one chunk for another
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(1);
+
+/// A global tile id plus flip flags, laid out like in Tiled files. Gid 0 is an empty cell.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Tile(pub u32);
+impl Tile {
+	pub const EMPTY: Self = Self(0);
+	const FLAGS: u32 = Self::FLIP_H | Self::FLIP_V | Self::FLIP_D | Self::ROTATE_HEX;
+	/// Swaps x and y, applied before the other flips. Together they give all 8 rotations and mirrors.
+	pub const FLIP_D: u32 = 0x2000_0000;
+	pub const FLIP_H: u32 = 0x8000_0000;
+	pub const FLIP_V: u32 = 0x4000_0000;
+	// hexagonal maps only, but it must still be masked out of the gid
+	const ROTATE_HEX: u32 = 0x1000_0000;
+
+	pub fn new(gid: u32) -> Self {
+		Self(gid & !Self::FLAGS)
+	}
+
+	pub fn flipped(self, flags: u32) -> Self {
+		Self(self.0 ^ (flags & Self::FLAGS))
+	}
+
+	pub fn gid(self) -> u32 {
+		self.0 & !Self::FLAGS
+	}
+
+	pub fn is_empty(self) -> bool {
+		self.gid() == 0
+	}
+
+	pub fn flip_h(self) -> bool {
+		self.0 & Self::FLIP_H != 0
+	}
+
+	pub fn flip_v(self) -> bool {
+		self.0 & Self::FLIP_V != 0
+	}
+
+	pub fn flip_d(self) -> bool {
+		self.0 & Self::FLIP_D != 0
+	}
+}
+
+/// `CHUNK_SIZE` squared cells, row by row.
+pub struct Chunk {
+	tiles: Box<[Tile]>,
+	count: u32,
+	version: u64,
+}
+impl Chunk {
+	fn new() -> Self {
+		let tiles = vec![Tile::EMPTY; (CHUNK_SIZE * CHUNK_SIZE) as usize].into_boxed_slice();
+		Self { tiles, count: 0, version: NEXT_VERSION.fetch_add(1, Ordering::Relaxed) }
+	}
+
+	pub fn tiles(&self) -> &[Tile] {
+		&self.tiles
+	}
+
+	/// Takes cell coordinates within the chunk.
+	pub fn get(&self, x: i32, y: i32) -> Tile {
+		self.tiles[(y * CHUNK_SIZE + x) as usize]
+	}
+
+	/// Number of non-empty cells.
+	pub fn count(&self) -> u32 {
+		self.count
+	}
+
+	/// Changes every time a tile in the chunk does.
+	pub fn version(&self) -> u64 {
+		self.version
+	}
+}
+
+/// An unbounded grid of tiles, stored in chunks so empty areas cost nothing and edits only touch one chunk.
+pub struct TileLayer {
+	pub name: String,
+	pub visible: bool,
+	/// Pixels, added to every tile's position.
+	pub offset: Vector2<f32>,
+	/// Draw order, shared with `Sprite::layer`.
+	pub layer: u16,
+	/// Multiplies the tile colors. Layers that aren't fully opaque are blended after opaque sprites.
+	pub tint: [f32; 4],
+	pub properties: Vec<(String, Value)>,
+	chunks: HashMap<(i32, i32), Chunk>,
+}
+impl TileLayer {
+	pub fn new(name: impl Into<String>, layer: u16) -> Self {
+		Self {
+			name: name.into(),
+			visible: true,
+			offset: Vector2::zeros(),
+			layer,
+			tint: [1.0; 4],
+			properties: vec![],
+			chunks: HashMap::new(),
+		}
+	}
+
+	pub fn get(&self, x: i32, y: i32) -> Tile {
+		let ((cx, cy), (lx, ly)) = split(x, y);
+		self.chunks.get(&(cx, cy)).map_or(Tile::EMPTY, |chunk| chunk.get(lx, ly))
+	}
+
+	/// Returns the previous tile. Chunks are freed when their last tile is cleared.
+	pub fn set(&mut self, x: i32, y: i32, tile: Tile) -> Tile {
+		let (key, (lx, ly)) = split(x, y);
+		if tile.is_empty() && !self.chunks.contains_key(&key) {
+			return Tile::EMPTY;
+		}
+
+		let chunk = self.chunks.entry(key).or_insert_with(Chunk::new);
+		let cell = &mut chunk.tiles[(ly * CHUNK_SIZE + lx) as usize];
+		let old = *cell;
+		if old == tile {
+			return old;
+		}
+		*cell = tile;
+		chunk.count = chunk.count + !tile.is_empty() as u32 - !old.is_empty() as u32;
+		chunk.version = NEXT_VERSION.fetch_add(1, Ordering::Relaxed);
+		if chunk.count == 0 {
+			self.chunks.remove(&key);
+		}
+		old
+	}
+
+	/// Sets every cell in `w` by `h` cells starting at `(x, y)`.
+	pub fn fill(&mut self, x: i32, y: i32, w: i32, h: i32, tile: Tile) {
+		for y in y..y + h {
+			for x in x..x + w {
+				self.set(x, y, tile);
+			}
+		}
+	}
+
+	pub fn clear(&mut self) {
+		self.chunks.clear();
+	}
+
+	pub fn chunk(&self, cx: i32, cy: i32) -> Option<&Chunk> {
+		self.chunks.get(&(cx, cy))
+	}
+
+	/// Chunks with at least one tile, keyed by chunk coordinates, in no particular order.
+	pub fn chunks(&self) -> impl Iterator<Item = ((i32, i32), &Chunk)> {
+		self.chunks.iter().map(|(&key, chunk)| (key, chunk))
+	}
+
+	/// The smallest range of cells containing every tile, as min and max (exclusive) corners.
+	pub fn bounds(&self) -> Option<((i32, i32), (i32, i32))> {
+		let mut bounds: Option<((i32, i32), (i32, i32))> = None;
+		for (&(cx, cy), chunk) in &self.chunks {
+			for (i, tile) in chunk.tiles.iter().enumerate() {
+				if tile.is_empty() {
+					continue;
+				}
+				let x = cx * CHUNK_SIZE + i as i32 % CHUNK_SIZE;
+				let y = cy * CHUNK_SIZE + i as i32 / CHUNK_SIZE;
+				bounds = Some(match bounds {
+					Some(((x0, y0), (x1, y1))) => ((x0.min(x), y0.min(y)), (x1.max(x + 1), y1.max(y + 1))),
+					None => ((x, y), (x + 1, y + 1)),
+				});
+			}
+		}
+		bounds
+	}
+
+	pub fn property(&self, name: &str) -> Option<&Value> {
+		self.properties.iter().find(|(prop, _)| prop == name).map(|(_, value)| value)
+	}
+}
+
+// chunk coordinates and coordinates within the chunk
+fn split(x: i32, y: i32) -> ((i32, i32), (i32, i32)) {
+	((x.div_euclid(CHUNK_SIZE), y.div_euclid(CHUNK_SIZE)), (x.rem_euclid(CHUNK_SIZE), y.rem_euclid(CHUNK_SIZE)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn chunk_count(layer: &TileLayer) -> usize {
+		layer.chunks().count()
+	}
+
+	#[test]
+	fn split_negative() {
+		assert_eq!(split(0, 15), ((0, 0), (0, 15)));
+		assert_eq!(split(16, 17), ((1, 1), (0, 1)));
+		assert_eq!(split(-1, -1), ((-1, -1), (15, 15)));
+		assert_eq!(split(-16, -17), ((-1, -2), (0, 15)));
+	}
+
+	#[test]
+	fn set_counts_and_versions() {
+		let mut layer = TileLayer::new("ground", 0);
+		assert_eq!(layer.set(-1, 2, Tile::new(5)), Tile::EMPTY);
+		let chunk = layer.chunk(-1, 0).unwrap();
+		let version = chunk.version();
+		assert_eq!((chunk.count(), chunk.get(15, 2)), (1, Tile::new(5)));
+		assert_eq!(layer.get(-1, 2), Tile::new(5));
+
+		// setting the same tile again changes nothing
+		assert_eq!(layer.set(-1, 2, Tile::new(5)), Tile::new(5));
+		assert_eq!(layer.chunk(-1, 0).unwrap().version(), version);
+
+		// replacing a tile keeps the count but bumps the version
+		assert_eq!(layer.set(-1, 2, Tile::new(6)), Tile::new(5));
+		let chunk = layer.chunk(-1, 0).unwrap();
+		assert_eq!(chunk.count(), 1);
+		assert!(chunk.version() > version);
+
+		layer.set(-2, 2, Tile::new(7));
+		assert_eq!(layer.chunk(-1, 0).unwrap().count(), 2);
+	}
+
+	#[test]
+	fn empty_chunks_freed() {
+		let mut layer = TileLayer::new("ground", 0);
+		// clearing a cell in a chunk that doesn't exist doesn't create it
+		assert_eq!(layer.set(3, 3, Tile::EMPTY), Tile::EMPTY);
+		assert_eq!(chunk_count(&layer), 0);
+
+		layer.set(3, 3, Tile::new(1));
+		layer.set(4, 3, Tile::new(1));
+		layer.set(3, 3, Tile::EMPTY);
+		assert_eq!(layer.chunk(0, 0).unwrap().count(), 1);
+		assert_eq!(layer.set(4, 3, Tile::EMPTY), Tile::new(1));
+		assert!(layer.chunk(0, 0).is_none());
+		assert_eq!(layer.bounds(), None);
+	}
+
+	#[test]
+	fn fill_and_bounds() {
+		let mut layer = TileLayer::new("ground", 0);
+		// straddles the four chunks around the origin
+		layer.fill(-2, -3, 4, 5, Tile::new(1).flipped(Tile::FLIP_H));
+		assert_eq!(chunk_count(&layer), 4);
+		assert_eq!(layer.chunks().map(|(_, chunk)| chunk.count()).sum::<u32>(), 20);
+		assert_eq!(layer.bounds(), Some(((-2, -3), (2, 2))));
+		assert!(layer.get(1, 1).flip_h());
+		assert!(layer.get(2, 1).is_empty());
+
+		layer.fill(-2, -3, 4, 3, Tile::EMPTY);
+		assert_eq!(chunk_count(&layer), 2);
+		assert_eq!(layer.bounds(), Some(((-2, 0), (2, 2))));
+	}
+}
@@ -0,0 +1,78 @@
+use crate::{scene::component::Value, tilemap::layer::Tile};
+use nalgebra::Vector2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+	Rect,
+	Ellipse,
+	Point,
+	/// Closed, with points relative to the object's position.
+	Polygon(Vec<Vector2<f32>>),
+	/// Open, with points relative to the object's position.
+	Polyline(Vec<Vector2<f32>>),
+	/// A tile drawn as a free-standing object. Its position is the bottom-left corner.
+	Tile(Tile),
+}
+
+/// Something placed by hand in an object layer, like a spawn point, trigger area or collision shape.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapObject {
+	/// Unique within the map. 0 for objects not made in an editor.
+	pub id: u32,
+	pub name: String,
+	/// User-defined type, like `enemy` or `door`.
+	pub kind: String,
+	/// Pixels, the top-left corner unless the shape says otherwise.
+	pub pos: Vector2<f32>,
+	pub size: Vector2<f32>,
+	/// Radians, clockwise around `pos`.
+	pub rotation: f32,
+	pub visible: bool,
+	pub shape: Shape,
+	pub properties: Vec<(String, Value)>,
+}
+impl MapObject {
+	pub fn new(name: impl Into<String>, kind: impl Into<String>, pos: Vector2<f32>, shape: Shape) -> Self {
+		Self {
+			id: 0,
+			name: name.into(),
+			kind: kind.into(),
+			pos,
+			size: Vector2::zeros(),
+			rotation: 0.0,
+			visible: true,
+			shape,
+			properties: vec![],
+		}
+	}
+
+	pub fn property(&self, name: &str) -> Option<&Value> {
+		self.properties.iter().find(|(prop, _)| prop == name).map(|(_, value)| value)
+	}
+}
+
+pub struct ObjectLayer {
+	pub name: String,
+	pub visible: bool,
+	/// Pixels, not yet added to the objects' positions.
+	pub offset: Vector2<f32>,
+	pub objects: Vec<MapObject>,
+	pub properties: Vec<(String, Value)>,
+}
+impl ObjectLayer {
+	pub fn new(name: impl Into<String>) -> Self {
+		Self { name: name.into(), visible: true, offset: Vector2::zeros(), objects: vec![], properties: vec![] }
+	}
+
+	pub fn find(&self, name: &str) -> Option<&MapObject> {
+		self.objects.iter().find(|object| object.name == name)
+	}
+
+	pub fn of_kind<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a MapObject> {
+		self.objects.iter().filter(move |object| object.kind == kind)
+	}
+
+	pub fn property(&self, name: &str) -> Option<&Value> {
+		self.properties.iter().find(|(prop, _)| prop == name).map(|(_, value)| value)
+	}
+}
@@ -0,0 +1,746 @@
+//! Import of maps made with the Tiled editor, in both its JSON (`.tmj`, `.json`) and XML (`.tmx`) formats, along with
+//! external tilesets (`.tsj`, `.tsx`). Only orthogonal maps with uncompressed layer data are supported. Groups are
+//! flattened into their layers, and image layers are skipped.
+
+use crate::{
+	fs::read_all_u8,
+	gfx::{texture::Subtex, Gfx},
+	json::Json,
+	scene::component::Value,
+	tilemap::{
+		layer::{Tile, TileLayer},
+		object::{MapObject, ObjectLayer, Shape},
+		tileset::{TileFrame, Tileset},
+		Layer, Tilemap,
+	},
+	xml::Element,
+};
+use log::info;
+use nalgebra::Vector2;
+use std::{
+	collections::HashMap,
+	io,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+impl Tilemap {
+	/// Loads a map along with its external tilesets and tileset images, picking the format from the extension.
+	pub async fn load<P: AsRef<Path>>(gfx: &Arc<Gfx>, path: P) -> io::Result<Self> {
+		let path = path.as_ref();
+		let src = read_string(path).await?;
+		let mut map = match extension(path) {
+			"tmx" => parse_tmx(&src)?,
+			_ => parse_tmj(&src)?,
+		};
+
+		for tileset in &mut map.tilesets {
+			let source = match tileset.source.take() {
+				Some(source) => source,
+				None => continue,
+			};
+			let src = read_string(&path.with_file_name(&source)).await?;
+			let mut external = match extension(Path::new(&source)) {
+				"tsx" => parse_tsx(&src, tileset.first_gid)?,
+				_ => parse_tsj(&src, tileset.first_gid)?,
+			};
+			// images are relative to the tileset file, but the map's directory is where they're loaded from
+			external.image = external.image.map(|image| path_str(&Path::new(&source).with_file_name(image)));
+			*tileset = external;
+		}
+
+		// tilesets commonly share one image
+		let mut textures: HashMap<PathBuf, Subtex> = HashMap::new();
+		for tileset in &mut map.tilesets {
+			let image_path = match &tileset.image {
+				Some(image) if tileset.texture.is_none() => path.with_file_name(image),
+				_ => continue,
+			};
+			if !textures.contains_key(&image_path) {
+				textures.insert(image_path.clone(), Subtex::load(gfx, &image_path).await?);
+			}
+			tileset.texture = Some(textures[&image_path].clone());
+		}
+		info!(target: "assets", "loaded tilemap {}", path.display());
+		Ok(map)
+	}
+}
+
+/// Parses a map in Tiled's JSON format. External tilesets are left for the caller, with `source` set.
+pub fn parse_tmj(src: &str) -> io::Result<Tilemap> {
+	let json = Json::parse(src)?;
+	let orientation = json.get("orientation").and_then(Json::as_str).unwrap_or("orthogonal");
+	if orientation != "orthogonal" {
+		return Err(invalid_data(&format!("{} maps aren't supported", orientation)));
+	}
+	let mut map = Tilemap::new(Vector2::new(json_u32(&json, "tilewidth")?, json_u32(&json, "tileheight")?));
+	map.properties = json_properties(&json);
+
+	for tileset in json.get("tilesets").and_then(Json::as_array).unwrap_or_default() {
+		let first_gid = json_u32(tileset, "firstgid")?;
+		let tileset = match tileset.get("source").and_then(Json::as_str) {
+			Some(source) => external(first_gid, source),
+			None => tsj_tileset(tileset, first_gid)?,
+		};
+		map.tilesets.push(tileset);
+	}
+	map.tilesets.sort_by_key(|tileset| tileset.first_gid);
+
+	let layers = json.get("layers").and_then(Json::as_array).unwrap_or_default();
+	json_layers(&mut map, layers, &Group::root())?;
+	Ok(map)
+}
+
+/// Parses an external tileset in Tiled's JSON format.
+pub fn parse_tsj(src: &str, first_gid: u32) -> io::Result<Tileset> {
+	tsj_tileset(&Json::parse(src)?, first_gid)
+}
+
+/// Parses a map in Tiled's XML format. External tilesets are left for the caller, with `source` set.
+pub fn parse_tmx(src: &str) -> io::Result<Tilemap> {
+	let root = Element::parse(src)?;
+	if root.name != "map" {
+		return Err(invalid_data("expected a `map` element"));
+	}
+	let orientation = root.attr("orientation").unwrap_or("orthogonal");
+	if orientation != "orthogonal" {
+		return Err(invalid_data(&format!("{} maps aren't supported", orientation)));
+	}
+	let mut map = Tilemap::new(Vector2::new(xml_u32(&root, "tilewidth")?, xml_u32(&root, "tileheight")?));
+	map.properties = xml_properties(&root);
+
+	for tileset in root.children_named("tileset") {
+		let first_gid = xml_u32(tileset, "firstgid")?;
+		let tileset = match tileset.attr("source") {
+			Some(source) => external(first_gid, source),
+			None => tsx_tileset(tileset, first_gid)?,
+		};
+		map.tilesets.push(tileset);
+	}
+	map.tilesets.sort_by_key(|tileset| tileset.first_gid);
+
+	xml_layers(&mut map, &root, &Group::root())?;
+	Ok(map)
+}
+
+/// Parses an external tileset in Tiled's XML format.
+pub fn parse_tsx(src: &str, first_gid: u32) -> io::Result<Tileset> {
+	let root = Element::parse(src)?;
+	if root.name != "tileset" {
+		return Err(invalid_data("expected a `tileset` element"));
+	}
+	tsx_tileset(&root, first_gid)
+}
+
+// what enclosing groups add to a layer
+struct Group {
+	offset: Vector2<f32>,
+	tint: [f32; 4],
+	visible: bool,
+}
+impl Group {
+	fn root() -> Self {
+		Self { offset: Vector2::zeros(), tint: [1.0; 4], visible: true }
+	}
+
+	fn child(&self, offset: Vector2<f32>, tint: [f32; 4], visible: bool) -> Self {
+		let mut child_tint = self.tint;
+		for (channel, tint) in child_tint.iter_mut().zip(&tint) {
+			*channel *= tint;
+		}
+		Self { offset: self.offset + offset, tint: child_tint, visible: self.visible && visible }
+	}
+}
+
+fn json_layers(map: &mut Tilemap, layers: &[Json], group: &Group) -> io::Result<()> {
+	for json in layers {
+		let name = json.get("name").and_then(Json::as_str).unwrap_or_default();
+		let offset = Vector2::new(json_f32(json, "offsetx"), json_f32(json, "offsety"));
+		let mut tint = json.get("tintcolor").and_then(Json::as_str).map_or(Ok([1.0; 4]), parse_color)?;
+		tint[3] *= json.get("opacity").and_then(Json::as_f64).unwrap_or(1.0) as f32;
+		let visible = json.get("visible").and_then(Json::as_bool).unwrap_or(true);
+		let group = group.child(offset, tint, visible);
+
+		match json.get("type").and_then(Json::as_str).unwrap_or_default() {
+			"tilelayer" => {
+				let mut layer = new_tile_layer(map, name, &group);
+				layer.properties = json_properties(json);
+				let encoding = json.get("encoding").and_then(Json::as_str);
+				let compression = json.get("compression").and_then(Json::as_str);
+				if let Some(chunks) = json.get("chunks").and_then(Json::as_array) {
+					for chunk in chunks {
+						let (x, y) = (json_i32(chunk, "x")?, json_i32(chunk, "y")?);
+						let width = json_u32(chunk, "width")?;
+						let gids = json_data(chunk.get("data"), encoding, compression)?;
+						set_tiles(&mut layer, x, y, width, &gids)?;
+					}
+				} else {
+					let width = json_u32(json, "width")?;
+					let gids = json_data(json.get("data"), encoding, compression)?;
+					set_tiles(&mut layer, 0, 0, width, &gids)?;
+				}
+				map.layers.push(Layer::Tiles(layer));
+			},
+			"objectgroup" => {
+				let mut layer = new_object_layer(name, &group);
+				layer.properties = json_properties(json);
+				for object in json.get("objects").and_then(Json::as_array).unwrap_or_default() {
+					layer.objects.push(json_object(object)?);
+				}
+				map.layers.push(Layer::Objects(layer));
+			},
+			"group" => json_layers(map, json.get("layers").and_then(Json::as_array).unwrap_or_default(), &group)?,
+			_ => (),
+		}
+	}
+	Ok(())
+}
+
+fn xml_layers(map: &mut Tilemap, parent: &Element, group: &Group) -> io::Result<()> {
+	for element in &parent.children {
+		let name = element.attr("name").unwrap_or_default();
+		let offset = Vector2::new(xml_f32(element, "offsetx")?, xml_f32(element, "offsety")?);
+		let mut tint = element.attr("tintcolor").map_or(Ok([1.0; 4]), parse_color)?;
+		tint[3] *= xml_parse(element, "opacity")?.unwrap_or(1.0);
+		let visible = element.attr("visible") != Some("0");
+		let group = group.child(offset, tint, visible);
+
+		match element.name.as_str() {
+			"layer" => {
+				let mut layer = new_tile_layer(map, name, &group);
+				layer.properties = xml_properties(element);
+				let data = element.child("data").ok_or_else(|| invalid_data("layer without `data`"))?;
+				let chunks: Vec<_> = data.children_named("chunk").collect();
+				if chunks.is_empty() {
+					let width = xml_u32(element, "width")?;
+					set_tiles(&mut layer, 0, 0, width, &xml_data(data, data)?)?;
+				}
+				for chunk in chunks {
+					let (x, y) = (xml_parse(chunk, "x")?.unwrap_or(0), xml_parse(chunk, "y")?.unwrap_or(0));
+					set_tiles(&mut layer, x, y, xml_u32(chunk, "width")?, &xml_data(data, chunk)?)?;
+				}
+				map.layers.push(Layer::Tiles(layer));
+			},
+			"objectgroup" => {
+				let mut layer = new_object_layer(name, &group);
+				layer.properties = xml_properties(element);
+				for object in element.children_named("object") {
+					layer.objects.push(xml_object(object)?);
+				}
+				map.layers.push(Layer::Objects(layer));
+			},
+			"group" => xml_layers(map, element, &group)?,
+			_ => (),
+		}
+	}
+	Ok(())
+}
+
+fn new_tile_layer(map: &Tilemap, name: &str, group: &Group) -> TileLayer {
+	// each layer draws over the ones before it, whatever their kind
+	let mut layer = TileLayer::new(name, map.layers.len() as u16);
+	layer.offset = group.offset;
+	layer.tint = group.tint;
+	layer.visible = group.visible;
+	layer
+}
+
+fn new_object_layer(name: &str, group: &Group) -> ObjectLayer {
+	let mut layer = ObjectLayer::new(name);
+	layer.offset = group.offset;
+	layer.visible = group.visible;
+	layer
+}
+
+// gids are row by row, `width` cells wide, starting at cell `(x, y)`
+fn set_tiles(layer: &mut TileLayer, x: i32, y: i32, width: u32, gids: &[u32]) -> io::Result<()> {
+	if width == 0 || gids.len() % width as usize != 0 {
+		return Err(invalid_data("layer data doesn't match its width"));
+	}
+	for (i, &gid) in gids.iter().enumerate() {
+		let (dx, dy) = ((i % width as usize) as i32, (i / width as usize) as i32);
+		layer.set(x + dx, y + dy, Tile(gid));
+	}
+	Ok(())
+}
+
+fn external(first_gid: u32, source: &str) -> Tileset {
+	let mut tileset = Tileset::empty("", first_gid, Vector2::zeros(), 0, 0);
+	tileset.source = Some(source.to_owned());
+	tileset
+}
+
+fn tsj_tileset(json: &Json, first_gid: u32) -> io::Result<Tileset> {
+	let name = json.get("name").and_then(Json::as_str).unwrap_or_default();
+	let tile_size = Vector2::new(json_u32(json, "tilewidth")?, json_u32(json, "tileheight")?);
+	let mut tileset =
+		Tileset::empty(name, first_gid, tile_size, json_u32(json, "tilecount")?, json_u32(json, "columns")?);
+	tileset.margin = json.get("margin").and_then(Json::as_u32).unwrap_or(0);
+	tileset.spacing = json.get("spacing").and_then(Json::as_u32).unwrap_or(0);
+	tileset.image = json.get("image").and_then(Json::as_str).map(str::to_owned);
+
+	for tile in json.get("tiles").and_then(Json::as_array).unwrap_or_default() {
+		let id = json_u32(tile, "id")?;
+		let props = json_properties(tile);
+		if !props.is_empty() {
+			tileset.tile_properties.insert(id, props);
+		}
+		if let Some(frames) = tile.get("animation").and_then(Json::as_array) {
+			let frames = frames
+				.iter()
+				.map(|frame| {
+					Ok(TileFrame {
+						tile: json_u32(frame, "tileid")?,
+						duration: json_u32(frame, "duration")? as f32 / 1000.0,
+					})
+				})
+				.collect::<io::Result<_>>()?;
+			add_animation(&mut tileset, id, frames)?;
+		}
+	}
+	Ok(tileset)
+}
+
+fn tsx_tileset(element: &Element, first_gid: u32) -> io::Result<Tileset> {
+	let name = element.attr("name").unwrap_or_default();
+	let tile_size = Vector2::new(xml_u32(element, "tilewidth")?, xml_u32(element, "tileheight")?);
+	let count = xml_u32(element, "tilecount")?;
+	let mut tileset = Tileset::empty(name, first_gid, tile_size, count, xml_u32(element, "columns")?);
+	tileset.margin = xml_parse(element, "margin")?.unwrap_or(0);
+	tileset.spacing = xml_parse(element, "spacing")?.unwrap_or(0);
+	tileset.image = element.child("image").and_then(|image| image.attr("source")).map(str::to_owned);
+
+	for tile in element.children_named("tile") {
+		let id = xml_u32(tile, "id")?;
+		let props = xml_properties(tile);
+		if !props.is_empty() {
+			tileset.tile_properties.insert(id, props);
+		}
+		if let Some(animation) = tile.child("animation") {
+			let frames = animation
+				.children_named("frame")
+				.map(|frame| {
+					Ok(TileFrame {
+						tile: xml_u32(frame, "tileid")?,
+						duration: xml_u32(frame, "duration")? as f32 / 1000.0,
+					})
+				})
+				.collect::<io::Result<_>>()?;
+			add_animation(&mut tileset, id, frames)?;
+		}
+	}
+	Ok(tileset)
+}
+
+fn add_animation(tileset: &mut Tileset, id: u32, frames: Vec<TileFrame>) -> io::Result<()> {
+	if frames.iter().any(|frame| frame.tile >= tileset.tile_count) {
+		return Err(invalid_data(&format!("tileset `{}`: animation frame out of range", tileset.name)));
+	}
+	tileset.add_animation(id, frames);
+	Ok(())
+}
+
+fn json_object(json: &Json) -> io::Result<MapObject> {
+	let points = |key| {
+		json.get(key)
+			.and_then(Json::as_array)
+			.map(|points| points.iter().map(|point| Vector2::new(json_f32(point, "x"), json_f32(point, "y"))).collect())
+	};
+	let shape = if let Some(gid) = json.get("gid").and_then(Json::as_u32) {
+		Shape::Tile(Tile(gid))
+	} else if let Some(points) = points("polygon") {
+		Shape::Polygon(points)
+	} else if let Some(points) = points("polyline") {
+		Shape::Polyline(points)
+	} else if json.get("ellipse").and_then(Json::as_bool) == Some(true) {
+		Shape::Ellipse
+	} else if json.get("point").and_then(Json::as_bool) == Some(true) {
+		Shape::Point
+	} else {
+		Shape::Rect
+	};
+
+	let name = json.get("name").and_then(Json::as_str).unwrap_or_default();
+	// newer versions call the type a class
+	let kind = json.get("type").or_else(|| json.get("class")).and_then(Json::as_str).unwrap_or_default();
+	let pos = Vector2::new(json_f32(json, "x"), json_f32(json, "y"));
+	let mut object = MapObject::new(name, kind, pos, shape);
+	object.id = json.get("id").and_then(Json::as_u32).unwrap_or(0);
+	object.size = Vector2::new(json_f32(json, "width"), json_f32(json, "height"));
+	object.rotation = json_f32(json, "rotation").to_radians();
+	object.visible = json.get("visible").and_then(Json::as_bool).unwrap_or(true);
+	object.properties = json_properties(json);
+	Ok(object)
+}
+
+fn xml_object(element: &Element) -> io::Result<MapObject> {
+	let points = |name| -> io::Result<Option<Vec<Vector2<f32>>>> {
+		let points = match element.child(name).and_then(|child| child.attr("points")) {
+			Some(points) => points,
+			None => return Ok(None),
+		};
+		points
+			.split_whitespace()
+			.map(|point| {
+				let mut coords = point.split(',').map(str::parse);
+				match (coords.next(), coords.next(), coords.next()) {
+					(Some(Ok(x)), Some(Ok(y)), None) => Ok(Vector2::new(x, y)),
+					_ => Err(invalid_data(&format!("invalid point `{}`", point))),
+				}
+			})
+			.collect::<io::Result<_>>()
+			.map(Some)
+	};
+	let shape = if let Some(gid) = xml_parse(element, "gid")? {
+		Shape::Tile(Tile(gid))
+	} else if let Some(points) = points("polygon")? {
+		Shape::Polygon(points)
+	} else if let Some(points) = points("polyline")? {
+		Shape::Polyline(points)
+	} else if element.child("ellipse").is_some() {
+		Shape::Ellipse
+	} else if element.child("point").is_some() {
+		Shape::Point
+	} else {
+		Shape::Rect
+	};
+
+	let name = element.attr("name").unwrap_or_default();
+	let kind = element.attr("type").or_else(|| element.attr("class")).unwrap_or_default();
+	let pos = Vector2::new(xml_f32(element, "x")?, xml_f32(element, "y")?);
+	let mut object = MapObject::new(name, kind, pos, shape);
+	object.id = xml_parse(element, "id")?.unwrap_or(0);
+	object.size = Vector2::new(xml_f32(element, "width")?, xml_f32(element, "height")?);
+	object.rotation = xml_f32(element, "rotation")?.to_radians();
+	object.visible = element.attr("visible") != Some("0");
+	object.properties = xml_properties(element);
+	Ok(object)
+}
+
+fn json_data(data: Option<&Json>, encoding: Option<&str>, compression: Option<&str>) -> io::Result<Vec<u32>> {
+	match data {
+		Some(Json::Array(gids)) => {
+			gids.iter().map(|gid| gid.as_u32().ok_or_else(|| invalid_data("invalid tile gid"))).collect()
+		},
+		Some(Json::String(data)) => decode_data(data, encoding.unwrap_or("base64"), compression),
+		_ => Err(invalid_data("missing layer data")),
+	}
+}
+
+// `data` holds the encoding; `parent` is either it or one of its chunks, holding the tiles
+fn xml_data(data: &Element, parent: &Element) -> io::Result<Vec<u32>> {
+	match data.attr("encoding") {
+		Some(encoding) => decode_data(&parent.text, encoding, data.attr("compression")),
+		None => parent.children_named("tile").map(|tile| Ok(xml_parse(tile, "gid")?.unwrap_or(0))).collect(),
+	}
+}
+
+fn decode_data(data: &str, encoding: &str, compression: Option<&str>) -> io::Result<Vec<u32>> {
+	if let Some(compression) = compression.filter(|compression| !compression.is_empty()) {
+		return Err(invalid_data(&format!("{} compressed layer data isn't supported", compression)));
+	}
+	match encoding {
+		"csv" => data
+			.split(',')
+			.map(|gid| gid.trim().parse().map_err(|_| invalid_data(&format!("invalid tile gid `{}`", gid.trim()))))
+			.collect(),
+		"base64" => {
+			let bytes = base64(data)?;
+			if bytes.len() % 4 != 0 {
+				return Err(invalid_data("layer data isn't a whole number of tiles"));
+			}
+			Ok(bytes.chunks_exact(4).map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]])).collect())
+		},
+		_ => Err(invalid_data(&format!("unknown layer encoding `{}`", encoding))),
+	}
+}
+
+fn base64(text: &str) -> io::Result<Vec<u8>> {
+	let mut out = vec![];
+	let (mut bits, mut len) = (0u32, 0);
+	for byte in text.bytes().filter(|byte| !byte.is_ascii_whitespace() && *byte != b'=') {
+		let value = match byte {
+			b'A'..=b'Z' => byte - b'A',
+			b'a'..=b'z' => byte - b'a' + 26,
+			b'0'..=b'9' => byte - b'0' + 52,
+			b'+' => 62,
+			b'/' => 63,
+			_ => return Err(invalid_data("invalid base64")),
+		};
+		bits = bits << 6 | value as u32;
+		len += 6;
+		if len >= 8 {
+			len -= 8;
+			out.push((bits >> len) as u8);
+		}
+	}
+	Ok(out)
+}
+
+fn json_properties(json: &Json) -> Vec<(String, Value)> {
+	let props = json.get("properties").and_then(Json::as_array).unwrap_or_default();
+	props
+		.iter()
+		.filter_map(|prop| {
+			let name = prop.get("name")?.as_str()?;
+			let value = prop.get("value")?;
+			let value = match prop.get("type").and_then(Json::as_str).unwrap_or("string") {
+				"bool" => Value::Bool(value.as_bool()?),
+				"int" | "object" => Value::Int(value.as_f64()? as i64),
+				"float" => Value::Float(value.as_f64()? as f32),
+				"file" => Value::Asset(value.as_str()?.to_owned()),
+				"string" | "color" => Value::Str(value.as_str()?.to_owned()),
+				_ => return None,
+			};
+			Some((name.to_owned(), value))
+		})
+		.collect()
+}
+
+fn xml_properties(element: &Element) -> Vec<(String, Value)> {
+	let props = element.child("properties").map(|props| props.children_named("property"));
+	props
+		.into_iter()
+		.flatten()
+		.filter_map(|prop| {
+			let name = prop.attr("name")?;
+			// multi-line strings are stored as text instead
+			let value = prop.attr("value").unwrap_or(&prop.text);
+			let value = match prop.attr("type").unwrap_or("string") {
+				"bool" => Value::Bool(value == "true"),
+				"int" | "object" => Value::Int(value.parse().ok()?),
+				"float" => Value::Float(value.parse().ok()?),
+				"file" => Value::Asset(value.to_owned()),
+				"string" | "color" => Value::Str(value.to_owned()),
+				_ => return None,
+			};
+			Some((name.to_owned(), value))
+		})
+		.collect()
+}
+
+// `#rrggbb` or `#aarrggbb`
+fn parse_color(color: &str) -> io::Result<[f32; 4]> {
+	let err = || invalid_data(&format!("invalid color `{}`", color));
+	let hex = color.trim_start_matches('#');
+	let value = u32::from_str_radix(hex, 16).map_err(|_| err())?;
+	let argb = match hex.len() {
+		6 => 0xff00_0000 | value,
+		8 => value,
+		_ => return Err(err()),
+	};
+	let channel = |shift: u32| (argb >> shift & 0xff) as f32 / 255.0;
+	Ok([channel(16), channel(8), channel(0), channel(24)])
+}
+
+fn json_u32(json: &Json, key: &str) -> io::Result<u32> {
+	json.get(key).and_then(Json::as_u32).ok_or_else(|| invalid_data(&format!("missing `{}`", key)))
+}
+
+fn json_i32(json: &Json, key: &str) -> io::Result<i32> {
+	json.get(key)
+		.and_then(Json::as_f64)
+		.map(|value| value as i32)
+		.ok_or_else(|| invalid_data(&format!("missing `{}`", key)))
+}
+
+fn json_f32(json: &Json, key: &str) -> f32 {
+	json.get(key).and_then(Json::as_f64).unwrap_or(0.0) as f32
+}
+
+fn xml_parse<T: std::str::FromStr>(element: &Element, name: &str) -> io::Result<Option<T>> {
+	element
+		.attr(name)
+		.map(|value| value.parse().map_err(|_| invalid_data(&format!("invalid `{}` in `{}`", name, element.name))))
+		.transpose()
+}
+
+fn xml_u32(element: &Element, name: &str) -> io::Result<u32> {
+	xml_parse(element, name)?.ok_or_else(|| invalid_data(&format!("missing `{}` in `{}`", name, element.name)))
+}
+
+fn xml_f32(element: &Element, name: &str) -> io::Result<f32> {
+	Ok(xml_parse(element, name)?.unwrap_or(0.0))
+}
+
+fn extension(path: &Path) -> &str {
+	path.extension().and_then(|ext| ext.to_str()).unwrap_or_default()
+}
+
+fn path_str(path: &Path) -> String {
+	path.to_string_lossy().into_owned()
+}
+
+async fn read_string(path: &Path) -> io::Result<String> {
+	let src = read_all_u8(path.to_owned()).await?;
+	String::from_utf8(src).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn tiles(map: &Tilemap, layer: &str, w: i32, h: i32) -> Vec<Tile> {
+		let layer = map.tile_layer(layer).unwrap();
+		(0..h).flat_map(|y| (0..w).map(move |x| layer.get(x, y))).collect()
+	}
+
+	const TMX_CSV: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="8">
+ <properties>
+  <property name="title" value="a &amp; b"/>
+  <property name="gravity" type="float" value="9.5"/>
+  <property name="notes">line one
+line two</property>
+ </properties>
+ <tileset firstgid="1" source="tiles.tsx"/>
+ <layer id="1" name="ground" width="3" height="2" opacity="0.5" offsetx="4">
+  <data encoding="csv">
+1,2,0,
+536870915,2147483649,1073741826
+</data>
+ </layer>
+</map>
+"#;
+
+	#[test]
+	fn tmx_csv() {
+		let map = parse_tmx(TMX_CSV).unwrap();
+		assert_eq!(map.tile_size, Vector2::new(16, 8));
+		assert_eq!(map.properties, [
+			("title".to_owned(), Value::Str("a & b".to_owned())),
+			("gravity".to_owned(), Value::Float(9.5)),
+			("notes".to_owned(), Value::Str("line one\nline two".to_owned())),
+		]);
+		assert_eq!(map.tilesets.len(), 1);
+		assert_eq!(map.tilesets[0].first_gid, 1);
+		assert_eq!(map.tilesets[0].source.as_deref(), Some("tiles.tsx"));
+
+		let layer = map.tile_layer("ground").unwrap();
+		assert_eq!(layer.offset, Vector2::new(4.0, 0.0));
+		assert_eq!(layer.tint, [1.0, 1.0, 1.0, 0.5]);
+		let gids: Vec<_> = tiles(&map, "ground", 3, 2).into_iter().map(Tile::gid).collect();
+		assert_eq!(gids, [1, 2, 0, 3, 1, 2]);
+	}
+
+	#[test]
+	fn flip_flags() {
+		let map = parse_tmx(TMX_CSV).unwrap();
+		let flips: Vec<_> =
+			tiles(&map, "ground", 3, 2).into_iter().map(|tile| (tile.flip_h(), tile.flip_v(), tile.flip_d())).collect();
+		let none = (false, false, false);
+		assert_eq!(flips, [none, none, none, (false, false, true), (true, false, false), (false, true, false)]);
+	}
+
+	#[test]
+	fn tmx_base64() {
+		// gids 1, 2 flipped horizontally, empty, and 5 flipped vertically and diagonally
+		let src = r#"<map tilewidth="8" tileheight="8">
+			<layer name="a" width="2"><data encoding="base64">
+				AQAAAAIAAIAAAAAABQAAYA==
+			</data></layer>
+			<layer name="b" width="2"><data encoding="base64" compression="zlib">eJw=</data></layer>
+		</map>"#;
+		assert!(parse_tmx(src).is_err());
+		let src = src.replace(r#" compression="zlib">eJw="#, ">");
+		let map = parse_tmx(&src).unwrap();
+		let expected = [Tile(1), Tile::new(2).flipped(Tile::FLIP_H), Tile::EMPTY, Tile(0x6000_0005)];
+		assert_eq!(tiles(&map, "a", 2, 2), expected);
+		assert!(tiles(&map, "b", 2, 2).iter().all(|tile| tile.is_empty()));
+	}
+
+	#[test]
+	fn tmx_xml_tiles_and_groups() {
+		// layers without an encoding list a `tile` element per cell
+		let src = r#"<map tilewidth="8" tileheight="8">
+			<group name="g" offsetx="10" visible="0">
+				<layer name="a" width="2" offsetx="1">
+					<data><tile gid="3"/><tile/><tile gid="2147483652"/><tile gid="1"/></data>
+				</layer>
+			</group>
+			<objectgroup name="things">
+				<object id="7" name="door" type="exit" x="1.5" y="2" width="8" height="16" rotation="90"/>
+				<object id="8" x="0" y="0"><polygon points="0,0 8,0 4,-6"/></object>
+				<object id="9" gid="2" x="0" y="0"/>
+			</objectgroup>
+		</map>"#;
+		let map = parse_tmx(src).unwrap();
+		let layer = map.tile_layer("a").unwrap();
+		assert_eq!(layer.offset, Vector2::new(11.0, 0.0));
+		assert!(!layer.visible);
+		assert_eq!(tiles(&map, "a", 2, 2), [Tile(3), Tile::EMPTY, Tile(0x8000_0004), Tile(1)]);
+
+		let objects = &map.object_layer("things").unwrap().objects;
+		assert_eq!((objects[0].id, objects[0].name.as_str(), objects[0].kind.as_str()), (7, "door", "exit"));
+		assert_eq!(objects[0].pos, Vector2::new(1.5, 2.0));
+		assert_eq!(objects[0].size, Vector2::new(8.0, 16.0));
+		assert!((objects[0].rotation - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+		let triangle = vec![Vector2::new(0.0, 0.0), Vector2::new(8.0, 0.0), Vector2::new(4.0, -6.0)];
+		assert_eq!(objects[1].shape, Shape::Polygon(triangle));
+		assert_eq!(objects[2].shape, Shape::Tile(Tile(2)));
+	}
+
+	#[test]
+	fn tmj() {
+		let src = r#"{
+			"orientation": "orthogonal", "tilewidth": 16, "tileheight": 16,
+			"tilesets": [
+				{ "firstgid": 5, "source": "b.tsj" },
+				{ "firstgid": 1, "name": "a", "tilewidth": 16, "tileheight": 16, "tilecount": 4, "columns": 2,
+				  "image": "a.png", "tiles": [{ "id": 0, "animation": [
+					{ "tileid": 0, "duration": 100 }, { "tileid": 3, "duration": 250 }
+				  ] }] }
+			],
+			"layers": [
+				{ "type": "tilelayer", "name": "csv", "width": 2, "data": [1, 2147483650, 0, 3221225473] },
+				{ "type": "tilelayer", "name": "b64", "width": 2, "encoding": "base64",
+				  "data": "AQAAAAIAAIAAAAAABQAAYA==" },
+				{ "type": "tilelayer", "name": "chunked", "chunks": [
+					{ "x": -16, "y": 0, "width": 1, "height": 2, "data": [4, 5] }
+				] }
+			]
+		}"#;
+		let map = parse_tmj(src).unwrap();
+		// sorted by first gid
+		assert_eq!(map.tilesets[0].name, "a");
+		assert_eq!(map.tilesets[0].image.as_deref(), Some("a.png"));
+		assert_eq!(map.tilesets[1].source.as_deref(), Some("b.tsj"));
+		let frames: Vec<_> = map.tilesets[0].animation(0).unwrap().iter().map(|f| (f.tile, f.duration)).collect();
+		assert_eq!(frames, [(0, 0.1), (3, 0.25)]);
+
+		let flipped = Tile::new(1).flipped(Tile::FLIP_H | Tile::FLIP_V);
+		assert_eq!(tiles(&map, "csv", 2, 2), [Tile(1), Tile::new(2).flipped(Tile::FLIP_H), Tile::EMPTY, flipped]);
+		assert_eq!(tiles(&map, "b64", 2, 2), [Tile(1), Tile(0x8000_0002), Tile::EMPTY, Tile(0x6000_0005)]);
+		let chunked = map.tile_layer("chunked").unwrap();
+		assert_eq!((chunked.get(-16, 0), chunked.get(-16, 1)), (Tile(4), Tile(5)));
+	}
+
+	#[test]
+	fn tsx_animation_out_of_range() {
+		let src = r#"<tileset name="t" tilewidth="8" tileheight="8" tilecount="2" columns="2">
+			<image source="t.png"/>
+			<tile id="0"><animation><frame tileid="1" duration="100"/><frame tileid="2" duration="100"/></animation></tile>
+		</tileset>"#;
+		assert!(parse_tsx(src, 1).is_err());
+		let tileset = parse_tsx(&src.replace(r#"tileid="2""#, r#"tileid="0""#), 1).unwrap();
+		assert_eq!(tileset.image.as_deref(), Some("t.png"));
+		assert!(tileset.is_animated(0));
+	}
+
+	#[test]
+	fn malformed() {
+		assert!(parse_tmj(r#"{ "orientation": "isometric", "tilewidth": 8, "tileheight": 8 }"#).is_err());
+		// three gids don't fill rows two wide
+		let src = r#"{ "tilewidth": 8, "tileheight": 8, "layers": [
+			{ "type": "tilelayer", "name": "a", "width": 2, "data": [1, 2, 3] }
+		] }"#;
+		assert!(parse_tmj(src).is_err());
+		assert!(decode_data("1,x", "csv", None).is_err());
+		assert!(decode_data("AQAA", "base64", None).is_err());
+		assert!(decode_data("AQAAAA==", "base32", None).is_err());
+	}
+}
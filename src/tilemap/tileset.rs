@@ -0,0 +1,177 @@
+use crate::{gfx::texture::Subtex, scene::component::Value};
+use nalgebra::Vector2;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileFrame {
+	/// Local id of the tile to show.
+	pub tile: u32,
+	/// Seconds.
+	pub duration: f32,
+}
+
+/// Equally sized tiles cut from a texture, which may be a region of an atlas. Tiles are numbered row by row from 0
+/// (their local id); maps refer to them by `first_gid + id`.
+pub struct Tileset {
+	pub name: String,
+	pub first_gid: u32,
+	pub tile_size: Vector2<u32>,
+	pub tile_count: u32,
+	pub columns: u32,
+	/// Pixels around the edge of the texture.
+	pub margin: u32,
+	/// Pixels between tiles.
+	pub spacing: u32,
+	/// Image path relative to the map file. Only used for loading.
+	pub image: Option<String>,
+	/// External tileset file relative to the map file, when it hasn't been loaded yet. Only used for loading.
+	pub source: Option<String>,
+	/// Tiles from tilesets without a texture aren't drawn.
+	pub texture: Option<Subtex>,
+	pub tile_properties: HashMap<u32, Vec<(String, Value)>>,
+	animations: HashMap<u32, Vec<TileFrame>>,
+	// frame currently shown by each animated tile
+	current: HashMap<u32, u32>,
+}
+impl Tileset {
+	/// Cuts as many `tile_w` by `tile_h` tiles as fit in `texture`. Add it to a map with `Tilemap::add_tileset`.
+	pub fn new(name: impl Into<String>, texture: Subtex, tile_w: u32, tile_h: u32, margin: u32, spacing: u32) -> Self {
+		let (w, h) = texture.size();
+		let count = |size: u32, tile: u32| (size.saturating_sub(margin * 2) + spacing) / (tile + spacing);
+		let columns = count(w, tile_w);
+		let mut tileset = Self::empty(name, 0, Vector2::new(tile_w, tile_h), columns * count(h, tile_h), columns);
+		tileset.margin = margin;
+		tileset.spacing = spacing;
+		tileset.texture = Some(texture);
+		tileset
+	}
+
+	/// A tileset whose texture is set later, for loaders.
+	pub fn empty(
+		name: impl Into<String>,
+		first_gid: u32,
+		tile_size: Vector2<u32>,
+		tile_count: u32,
+		columns: u32,
+	) -> Self {
+		Self {
+			name: name.into(),
+			first_gid,
+			tile_size,
+			tile_count,
+			columns,
+			margin: 0,
+			spacing: 0,
+			image: None,
+			source: None,
+			texture: None,
+			tile_properties: HashMap::new(),
+			animations: HashMap::new(),
+			current: HashMap::new(),
+		}
+	}
+
+	pub fn contains(&self, gid: u32) -> bool {
+		gid >= self.first_gid && gid - self.first_gid < self.tile_count
+	}
+
+	/// Texel rectangle of a tile in the texture, as `(x, y, w, h)`.
+	pub fn tile_rect(&self, id: u32) -> (u32, u32, u32, u32) {
+		let (x0, y0) = self.texture.as_ref().map_or((0, 0), Subtex::pos);
+		let columns = self.columns.max(1);
+		let x = x0 + self.margin + id % columns * (self.tile_size.x + self.spacing);
+		let y = y0 + self.margin + id / columns * (self.tile_size.y + self.spacing);
+		(x, y, self.tile_size.x, self.tile_size.y)
+	}
+
+	/// Makes tile `id` cycle through `frames`, starting over at the end.
+	pub fn add_animation(&mut self, id: u32, frames: Vec<TileFrame>) {
+		assert!(frames.iter().all(|frame| frame.tile < self.tile_count), "animation frame out of range");
+		if let Some(first) = frames.first() {
+			self.current.insert(id, first.tile);
+		}
+		self.animations.insert(id, frames);
+	}
+
+	pub fn animation(&self, id: u32) -> Option<&[TileFrame]> {
+		self.animations.get(&id).map(Vec::as_slice)
+	}
+
+	pub fn is_animated(&self, id: u32) -> bool {
+		self.animations.contains_key(&id)
+	}
+
+	/// The tile to draw for `id` at this point of its animation.
+	pub fn displayed(&self, id: u32) -> u32 {
+		self.current.get(&id).copied().unwrap_or(id)
+	}
+
+	pub fn tile_property(&self, id: u32, name: &str) -> Option<&Value> {
+		let props = self.tile_properties.get(&id)?;
+		props.iter().find(|(prop, _)| prop == name).map(|(_, value)| value)
+	}
+
+	// picks every animated tile's frame at `time` seconds, returning whether any changed
+	pub(super) fn animate(&mut self, time: f32) -> bool {
+		let mut changed = false;
+		for (&id, frames) in &self.animations {
+			let total: f32 = frames.iter().map(|frame| frame.duration).sum();
+			if total <= 0.0 {
+				continue;
+			}
+			let mut t = time % total;
+			let frame = frames
+				.iter()
+				.find(|frame| {
+					t -= frame.duration;
+					t < 0.0
+				})
+				.unwrap_or_else(|| frames.last().unwrap());
+			let current = self.current.entry(id).or_insert(frame.tile);
+			changed |= *current != frame.tile;
+			*current = frame.tile;
+		}
+		changed
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn animated() -> Tileset {
+		let mut tileset = Tileset::empty("terrain", 1, Vector2::new(16, 16), 8, 4);
+		let frames = [(4, 0.25), (5, 0.5), (6, 0.25)];
+		tileset.add_animation(2, frames.iter().map(|&(tile, duration)| TileFrame { tile, duration }).collect());
+		tileset
+	}
+
+	#[test]
+	fn animate_frames() {
+		let mut tileset = animated();
+		assert_eq!(tileset.displayed(2), 4);
+		assert_eq!(tileset.displayed(3), 3);
+
+		assert!(!tileset.animate(0.1));
+		assert!(tileset.animate(0.25));
+		assert_eq!(tileset.displayed(2), 5);
+		assert!(!tileset.animate(0.6));
+		assert!(tileset.animate(0.8));
+		assert_eq!(tileset.displayed(2), 6);
+	}
+
+	#[test]
+	fn animate_wraps() {
+		let mut tileset = animated();
+		// the end of one cycle is the start of the next
+		tileset.animate(1.0);
+		assert_eq!(tileset.displayed(2), 4);
+		tileset.animate(2.3);
+		assert_eq!(tileset.displayed(2), 5);
+		tileset.animate(3.99);
+		assert_eq!(tileset.displayed(2), 6);
+		// rounding can leave time just short of the cycle's end, which still shows the last frame
+		tileset.animate(1.0 - f32::EPSILON);
+		assert_eq!(tileset.displayed(2), 6);
+	}
+}
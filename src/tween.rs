@@ -0,0 +1,163 @@
+pub mod curve;
+pub mod ease;
+pub mod track;
+
+use crate::{
+	gfx::camera::Camera,
+	scene::{transform::Transform, NodeId, Scene},
+};
+use curve::Curve;
+use nalgebra::Vector2;
+use std::time::Duration;
+use track::{Track, Tween};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TweenId(u64);
+
+struct Playing<C> {
+	id: TweenId,
+	track: Box<dyn Track<C>>,
+	time: f32,
+}
+
+/// Plays tracks against a target each frame, such as a `Scene`, a camera or a GUI state.
+pub struct Tweener<C> {
+	playing: Vec<Playing<C>>,
+	next_id: u64,
+}
+impl<C> Tweener<C> {
+	pub fn new() -> Self {
+		Self { playing: vec![], next_id: 0 }
+	}
+
+	pub fn play(&mut self, track: impl Track<C> + 'static) -> TweenId {
+		let id = TweenId(self.next_id);
+		self.next_id += 1;
+		self.playing.push(Playing { id, track: Box::new(track), time: 0.0 });
+		id
+	}
+
+	/// Stops a track where it is, without applying its end state.
+	pub fn stop(&mut self, id: TweenId) {
+		self.playing.retain(|playing| playing.id != id);
+	}
+
+	pub fn clear(&mut self) {
+		self.playing.clear();
+	}
+
+	pub fn is_playing(&self, id: TweenId) -> bool {
+		self.playing.iter().any(|playing| playing.id == id)
+	}
+
+	/// Advances every track by `dt` and applies it to `target`, in the order they were started so later ones win.
+	/// Tracks that finish apply their end state one last time, then stop.
+	pub fn update(&mut self, dt: Duration, target: &mut C) {
+		let dt = dt.as_secs_f32();
+		self.playing.retain_mut(|playing| {
+			playing.time += dt;
+			let duration = playing.track.duration();
+			playing.track.apply(target, playing.time.min(duration));
+			playing.time < duration
+		});
+	}
+}
+impl<C> Default for Tweener<C> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Tweens a node's translation.
+pub fn position(node: NodeId, curve: Curve<Vector2<f32>>) -> Tween<Scene, Vector2<f32>> {
+	Tween::new(curve, move |scene: &mut Scene, pos| {
+		modify_transform(scene, node, |transform| transform.isometry.translation.vector = pos)
+	})
+}
+
+/// Tweens a node's rotation, in radians.
+pub fn rotation(node: NodeId, curve: Curve<f32>) -> Tween<Scene, f32> {
+	Tween::new(curve, move |scene: &mut Scene, angle| {
+		modify_transform(scene, node, |transform| {
+			*transform = Transform::new(transform.translation(), angle, transform.scale);
+		})
+	})
+}
+
+pub fn scale(node: NodeId, curve: Curve<Vector2<f32>>) -> Tween<Scene, Vector2<f32>> {
+	Tween::new(curve, move |scene: &mut Scene, scale| {
+		modify_transform(scene, node, |transform| transform.scale = scale)
+	})
+}
+
+/// Tweens the tint of a node's sprite.
+pub fn tint(node: NodeId, curve: Curve<[f32; 4]>) -> Tween<Scene, [f32; 4]> {
+	Tween::new(curve, move |scene: &mut Scene, tint| {
+		if let Some(sprite) = scene.node_mut(node).and_then(|node| node.sprite.as_mut()) {
+			sprite.tint = tint;
+		}
+	})
+}
+
+/// Tweens the world position shown at a camera's top-left corner.
+pub fn camera_pos(curve: Curve<Vector2<f32>>) -> Tween<Camera, Vector2<f32>> {
+	Tween::new(curve, |camera: &mut Camera, pos| camera.pos = pos)
+}
+
+/// Tweens a camera's zoom about `focus`, a point on screen such as the middle of the window, which stays over the same
+/// world position.
+pub fn camera_zoom(focus: Vector2<f32>, curve: Curve<f32>) -> Tween<Camera, f32> {
+	Tween::new(curve, move |camera: &mut Camera, zoom| {
+		let world = camera.screen_to_world(focus);
+		camera.zoom = zoom;
+		camera.pos = world - focus / zoom;
+	})
+}
+
+// nodes removed while a tween is playing are skipped
+fn modify_transform(scene: &mut Scene, node: NodeId, f: impl FnOnce(&mut Transform)) {
+	if let Some(mut transform) = scene.node(node).map(|node| *node.transform()) {
+		f(&mut transform);
+		scene.set_transform(node, transform);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use curve::Interp;
+	use track::Sequence;
+
+	fn tween(idx: usize) -> Tween<[f32; 2], f32> {
+		Tween::new(Curve::between(0.0, 10.0, 1.0, Interp::Linear), move |values: &mut [f32; 2], x| values[idx] = x)
+	}
+
+	#[test]
+	fn finishes_on_end_state() {
+		let mut tweener = Tweener::new();
+		let mut values = [0.0; 2];
+		let id = tweener.play(tween(0));
+		tweener.update(Duration::from_millis(250), &mut values);
+		tweener.update(Duration::from_millis(250), &mut values);
+		assert!((values[0] - 5.0).abs() < 1e-4);
+		assert!(tweener.is_playing(id));
+		// overshooting the end still lands exactly on it
+		tweener.update(Duration::from_millis(700), &mut values);
+		assert_eq!(values[0], 10.0);
+		assert!(!tweener.is_playing(id));
+	}
+
+	#[test]
+	fn sequence_carries_time_over() {
+		let mut tweener = Tweener::new();
+		let mut values = [0.0; 2];
+		let id = tweener.play(Sequence::new().then(tween(0)).then(tween(1)));
+		// the half second past the first tween goes to the second
+		tweener.update(Duration::from_millis(1500), &mut values);
+		assert_eq!(values[0], 10.0);
+		assert!((values[1] - 5.0).abs() < 1e-4);
+		tweener.update(Duration::from_millis(500), &mut values);
+		assert_eq!(values, [10.0, 10.0]);
+		assert!(!tweener.is_playing(id));
+	}
+}
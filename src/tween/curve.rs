@@ -0,0 +1,176 @@
+use crate::tween::ease::{cubic_bezier, Ease};
+use nalgebra::{Vector2, Vector3, Vector4};
+
+/// Values that can be blended. `t` may go a little outside `0..=1` with overshooting easing.
+pub trait Lerp: Copy {
+	fn lerp(self, other: Self, t: f32) -> Self;
+}
+impl Lerp for f32 {
+	fn lerp(self, other: Self, t: f32) -> Self {
+		self + (other - self) * t
+	}
+}
+impl Lerp for Vector2<f32> {
+	fn lerp(self, other: Self, t: f32) -> Self {
+		self + (other - self) * t
+	}
+}
+impl Lerp for Vector3<f32> {
+	fn lerp(self, other: Self, t: f32) -> Self {
+		self + (other - self) * t
+	}
+}
+impl Lerp for Vector4<f32> {
+	fn lerp(self, other: Self, t: f32) -> Self {
+		self + (other - self) * t
+	}
+}
+/// Colors, blended per channel.
+impl Lerp for [f32; 4] {
+	fn lerp(self, other: Self, t: f32) -> Self {
+		let mut ret = self;
+		for (channel, other) in ret.iter_mut().zip(&other) {
+			*channel = channel.lerp(*other, t);
+		}
+		ret
+	}
+}
+
+/// How a key blends into the next one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interp {
+	/// Holds the value until the next key.
+	Step,
+	Linear,
+	Ease(Ease),
+	/// Timing handles `[x1, y1, x2, y2]`, like in an animation editor's graph view. See `ease::cubic_bezier`.
+	Bezier([f32; 4]),
+}
+impl Interp {
+	pub fn apply(self, t: f32) -> f32 {
+		match self {
+			Self::Step => 0.0,
+			Self::Linear => t.clamp(0.0, 1.0),
+			Self::Ease(ease) => ease.apply(t),
+			Self::Bezier([x1, y1, x2, y2]) => cubic_bezier(x1, y1, x2, y2, t),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Key<T> {
+	/// Seconds from the start of the curve.
+	pub time: f32,
+	pub value: T,
+	pub interp: Interp,
+}
+
+/// A value changing over time through keyframes. Before the first key it holds the first value, after the last key
+/// the last value. There's always at least one key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Curve<T> {
+	keys: Vec<Key<T>>,
+}
+impl<T: Lerp> Curve<T> {
+	/// A curve with one key, holding its value until more keys are added.
+	pub fn new(time: f32, value: T, interp: Interp) -> Self {
+		Self { keys: vec![Key { time, value, interp }] }
+	}
+
+	/// Goes from `from` to `to` over `duration` seconds.
+	pub fn between(from: T, to: T, duration: f32, interp: Interp) -> Self {
+		Self::new(0.0, from, interp).key(duration, to, Interp::Linear)
+	}
+
+	/// Adds a key, keeping keys sorted by time. Keys at the same time stay in the order they were added, which allows
+	/// jumps.
+	pub fn key(mut self, time: f32, value: T, interp: Interp) -> Self {
+		let idx = self.keys.iter().position(|key| key.time > time).unwrap_or(self.keys.len());
+		self.keys.insert(idx, Key { time, value, interp });
+		self
+	}
+
+	pub fn keys(&self) -> &[Key<T>] {
+		&self.keys
+	}
+
+	/// Time of the last key.
+	pub fn duration(&self) -> f32 {
+		self.keys[self.keys.len() - 1].time
+	}
+
+	pub fn sample(&self, time: f32) -> T {
+		// index of the first key after `time`
+		let next = self.keys.iter().position(|key| key.time > time);
+		match next {
+			Some(0) => self.keys[0].value,
+			Some(next) => {
+				let (a, b) = (&self.keys[next - 1], &self.keys[next]);
+				let t = (time - a.time) / (b.time - a.time);
+				a.value.lerp(b.value, a.interp.apply(t))
+			},
+			None => self.keys[self.keys.len() - 1].value,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_close(a: f32, b: f32) {
+		assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+	}
+
+	#[test]
+	fn sample_linear() {
+		let curve = Curve::new(1.0, 10.0, Interp::Linear).key(3.0, 20.0, Interp::Linear).key(4.0, 0.0, Interp::Step);
+		assert_close(curve.duration(), 4.0);
+		// held before the first key and after the last
+		assert_close(curve.sample(0.0), 10.0);
+		assert_close(curve.sample(1.0), 10.0);
+		assert_close(curve.sample(2.0), 15.0);
+		assert_close(curve.sample(2.5), 17.5);
+		assert_close(curve.sample(4.0), 0.0);
+		assert_close(curve.sample(100.0), 0.0);
+	}
+
+	#[test]
+	fn sample_step_and_ease() {
+		let curve = Curve::new(0.0, 0.0, Interp::Step)
+			.key(1.0, 1.0, Interp::Ease(Ease::QuadIn))
+			.key(2.0, 3.0, Interp::Linear);
+		assert_close(curve.sample(0.99), 0.0);
+		assert_close(curve.sample(1.0), 1.0);
+		assert_close(curve.sample(1.5), 1.5);
+	}
+
+	#[test]
+	fn keys_sorted() {
+		// added out of order, and a jump where two keys share a time
+		let curve = Curve::new(2.0, 4.0, Interp::Linear)
+			.key(0.0, 0.0, Interp::Linear)
+			.key(1.0, 1.0, Interp::Linear)
+			.key(1.0, 3.0, Interp::Linear);
+		let times: Vec<_> = curve.keys().iter().map(|key| (key.time, key.value)).collect();
+		assert_eq!(times, [(0.0, 0.0), (1.0, 1.0), (1.0, 3.0), (2.0, 4.0)]);
+		assert_close(curve.sample(0.5), 0.5);
+		assert_close(curve.sample(1.5), 3.5);
+	}
+
+	#[test]
+	fn single_key() {
+		// held everywhere, before and after its time alike
+		let curve = Curve::new(1.0, 5.0, Interp::Ease(Ease::QuadIn));
+		assert_close(curve.duration(), 1.0);
+		assert_close(curve.sample(-1.0), 5.0);
+		assert_close(curve.sample(1.0), 5.0);
+		assert_close(curve.sample(f32::INFINITY), 5.0);
+	}
+
+	#[test]
+	fn colors() {
+		let curve = Curve::between([0.0, 0.0, 0.0, 1.0], [1.0, 0.5, 0.0, 0.0], 2.0, Interp::Linear);
+		assert_eq!(curve.sample(1.0), [0.5, 0.25, 0.0, 0.5]);
+	}
+}
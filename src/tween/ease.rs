@@ -0,0 +1,218 @@
+use std::f32::consts::PI;
+
+const BACK: f32 = 1.70158;
+
+/// Standard easing functions, mapping progress in `0..=1` to eased progress. `Back` and `Elastic` overshoot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ease {
+	Linear,
+	QuadIn,
+	QuadOut,
+	QuadInOut,
+	CubicIn,
+	CubicOut,
+	CubicInOut,
+	SineIn,
+	SineOut,
+	SineInOut,
+	ExpoIn,
+	ExpoOut,
+	ExpoInOut,
+	BackIn,
+	BackOut,
+	BackInOut,
+	ElasticIn,
+	ElasticOut,
+	ElasticInOut,
+	BounceIn,
+	BounceOut,
+	BounceInOut,
+}
+impl Ease {
+	pub fn apply(self, t: f32) -> f32 {
+		let t = t.clamp(0.0, 1.0);
+		match self {
+			Self::Linear => t,
+			Self::QuadIn => quad(t),
+			Self::QuadOut => out(quad, t),
+			Self::QuadInOut => in_out(quad, t),
+			Self::CubicIn => cubic(t),
+			Self::CubicOut => out(cubic, t),
+			Self::CubicInOut => in_out(cubic, t),
+			Self::SineIn => sine(t),
+			Self::SineOut => out(sine, t),
+			Self::SineInOut => in_out(sine, t),
+			Self::ExpoIn => expo(t),
+			Self::ExpoOut => out(expo, t),
+			Self::ExpoInOut => in_out(expo, t),
+			Self::BackIn => back(t),
+			Self::BackOut => out(back, t),
+			Self::BackInOut => in_out(back, t),
+			Self::ElasticIn => elastic(t),
+			Self::ElasticOut => out(elastic, t),
+			Self::ElasticInOut => in_out(elastic, t),
+			Self::BounceIn => bounce(t),
+			Self::BounceOut => out(bounce, t),
+			Self::BounceInOut => in_out(bounce, t),
+		}
+	}
+}
+
+/// The CSS `cubic-bezier(x1, y1, x2, y2)` timing function: a curve from (0, 0) to (1, 1) shaped by two control points,
+/// evaluated at `x = t`. `x1` and `x2` must be within `0..=1`.
+pub fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32, t: f32) -> f32 {
+	let t = t.clamp(0.0, 1.0);
+	let bezier =
+		|p1: f32, p2: f32, s: f32| 3.0 * (1.0 - s) * (1.0 - s) * s * p1 + 3.0 * (1.0 - s) * s * s * p2 + s * s * s;
+
+	// find s where x(s) = t. x is monotonic, so bisection always converges, but Newton's method usually gets there
+	// first.
+	let mut s = t;
+	for _ in 0..8 {
+		let err = bezier(x1, x2, s) - t;
+		if err.abs() < 1e-6 {
+			return bezier(y1, y2, s);
+		}
+		let slope = 3.0 * (1.0 - s) * (1.0 - s) * x1 + 6.0 * (1.0 - s) * s * (x2 - x1) + 3.0 * s * s * (1.0 - x2);
+		if slope.abs() < 1e-6 {
+			break;
+		}
+		s -= err / slope;
+	}
+
+	let (mut lo, mut hi) = (0.0, 1.0);
+	s = t;
+	for _ in 0..32 {
+		if bezier(x1, x2, s) < t {
+			lo = s;
+		} else {
+			hi = s;
+		}
+		s = (lo + hi) * 0.5;
+	}
+	bezier(y1, y2, s)
+}
+
+fn out(f: fn(f32) -> f32, t: f32) -> f32 {
+	1.0 - f(1.0 - t)
+}
+
+fn in_out(f: fn(f32) -> f32, t: f32) -> f32 {
+	if t < 0.5 {
+		f(t * 2.0) * 0.5
+	} else {
+		1.0 - f(2.0 - t * 2.0) * 0.5
+	}
+}
+
+fn quad(t: f32) -> f32 {
+	t * t
+}
+
+fn cubic(t: f32) -> f32 {
+	t * t * t
+}
+
+fn sine(t: f32) -> f32 {
+	1.0 - (t * PI * 0.5).cos()
+}
+
+fn expo(t: f32) -> f32 {
+	if t <= 0.0 {
+		0.0
+	} else {
+		2f32.powf(10.0 * t - 10.0)
+	}
+}
+
+fn back(t: f32) -> f32 {
+	(BACK + 1.0) * t * t * t - BACK * t * t
+}
+
+fn elastic(t: f32) -> f32 {
+	if t <= 0.0 || t >= 1.0 {
+		t
+	} else {
+		-(2f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * PI * 2.0 / 3.0).sin()
+	}
+}
+
+fn bounce(t: f32) -> f32 {
+	// the classic bounce is defined as an ease out, in four parabolic arcs
+	let t = 1.0 - t;
+	let (n, d) = (7.5625, 2.75);
+	let bounced = if t < 1.0 / d {
+		n * t * t
+	} else if t < 2.0 / d {
+		let t = t - 1.5 / d;
+		n * t * t + 0.75
+	} else if t < 2.5 / d {
+		let t = t - 2.25 / d;
+		n * t * t + 0.9375
+	} else {
+		let t = t - 2.625 / d;
+		n * t * t + 0.984375
+	};
+	1.0 - bounced
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ALL: [Ease; 22] = [
+		Ease::Linear,
+		Ease::QuadIn,
+		Ease::QuadOut,
+		Ease::QuadInOut,
+		Ease::CubicIn,
+		Ease::CubicOut,
+		Ease::CubicInOut,
+		Ease::SineIn,
+		Ease::SineOut,
+		Ease::SineInOut,
+		Ease::ExpoIn,
+		Ease::ExpoOut,
+		Ease::ExpoInOut,
+		Ease::BackIn,
+		Ease::BackOut,
+		Ease::BackInOut,
+		Ease::ElasticIn,
+		Ease::ElasticOut,
+		Ease::ElasticInOut,
+		Ease::BounceIn,
+		Ease::BounceOut,
+		Ease::BounceInOut,
+	];
+
+	#[test]
+	fn ends() {
+		for &ease in &ALL {
+			assert!(ease.apply(0.0).abs() < 1e-5, "{:?} starts at {}", ease, ease.apply(0.0));
+			assert!((ease.apply(1.0) - 1.0).abs() < 1e-5, "{:?} ends at {}", ease, ease.apply(1.0));
+			// progress outside the tween is clamped
+			assert_eq!(ease.apply(-1.0), ease.apply(0.0));
+			assert_eq!(ease.apply(2.0), ease.apply(1.0));
+		}
+	}
+
+	#[test]
+	fn in_out_symmetry() {
+		for &ease in &[Ease::QuadInOut, Ease::SineInOut, Ease::BackInOut, Ease::BounceInOut] {
+			assert!((ease.apply(0.5) - 0.5).abs() < 1e-5, "{:?}", ease);
+			assert!((ease.apply(0.2) + ease.apply(0.8) - 1.0).abs() < 1e-5, "{:?}", ease);
+		}
+	}
+
+	#[test]
+	fn css_ease() {
+		// CSS `ease`, sampled from browsers' implementation of the same curve
+		let expected = [(0.1, 0.0948), (0.25, 0.4085), (0.5, 0.8024), (0.75, 0.9605), (0.9, 0.9943), (1.0, 1.0)];
+		for &(t, y) in &expected {
+			let eased = cubic_bezier(0.25, 0.1, 0.25, 1.0, t);
+			assert!((eased - y).abs() < 1e-3, "ease({}) = {}, not {}", t, eased, y);
+		}
+		// with the handles on the diagonal, it's linear
+		assert!((cubic_bezier(0.3, 0.3, 0.7, 0.7, 0.4) - 0.4).abs() < 1e-4);
+	}
+}
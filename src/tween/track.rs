@@ -0,0 +1,246 @@
+use crate::tween::curve::{Curve, Lerp};
+use nalgebra::Vector2;
+use std::f32::consts::PI;
+
+type Setter<C, T> = Box<dyn FnMut(&mut C, T)>;
+
+/// Sets properties of a `C` as a function of time.
+pub trait Track<C> {
+	/// Seconds, possibly infinite.
+	fn duration(&self) -> f32;
+
+	/// Sets the properties to what they are `time` seconds in, where `time` is within `0..=duration`.
+	fn apply(&mut self, target: &mut C, time: f32);
+}
+
+/// Drives one property with a curve, through a setter.
+pub struct Tween<C, T> {
+	curve: Curve<T>,
+	set: Setter<C, T>,
+}
+impl<C, T: Lerp> Tween<C, T> {
+	pub fn new(curve: Curve<T>, set: impl FnMut(&mut C, T) + 'static) -> Self {
+		Self { curve, set: Box::new(set) }
+	}
+}
+impl<C, T: Lerp> Track<C> for Tween<C, T> {
+	fn duration(&self) -> f32 {
+		self.curve.duration()
+	}
+
+	fn apply(&mut self, target: &mut C, time: f32) {
+		(self.set)(target, self.curve.sample(time));
+	}
+}
+
+/// Does nothing for a while. Mostly useful in sequences.
+pub struct Delay(pub f32);
+impl<C> Track<C> for Delay {
+	fn duration(&self) -> f32 {
+		self.0
+	}
+
+	fn apply(&mut self, _target: &mut C, _time: f32) {}
+}
+
+/// Plays tracks one after another.
+pub struct Sequence<C> {
+	tracks: Vec<Box<dyn Track<C>>>,
+}
+impl<C> Sequence<C> {
+	pub fn new() -> Self {
+		Self { tracks: vec![] }
+	}
+
+	pub fn then(mut self, track: impl Track<C> + 'static) -> Self {
+		self.tracks.push(Box::new(track));
+		self
+	}
+}
+impl<C> Track<C> for Sequence<C> {
+	fn duration(&self) -> f32 {
+		self.tracks.iter().map(|track| track.duration()).sum()
+	}
+
+	fn apply(&mut self, target: &mut C, time: f32) {
+		// finished tracks are applied at their end too, so skipping over one in a long frame still lands its final
+		// value
+		let mut start = 0.0;
+		for track in &mut self.tracks {
+			if time < start {
+				break;
+			}
+			let duration = track.duration();
+			track.apply(target, (time - start).min(duration));
+			start += duration;
+		}
+	}
+}
+impl<C> Default for Sequence<C> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Plays tracks at the same time. Tracks added later win when they set the same property.
+pub struct Group<C> {
+	tracks: Vec<Box<dyn Track<C>>>,
+}
+impl<C> Group<C> {
+	pub fn new() -> Self {
+		Self { tracks: vec![] }
+	}
+
+	pub fn with(mut self, track: impl Track<C> + 'static) -> Self {
+		self.tracks.push(Box::new(track));
+		self
+	}
+}
+impl<C> Track<C> for Group<C> {
+	fn duration(&self) -> f32 {
+		self.tracks.iter().map(|track| track.duration()).fold(0.0, f32::max)
+	}
+
+	fn apply(&mut self, target: &mut C, time: f32) {
+		for track in &mut self.tracks {
+			let duration = track.duration();
+			track.apply(target, time.min(duration));
+		}
+	}
+}
+impl<C> Default for Group<C> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Plays a track `count` times, or forever for `None`.
+pub struct Repeat<C> {
+	track: Box<dyn Track<C>>,
+	count: Option<u32>,
+}
+impl<C> Repeat<C> {
+	pub fn new(track: impl Track<C> + 'static, count: Option<u32>) -> Self {
+		Self { track: Box::new(track), count }
+	}
+}
+impl<C> Track<C> for Repeat<C> {
+	fn duration(&self) -> f32 {
+		match self.count {
+			Some(count) => self.track.duration() * count as f32,
+			None => f32::INFINITY,
+		}
+	}
+
+	fn apply(&mut self, target: &mut C, time: f32) {
+		let duration = self.track.duration();
+		let local = if duration <= 0.0 || time >= self.duration() { duration } else { time % duration };
+		self.track.apply(target, local);
+	}
+}
+
+/// Random-looking, decaying offsets, for camera shakes and impacts. Ends at zero.
+pub struct Shake<C> {
+	amplitude: f32,
+	// oscillations per second
+	frequency: f32,
+	duration: f32,
+	seed: f32,
+	set: Setter<C, Vector2<f32>>,
+}
+impl<C> Shake<C> {
+	/// `seed` picks one of many different-looking shakes.
+	pub fn new(
+		amplitude: f32,
+		frequency: f32,
+		duration: f32,
+		seed: u32,
+		set: impl FnMut(&mut C, Vector2<f32>) + 'static,
+	) -> Self {
+		Self { amplitude, frequency, duration, seed: seed as f32 * 12.9898, set: Box::new(set) }
+	}
+}
+impl<C> Track<C> for Shake<C> {
+	fn duration(&self) -> f32 {
+		self.duration
+	}
+
+	fn apply(&mut self, target: &mut C, time: f32) {
+		let fade = if self.duration > 0.0 { 1.0 - (time / self.duration).min(1.0) } else { 0.0 };
+		// sums of sines at unrelated frequencies never quite repeat
+		let phase = time * self.frequency * PI * 2.0;
+		let wave = |a: f32, b: f32| ((phase + a).sin() + (phase * 1.618 + b).sin() * 0.5) / 1.5;
+		let offset = Vector2::new(wave(self.seed, self.seed * 2.3), wave(self.seed * 3.7, self.seed * 5.1));
+		(self.set)(target, offset * self.amplitude * fade * fade);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tween::curve::Interp;
+
+	type Log = Vec<(&'static str, f32)>;
+
+	// records the times it's applied at
+	struct Record(&'static str, f32);
+	impl Track<Log> for Record {
+		fn duration(&self) -> f32 {
+			self.1
+		}
+
+		fn apply(&mut self, log: &mut Log, time: f32) {
+			log.push((self.0, time));
+		}
+	}
+
+	fn apply(track: &mut impl Track<Log>, time: f32) -> Log {
+		let mut log = vec![];
+		track.apply(&mut log, time);
+		log
+	}
+
+	#[test]
+	fn sequence() {
+		let mut seq = Sequence::new().then(Record("a", 1.0)).then(Delay(0.5)).then(Record("b", 2.0));
+		assert_eq!(seq.duration(), 3.5);
+		assert_eq!(apply(&mut seq, 0.25), [("a", 0.25)]);
+		// a track that's been skipped over still lands on its end
+		assert_eq!(apply(&mut seq, 1.25), [("a", 1.0)]);
+		assert_eq!(apply(&mut seq, 2.0), [("a", 1.0), ("b", 0.5)]);
+		assert_eq!(apply(&mut seq, 3.5), [("a", 1.0), ("b", 2.0)]);
+	}
+
+	#[test]
+	fn group() {
+		let mut group = Group::new().with(Record("a", 1.0)).with(Record("b", 3.0));
+		assert_eq!(group.duration(), 3.0);
+		assert_eq!(apply(&mut group, 0.5), [("a", 0.5), ("b", 0.5)]);
+		// finished tracks hold their end while the rest play on
+		assert_eq!(apply(&mut group, 2.0), [("a", 1.0), ("b", 2.0)]);
+	}
+
+	#[test]
+	fn repeat() {
+		let mut repeat = Repeat::new(Record("a", 2.0), Some(3));
+		assert_eq!(repeat.duration(), 6.0);
+		assert_eq!(apply(&mut repeat, 1.0), [("a", 1.0)]);
+		// time past the end of one repetition carries into the next
+		assert_eq!(apply(&mut repeat, 4.5), [("a", 0.5)]);
+		// the last repetition ends on the track's end rather than wrapping to its start
+		assert_eq!(apply(&mut repeat, 6.0), [("a", 2.0)]);
+
+		let mut forever = Repeat::new(Record("a", 2.0), None);
+		assert_eq!(forever.duration(), f32::INFINITY);
+		assert_eq!(apply(&mut forever, 1001.0), [("a", 1.0)]);
+	}
+
+	#[test]
+	fn tween() {
+		let mut tween = Tween::new(Curve::between(0.0, 10.0, 2.0, Interp::Linear), |value: &mut f32, x| *value = x);
+		let mut value = 0.0;
+		tween.apply(&mut value, 0.5);
+		assert_eq!(value, 2.5);
+		assert_eq!(tween.duration(), 2.0);
+	}
+}
@@ -0,0 +1,256 @@
+use std::{char, io, str};
+
+/// An XML element. Only what data files need is supported: no DTDs, namespaces are kept as part of names, and text
+/// is the concatenation of everything directly inside the element.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Element {
+	pub name: String,
+	pub attrs: Vec<(String, String)>,
+	pub children: Vec<Element>,
+	pub text: String,
+}
+impl Element {
+	/// Parses a document and returns its root element.
+	pub fn parse(src: &str) -> io::Result<Self> {
+		let mut parser = Parser { src, pos: 0 };
+		parser.skip_misc()?;
+		let root = parser.element()?;
+		parser.skip_misc()?;
+		if parser.pos < src.len() {
+			return Err(parser.err("trailing characters"));
+		}
+		Ok(root)
+	}
+
+	pub fn attr(&self, name: &str) -> Option<&str> {
+		self.attrs.iter().find(|(attr, _)| attr == name).map(|(_, value)| value.as_str())
+	}
+
+	pub fn child(&self, name: &str) -> Option<&Element> {
+		self.children.iter().find(|child| child.name == name)
+	}
+
+	pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+		self.children.iter().filter(move |child| child.name == name)
+	}
+}
+
+struct Parser<'a> {
+	src: &'a str,
+	pos: usize,
+}
+impl<'a> Parser<'a> {
+	fn element(&mut self) -> io::Result<Element> {
+		if !self.eat("<") {
+			return Err(self.err("expected an element"));
+		}
+		let name = self.name()?;
+		let mut element = Element { name, attrs: vec![], children: vec![], text: String::new() };
+		loop {
+			self.skip_whitespace();
+			if self.eat("/>") {
+				return Ok(element);
+			}
+			if self.eat(">") {
+				break;
+			}
+			let attr = self.name()?;
+			self.skip_whitespace();
+			if !self.eat("=") {
+				return Err(self.err("expected `=`"));
+			}
+			self.skip_whitespace();
+			let quote = match self.rest().chars().next() {
+				Some(quote @ '"') | Some(quote @ '\'') => quote,
+				_ => return Err(self.err("expected a quoted value")),
+			};
+			self.pos += 1;
+			let len = self.rest().find(quote).ok_or_else(|| self.err("unterminated attribute"))?;
+			let value = self.unescape(&self.src[self.pos..self.pos + len])?;
+			self.pos += len + 1;
+			element.attrs.push((attr, value));
+		}
+
+		loop {
+			let len = self.rest().find('<').ok_or_else(|| self.err("unterminated element"))?;
+			let text = self.unescape(&self.src[self.pos..self.pos + len])?;
+			element.text.push_str(&text);
+			self.pos += len;
+
+			if self.eat("</") {
+				let name = self.name()?;
+				self.skip_whitespace();
+				if name != element.name || !self.eat(">") {
+					return Err(self.err(&format!("expected `</{}>`", element.name)));
+				}
+				return Ok(element);
+			} else if self.eat("<![CDATA[") {
+				let len = self.rest().find("]]>").ok_or_else(|| self.err("unterminated CDATA"))?;
+				element.text.push_str(&self.src[self.pos..self.pos + len]);
+				self.pos += len + 3;
+			} else if !self.skip_comment_or_pi()? {
+				element.children.push(self.element()?);
+			}
+		}
+	}
+
+	fn name(&mut self) -> io::Result<String> {
+		let len = self
+			.rest()
+			.find(|ch: char| ch.is_whitespace() || matches!(ch, '=' | '>' | '/' | '<'))
+			.unwrap_or_else(|| self.rest().len());
+		if len == 0 {
+			return Err(self.err("expected a name"));
+		}
+		let name = self.src[self.pos..self.pos + len].to_owned();
+		self.pos += len;
+		Ok(name)
+	}
+
+	fn unescape(&self, text: &str) -> io::Result<String> {
+		let mut out = String::with_capacity(text.len());
+		let mut rest = text;
+		while let Some(start) = rest.find('&') {
+			out.push_str(&rest[..start]);
+			let end = rest[start..].find(';').ok_or_else(|| self.err("unterminated entity"))? + start;
+			let entity = &rest[start + 1..end];
+			let ch = match entity {
+				"amp" => '&',
+				"lt" => '<',
+				"gt" => '>',
+				"quot" => '"',
+				"apos" => '\'',
+				_ => {
+					let code = if let Some(hex) = entity.strip_prefix("#x") {
+						u32::from_str_radix(hex, 16).ok()
+					} else {
+						entity.strip_prefix('#').and_then(|dec| dec.parse().ok())
+					};
+					code.and_then(char::from_u32).ok_or_else(|| self.err(&format!("unknown entity `&{};`", entity)))?
+				},
+			};
+			out.push(ch);
+			rest = &rest[end + 1..];
+		}
+		out.push_str(rest);
+		Ok(out)
+	}
+
+	// the prolog, comments and processing instructions around the root element
+	fn skip_misc(&mut self) -> io::Result<()> {
+		loop {
+			self.skip_whitespace();
+			if self.eat("<!DOCTYPE") {
+				let len = self.rest().find('>').ok_or_else(|| self.err("unterminated doctype"))?;
+				self.pos += len + 1;
+			} else if !self.skip_comment_or_pi()? {
+				return Ok(());
+			}
+		}
+	}
+
+	fn skip_comment_or_pi(&mut self) -> io::Result<bool> {
+		let end = if self.eat("<!--") {
+			"-->"
+		} else if self.eat("<?") {
+			"?>"
+		} else {
+			return Ok(false);
+		};
+		let len = self.rest().find(end).ok_or_else(|| self.err("unterminated comment"))?;
+		self.pos += len + end.len();
+		Ok(true)
+	}
+
+	fn skip_whitespace(&mut self) {
+		self.pos += self.rest().len() - self.rest().trim_start().len();
+	}
+
+	fn rest(&self) -> &'a str {
+		&self.src[self.pos..]
+	}
+
+	fn eat(&mut self, token: &str) -> bool {
+		let matches = self.rest().starts_with(token);
+		if matches {
+			self.pos += token.len();
+		}
+		matches
+	}
+
+	fn err(&self, msg: &str) -> io::Error {
+		let line = self.src[..self.pos].matches('\n').count() + 1;
+		io::Error::new(io::ErrorKind::InvalidData, format!("xml line {}: {}", line, msg))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn attributes() {
+		let root = Element::parse(r#"<a  x="1" y = '2'	z="it's"/>"#).unwrap();
+		assert_eq!(root.name, "a");
+		let attrs: Vec<_> = root.attrs.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+		assert_eq!(attrs, [("x", "1"), ("y", "2"), ("z", "it's")]);
+		assert_eq!(root.attr("y"), Some("2"));
+		assert_eq!(root.attr("w"), None);
+		assert!(root.children.is_empty() && root.text.is_empty());
+	}
+
+	#[test]
+	fn escapes() {
+		let root = Element::parse(r#"<a title="&lt;&amp;&quot;&#65;&#x42;">x &gt; y &apos;&#x1F600;</a>"#).unwrap();
+		assert_eq!(root.attr("title"), Some("<&\"AB"));
+		assert_eq!(root.text, "x > y '\u{1f600}");
+
+		assert!(Element::parse("<a>&nbsp;</a>").is_err());
+		assert!(Element::parse("<a>&amp</a>").is_err());
+		assert!(Element::parse(r#"<a b="&#xd800;"/>"#).is_err());
+	}
+
+	#[test]
+	fn cdata() {
+		// nothing inside is unescaped or parsed as markup
+		let root = Element::parse("<s>1 <![CDATA[a < b && <c>&amp;]]> 2</s>").unwrap();
+		assert_eq!(root.text, "1 a < b && <c>&amp; 2");
+		assert!(root.children.is_empty());
+		assert!(Element::parse("<s><![CDATA[a]></s>").is_err());
+	}
+
+	#[test]
+	fn children() {
+		let src = r#"<?xml version="1.0" encoding="UTF-8"?>
+			<!DOCTYPE map>
+			<!-- before -->
+			<map>
+				<layer name="a"><data>1</data></layer>
+				<!-- <layer name="commented"/> -->
+				<?pi ignored?>
+				<layer name="b"/>
+				<objectgroup/>
+			</map>
+			<!-- after -->
+		"#;
+		let root = Element::parse(src).unwrap();
+		let names: Vec<_> = root.children_named("layer").filter_map(|layer| layer.attr("name")).collect();
+		assert_eq!(names, ["a", "b"]);
+		assert_eq!(root.children.len(), 3);
+		assert_eq!(root.child("layer").and_then(|layer| layer.child("data")).unwrap().text, "1");
+		assert_eq!(root.text.trim(), "");
+	}
+
+	#[test]
+	fn malformed() {
+		let err = Element::parse("<a>\n<b></a>").unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+		assert!(err.to_string().starts_with("xml line 2:"), "{}", err);
+
+		assert!(Element::parse("<a></a><b/>").is_err());
+		assert!(Element::parse("<a x=1/>").is_err());
+		assert!(Element::parse("<a x=\"1/>").is_err());
+		assert!(Element::parse("<a>").is_err());
+		assert!(Element::parse("text").is_err());
+	}
+}